@@ -1,46 +1,356 @@
 use proc_macro::TokenStream;
+use quote::{quote, format_ident};
+use syn::{
+	braced, parse_macro_input,
+	parse::{Parse, ParseStream},
+	punctuated::Punctuated,
+	Attribute, Expr, Ident, Lit, Token, Type,
+};
 
-enum DataType {
+// What a vertex attribute is, as far as glVertexAttribPointer is concerned
+enum AttributeType {
 	Float,
+	Int,
+	UInt,
+}
+
+struct AttributeDefinition {
+	name: Ident,
+	ty: Type,
+	size: i32,
+	data_type: AttributeType,
+}
+
+enum UniformType {
+	Bool,
+	Float,
+	Int,
+	UInt,
 	FloatVec2,
 	FloatVec3,
 	FloatVec4,
 	FloatMatrix2x2,
-	FloatMatrix2x3,
-	FloatMatrix2x4,
-	FloatMatrix3x2,
 	FloatMatrix3x3,
-	FloatMatrix3x4,
-	FloatMatrix4x2,
-	FloatMatrix4x3,
 	FloatMatrix4x4,
-	Int,
-	IntVec2,
-	IntVec3,
-	IntVec4,
-	UInt,
-	UIntVec2,
-	UIntVec3,
-	UIntVec4,
-	Bool,
-	BoolVec2,
-	BoolVec3,
-	BoolVec4,
+	// Rc<Texture>
+	Texture,
+	// Option<Rc<Texture>>. Also sets has<Name> to whether there is a texture.
+	OptionalTexture,
 }
 
-struct AttributeDefinition {
-	name: String,
-	size: usize,
-	data_type: DataType,
+// What kind of texture a sampler uniform uses
+enum TextureTarget {
+	Texture2D,
+	// #[cube]
+	Cube,
+	// #[buffer], for samplerBuffer, or a 2D texture where there aren't any
+	// buffer textures
+	Buffer,
 }
 
 struct UniformDefinition {
-	mutable: bool,
-	name: String,
-	data_type: DataType,
+	name: Ident,
+	ty: Type,
+	data_type: UniformType,
+	target: TextureTarget,
+}
+
+struct ModelData {
+	attrs: Vec<Attribute>,
+	name: Ident,
+	attributes: Vec<AttributeDefinition>,
+	uniforms: Vec<UniformDefinition>,
+}
+
+enum Field {
+	Attribute(AttributeDefinition),
+	Uniform(UniformDefinition),
+}
+
+fn type_name(ty: &Type) -> String {
+	quote!(#ty).to_string().replace(' ', "")
 }
 
+fn attribute_type(ty: &Type) -> Option<(i32, AttributeType)> {
+	if let Type::Array(array) = ty {
+		let size = match &array.len {
+			Expr::Lit(lit) => match &lit.lit {
+				Lit::Int(size) => size.base10_parse().ok()?,
+				_ => return None,
+			},
+			_ => return None,
+		};
+		let (_, data_type) = attribute_type(&array.elem)?;
+		return Some((size, data_type));
+	}
+	Some(match type_name(ty).as_str() {
+		"f32" => (1, AttributeType::Float),
+		"i32" => (1, AttributeType::Int),
+		"u32" => (1, AttributeType::UInt),
+		"Vec2" => (2, AttributeType::Float),
+		"Vec3" => (3, AttributeType::Float),
+		"Vec4" => (4, AttributeType::Float),
+		_ => return None,
+	})
+}
+
+fn uniform_type(ty: &Type) -> Option<UniformType> {
+	Some(match type_name(ty).as_str() {
+		"bool" => UniformType::Bool,
+		"f32" => UniformType::Float,
+		"i32" => UniformType::Int,
+		"u32" => UniformType::UInt,
+		"Vec2" => UniformType::FloatVec2,
+		"Vec3" => UniformType::FloatVec3,
+		"Vec4" => UniformType::FloatVec4,
+		"Mat2" => UniformType::FloatMatrix2x2,
+		"Mat3" => UniformType::FloatMatrix3x3,
+		"Mat4" => UniformType::FloatMatrix4x4,
+		"Rc<Texture>" => UniformType::Texture,
+		"Option<Rc<Texture>>" => UniformType::OptionalTexture,
+		_ => return None,
+	})
+}
+
+// normalMap -> hasNormalMap
+fn has_name(name: &Ident) -> Ident {
+	let name = name.to_string();
+	let mut chars = name.chars();
+	let capitalized: String = chars.next().into_iter()
+		.flat_map(char::to_uppercase).chain(chars).collect();
+	format_ident!("has{}", capitalized)
+}
+
+impl Parse for Field {
+	fn parse(input: ParseStream) -> syn::Result<Self> {
+		let attrs = input.call(Attribute::parse_outer)?;
+		let kind: Ident = input.parse()?;
+		let name: Ident = input.parse()?;
+		input.parse::<Token![:]>()?;
+		let ty: Type = input.parse()?;
+		match kind.to_string().as_str() {
+			"attr" => {
+				if let Some(attr) = attrs.first() {
+					return Err(syn::Error::new_spanned(attr, "Vertex attributes can't have attributes"));
+				}
+				let (size, data_type) = attribute_type(&ty)
+					.ok_or_else(|| syn::Error::new_spanned(&ty, "Unsupported vertex attribute type"))?;
+				Ok(Field::Attribute(AttributeDefinition { name, ty, size, data_type }))
+			},
+			"uniform" => {
+				let data_type = uniform_type(&ty)
+					.ok_or_else(|| syn::Error::new_spanned(&ty, "Unsupported uniform type"))?;
+				let mut target = TextureTarget::Texture2D;
+				for attr in attrs.iter() {
+					if attr.path.is_ident("cube") {
+						target = TextureTarget::Cube;
+					} else if attr.path.is_ident("buffer") {
+						target = TextureTarget::Buffer;
+					} else {
+						return Err(syn::Error::new_spanned(attr, "Unknown uniform attribute"));
+					}
+				}
+				if !matches!(target, TextureTarget::Texture2D) && !matches!(data_type, UniformType::Texture | UniformType::OptionalTexture) {
+					return Err(syn::Error::new_spanned(&ty, "Only textures can be cube maps or buffer textures"));
+				}
+				Ok(Field::Uniform(UniformDefinition { name, ty, data_type, target }))
+			},
+			_ => Err(syn::Error::new_spanned(kind, "Expected attr or uniform")),
+		}
+	}
+}
+
+impl Parse for ModelData {
+	fn parse(input: ParseStream) -> syn::Result<Self> {
+		let attrs = input.call(Attribute::parse_outer)?;
+		let name: Ident = input.parse()?;
+		let content;
+		braced!(content in input);
+		let fields = Punctuated::<Field, Token![,]>::parse_terminated(&content)?;
+		let mut attributes = Vec::new();
+		let mut uniforms = Vec::new();
+		fields.into_iter().for_each(|field| match field {
+			Field::Attribute(a) => attributes.push(a),
+			Field::Uniform(u) => uniforms.push(u),
+		});
+		Ok(ModelData { attrs, name, attributes, uniforms })
+	}
+}
+
+// Declare the vertex and uniform data for a shader program. For example:
+//
+// model_data!(#[derive(Debug, Clone)] Thing {
+// 	attr position: Vec3,
+// 	attr uv: Vec2,
+// 	uniform eye: Mat4,
+// 	uniform tex: Rc<Texture>,
+// 	#[cube] uniform sky: Option<Rc<Texture>>,
+// 	#[buffer] uniform values: Rc<Texture>,
+// });
+//
+// generates VertexThing, UniformsThing (with the given attributes), and
+// UniformsThingLocations, along with their InterleavedVertexAttribute,
+// ShaderUniformLocations, and ShaderUniforms implementations. Vertex
+// attributes get consecutive locations, and textures get consecutive texture
+// units, in the order they are declared. The uniforms have the same names
+// in the shader as the fields. InterleavedVertexAttribute,
+// ShaderUniformLocations, ShaderUniforms, Texture, and TextureUnit must be in
+// scope.
 #[proc_macro]
 pub fn model_data(v: TokenStream) -> TokenStream {
-	v
+	let ModelData { attrs, name, attributes, uniforms } = parse_macro_input!(v as ModelData);
+	let vertex_name = format_ident!("Vertex{}", name);
+	let uniforms_name = format_ident!("Uniforms{}", name);
+	let locations_name = format_ident!("Uniforms{}Locations", name);
+
+	let vertex = if attributes.is_empty() { quote!() } else {
+		let fields = attributes.iter().map(|AttributeDefinition { name, ty, .. }| quote!(pub #name: #ty));
+		let last = attributes.len() - 1;
+		let setup = attributes.iter().enumerate().map(|(index, attribute)| {
+			let AttributeDefinition { ty, size, data_type, .. } = attribute;
+			let index = index as u32;
+			let pointer = match data_type {
+				AttributeType::Float => quote!(glc.vertex_attrib_pointer_f32(#index, #size, ::glow::FLOAT, false, stride, offset)),
+				AttributeType::Int => quote!(glc.vertex_attrib_pointer_i32(#index, #size, ::glow::INT, stride, offset)),
+				AttributeType::UInt => quote!(glc.vertex_attrib_pointer_i32(#index, #size, ::glow::UNSIGNED_INT, stride, offset)),
+			};
+			let next = if index as usize == last { quote!() } else {
+				quote!(offset += ::std::mem::size_of::<#ty>() as i32;)
+			};
+			quote! {
+				#pointer;
+				glc.enable_vertex_attrib_array(#index);
+				#next
+			}
+		});
+		let offset = if last == 0 { quote!(let offset = 0;) } else { quote!(let mut offset = 0;) };
+		let types: Vec<_> = attributes.iter().map(|attribute| &attribute.ty).collect();
+		// Pod is implemented by hand, since the derive's padding check
+		// leaves unused code behind. The fields have to be Pod themselves,
+		// and there can't be any padding between them.
+		quote! {
+			#[allow(non_snake_case)]
+			#[repr(C)]
+			#[derive(Debug, Clone, Copy, Default)]
+			pub struct #vertex_name {
+				#(#fields,)*
+			}
+
+			const _: () = {
+				const fn pod<T: ::bytemuck::Pod>() -> usize { ::std::mem::size_of::<T>() }
+				assert!(::std::mem::size_of::<#vertex_name>() == 0 #(+ pod::<#types>())*);
+			};
+			unsafe impl ::bytemuck::Zeroable for #vertex_name {}
+			unsafe impl ::bytemuck::Pod for #vertex_name {}
+
+			impl InterleavedVertexAttribute for #vertex_name {
+				unsafe fn setup_vertex_attrs(glc: &::glow::Context) {
+					use ::glow::HasContext;
+					#offset
+					let stride = Self::stride();
+					#(#setup)*
+				}
+			}
+		}
+	};
+
+	let fields = uniforms.iter().map(|UniformDefinition { name, ty, .. }| quote!(pub #name: #ty));
+	// Uniforms which are set automatically, along with the fields
+	let location_names: Vec<_> = uniforms.iter().flat_map(|uniform| {
+		let has = match uniform.data_type {
+			UniformType::OptionalTexture => Some(has_name(&uniform.name)),
+			_ => None,
+		};
+		std::iter::once(uniform.name.clone()).chain(has)
+	}).collect();
+	let location_strings = location_names.iter().map(|name| name.to_string());
+
+	let texture_count = uniforms.iter()
+		.filter(|u| matches!(u.data_type, UniformType::Texture | UniformType::OptionalTexture))
+		.count();
+	let texture_unit = match texture_count {
+		0 => quote!(),
+		1 => quote!(let texture = TextureUnit::default();),
+		_ => quote!(let mut texture = TextureUnit::default();),
+	};
+	let mut first_texture = true;
+	let set = uniforms.iter().map(|uniform| {
+		let UniformDefinition { name, data_type, target, .. } = uniform;
+		let location = quote!(locations.#name.as_ref());
+		let target = match target {
+			TextureTarget::Texture2D => quote!(::glow::TEXTURE_2D),
+			TextureTarget::Cube => quote!(::glow::TEXTURE_CUBE_MAP),
+			TextureTarget::Buffer => quote!(crate::render::buffer_texture_target()),
+		};
+		let next = if first_texture { quote!() } else { quote!(texture.next();) };
+		match data_type {
+			UniformType::Bool => quote!(glc.uniform_1_u32(#location, self.#name as u32);),
+			UniformType::Float => quote!(glc.uniform_1_f32(#location, self.#name);),
+			UniformType::Int => quote!(glc.uniform_1_i32(#location, self.#name);),
+			UniformType::UInt => quote!(glc.uniform_1_u32(#location, self.#name);),
+			UniformType::FloatVec2 => quote!(glc.uniform_2_f32_slice(#location, self.#name.as_ref());),
+			UniformType::FloatVec3 => quote!(glc.uniform_3_f32_slice(#location, self.#name.as_ref());),
+			UniformType::FloatVec4 => quote!(glc.uniform_4_f32_slice(#location, self.#name.as_ref());),
+			UniformType::FloatMatrix2x2 => quote!(glc.uniform_matrix_2_f32_slice(#location, false, self.#name.as_ref());),
+			UniformType::FloatMatrix3x3 => quote!(glc.uniform_matrix_3_f32_slice(#location, false, self.#name.as_ref());),
+			UniformType::FloatMatrix4x4 => quote!(glc.uniform_matrix_4_f32_slice(#location, false, self.#name.as_ref());),
+			UniformType::Texture => {
+				first_texture = false;
+				quote! {
+					#next
+					glc.active_texture(texture.slot());
+					glc.bind_texture(#target, Some(self.#name.tex()));
+					glc.uniform_1_i32(#location, texture.uniform());
+				}
+			},
+			UniformType::OptionalTexture => {
+				first_texture = false;
+				let has = has_name(name);
+				// Samplers of different types can't share a texture unit, so
+				// a texture unit is used even if there is no texture
+				quote! {
+					#next
+					glc.active_texture(texture.slot());
+					glc.bind_texture(#target, self.#name.as_ref().map(|t| t.tex()));
+					glc.uniform_1_i32(#location, texture.uniform());
+					glc.uniform_1_u32(locations.#has.as_ref(), self.#name.is_some() as u32);
+				}
+			},
+		}
+	}).collect::<Vec<_>>();
+
+	quote! {
+		#vertex
+
+		#[allow(non_snake_case)]
+		#(#attrs)*
+		pub struct #uniforms_name {
+			#(#fields,)*
+		}
+
+		#[allow(non_snake_case)]
+		#[derive(Debug, Clone, Default)]
+		pub struct #locations_name {
+			#(#location_names: Option<::glow::UniformLocation>,)*
+		}
+
+		impl ShaderUniformLocations for #locations_name {
+			fn setup(&mut self, glc: &::glow::Context, program: <::glow::Context as ::glow::HasContext>::Program) {
+				use ::glow::HasContext;
+				unsafe {
+					#(self.#location_names = glc.get_uniform_location(program, #location_strings);)*
+				}
+			}
+		}
+
+		impl ShaderUniforms<#locations_name> for #uniforms_name {
+			fn set(&self, glc: &::glow::Context, locations: &#locations_name) {
+				use ::glow::HasContext;
+				#texture_unit
+				unsafe {
+					#(#set)*
+				}
+			}
+		}
+	}.into()
 }
@@ -1,32 +1,145 @@
 #version 330 core
 
 uniform sampler2D tex;
+uniform samplerCube sky;
 uniform uint mode;
+uniform bool envmap;
+uniform bool skyReflection;
+uniform sampler2D normalMap;
+uniform bool hasNormalMap;
+uniform sampler2D specularMap;
+uniform bool hasSpecularMap;
+uniform float alphaThreshold;
+uniform bool entityLight;
+uniform vec3 ambientLight;
+uniform vec3 directedLight;
+uniform vec3 lightDirection; // Towards the light, in model space
+uniform float maxDeviation;
+uniform float normalThreshold; // Degrees
+uniform sampler2D checker;
+uniform sampler2D fullbright;
+uniform bool hasFullbright;
+uniform float outline;
+uniform bool opaque;
 in vec3 position;
 in vec3 eyeNormal;
+in vec3 modelNormal;
+in vec3 eyeTangent;
+in vec3 eyeBitangent;
+in vec3 reflection;
 in vec2 uv;
+in float vertexDeviation;
+in float vertexNormalError;
 out vec4 FragColor;
 
 const uint MODE_TEXTURED = 0u;
 const uint MODE_UNTEXTURED = 1u;
 const uint MODE_NORMALS = 2u;
+const uint MODE_LIT = 3u;
+const uint MODE_DEVIATION = 4u;
+const uint MODE_NORMAL_CHECK = 5u;
+const uint MODE_UV_CHECKER = 6u;
+const uint MODE_BACKFACES = 7u;
+const uint MODE_SILHOUETTE = 8u;
+
+const float SHININESS = 32.;
+
+// Same as RB_CalcDiffuseColor in Quake 3
+vec3 entityLighting(vec3 normal) {
+	float incoming = max(0., dot(normalize(normal), lightDirection));
+	return min(ambientLight + directedLight * incoming, vec3(1.));
+}
 
 void main() {
 	vec3 up = vec3(0., 1., 0.);
-	float brightness = max(0., eyeNormal.z);
+	// The back of a two-sided surface is lit like the front
+	vec3 frontNormal = gl_FrontFacing ? eyeNormal : -eyeNormal;
+	float brightness = max(0., frontNormal.z);
+	vec3 light = vec3(brightness);
+	if (entityLight) {
+		light = entityLighting(gl_FrontFacing ? modelNormal : -modelNormal);
+	}
 	// FragColor = vec4(vec3(brightness), 1.);
 	// FragColor = vec4(eyeNormal, 1.);
 	switch (mode) {
 		case MODE_NORMALS:
 			FragColor = vec4(eyeNormal, 1.);
 			break;
+		case MODE_LIT: {
+			vec3 normal = normalize(frontNormal);
+			if (hasNormalMap) {
+				vec3 tangentNormal = texture(normalMap, uv).xyz * 2. - 1.;
+				mat3 tbn = mat3(normalize(eyeTangent), normalize(eyeBitangent), normal);
+				normal = normalize(tbn * tangentNormal);
+			}
+			float diffuse = max(0., normal.z);
+			// The light is at the camera, so the half vector is the same as
+			// the view direction
+			float specular = 0.;
+			if (hasSpecularMap) {
+				specular = pow(diffuse, SHININESS) * texture(specularMap, uv).r;
+			}
+			vec4 texel = texture(tex, uv);
+			FragColor = texel;
+			FragColor.rgb = FragColor.rgb * diffuse + vec3(specular);
+			if (hasFullbright) {
+				FragColor.rgb = mix(FragColor.rgb, texel.rgb, texture(fullbright, uv).r);
+			}
+			break;
+		}
+		case MODE_DEVIATION: {
+			// Blue where the models are the same, through green, to red where
+			// they are the furthest apart
+			float heat = maxDeviation > 0. ? clamp(vertexDeviation / maxDeviation, 0., 1.) : 0.;
+			vec3 colour = heat < .5 ?
+				mix(vec3(0., 0., 1.), vec3(0., 1., 0.), heat * 2.) :
+				mix(vec3(0., 1., 0.), vec3(1., 0., 0.), heat * 2. - 1.);
+			FragColor = vec4(colour * (.5 + .5 * brightness), 1.);
+			break;
+		}
+		case MODE_NORMAL_CHECK:
+			// Normals which are too far from the calculated normals are red
+			FragColor = vertexNormalError > normalThreshold ?
+				vec4(vec3(1., 0., 0.) * (.5 + .5 * brightness), 1.) :
+				vec4(vec3(.5 + .5 * brightness), 1.);
+			break;
+		case MODE_UV_CHECKER:
+			FragColor = vec4(texture(checker, uv).rgb * (.5 + .5 * brightness), 1.);
+			break;
+		case MODE_BACKFACES:
+			// Back faces are drawn even on one-sided surfaces
+			FragColor = gl_FrontFacing ? vec4(light, 1.) : vec4(1., 0., 1., 1.);
+			break;
+		case MODE_SILHOUETTE:
+			// The model is drawn in the background colour, inside a bigger
+			// copy of it turned inside out
+			FragColor = vec4(vec3(outline > 0. ? 1. : 0.), texture(tex, uv).a);
+			break;
 		case MODE_UNTEXTURED:
-			FragColor = vec4(vec3(brightness), 1.);
+			FragColor = vec4(light, 1.);
 			break;
 		case MODE_TEXTURED:
 		default:
-			FragColor = texture(tex, uv);
-			FragColor.rgb *= brightness;
+			vec4 texel = texture(tex, uv);
+			FragColor = texel;
+			if (envmap && skyReflection) {
+				// Quake is Z-up, but OpenGL cube maps are Y-up
+				FragColor.rgb *= texture(sky, reflection.xzy).rgb;
+			} else {
+				FragColor.rgb *= light;
+			}
+			// Fullbright texels are drawn as they are, whatever the lighting
+			if (hasFullbright) {
+				FragColor.rgb = mix(FragColor.rgb, texel.rgb, texture(fullbright, uv).r);
+			}
 			break;
 	}
+	if (FragColor.a < alphaThreshold) {
+		discard;
+	}
+	// Screenshots with a transparent background shouldn't be see-through
+	// where the skin has alpha
+	if (opaque) {
+		FragColor.a = 1.;
+	}
 }
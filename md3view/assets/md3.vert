@@ -1,15 +1,40 @@
 #version 330 core
 
+// Without buffer textures (OpenGL ES 3.0), the data is in 2D textures, row
+// after row
+#ifdef BUFFER_TEXTURES
+#define VALUE_SAMPLER samplerBuffer
+#define ANIM_SAMPLER isamplerBuffer
+#else
+#define VALUE_SAMPLER sampler2D
+#define ANIM_SAMPLER isampler2D
+#endif
+
 uniform bool gzdoom;
-uniform isampler2D anim;
+uniform ANIM_SAMPLER anim; // Every vertex of every frame
 uniform mat4 eye;
-uniform int rowsPerFrame;
+uniform mat4 model; // Model space to world space
+uniform int numVerts;
 uniform float frame; // interpolated
+uniform bool envmap; // tcGen environment
+uniform vec3 viewOrigin; // Camera position in model space
+uniform VALUE_SAMPLER deviation; // Laid out like anim
+uniform bool hasDeviation;
+uniform VALUE_SAMPLER normalError; // Laid out like anim
+uniform bool hasNormalError;
+uniform float outline; // Distance to push the vertices out along their normals
 layout(location=0) in uint aIndex;
 layout(location=1) in vec2 aUv;
+layout(location=2) in vec4 aTangent; // w is the handedness of the bitangent
 out vec3 position;
 out vec3 eyeNormal;
+out vec3 modelNormal;
+out vec3 eyeTangent;
+out vec3 eyeBitangent;
+out vec3 reflection;
 out vec2 uv;
+out float vertexDeviation;
+out float vertexNormalError;
 
 const float MD3_XYZ_SCALE = 0.015625; //1./64
 const float BYTE_TAU = 40.58451048843331062106; //255./(2.*pi)
@@ -17,6 +42,8 @@ const float BYTE_TAU = 40.58451048843331062106; //255./(2.*pi)
 vec3[2] toPosNorm(ivec4 raw) {
 	vec3 xyz = vec3(raw.xyz) * MD3_XYZ_SCALE;
 	vec3 normal = vec3(0.);
+	// The texture is signed 16-bit, but the normal is unsigned
+	raw.w &= 0xFFFF;
 	if (!gzdoom) {
 		switch (raw.w) {
 			// special cases
@@ -43,33 +70,75 @@ vec3[2] toPosNorm(ivec4 raw) {
 	return vec3[2](xyz, normal);
 }
 
-ivec2 indexToVertexLoc(uint index, int width, int frame) {
-	int col = int(index) % width;
-	int row = int(index) / width + frame * rowsPerFrame;
-	return ivec2(col, row);
+int vertexLoc(int frame) {
+	return frame * numVerts + int(aIndex);
+}
+
+#ifdef BUFFER_TEXTURES
+ivec4 fetchAnim(int loc) {
+	return texelFetch(anim, loc);
+}
+
+float fetchValue(VALUE_SAMPLER values, int loc) {
+	return texelFetch(values, loc).r;
+}
+#else
+ivec2 texelLoc(int loc, int width) {
+	return ivec2(loc % width, loc / width);
+}
+
+ivec4 fetchAnim(int loc) {
+	return texelFetch(anim, texelLoc(loc, textureSize(anim, 0).x), 0);
+}
+
+float fetchValue(VALUE_SAMPLER values, int loc) {
+	return texelFetch(values, texelLoc(loc, textureSize(values, 0).x), 0).r;
+}
+#endif
+
+// Interpolated value of this vertex from a float texture laid out like anim
+float vertexValue(VALUE_SAMPLER values, float frame) {
+	float a = fetchValue(values, vertexLoc(int(floor(frame))));
+	float b = fetchValue(values, vertexLoc(int(ceil(frame))));
+	return mix(a, b, fract(frame));
 }
 
 void main() {
-	int animWidth = textureSize(anim, 0).x;
+	mat4 modelEye = eye * model;
 	float interp = fract(frame);
 	// Which frames to use?
 	int framea = int(floor(frame));
 	int frameb = int(ceil(frame));
-	// Vertex positions and normals are stored in an RGBA integer texture, with
-	// the vertices (by index) on rectangles within the texture. The frames are
-	// rectangles, stacked vertically, containing the vertex position data as
-	// RGBA colours, which are converted into positions and normals by the
-	// toPosNorm function
-	ivec2 uva = indexToVertexLoc(aIndex, animWidth, framea);
-	ivec2 uvb = indexToVertexLoc(aIndex, animWidth, frameb);
-	ivec4 ia = texelFetch(anim, uva, 0);
-	ivec4 ib = texelFetch(anim, uvb, 0);
+	// Vertex positions and normals are stored in an RGBA integer buffer
+	// texture, one frame after another, which are converted into positions
+	// and normals by the toPosNorm function
+	ivec4 ia = fetchAnim(vertexLoc(framea));
+	ivec4 ib = fetchAnim(vertexLoc(frameb));
 	vec3[2] va = toPosNorm(ia);
 	vec3[2] vb = toPosNorm(ib);
 	position = mix(va[0], vb[0], interp);
+	vertexDeviation = hasDeviation ? vertexValue(deviation, frame) : 0.;
+	vertexNormalError = hasNormalError ? vertexValue(normalError, frame) : 0.;
 	// Thanks to https://en.wikibooks.org/wiki/GLSL_Programming/Applying_Matrix_Transformations#Transforming_Directions for "pointing me in the right direction" 😉😉
-	eyeNormal = (eye * vec4(mix(va[1], vb[1], interp), 0.)).xyz;
+	eyeNormal = (modelEye * vec4(mix(va[1], vb[1], interp), 0.)).xyz;
 	eyeNormal.z = -eyeNormal.z;
-	uv = aUv;
-	gl_Position = eye * vec4(position, 1.);
+	vec3 normal = normalize(mix(va[1], vb[1], interp));
+	modelNormal = normal;
+	// The tangent was calculated for the first frame, so it needs to be
+	// re-orthogonalized for the current frame's normal
+	vec3 tangent = normalize(aTangent.xyz - normal * dot(normal, aTangent.xyz));
+	vec3 bitangent = cross(normal, tangent) * aTangent.w;
+	eyeTangent = (modelEye * vec4(tangent, 0.)).xyz;
+	eyeTangent.z = -eyeTangent.z;
+	eyeBitangent = (modelEye * vec4(bitangent, 0.)).xyz;
+	eyeBitangent.z = -eyeBitangent.z;
+	vec3 viewer = normalize(viewOrigin - position);
+	reflection = normal * 2. * dot(normal, viewer) - viewer;
+	if (envmap) {
+		// Same as RB_CalcEnvironmentTexCoords in Quake 3
+		uv = vec2(.5 + reflection.y * .5, .5 - reflection.z * .5);
+	} else {
+		uv = aUv;
+	}
+	gl_Position = modelEye * vec4(position + normal * outline, 1.);
 }
@@ -0,0 +1,11 @@
+#version 330 core
+
+uniform samplerCube sky;
+in vec4 direction;
+out vec4 FragColor;
+
+void main() {
+	// Quake is Z-up, but OpenGL cube maps are Y-up
+	vec3 dir = direction.xyz / direction.w;
+	FragColor = vec4(texture(sky, dir.xzy).rgb, 1.);
+}
@@ -0,0 +1,12 @@
+#version 330 core
+
+uniform mat4 invEye;
+layout(location=0) in vec2 aPosition;
+out vec4 direction;
+
+void main() {
+	// Un-project the screen position to get the view direction. The division
+	// by w is done per-fragment, since the interpolation must be projective.
+	direction = invEye * vec4(aPosition, 1., 1.);
+	gl_Position = vec4(aPosition, 1., 1.);
+}
@@ -0,0 +1,124 @@
+use anyhow::Error;
+use serde::{Serialize, Deserialize};
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+const BACKUP_EXTENSION: &str = "bak";
+const BACKUP_FOLDER: &str = "backups";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+	pub enabled: bool,
+	// Put backups in a "backups" folder instead of beside the file
+	pub in_folder: bool,
+	// How many backups of each file to keep. 0 to keep all of them.
+	pub keep: usize,
+}
+
+impl Default for BackupSettings {
+	fn default() -> Self {
+		Self {
+			enabled: true,
+			in_folder: true,
+			keep: 5,
+		}
+	}
+}
+
+// Copy a file which is about to be overwritten, and remove its oldest
+// backups. Returns the path of the backup, if one was made.
+pub fn backup(path: &Path, settings: &BackupSettings) -> Result<Option<PathBuf>, Error> {
+	if !settings.enabled || !path.is_file() {
+		return Ok(None);
+	}
+	let file_name = path.file_name()
+		.ok_or_else(|| Error::msg("Path does not have a file name"))?.to_string_lossy();
+	let directory = path.parent().unwrap_or(Path::new(""));
+	let directory = match settings.in_folder {
+		true => directory.join(BACKUP_FOLDER),
+		false => directory.to_path_buf(),
+	};
+	fs::create_dir_all(&directory)?;
+	let backup_path = directory.join(format!("{}.{}.{}",
+		file_name, timestamp(SystemTime::now()), BACKUP_EXTENSION));
+	fs::copy(path, &backup_path)?;
+	if settings.keep > 0 {
+		prune(&directory, &file_name, settings.keep)?;
+	}
+	Ok(Some(backup_path))
+}
+
+// Remove all but the newest backups of a file
+fn prune(directory: &Path, file_name: &str, keep: usize) -> Result<(), Error> {
+	let prefix = format!("{}.", file_name);
+	let suffix = format!(".{}", BACKUP_EXTENSION);
+	let mut backups: Vec<PathBuf> = fs::read_dir(directory)?
+		.filter_map(|entry| entry.ok().map(|e| e.path()))
+		.filter(|path| path.file_name().map(|name| {
+			let name = name.to_string_lossy();
+			name.starts_with(&prefix) && name.ends_with(&suffix)
+		}).unwrap_or(false))
+		.collect();
+	// The timestamps sort in the same order as the times
+	backups.sort();
+	let excess = backups.len().saturating_sub(keep);
+	backups[..excess].iter().try_for_each(fs::remove_file)?;
+	Ok(())
+}
+
+// UTC date and time, like 20221231-235959
+fn timestamp(time: SystemTime) -> String {
+	let seconds = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+	let (days, time_of_day) = (seconds / 86400, seconds % 86400);
+	// Convert days since 1970-01-01 to a date. See
+	// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+	let z = days as i64 + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + i64::from(month <= 2);
+	format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day,
+		time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn timestamps() {
+		assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000");
+		let time = UNIX_EPOCH + Duration::from_secs(1709251199);
+		assert_eq!(timestamp(time), "20240229-235959");
+	}
+
+	#[test]
+	fn backup_retention() {
+		let directory = std::env::temp_dir().join(format!("rustmd3view-backup-{}", std::process::id()));
+		fs::create_dir_all(&directory).unwrap();
+		let model = directory.join("model.md3");
+		fs::write(&model, b"IDP3").unwrap();
+		// Older backups of this model, and a backup of a different model
+		let backups = directory.join(BACKUP_FOLDER);
+		fs::create_dir_all(&backups).unwrap();
+		["model.md3.20000101-000000.bak", "model.md3.20010101-000000.bak",
+		"other.md3.20000101-000000.bak"].iter()
+			.for_each(|name| fs::write(backups.join(name), b"").unwrap());
+		let settings = BackupSettings { keep: 2, ..Default::default() };
+		let backup_path = backup(&model, &settings).unwrap().unwrap();
+		assert_eq!(fs::read(&backup_path).unwrap(), b"IDP3");
+		assert!(!backups.join("model.md3.20000101-000000.bak").exists());
+		assert!(backups.join("model.md3.20010101-000000.bak").exists());
+		assert!(backups.join("other.md3.20000101-000000.bak").exists());
+		fs::remove_dir_all(&directory).unwrap();
+	}
+}
@@ -0,0 +1,74 @@
+// Checking models without opening a window, for build scripts. The report is
+// JSON, so other tools can read it.
+use anyhow::Error;
+use rustmd3::{limits::{self, EngineProfile}, md3};
+use serde::Serialize;
+use std::{
+	fs::File,
+	path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckedModel {
+	pub path: PathBuf,
+	// Why the model couldn't be read
+	pub errors: Vec<String>,
+	pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+	pub models: Vec<CheckedModel>,
+	pub errors: usize,
+	pub warnings: usize,
+}
+
+impl Report {
+	pub fn passed(&self) -> bool {
+		self.errors == 0
+	}
+}
+
+pub fn check_model(path: &Path, engine: EngineProfile) -> CheckedModel {
+	let model = File::open(path).map_err(Error::from)
+		.and_then(|mut f| md3::read_md3(&mut f).map_err(Error::from));
+	let (errors, warnings) = match model {
+		Ok(model) => (vec![], model.warnings().iter().map(ToString::to_string)
+			.chain(limits::check_limits(&model, engine).iter().map(ToString::to_string))
+			.collect()),
+		Err(e) => (vec![e.to_string()], vec![]),
+	};
+	CheckedModel { path: path.to_path_buf(), errors, warnings }
+}
+
+// Check the given models, and the models in the given folders
+pub fn check(paths: &[PathBuf], engine: EngineProfile) -> Report {
+	let models: Vec<CheckedModel> = paths.iter().flat_map(|path| match path.is_dir() {
+		true => match crate::find_models(path) {
+			Ok(models) => models.iter().map(|model| check_model(model, engine)).collect(),
+			Err(e) => vec![CheckedModel { path: path.clone(), errors: vec![e.to_string()], warnings: vec![] }],
+		},
+		false => vec![check_model(path, engine)],
+	}).collect();
+	Report {
+		errors: models.iter().map(|m| m.errors.len()).sum(),
+		warnings: models.iter().map(|m| m.warnings.len()).sum(),
+		models,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn missing_model() {
+		let report = check(&[PathBuf::from("does/not/exist.md3")], EngineProfile::default());
+		assert_eq!(report.models.len(), 1);
+		assert_eq!(report.errors, 1);
+		assert!(!report.passed());
+		let json = serde_json::to_value(&report).unwrap();
+		assert_eq!(json["models"][0]["path"], "does/not/exist.md3");
+		assert_eq!(json["warnings"], 0);
+	}
+}
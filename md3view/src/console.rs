@@ -0,0 +1,120 @@
+// Everything logged with the log crate, kept for the log window and written
+// to a file, because there's nowhere to see stderr when the viewer is
+// started from a desktop icon.
+use crate::settings::Settings;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+use std::{
+	collections::VecDeque,
+	fs::{self, File},
+	io::Write,
+	path::PathBuf,
+	sync::{Mutex, atomic::{AtomicBool, Ordering}},
+	time::Duration,
+};
+use instant::Instant;
+
+// How many entries the log window keeps
+const LENGTH: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+	pub level: Level,
+	pub target: String,
+	pub message: String,
+	// Since the viewer started
+	pub time: Duration,
+}
+
+impl std::fmt::Display for Entry {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "[{:.3}] {} {}: {}", self.time.as_secs_f32(), self.level, self.target, self.message)
+	}
+}
+
+struct Console {
+	start: Instant,
+	entries: Mutex<VecDeque<Entry>>,
+	file: Mutex<Option<File>>,
+	// Something was logged since the window was last drawn
+	changed: AtomicBool,
+}
+
+static CONSOLE: Lazy<Console> = Lazy::new(|| Console {
+	start: Instant::now(),
+	entries: Mutex::new(VecDeque::new()),
+	file: Mutex::new(None),
+	changed: AtomicBool::new(false),
+});
+
+impl Log for Console {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= log::max_level()
+	}
+	fn log(&self, record: &Record) {
+		if !self.enabled(record.metadata()) { return; }
+		let entry = Entry {
+			level: record.level(),
+			target: record.target().to_string(),
+			message: record.args().to_string(),
+			time: self.start.elapsed(),
+		};
+		#[cfg(not(target_arch = "wasm32"))]
+		eprintln!("{}", entry);
+		#[cfg(target_arch = "wasm32")]
+		web_sys::console::log_1(&entry.to_string().into());
+		if let Ok(mut file) = self.file.lock() {
+			if let Some(file) = file.as_mut() {
+				writeln!(file, "{}", entry).ok();
+			}
+		}
+		if let Ok(mut entries) = self.entries.lock() {
+			if entries.len() == LENGTH {
+				entries.pop_front();
+			}
+			entries.push_back(entry);
+		}
+		self.changed.store(true, Ordering::Relaxed);
+	}
+	fn flush(&self) {
+		if let Ok(mut file) = self.file.lock() {
+			if let Some(file) = file.as_mut() {
+				file.flush().ok();
+			}
+		}
+	}
+}
+
+// Next to the settings file
+pub fn path() -> Option<PathBuf> {
+	Settings::path().map(|settings| settings.with_file_name("rustmd3view.log"))
+}
+
+// Start logging, to the log file too if it can be made
+pub fn init() {
+	let file = path().and_then(|path| {
+		fs::create_dir_all(path.parent()?).ok()?;
+		File::create(path).ok()
+	});
+	if let Ok(mut console_file) = CONSOLE.file.lock() {
+		*console_file = file;
+	}
+	if log::set_logger(&*CONSOLE).is_ok() {
+		log::set_max_level(if cfg!(debug_assertions) { LevelFilter::Debug } else { LevelFilter::Info });
+	}
+}
+
+pub fn with_entries<T>(f: impl FnOnce(&VecDeque<Entry>) -> T) -> Option<T> {
+	CONSOLE.entries.lock().ok().map(|entries| f(&entries))
+}
+
+pub fn clear() {
+	if let Ok(mut entries) = CONSOLE.entries.lock() {
+		entries.clear();
+	}
+}
+
+// Whether anything was logged since the last call
+pub fn changed() -> bool {
+	CONSOLE.changed.swap(false, Ordering::Relaxed)
+}
@@ -1,4 +1,9 @@
-use std::error::Error;
+use std::{
+	error::Error,
+	sync::atomic::{AtomicBool, Ordering},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::ffi::{c_char, c_void, CStr};
 use glow::{
 	Context as GLContext, HasContext,
 	NO_ERROR,
@@ -64,3 +69,90 @@ impl GLError {
 		}
 	}
 }
+
+// Messages from the driver, through KHR_debug, which WebGL doesn't have
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct DebugMessage {
+	pub source: u32,
+	pub kind: u32,
+	pub id: u32,
+	pub severity: u32,
+	pub message: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DebugMessage {
+	pub fn severity_name(&self) -> &'static str {
+		match self.severity {
+			glow::DEBUG_SEVERITY_HIGH => "High",
+			glow::DEBUG_SEVERITY_MEDIUM => "Medium",
+			glow::DEBUG_SEVERITY_LOW => "Low",
+			_ => "Notification",
+		}
+	}
+	pub fn source_name(&self) -> &'static str {
+		match self.source {
+			glow::DEBUG_SOURCE_API => "API",
+			glow::DEBUG_SOURCE_WINDOW_SYSTEM => "Window system",
+			glow::DEBUG_SOURCE_SHADER_COMPILER => "Shader compiler",
+			glow::DEBUG_SOURCE_THIRD_PARTY => "Third party",
+			glow::DEBUG_SOURCE_APPLICATION => "Application",
+			_ => "Other",
+		}
+	}
+	pub fn kind_name(&self) -> &'static str {
+		match self.kind {
+			glow::DEBUG_TYPE_ERROR => "Error",
+			glow::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "Deprecated",
+			glow::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "Undefined behaviour",
+			glow::DEBUG_TYPE_PORTABILITY => "Portability",
+			glow::DEBUG_TYPE_PERFORMANCE => "Performance",
+			_ => "Other",
+		}
+	}
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl std::fmt::Display for DebugMessage {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} {} ({}, {}): {}", self.source_name(), self.kind_name(),
+			self.severity_name(), self.id, self.message)
+	}
+}
+
+// glDebugMessageCallback, which is loaded with the window. glow has it, but
+// the callback it gives OpenGL doesn't live long enough.
+#[cfg(not(target_arch = "wasm32"))]
+pub type DebugProc = extern "system" fn(source: u32, kind: u32, id: u32, severity: u32,
+	length: i32, message: *const c_char, user_param: *mut c_void);
+#[cfg(not(target_arch = "wasm32"))]
+pub type DebugMessageCallbackFn = unsafe extern "system" fn(callback: Option<DebugProc>, user_param: *const c_void);
+
+static DEBUG_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+// Whether errors are reported by the driver, so they don't need to be
+// checked for after every draw call
+pub fn debug_output() -> bool {
+	DEBUG_OUTPUT.load(Ordering::Relaxed)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_debug_output(enabled: bool) {
+	DEBUG_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub extern "system" fn debug_callback(source: u32, kind: u32, id: u32, severity: u32,
+	_length: i32, message: *const c_char, _user_param: *mut c_void) {
+	if message.is_null() { return; }
+	let message = unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned();
+	let message = DebugMessage { source, kind, id, severity, message };
+	let level = match severity {
+		glow::DEBUG_SEVERITY_HIGH => log::Level::Error,
+		glow::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
+		glow::DEBUG_SEVERITY_LOW => log::Level::Info,
+		_ => log::Level::Debug,
+	};
+	log::log!(target: "opengl", level, "{}", message);
+}
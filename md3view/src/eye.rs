@@ -1,4 +1,6 @@
 use glam::{Vec3, Mat4};
+use std::f32::consts::{PI, TAU};
+use serde::{Serialize, Deserialize};
 
 pub trait Camera {
 	fn view_projection(&self) -> Mat4;
@@ -12,15 +14,61 @@ pub struct OrbitCamera {
 	pub distance: f32,
 	pub fov: f32,
 	pub aspect: f32,
+	pub near: f32,
+	pub far: f32,
+	pub orthographic: bool,
+	// The point the camera orbits around
+	pub target: Vec3,
 }
 
 impl OrbitCamera {
-	pub fn position(&self) -> Vec3 {
+	// Unit vector pointing from the camera towards the target
+	pub fn forward(&self) -> Vec3 {
 		Vec3::new(
 			self.longtude.cos() * self.latitude.cos(),
 			self.longtude.sin() * self.latitude.cos(),
 			self.latitude.sin(),
-		) * -self.distance
+		)
+	}
+	pub fn position(&self) -> Vec3 {
+		self.target - self.forward() * self.distance
+	}
+	// Move the target across the view. dx and dy are relative to half of the
+	// view's height at the target, and positive y is down.
+	pub fn pan(&mut self, dx: f32, dy: f32) {
+		let forward = self.forward();
+		let right = Vec3::Z.cross(forward).normalize_or_zero();
+		let up = forward.cross(right);
+		self.target += (right * -dx + up * dy) * self.ortho_scale();
+	}
+	// Look at the centre of a bounding box, from far enough away to see all
+	// of it from any angle
+	pub fn frame(&mut self, min: Vec3, max: Vec3) {
+		let radius = (max - min).length() * 0.5;
+		let half_fov = self.fov * 0.5;
+		let half_fov = half_fov.min((half_fov.tan() * self.aspect).atan());
+		self.target = (min + max) * 0.5;
+		self.distance = (radius / half_fov.sin()).max(self.near);
+	}
+	// Half of the height of the orthographic view. Things at the origin are
+	// the same size in both projections.
+	pub fn ortho_scale(&self) -> f32 {
+		self.distance.max(self.near) * (self.fov * 0.5).tan()
+	}
+	pub fn projection(&self) -> Mat4 {
+		if self.orthographic {
+			let (height, width) = (self.ortho_scale(), self.ortho_scale() * self.aspect);
+			// Nothing behind the camera should be cut off
+			Mat4::orthographic_lh(-width, width, -height, height, -self.far, self.far)
+		} else {
+			Mat4::perspective_lh(self.fov, self.aspect, self.near, self.far)
+		}
+	}
+	// View-projection matrix without translation, for drawing the skybox. The
+	// skybox is always drawn in perspective.
+	pub fn rotation_projection(&self) -> Mat4 {
+		let view = Mat4::look_at_lh(Vec3::ZERO, self.forward(), Vec3::Z);
+		Mat4::perspective_lh(self.fov, self.aspect, self.near, self.far) * view
 	}
 }
 
@@ -32,6 +80,10 @@ impl Default for OrbitCamera {
 			distance: 0.,
 			fov: 80f32.to_radians(),
 			aspect: 1.,
+			near: 0.25,
+			far: 4096.,
+			orthographic: false,
+			target: Vec3::ZERO,
 		}
 	}
 }
@@ -39,8 +91,163 @@ impl Default for OrbitCamera {
 impl Camera for OrbitCamera {
 	fn view_projection(&self) -> Mat4 {
 		let eye = self.position();
-		let view = Mat4::look_at_lh(eye, Vec3::ZERO, Vec3::Z);
-		let proj = Mat4::perspective_lh(self.fov, self.aspect, 0.25, 4096.);
-		proj * view
+		let view = Mat4::look_at_lh(eye, self.target, Vec3::Z);
+		self.projection() * view
+	}
+}
+
+// Moves an orbit camera smoothly from one view to another
+#[derive(Debug, Clone, Copy)]
+pub struct CameraTween {
+	from: OrbitCamera,
+	to: OrbitCamera,
+	// Seconds
+	duration: f32,
+}
+
+impl CameraTween {
+	pub fn new(from: OrbitCamera, mut to: OrbitCamera, duration: f32) -> Self {
+		// Go the short way around
+		let turn = (to.longtude - from.longtude + PI).rem_euclid(TAU) - PI;
+		to.longtude = from.longtude + turn;
+		Self { from, to, duration }
+	}
+	// Set the orbit of the camera as it is at the given time since the start
+	pub fn apply(&self, camera: &mut OrbitCamera, time: f32) {
+		let t = (time / self.duration).clamp(0., 1.);
+		// Smoothstep, to ease in and out
+		let t = t * t * (3. - 2. * t);
+		let lerp = |a: f32, b: f32| a + (b - a) * t;
+		camera.longtude = lerp(self.from.longtude, self.to.longtude);
+		camera.latitude = lerp(self.from.latitude, self.to.latitude);
+		camera.distance = lerp(self.from.distance, self.to.distance);
+		camera.target = self.from.target.lerp(self.to.target, t);
+	}
+	pub fn finished(&self, time: f32) -> bool {
+		time >= self.duration
+	}
+}
+
+// A camera which looks from one point towards another
+#[derive(Debug, Clone, Copy)]
+pub struct LookAtCamera {
+	pub position: Vec3,
+	pub target: Vec3,
+	pub fov: f32,
+	pub aspect: f32,
+	pub near: f32,
+	pub far: f32,
+}
+
+impl LookAtCamera {
+	pub fn projection(&self) -> Mat4 {
+		Mat4::perspective_lh(self.fov, self.aspect, self.near, self.far)
+	}
+	// View-projection matrix without translation, for drawing the skybox
+	pub fn rotation_projection(&self) -> Mat4 {
+		let view = Mat4::look_at_lh(Vec3::ZERO, self.target - self.position, Vec3::Z);
+		self.projection() * view
+	}
+}
+
+impl Camera for LookAtCamera {
+	fn view_projection(&self) -> Mat4 {
+		let view = Mat4::look_at_lh(self.position, self.target, Vec3::Z);
+		self.projection() * view
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+	// Seconds since the start of the path
+	pub time: f32,
+	pub position: Vec3,
+	pub target: Vec3,
+}
+
+// Linearly interpolate the camera position and target along a path at the
+// given time. The keyframes must be sorted by time.
+pub fn sample_camera_path(path: &[CameraKeyframe], time: f32) -> Option<(Vec3, Vec3)> {
+	let next = path.iter().position(|kf| kf.time > time);
+	match next {
+		Some(0) => path.first().map(|kf| (kf.position, kf.target)),
+		Some(next) => {
+			let (a, b) = (&path[next - 1], &path[next]);
+			let f = (time - a.time) / (b.time - a.time);
+			Some((a.position.lerp(b.position, f), a.target.lerp(b.target, f)))
+		},
+		None => path.last().map(|kf| (kf.position, kf.target)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn camera_path() {
+		let path = [
+			CameraKeyframe { time: 1., position: Vec3::ZERO, target: Vec3::X },
+			CameraKeyframe { time: 3., position: Vec3::new(0., 4., 0.), target: Vec3::X },
+		];
+		assert_eq!(sample_camera_path(&[], 0.), None);
+		assert_eq!(sample_camera_path(&path, 0.), Some((Vec3::ZERO, Vec3::X)));
+		assert_eq!(sample_camera_path(&path, 2.), Some((Vec3::new(0., 2., 0.), Vec3::X)));
+		assert_eq!(sample_camera_path(&path, 5.), Some((Vec3::new(0., 4., 0.), Vec3::X)));
+	}
+
+	#[test]
+	fn orthographic_scale() {
+		let camera = OrbitCamera {
+			distance: 100.,
+			fov: 90f32.to_radians(),
+			orthographic: true,
+			..Default::default()
+		};
+		// 45 degrees up from the view direction, 100 units away
+		let top = camera.view_projection().project_point3(Vec3::new(0., 0., 100.));
+		assert!((top.y - 1.).abs() < 1e-4);
+		// Same place on screen, but further away
+		let behind = camera.view_projection().project_point3(Vec3::new(50., 0., 100.));
+		assert!((behind.y - 1.).abs() < 1e-4);
+		assert!(behind.z > top.z);
+	}
+
+	#[test]
+	fn frame_and_pan() {
+		let mut camera = OrbitCamera { aspect: 0.5, latitude: 0.3, ..Default::default() };
+		let (min, max) = (Vec3::new(10., 10., 0.), Vec3::new(30., 20., 50.));
+		camera.frame(min, max);
+		assert_eq!(camera.target, Vec3::new(20., 15., 25.));
+		let view_projection = camera.view_projection();
+		(0..8).for_each(|corner| {
+			let point = Vec3::select(glam::BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0), max, min);
+			let ndc = view_projection.project_point3(point);
+			assert!(ndc.x.abs() <= 1. && ndc.y.abs() <= 1., "{:?} is outside of the view", point);
+		});
+		// The camera looks along +X, so its right is +Y. Dragging to the right
+		// moves the target to the left.
+		camera.latitude = 0.;
+		camera.pan(1., 0.);
+		assert!(camera.target.y < 15.);
+		assert_eq!(camera.target.z, 25.);
+	}
+
+	#[test]
+	fn tween() {
+		let from = OrbitCamera { longtude: 0.1, distance: 10., ..Default::default() };
+		let to = OrbitCamera { longtude: TAU - 0.1, distance: 20., target: Vec3::X, ..Default::default() };
+		let tween = CameraTween::new(from, to, 0.25);
+		let mut camera = from;
+		tween.apply(&mut camera, 0.125);
+		// Halfway, going backwards through 0
+		assert!(camera.longtude.abs() < 1e-5);
+		assert_eq!(camera.distance, 15.);
+		assert_eq!(camera.target, Vec3::new(0.5, 0., 0.));
+		assert!(!tween.finished(0.125));
+		tween.apply(&mut camera, 1.);
+		assert!((camera.longtude + 0.1).abs() < 1e-5);
+		assert_eq!(camera.distance, 20.);
+		assert!(tween.finished(1.));
 	}
 }
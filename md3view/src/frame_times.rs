@@ -0,0 +1,67 @@
+// How long recent frames took to draw, for the frame time graph, and for
+// limiting the frame rate
+use std::{
+	collections::VecDeque,
+	time::Duration,
+};
+use instant::Instant;
+
+// Number of frames shown in the graph
+pub const HISTORY: usize = 240;
+
+#[derive(Debug, Clone, Default)]
+pub struct FrameTimes {
+	times: VecDeque<Duration>,
+	last_frame: Option<Instant>,
+}
+
+impl FrameTimes {
+	pub fn record(&mut self, start: Instant, end: Instant) {
+		if self.times.len() == HISTORY {
+			self.times.pop_front();
+		}
+		self.times.push_back(end.saturating_duration_since(start));
+		self.last_frame = Some(start);
+	}
+	// Oldest first
+	pub fn times(&self) -> impl Iterator<Item = Duration> + '_ {
+		self.times.iter().copied()
+	}
+	pub fn average(&self) -> Duration {
+		match self.times.len() {
+			0 => Duration::ZERO,
+			count => self.times.iter().sum::<Duration>() / count as u32,
+		}
+	}
+	pub fn max(&self) -> Duration {
+		self.times.iter().copied().max().unwrap_or_default()
+	}
+	// The earliest time the next frame can start without going over the
+	// frame rate limit
+	pub fn next_frame(&self, fps_limit: Option<u32>) -> Option<Instant> {
+		let interval = Duration::from_secs(1).checked_div(fps_limit?)?;
+		Some(self.last_frame? + interval)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn frame_limit() {
+		let mut frames = FrameTimes::default();
+		let start = Instant::now();
+		assert_eq!(frames.next_frame(Some(50)), None);
+		frames.record(start, start + Duration::from_millis(4));
+		frames.record(start, start + Duration::from_millis(8));
+		assert_eq!(frames.average(), Duration::from_millis(6));
+		assert_eq!(frames.max(), Duration::from_millis(8));
+		assert_eq!(frames.next_frame(Some(50)), Some(start + Duration::from_millis(20)));
+		assert_eq!(frames.next_frame(None), None);
+		assert_eq!(frames.next_frame(Some(0)), None);
+		(0..HISTORY).for_each(|_| frames.record(start, start));
+		assert_eq!(frames.times().count(), HISTORY);
+		assert_eq!(frames.max(), Duration::ZERO);
+	}
+}
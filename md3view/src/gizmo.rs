@@ -0,0 +1,51 @@
+// Screen space maths for the handles used to move and turn tags. Positions
+// are in logical pixels, with +Y down.
+use glam::{Mat4, Vec2, Vec3};
+
+// Where a point is on the screen, or None if it's behind the camera
+pub fn project(view_projection: Mat4, point: Vec3, screen_size: Vec2) -> Option<Vec2> {
+	let clip = view_projection * point.extend(1.);
+	if clip.w <= 0. { return None; }
+	let ndc = clip.truncate() / clip.w;
+	Some(Vec2::new(ndc.x.mul_add(0.5, 0.5), (-ndc.y).mul_add(0.5, 0.5)) * screen_size)
+}
+
+// How far a handle at the tip of an axis was dragged along the axis, as a
+// fraction of the axis length
+pub fn axis_drag(origin: Vec2, tip: Vec2, drag: Vec2) -> f32 {
+	let axis = tip - origin;
+	// Axes pointing straight at the camera can't be dragged along
+	if axis.length_squared() < 1. { return 0.; }
+	drag.dot(axis) / axis.length_squared()
+}
+
+// Angle in radians to turn around an axis, when the pointer moves from one
+// place to another around the origin. Positive angles turn the tip of the
+// first other axis (j) towards the second (k).
+pub fn rotation_drag(origin: Vec2, from: Vec2, to: Vec2, j: Vec2, k: Vec2) -> f32 {
+	let (from, to) = (from - origin, to - origin);
+	if from.length_squared() < 1. || to.length_squared() < 1. { return 0.; }
+	let angle = from.angle_between(to);
+	// Which way j to k goes on the screen
+	let direction = (j - origin).perp_dot(k - origin);
+	if direction < 0. { -angle } else { angle }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn drags() {
+		let origin = Vec2::new(100., 100.);
+		assert_eq!(axis_drag(origin, Vec2::new(150., 100.), Vec2::new(25., 40.)), 0.5);
+		assert_eq!(axis_drag(origin, origin, Vec2::new(25., 40.)), 0.);
+		// A quarter turn from j to k
+		let (j, k) = (Vec2::new(150., 100.), Vec2::new(100., 150.));
+		let angle = rotation_drag(origin, j, k, j, k);
+		assert!((angle - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+		assert!((rotation_drag(origin, j, k, k, j) + std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+		let centre = project(Mat4::IDENTITY, Vec3::ZERO, Vec2::new(640., 480.));
+		assert_eq!(centre, Some(Vec2::new(320., 240.)));
+	}
+}
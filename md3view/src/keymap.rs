@@ -0,0 +1,278 @@
+// Keyboard shortcuts, which can be changed in the keyboard shortcuts window.
+// Only the bindings which have been changed are saved, so new actions get
+// their default keys.
+use winit::event::{ModifiersState, VirtualKeyCode};
+use serde::{Serialize, Deserialize};
+use std::{
+	borrow::Cow,
+	collections::BTreeMap,
+	fmt,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+	Open,
+	OpenProject,
+	SaveProject,
+	Screenshot,
+	Quit,
+	PlayPause,
+	PreviousFrame,
+	NextFrame,
+	FirstFrame,
+	LastFrame,
+	TexturedView,
+	UntexturedView,
+	NormalsView,
+	LitView,
+	DeviationView,
+	NormalCheckView,
+	UvCheckerView,
+	BackfacesView,
+	SilhouetteView,
+	ToggleSurface1,
+	ToggleSurface2,
+	ToggleSurface3,
+	ToggleSurface4,
+	ToggleSurface5,
+	ToggleSurface6,
+	ToggleSurface7,
+	ToggleSurface8,
+	ToggleSurface9,
+	FrontView,
+	BackView,
+	RightView,
+	LeftView,
+	TopView,
+	BottomView,
+	Orthographic,
+	FrameModel,
+	ResetCamera,
+	OrbitLeft,
+	OrbitRight,
+	OrbitUp,
+	OrbitDown,
+	ZoomIn,
+	ZoomOut,
+	ToggleHud,
+	Shortcuts,
+}
+
+impl Action {
+	pub const ALL: [Action; 45] = {
+		use Action::*;
+		[Open, OpenProject, SaveProject, Screenshot, Quit, PlayPause, PreviousFrame, NextFrame,
+		FirstFrame, LastFrame, TexturedView, UntexturedView, NormalsView, LitView, DeviationView,
+		NormalCheckView, UvCheckerView, BackfacesView, SilhouetteView, ToggleSurface1, ToggleSurface2, ToggleSurface3,
+		ToggleSurface4, ToggleSurface5, ToggleSurface6, ToggleSurface7, ToggleSurface8, ToggleSurface9,
+		FrontView, BackView, RightView, LeftView, TopView, BottomView, Orthographic, FrameModel,
+		ResetCamera, OrbitLeft, OrbitRight, OrbitUp, OrbitDown, ZoomIn, ZoomOut, ToggleHud, Shortcuts]
+	};
+	// The view modes, in the same order as ViewMode
+	pub const VIEW_MODES: [Action; 9] = {
+		use Action::*;
+		[TexturedView, UntexturedView, NormalsView, LitView, DeviationView, NormalCheckView,
+		UvCheckerView, BackfacesView, SilhouetteView]
+	};
+	pub const TOGGLE_SURFACES: [Action; 9] = {
+		use Action::*;
+		[ToggleSurface1, ToggleSurface2, ToggleSurface3, ToggleSurface4, ToggleSurface5,
+		ToggleSurface6, ToggleSurface7, ToggleSurface8, ToggleSurface9]
+	};
+
+	pub fn description(self) -> Cow<'static, str> {
+		use Action::*;
+		if let Some(surface) = Action::TOGGLE_SURFACES.iter().position(|&a| a == self) {
+			return Cow::Owned(format!("Show/hide surface {}", surface + 1));
+		}
+		Cow::Borrowed(match self {
+			Open => "Open model",
+			OpenProject => "Open project",
+			SaveProject => "Save project",
+			Screenshot => "Save a screenshot of the view",
+			Quit => "Quit",
+			PlayPause => "Play/pause",
+			PreviousFrame => "Previous frame",
+			NextFrame => "Next frame",
+			FirstFrame => "First frame",
+			LastFrame => "Last frame",
+			TexturedView => "Textured view",
+			UntexturedView => "Untextured view",
+			NormalsView => "Normals view",
+			LitView => "Lit view",
+			DeviationView => "Deviation view",
+			NormalCheckView => "Normal check view",
+			UvCheckerView => "UV checker view",
+			BackfacesView => "Back face view",
+			SilhouetteView => "Silhouette view",
+			FrontView => "Front view",
+			BackView => "Back view",
+			RightView => "Right view",
+			LeftView => "Left view",
+			TopView => "Top view",
+			BottomView => "Bottom view",
+			Orthographic => "Toggle orthographic projection",
+			FrameModel => "Frame the model",
+			ResetCamera => "Reset the camera",
+			OrbitLeft => "Orbit left",
+			OrbitRight => "Orbit right",
+			OrbitUp => "Orbit up",
+			OrbitDown => "Orbit down",
+			ZoomIn => "Zoom in",
+			ZoomOut => "Zoom out",
+			ToggleHud => "Show/hide the model info over the view",
+			Shortcuts => "Show/hide the keyboard shortcuts",
+			_ => "",
+		})
+	}
+	pub fn default_bindings(self) -> Vec<Chord> {
+		use Action::*;
+		// Some keys have the same names as actions
+		use VirtualKeyCode as K;
+		const NUMBERS: [K; 9] = [K::Key1, K::Key2, K::Key3, K::Key4, K::Key5, K::Key6, K::Key7, K::Key8, K::Key9];
+		if let Some(mode) = Action::VIEW_MODES.iter().position(|&a| a == self) {
+			return vec![Chord::key(NUMBERS[mode])];
+		}
+		if let Some(surface) = Action::TOGGLE_SURFACES.iter().position(|&a| a == self) {
+			return vec![Chord::ctrl(NUMBERS[surface])];
+		}
+		match self {
+			Open => vec![Chord::ctrl(K::O)],
+			OpenProject => vec![Chord { shift: true, ..Chord::ctrl(K::O) }],
+			SaveProject => vec![Chord::ctrl(K::S)],
+			Screenshot => vec![Chord::key(K::F12)],
+			Quit => vec![Chord::ctrl(K::Q)],
+			PlayPause => vec![Chord::key(K::Space)],
+			PreviousFrame => vec![Chord::key(K::Comma)],
+			NextFrame => vec![Chord::key(K::Period)],
+			FirstFrame => vec![Chord::key(K::Home)],
+			LastFrame => vec![Chord::key(K::End)],
+			FrontView => vec![Chord::key(K::Numpad1)],
+			BackView => vec![Chord::ctrl(K::Numpad1)],
+			RightView => vec![Chord::key(K::Numpad3)],
+			LeftView => vec![Chord::ctrl(K::Numpad3)],
+			TopView => vec![Chord::key(K::Numpad7)],
+			BottomView => vec![Chord::ctrl(K::Numpad7)],
+			Orthographic => vec![Chord::key(K::Numpad5)],
+			FrameModel => vec![Chord::key(K::F)],
+			ResetCamera => vec![Chord::key(K::R)],
+			OrbitLeft => vec![Chord::key(K::Left), Chord::key(K::A)],
+			OrbitRight => vec![Chord::key(K::Right), Chord::key(K::D)],
+			OrbitUp => vec![Chord::key(K::Up), Chord::key(K::W)],
+			OrbitDown => vec![Chord::key(K::Down), Chord::key(K::S)],
+			ZoomIn => vec![Chord::key(K::Equals), Chord::key(K::Plus), Chord::key(K::NumpadAdd)],
+			ZoomOut => vec![Chord::key(K::Minus), Chord::key(K::NumpadSubtract)],
+			ToggleHud => vec![Chord::key(K::H)],
+			Shortcuts => vec![Chord::key(K::F1)],
+			_ => vec![],
+		}
+	}
+}
+
+// A key, and the modifiers which have to be held with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Chord {
+	pub key: VirtualKeyCode,
+	#[serde(default)]
+	pub ctrl: bool,
+	#[serde(default)]
+	pub shift: bool,
+	#[serde(default)]
+	pub alt: bool,
+}
+
+impl Chord {
+	pub fn key(key: VirtualKeyCode) -> Self {
+		Self { key, ctrl: false, shift: false, alt: false }
+	}
+	pub fn ctrl(key: VirtualKeyCode) -> Self {
+		Self { ctrl: true, ..Chord::key(key) }
+	}
+	pub fn pressed(key: VirtualKeyCode, modifiers: ModifiersState) -> Self {
+		Self { key, ctrl: modifiers.ctrl(), shift: modifiers.shift(), alt: modifiers.alt() }
+	}
+	// Modifier keys on their own can't be bound
+	pub fn is_modifier(key: VirtualKeyCode) -> bool {
+		use VirtualKeyCode::*;
+		matches!(key, LControl | RControl | LShift | RShift | LAlt | RAlt | LWin | RWin)
+	}
+}
+
+impl fmt::Display for Chord {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		use VirtualKeyCode::*;
+		[(self.ctrl, "Ctrl+"), (self.shift, "Shift+"), (self.alt, "Alt+")].iter()
+			.filter(|(held, _)| *held)
+			.try_for_each(|(_, name)| f.write_str(name))?;
+		let name = format!("{:?}", self.key);
+		match self.key {
+			Comma => f.write_str(","),
+			Period => f.write_str("."),
+			Equals => f.write_str("="),
+			Plus => f.write_str("+"),
+			Minus => f.write_str("-"),
+			NumpadAdd => f.write_str("Numpad +"),
+			NumpadSubtract => f.write_str("Numpad -"),
+			_ => match (name.strip_prefix("Key"), name.strip_prefix("Numpad")) {
+				(Some(number), _) => f.write_str(number),
+				(_, Some(rest)) => write!(f, "Numpad {}", rest),
+				_ => f.write_str(&name),
+			},
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keymap(BTreeMap<Action, Vec<Chord>>);
+
+impl Keymap {
+	pub fn bindings(&self, action: Action) -> Cow<'_, [Chord]> {
+		match self.0.get(&action) {
+			Some(chords) => Cow::Borrowed(chords),
+			None => Cow::Owned(action.default_bindings()),
+		}
+	}
+	pub fn set_bindings(&mut self, action: Action, chords: Vec<Chord>) {
+		match chords == action.default_bindings() {
+			true => { self.0.remove(&action); },
+			false => { self.0.insert(action, chords); },
+		}
+	}
+	pub fn is_default(&self, action: Action) -> bool {
+		!self.0.contains_key(&action)
+	}
+	pub fn reset(&mut self) {
+		self.0.clear();
+	}
+	// The first action bound to the chord
+	pub fn action(&self, chord: Chord) -> Option<Action> {
+		Action::ALL.into_iter().find(|&action| self.bindings(action).contains(&chord))
+	}
+	// The keys for an action, for showing in menus
+	pub fn describe(&self, action: Action) -> String {
+		self.bindings(action).iter().map(ToString::to_string).collect::<Vec<_>>().join(" / ")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bindings() {
+		let mut keymap = Keymap::default();
+		assert_eq!(keymap.action(Chord::ctrl(VirtualKeyCode::O)), Some(Action::Open));
+		assert_eq!(keymap.action(Chord::key(VirtualKeyCode::Key3)), Some(Action::NormalsView));
+		assert_eq!(keymap.describe(Action::OpenProject), "Ctrl+Shift+O");
+		assert_eq!(keymap.describe(Action::ZoomOut), "- / Numpad -");
+		keymap.set_bindings(Action::Screenshot, vec![Chord::key(VirtualKeyCode::P)]);
+		assert_eq!(keymap.action(Chord::key(VirtualKeyCode::P)), Some(Action::Screenshot));
+		assert_eq!(keymap.action(Chord::key(VirtualKeyCode::F12)), None);
+		// Only the changed bindings are saved
+		let json = serde_json::to_string(&keymap).unwrap();
+		assert_eq!(json, r#"{"Screenshot":[{"key":"P","ctrl":false,"shift":false,"alt":false}]}"#);
+		assert_eq!(serde_json::from_str::<Keymap>(&json).unwrap(), keymap);
+		keymap.set_bindings(Action::Screenshot, Action::Screenshot.default_bindings());
+		assert!(keymap.is_default(Action::Screenshot));
+	}
+}
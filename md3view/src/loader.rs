@@ -0,0 +1,136 @@
+// Reading models and decoding their textures on other threads, so the window
+// keeps responding while big models load. OpenGL can only be used on the main
+// thread, so the textures are sent back to be uploaded as they're decoded.
+// Browsers can't start threads, so there they're fetched asynchronously
+// instead.
+use anyhow::Error;
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use crate::markers::{self, Marker};
+use crate::notes::{self, Note};
+use crate::res::Surface;
+use rustmd3::md3::{self, MD3Model, MD3Shader};
+use rustmd3::q3shader::{self, Q3Shader};
+use rustmd3::str_util::StringFromBytes;
+use std::{
+	borrow::Cow,
+	collections::{HashMap, HashSet},
+	io::{self, Read, Seek},
+	path::{Path, PathBuf},
+	sync::mpsc::{self, Receiver, TryRecvError},
+};
+
+pub struct LoadedModel {
+	pub path: PathBuf,
+	pub model: MD3Model,
+	// Problems with the file which were worked around, when reading tolerantly
+	pub recovered: Vec<md3::MD3ReadError>,
+	pub markers: Result<Vec<Marker>, Error>,
+	pub notes: Result<Vec<Note>, Error>,
+	// The game's shader scripts, if there's a scripts folder
+	pub shaders: Option<io::Result<HashMap<String, Q3Shader>>>,
+	// Textures, normal maps and specular maps which are still being decoded
+	pub textures: Vec<PathBuf>,
+	pub images: Receiver<DecodedImage>,
+}
+
+pub type DecodedImage = (PathBuf, Result<Surface, Error>);
+
+// A model being loaded
+pub struct Loading {
+	pub path: PathBuf,
+	receiver: Receiver<Result<LoadedModel, Error>>,
+}
+
+impl Loading {
+	// `textures` are images which will be needed besides the ones named by
+	// the model's shaders
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn start(path: &Path, textures: Vec<PathBuf>, tolerant: bool) -> Self {
+		let (sender, receiver) = mpsc::channel();
+		let thread_path = path.to_path_buf();
+		std::thread::spawn(move || {
+			let (image_sender, images) = mpsc::channel();
+			let loaded = std::fs::File::open(&thread_path).map_err(Error::from)
+				.and_then(|mut file| read(&thread_path, &mut file, textures, images, tolerant));
+			let decode = loaded.as_ref().map(|l| l.textures.clone()).unwrap_or_default();
+			// Nobody is waiting any more if the receiver is gone
+			if sender.send(loaded).is_err() { return; }
+			decode.into_par_iter().for_each_with(image_sender, |sender, path| {
+				let image = Surface::read_image(&path);
+				sender.send((path, image)).ok();
+			});
+		});
+		Self { path: path.to_path_buf(), receiver }
+	}
+	// The model and textures were picked by the user, or are fetched from the
+	// site, one after another
+	#[cfg(target_arch = "wasm32")]
+	pub fn start(path: &Path, textures: Vec<PathBuf>, tolerant: bool) -> Self {
+		let (sender, receiver) = mpsc::channel();
+		let task_path = path.to_path_buf();
+		wasm_bindgen_futures::spawn_local(async move {
+			let (image_sender, images) = mpsc::channel();
+			let loaded = crate::web::read(&task_path).await
+				.and_then(|bytes| read(&task_path, &mut io::Cursor::new(bytes), textures, images, tolerant));
+			let decode = loaded.as_ref().map(|l| l.textures.clone()).unwrap_or_default();
+			if sender.send(loaded).is_err() { return; }
+			for path in decode {
+				let image = crate::web::read(&path).await
+					.and_then(|bytes| Surface::from_image_bytes(&bytes));
+				if image_sender.send((path, image)).is_err() { return; }
+			}
+		});
+		Self { path: path.to_path_buf(), receiver }
+	}
+	// The model, once it has been read
+	pub fn poll(&self) -> Option<Result<LoadedModel, Error>> {
+		match self.receiver.try_recv() {
+			Ok(result) => Some(result),
+			Err(TryRecvError::Empty) => None,
+			Err(TryRecvError::Disconnected) => Some(Err(Error::msg("The loading thread stopped unexpectedly"))),
+		}
+	}
+}
+
+fn read(path: &Path, file: &mut (impl Read + Seek), textures: Vec<PathBuf>, images: Receiver<DecodedImage>, tolerant: bool) -> Result<LoadedModel, Error> {
+	let (model, recovered) = match tolerant {
+		true => md3::read_md3_tolerant(file)?,
+		false => (md3::read_md3(file)?, vec![]),
+	};
+	let markers = markers::read(path);
+	let notes = notes::read(path);
+	let shaders = q3shader::find_scripts(path).map(|scripts| q3shader::read_scripts(&scripts));
+	let diffuse: Vec<PathBuf> = model.surfaces.iter()
+		.map(|surface| shader_texture_path(path, surface.shaders.first()))
+		.collect();
+	let maps: Vec<PathBuf> = diffuse.iter()
+		.flat_map(|texture| [detect_texture_map(texture, "_n"), detect_texture_map(texture, "_s")])
+		.flatten()
+		.collect();
+	// Roughly in the order they're needed, although they're decoded in
+	// parallel. Whether the site has them is only known once they're fetched.
+	let mut textures: Vec<PathBuf> = textures.into_iter().chain(diffuse).chain(maps)
+		.filter(|path| cfg!(target_arch = "wasm32") || path.is_file())
+		.collect();
+	let mut seen = HashSet::new();
+	textures.retain(|path| seen.insert(path.clone()));
+	Ok(LoadedModel { path: path.to_path_buf(), model, recovered, markers, notes, shaders, textures, images })
+}
+
+pub fn shader_texture_path(model_path: &Path, shader: Option<&MD3Shader>) -> PathBuf {
+	shader.map(|s| model_path.parent().unwrap_or(model_path).join(
+		String::from_utf8_stop(&s.name)
+		.trim_matches(|c| c == char::from_u32(0).unwrap())
+		.trim())).unwrap_or_default()
+}
+
+// Look for a texture next to the given one with a suffix added to the file
+// name, e.g. "skin_n.png" for the normal map of "skin.tga".
+pub fn detect_texture_map(texture: &Path, suffix: &str) -> Option<PathBuf> {
+	let stem = texture.file_stem()?.to_string_lossy();
+	let extension = texture.extension().map(|e| e.to_string_lossy());
+	extension.into_iter().chain(["png", "tga", "jpg"].map(Cow::from))
+		.map(|ext| texture.with_file_name(format!("{}{}.{}", stem, suffix, ext)))
+		.find(|path| path.is_file())
+}
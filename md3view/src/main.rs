@@ -1,99 +1,322 @@
-mod md3;
 mod window;
 mod res;
 mod eye;
 mod render;
 mod err_util;
-mod str_util;
+mod project;
+mod settings;
+mod units;
+mod backup;
+mod renderer;
+mod markers;
+mod notes;
+mod gizmo;
+mod loader;
+mod frame_times;
+mod console;
+mod problems;
+mod palette;
+mod check;
+mod keymap;
+#[cfg(not(target_arch = "wasm32"))]
+mod video;
+#[cfg(not(target_arch = "wasm32"))]
+mod webm;
+mod texture_check;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
 use ahash::RandomState;
 use egui::{Color32, LayerId, TextStyle, Order, Pos2, Id};
-use eye::{Camera, OrbitCamera};
-use glam::{Affine3A, Vec3, Mat4};
+use frame_times::FrameTimes;
+use keymap::{Action, Chord};
+use eye::{Camera, CameraKeyframe, CameraTween, LookAtCamera, OrbitCamera};
+use glam::{Affine3A, EulerRot, Vec2, Vec3, Mat3, Mat4, Quat};
 use glow::{Context as GLContext, HasContext};
-use glutin::event_loop::{EventLoopBuilder, ControlFlow};
-use glutin::event::Event;
-use res::{AppResources, Surface};
+use winit::event_loop::{EventLoopBuilder, ControlFlow};
+use winit::event::{DeviceEvent, Event, ModifiersState, Touch, VirtualKeyCode};
+use loader::{detect_texture_map, shader_texture_path, DecodedImage, LoadedModel, Loading};
+use problems::{Fix, Problem, Retry, Severity, Source};
+use res::{AppResources, Surface, CHECKER_MAX_CELLS};
 use std::{
-	borrow::Cow,
-	collections::HashMap,
+	collections::{BTreeSet, HashMap, HashSet},
 	env,
-	f32::consts::FRAC_PI_2,
-	ffi::OsString,
-	fs::File,
-	sync::Arc,
-	ops::{RangeInclusive, RangeBounds, Bound, Add, Mul},
-	path::Path,
+	f32::consts::{FRAC_PI_2, PI, TAU},
+	fs::{self, File},
+	io::{BufWriter, Write},
+	sync::{Arc, mpsc::{self, Receiver}},
+	ops::{RangeInclusive, Add, Mul},
+	path::{Path, PathBuf},
 	rc::Rc,
-	time::Instant,
+	time::Duration,
 };
+use instant::Instant;
 use anyhow::{Error as AError, Context as AContext};
-use md3::MD3Model;
+use ab_glyph::FontRef;
+use image::RgbaImage;
+use rustmd3::{md3, compare, export, layout, limits, lod, optimize, q3shader};
+use rustmd3::limits::{EngineProfile, LimitWarning};
+use texture_check::TextureWarning;
+use rustmd3::compare::MeshDiff;
+use rustmd3::optimize::{OptimizeReport, OptimizeSettings, SnapReport};
+use rustmd3::voxel::{self, VoxelGrid};
+use rustmd3::md3::{FrameEdit, MD3EditError, MD3FrameTag, MD3Model, MD3Surface, ModelTransform, NormalWeighting};
+use project::{AttachmentSettings, CameraSettings, DisplayTransform, LightSettings, Project, SurfaceSettings, ViewState};
+use renderer::{BlendMode, Renderer, RenderPass, Scene, View};
+use settings::Settings;
+use markers::Marker;
+use notes::{Note, NoteAnchor};
+use units::{NumberFormat, UnitSystem};
 use render::{
 	BasicModel,
+	Framebuffer,
 	VertexBuffer,
 	IndexBuffer,
 	Texture,
+	ShaderProgram,
 	ShaderProgramBuilder,
 	ShaderStage,
 	UniformsMD3,
 	UniformsMD3Locations,
 	UniformsRes,
 	UniformsResLocations,
+	VertexRes,
+	UniformsSky,
+	UniformsSkyLocations,
 };
-use str_util::StringFromBytes;
+use rustmd3::str_util::StringFromBytes;
+use rustmd3::export::{MeshFormat, VertexDataFormat};
 
 use egui_file::FileDialog;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use regex::Regex;
+
+struct CachedTexture {
+	texture: Rc<Texture>,
+	// Size of the image data
+	bytes: usize,
+	// When it was last asked for, to find the least recently used textures
+	last_used: u64,
+}
+
+// How much memory the cached textures use, in bytes
+#[derive(Debug, Clone, Copy, Default)]
+struct TextureMemory {
+	count: usize,
+	total: usize,
+	// Textures which are drawn on surfaces, and can't be evicted
+	in_use: usize,
+}
 
 struct TextureCache {
-	cache: HashMap<String, Rc<Texture>, RandomState>,
+	cache: HashMap<String, CachedTexture, RandomState>,
+	uses: u64,
+	// Directories of the cached textures are watched, since some editors
+	// replace the file instead of writing to it.
+	watcher: Option<RecommendedWatcher>,
+	watched: HashSet<PathBuf, RandomState>,
+	changes: Receiver<notify::Result<notify::Event>>,
+	// Textures still being decoded on other threads, which use the null
+	// texture until they're uploaded
+	pending: HashSet<PathBuf, RandomState>,
+	incoming: Option<Receiver<DecodedImage>>,
+	decoded: Vec<(PathBuf, Option<Surface>)>,
+	nearest: bool,
+}
+
+// Watching needs a thread, which browsers can't start
+fn watch_files(sender: mpsc::Sender<notify::Result<notify::Event>>) -> notify::Result<RecommendedWatcher> {
+	if cfg!(target_arch = "wasm32") {
+		return Err(notify::Error::generic("Files can't be watched in a browser"));
+	}
+	notify::recommended_watcher(sender)
 }
 
 const NULL_TEXTURE_NAME: &str = "__null_texture__";
 
+// Textures are cached by their absolute path, which is what the watcher
+// reports when they change
+fn texture_key(path: &Path) -> String {
+	path.canonicalize().unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().into_owned()
+}
+
 impl TextureCache {
-	fn new(glc: Arc<GLContext>, null_texture: &Surface) -> Self {
+	fn new(glc: Arc<GLContext>, null_texture: &Surface, nearest: bool) -> Self {
 		let mut cache = HashMap::default();
-		cache.insert(String::from(NULL_TEXTURE_NAME), Rc::new(Texture::try_from_surface(glc, null_texture).unwrap()));
-		Self { cache }
+		let null = Texture::try_from_surface(glc, null_texture).unwrap();
+		null.set_nearest(nearest);
+		cache.insert(String::from(NULL_TEXTURE_NAME), CachedTexture {
+			texture: Rc::new(null),
+			bytes: null_texture.data.len(),
+			last_used: 0,
+		});
+		let (sender, changes) = mpsc::channel();
+		let watcher = watch_files(sender)
+			.map_err(|e| log::warn!("Texture hot-reloading is unavailable: {}", e)).ok();
+		Self {
+			cache, uses: 0, watcher, watched: HashSet::default(), changes,
+			pending: HashSet::default(), incoming: None, decoded: vec![], nearest,
+		}
+	}
+	// Re-read the cached 8-bit textures with a different palette
+	fn reload_palettized(&mut self) -> Vec<Result<PathBuf, AError>> {
+		self.cache.iter().map(|(key, cached)| (Path::new(key), cached))
+			.filter(|(path, _)| palette::is_palettized(path))
+			.map(|(path, cached)| Surface::read_image(path)
+				.and_then(|s| cached.texture.update_from_surface(&s))
+				.map(|_| path.to_path_buf())
+				.map_err(|e| AError::msg(format!("Could not reload texture {}: {:?}", path.display(), e))))
+			.collect()
+	}
+	fn set_nearest(&mut self, nearest: bool) {
+		self.nearest = nearest;
+		self.cache.values().for_each(|cached| cached.texture.set_nearest(nearest));
+	}
+	fn watch(&mut self, path: &Path) {
+		let (watcher, directory) = match (self.watcher.as_mut(), path.parent()) {
+			(Some(w), Some(d)) => (w, d),
+			_ => return,
+		};
+		if self.watched.contains(directory) { return; }
+		match watcher.watch(directory, RecursiveMode::NonRecursive) {
+			Ok(_) => { self.watched.insert(directory.to_path_buf()); },
+			Err(e) => log::warn!("Could not watch {}: {}", directory.display(), e),
+		}
+	}
+	// Re-upload the cached textures which have changed on disk since the last
+	// call, and return the errors for the ones which could not be reloaded.
+	fn reload_changed(&mut self) -> Vec<Result<PathBuf, AError>> {
+		let changed: HashSet<PathBuf, RandomState> = self.changes.try_iter()
+			.filter_map(Result::ok)
+			.filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+			.flat_map(|event| event.paths)
+			.collect();
+		changed.into_iter().filter_map(|path| self.reload(&path).map(|result| result.map(|_| path))).collect()
+	}
+	// Read a cached texture again. None if it isn't cached.
+	fn reload(&mut self, path: &Path) -> Option<Result<(), AError>> {
+		let cached = self.cache.get_mut(&texture_key(path))?;
+		let result = Surface::read_image(path)
+			.and_then(|s| {
+				cached.texture.update_from_surface(&s)?;
+				cached.bytes = s.data.len();
+				Ok(())
+			})
+			.map_err(|e| AError::msg(format!("Could not reload texture {}: {:?}", path.display(), e)));
+		Some(result)
+	}
+	fn null(&self) -> Rc<Texture> {
+		Rc::clone(&self.cache[NULL_TEXTURE_NAME].texture)
+	}
+	fn insert(&mut self, path: &Path, texture: Texture, bytes: usize) -> Rc<Texture> {
+		let key = texture_key(path);
+		self.watch(Path::new(&key));
+		self.uses += 1;
+		texture.set_nearest(self.nearest);
+		let texture = Rc::new(texture);
+		self.cache.insert(key,
+			CachedTexture { texture: Rc::clone(&texture), bytes, last_used: self.uses });
+		texture
+	}
+	fn memory(&self) -> TextureMemory {
+		self.cache.values().fold(TextureMemory::default(), |memory, cached| TextureMemory {
+			count: memory.count + 1,
+			total: memory.total + cached.bytes,
+			in_use: memory.in_use + if Rc::strong_count(&cached.texture) > 1 { cached.bytes } else { 0 },
+		})
+	}
+	// Throw away the least recently used textures which aren't being drawn,
+	// until the cache fits in the budget
+	fn evict(&mut self, budget: usize) {
+		let mut total = self.memory().total;
+		if total <= budget { return; }
+		let mut unused: Vec<(String, usize, u64)> = self.cache.iter()
+			.filter(|(key, cached)| key.as_str() != NULL_TEXTURE_NAME && Rc::strong_count(&cached.texture) == 1)
+			.map(|(key, cached)| (key.clone(), cached.bytes, cached.last_used))
+			.collect();
+		unused.sort_by_key(|(_, _, last_used)| *last_used);
+		for (key, bytes, _) in unused {
+			if total <= budget { break; }
+			self.cache.remove(&key);
+			total -= bytes;
+		}
 	}
 	fn get(&mut self, glc: Arc<GLContext>, path: &dyn AsRef<Path>) -> (Rc<Texture>, Option<AError>) {
-		let null_key = Cow::from(NULL_TEXTURE_NAME);
 		let path = path.as_ref();
-		let key = path.to_string_lossy();
-		if let Some(r) = self.cache.get(key.as_ref()) {
-			return (Rc::clone(r), None);
+		let key = texture_key(path);
+		self.uses += 1;
+		if let Some(cached) = self.cache.get_mut(&key) {
+			cached.last_used = self.uses;
+			return (Rc::clone(&cached.texture), None);
+		}
+		if self.is_pending(path) {
+			return (self.null(), None);
 		}
 		match Surface::read_image(path) {
 			Ok(s) => {
 				let texture = Texture::try_from_surface(glc, &s);
 				match texture {
-					Ok(t) => {
-						let txref = Rc::new(t);
-						let myref = Rc::clone(&txref);
-						let path = path.to_string_lossy();
-						self.cache.insert(path.into_owned(), txref);
-						(myref, None)
-					},
+					Ok(t) => (self.insert(path, t, s.data.len()), None),
 					Err(e) => {
-						(Rc::clone(self.cache.get(null_key.as_ref()).as_ref().unwrap()),
+						(self.null(),
 						Some(AError::msg(format!("Could not load texture {}: {:?}", path.display(), e)))
 						)
 					},
 				}
 			},
 			Err(e) => {
-				(Rc::clone(self.cache.get(null_key.as_ref()).as_ref().unwrap()),
+				(self.null(),
 				Some(AError::msg(format!("Could not load texture {}: {:?}", path.display(), e)))
 				)
 			},
 		}
 	}
+	fn stream(&mut self, textures: Vec<PathBuf>, incoming: Receiver<DecodedImage>) {
+		self.pending = textures.into_iter().collect();
+		self.incoming = (!self.pending.is_empty()).then_some(incoming);
+		self.decoded.clear();
+	}
+	fn is_pending(&self, path: &Path) -> bool {
+		self.pending.contains(path)
+	}
+	fn is_streaming(&self) -> bool {
+		self.incoming.is_some() || !self.decoded.is_empty()
+	}
+	// Upload the decoded texture which is needed most, so that big textures
+	// are spread over several frames. Returns the path of the texture which
+	// is done, or couldn't be decoded.
+	fn upload_next(&mut self, glc: Arc<GLContext>, priority: impl Fn(&Path) -> usize) -> Option<PathBuf> {
+		if let Some(incoming) = self.incoming.as_ref() {
+			// Images which couldn't be decoded are read again by get(), to
+			// report the error
+			self.decoded.extend(incoming.try_iter().map(|(path, image)| (path, image.ok())));
+		}
+		let next = self.decoded.iter().enumerate()
+			.min_by_key(|(_, (path, _))| priority(path))
+			.map(|(index, _)| index)?;
+		let (path, image) = self.decoded.swap_remove(next);
+		self.pending.remove(&path);
+		if let Some(image) = image {
+			if let Ok(texture) = Texture::try_from_surface(glc, &image) {
+				self.insert(&path, texture, image.data.len());
+			}
+		}
+		if self.pending.is_empty() {
+			self.incoming = None;
+		}
+		Some(path)
+	}
 	fn clear(&mut self) {
 		let non_null_textures: Box<[String]> = self.cache.keys().cloned()
 			.filter(|f| f != NULL_TEXTURE_NAME).collect();
 		non_null_textures.into_iter().map(String::as_str)
 			.for_each(|k| {self.cache.remove(k);});
+		if let Some(watcher) = self.watcher.as_mut() {
+			self.watched.drain().for_each(|d| {watcher.unwatch(&d).ok();});
+		}
+		self.pending.clear();
+		self.incoming = None;
+		self.decoded.clear();
 	}
 }
 
@@ -104,35 +327,841 @@ enum ViewMode {
 	Textured,
 	Untextured,
 	Normals,
+	Lit,
+	// Distance from the model being compared with
+	Deviation,
+	// Vertices with bad normals are red
+	NormalCheck,
+	// A numbered checkerboard instead of the textures
+	UvChecker,
+	// Back faces are magenta
+	Backfaces,
+	// Only the outline of the model
+	Silhouette,
+}
+
+impl ViewMode {
+	// In the same order as their numbers
+	const ALL: [ViewMode; 9] = [ViewMode::Textured, ViewMode::Untextured, ViewMode::Normals, ViewMode::Lit,
+		ViewMode::Deviation, ViewMode::NormalCheck, ViewMode::UvChecker, ViewMode::Backfaces,
+		ViewMode::Silhouette];
 }
 
 #[derive(Debug, Clone, Copy, Default)]
 struct AppControls {
 	lmb_dragging: bool,
 	rmb_dragging: bool,
+	mmb_dragging: bool,
 	view_mode: ViewMode,
 	gzdoom_normals: bool,
+	show_skybox: bool,
+	sky_reflection: bool,
+	// View the scene from the debug camera, and draw the main camera's frustum
+	debug_camera: bool,
+	// Bigger tag gizmos and labels, and tag animation paths
+	tag_mode: bool,
+	// Texels of 8-bit skins in the fullbright part of the palette aren't lit
+	fullbright: bool,
+	modifiers: ModifiersState,
+	focused: bool,
+	// Last position of the mouse cursor in the window, in physical pixels
+	cursor: Option<Vec2>,
+	// When the left mouse button was last pressed, to find double clicks
+	last_click: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextureMap {
+	Normal,
+	Specular,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DialogPurpose {
+	OpenProject,
+	SaveProject,
+	ExportUvLayout,
+	LoadSkybox,
+	ExportVertexData,
+	ExportPrintMesh,
+	NormalMap(usize),
+	SpecularMap(usize),
+	RenderCameraPath,
+	#[cfg(not(target_arch = "wasm32"))]
+	ExportVideo,
+	ExportSprites,
+	ExportVox,
+	ContactSheet,
+	SurfaceTexture(usize),
+	ExportFrameBounds,
+	AttachModel,
+	AddModel,
+	AttachmentTexture(usize),
+	SaveModel,
+	CompareModel,
+	SaveLods,
+	MergeSurfaces,
+	SplitSurface,
+	FragmentShader,
+	Palette,
+	// Including the triangles, texture coordinates and vertices
+	ExportStructure { full: bool },
+	Screenshot,
+}
+
+// The differences between the loaded model and another model
+struct Comparison {
+	path: PathBuf,
+	diff: MeshDiff,
+	open: bool,
+}
+
+#[derive(Debug, Clone)]
+struct UvExportSettings {
+	open: bool,
+	size: u32,
+	// Whether or not each surface is included in the layout
+	surfaces: Vec<bool>,
+	over_texture: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct VertexExportSettings {
+	open: bool,
+	surface: usize,
+	first_frame: usize,
+	last_frame: usize,
+	format: VertexDataFormat,
+}
+
+// The current frame as a mesh for 3D printing
+#[derive(Debug, Clone)]
+struct PrintExport {
+	open: bool,
+	format: MeshFormat,
+	// Millimetres per unit
+	scale: f32,
+}
+
+impl Default for PrintExport {
+	fn default() -> Self {
+		Self { open: false, format: MeshFormat::Stl, scale: 1. }
+	}
+}
+
+#[derive(Debug, Clone)]
+struct PathRenderSettings {
+	open: bool,
+	width: u32,
+	height: u32,
+	fps: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VideoSource {
+	// The section being played, or every frame
+	Animation,
+	// The camera going once around the model
+	Turntable,
+}
+
+// Doom-style sprites of the model, seen from all around
+struct SpriteExport {
+	open: bool,
+	// The first four letters of each sprite's name
+	prefix: String,
+	// 8, or 16 for GZDoom
+	rotations: usize,
+	first_frame: usize,
+	last_frame: usize,
+	// Use every nth frame
+	step: usize,
+	// Pixels per unit
+	scale: f32,
+}
+
+impl Default for SpriteExport {
+	fn default() -> Self {
+		Self {
+			open: false,
+			prefix: String::from("SPRT"),
+			rotations: 8,
+			first_frame: 0,
+			last_frame: 0,
+			step: 1,
+			scale: 1.,
+		}
+	}
+}
+
+impl SpriteExport {
+	fn frames(&self) -> impl Iterator<Item = usize> {
+		(self.first_frame..=self.last_frame).step_by(self.step.max(1))
+	}
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct VideoExportSettings {
+	open: bool,
+	source: VideoSource,
+	width: u32,
+	height: u32,
+	fps: u32,
+	// How fast the model is animated
+	anim_fps: f32,
+	turntable_seconds: f32,
+	transparent: bool,
+	// 0 is lossless, which is much quicker to encode than the others
+	quantizer: u8,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for VideoExportSettings {
+	fn default() -> Self {
+		Self {
+			open: false,
+			source: VideoSource::Animation,
+			width: 1280,
+			height: 720,
+			fps: 30,
+			anim_fps: 15.,
+			turntable_seconds: 6.,
+			transparent: false,
+			quantizer: 0,
+		}
+	}
+}
+
+// Where each frame being rendered is seen from
+enum RenderShots {
+	CameraPath { keyframes: Vec<CameraKeyframe>, camera: OrbitCamera },
+	// Playing the animation from `start` to `end`
+	#[cfg(not(target_arch = "wasm32"))]
+	Animation { camera: OrbitCamera, start: f32, end: f32, anim_fps: f32 },
+	#[cfg(not(target_arch = "wasm32"))]
+	Turntable(OrbitCamera),
+}
+
+enum RenderOutput {
+	// PNG images in a folder
+	Images(PathBuf),
+	#[cfg(not(target_arch = "wasm32"))]
+	Video(PathBuf, video::Encoding),
+}
+
+// A camera path or a video being rendered a frame at a time, so the window
+// keeps responding and it can be cancelled
+struct RenderJob {
+	shots: RenderShots,
+	output: RenderOutput,
+	frames: usize,
+	// The next frame to render
+	next: usize,
+	fps: u32,
+	transparent: bool,
+	renderer: Renderer,
+	framebuffer: Framebuffer,
+}
+
+impl RenderJob {
+	fn path(&self) -> &Path {
+		match &self.output {
+			RenderOutput::Images(directory) => directory,
+			#[cfg(not(target_arch = "wasm32"))]
+			RenderOutput::Video(path, _) => path,
+		}
+	}
+	// How many frames have been saved
+	fn done(&self) -> usize {
+		match &self.output {
+			RenderOutput::Images(_) => self.next,
+			#[cfg(not(target_arch = "wasm32"))]
+			RenderOutput::Video(_, encoding) => encoding.done,
+		}
+	}
+	// The images which were already rendered are kept
+	fn cancel(self) {
+		match self.output {
+			RenderOutput::Images(_) => (),
+			#[cfg(not(target_arch = "wasm32"))]
+			RenderOutput::Video(_, encoding) => encoding.cancel(),
+		}
+	}
+}
+
+impl Default for PathRenderSettings {
+	fn default() -> Self {
+		Self {
+			open: false,
+			width: 1280,
+			height: 720,
+			fps: 30,
+		}
+	}
+}
+
+impl Default for UvExportSettings {
+	fn default() -> Self {
+		Self {
+			open: false,
+			size: 1024,
+			surfaces: vec![],
+			over_texture: false,
+		}
+	}
+}
+
+// Quake 3 lights models using the light grid of the map, which has an ambient
+// colour, and a colour coming from one direction
+#[derive(Debug, Clone, Copy)]
+struct EntityLight {
+	open: bool,
+	enabled: bool,
+	ambient: [f32; 3],
+	directed: [f32; 3],
+	// Where the directed light comes from, in degrees
+	yaw: f32,
+	pitch: f32,
+}
+
+impl Default for EntityLight {
+	fn default() -> Self {
+		Self {
+			open: false,
+			enabled: false,
+			ambient: [0.25; 3],
+			directed: [0.75; 3],
+			yaw: 45.,
+			pitch: 45.,
+		}
+	}
+}
+
+// The stored and calculated normal of a vertex
+#[derive(Debug, Clone, Copy)]
+struct VertexNormal {
+	surface: usize,
+	vertex: usize,
+	position: Vec3,
+	lat_long: (u8, u8),
+	stored: Vec3,
+	smooth: Vec3,
+	// Degrees
+	error: f32,
+}
+
+// The normals of the current frame, and lines showing them
+struct NormalLines {
+	frame: usize,
+	gzdoom: bool,
+	threshold: f32,
+	all: bool,
+	normals: Vec<VertexNormal>,
+	lines: Option<BasicModel<u32, UniformsRes, UniformsResLocations>>,
+}
+
+// Triangles facing away from their vertex normals
+struct FlippedTriangles {
+	frame: usize,
+	gzdoom: bool,
+	// Surface and triangle indices
+	triangles: Vec<(usize, usize)>,
+}
+
+struct UvChecker {
+	// Squares along each side of the checkerboard
+	cells: u32,
+	texture: Option<(u32, Rc<Texture>)>,
+}
+
+impl Default for UvChecker {
+	fn default() -> Self {
+		Self { cells: 8, texture: None }
+	}
+}
+
+// Spinning the camera around the model by itself
+struct Turntable {
+	enabled: bool,
+	// Degrees per second
+	speed: f32,
+	// When the camera started spinning, and its longitude then
+	start: Option<(Instant, f32)>,
+	// The longitude set last, so moving the camera some other way restarts
+	// the spin from there
+	longtude: f32,
+}
+
+impl Default for Turntable {
+	fn default() -> Self {
+		Self { enabled: false, speed: 20., start: None, longtude: 0. }
+	}
+}
+
+// For finding vertices where an exporter wrote bad normals
+struct NormalInspector {
+	open: bool,
+	// Degrees
+	threshold: f32,
+	// Draw lines for every vertex, not just those with bad normals
+	all: bool,
+	// Encoding the error textures were made with
+	gzdoom: Option<bool>,
+	lines: Option<NormalLines>,
+}
+
+// What a motion trail follows through the animation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrailTarget {
+	Tag(usize),
+	Vertex { surface: usize, vertex: usize },
+}
+
+// The path of a vertex or tag over all frames, for spotting popping and
+// jittery frames
+#[derive(Default)]
+struct MotionTrail {
+	open: bool,
+	target: Option<TrailTarget>,
+	// The next click on the model picks the vertex
+	picking: bool,
+	// What points and lines were made for
+	built: Option<TrailTarget>,
+	// Where the target is in each frame
+	points: Vec<Vec3>,
+	lines: Option<BasicModel<u32, UniformsRes, UniformsResLocations>>,
+}
+
+// Reviewers' notes on the model, kept in its sidecar file
+#[derive(Default)]
+struct NotesWindow {
+	open: bool,
+	// Don't draw the notes over the view
+	hidden: bool,
+	// Text of the next note
+	text: String,
+	// The tag the next note goes on, or None to put it on a vertex
+	tag: Option<String>,
+	// The next click on the model puts the note there
+	placing: bool,
+	// Only show the next note on the current frame
+	this_frame: bool,
+}
+
+// The current frame made out of voxels, for converting props into voxel
+// models
+struct VoxelPreview {
+	open: bool,
+	// Voxels along the longest side of the frame
+	resolution: u32,
+	// Draw the voxels in place of the model
+	show: bool,
+	// The frame the voxels were made from
+	frame: usize,
+	grid: Option<VoxelGrid>,
+	// The faces of the voxels which can be seen
+	mesh: Option<BasicModel<u32, UniformsRes, UniformsResLocations>>,
+}
+
+// Splitting a surface by UV island, so that the parts can have different skins
+#[derive(Default)]
+struct SurfaceSplit {
+	open: bool,
+	surface: usize,
+	// Give every island a surface of its own, instead of moving the selected
+	// islands into one new surface
+	each: bool,
+	selected: BTreeSet<usize>,
+	// The island under the pointer in the list
+	hovered: Option<usize>,
+	// What the outline was made for: the surface, frame, selected islands and
+	// hovered island
+	built: Option<(usize, usize, Vec<usize>, Option<usize>)>,
+	outline: Option<BasicModel<u32, UniformsRes, UniformsResLocations>>,
+}
+
+impl Default for VoxelPreview {
+	fn default() -> Self {
+		Self { open: false, resolution: 64, show: true, frame: 0, grid: None, mesh: None }
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+struct TagEditor {
+	open: bool,
+	selected: Option<usize>,
+	// Change the tag in every frame, instead of just the current one
+	all_frames: bool,
+	rename: String,
+	// Name for new and duplicated tags
+	new_name: String,
+	// Tags whose names aren't drawn over the view
+	hidden_labels: BTreeSet<usize>,
+}
+
+// Something in the scene which can be selected in the outliner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum SceneNode {
+	Model,
+	Surface(usize),
+	Tag(usize),
+	Attachment(usize),
+	Light,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Outliner {
+	open: bool,
+	selected: Option<SceneNode>,
+	// Locked nodes can't be changed in the other windows
+	locked: BTreeSet<SceneNode>,
+}
+
+impl Outliner {
+	fn is_locked(&self, node: SceneNode) -> bool {
+		self.locked.contains(&node)
+	}
+	// Forget the nodes which no longer exist
+	fn retain(&mut self, keep: impl Fn(SceneNode) -> bool) {
+		self.locked.retain(|&node| keep(node));
+		self.selected = self.selected.filter(|&node| keep(node));
+	}
+	// The attachments after the removed one move up
+	fn remove_attachment(&mut self, index: usize) {
+		let shift = |node: SceneNode| match node {
+			SceneNode::Attachment(i) if i > index => SceneNode::Attachment(i - 1),
+			node => node,
+		};
+		self.retain(|node| node != SceneNode::Attachment(index));
+		self.locked = self.locked.iter().copied().map(shift).collect();
+		self.selected = self.selected.map(shift);
+	}
+}
+
+// A change to the tags of the model. Local changes are along the tag's own
+// axes, and the others are in model space.
+#[derive(Debug, Clone)]
+enum TagEdit {
+	Rename(usize, String),
+	Add(String),
+	Duplicate(usize, String),
+	Remove(usize),
+	// Copy the tag in the current frame to every other frame
+	CopyToAllFrames(usize),
+	Move(usize, Vec3),
+	Rotate(usize, Mat3),
+	MoveLocal(usize, usize, f32),
+	TurnLocal(usize, usize, f32),
+}
+
+// A name stored in the model, which is being edited in the shaders panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NameTarget {
+	Surface(usize),
+	// Surface and shader index
+	Shader(usize, usize),
+}
+
+#[derive(Debug, Clone)]
+struct NameEdit {
+	target: NameTarget,
+	text: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TransformSettings {
+	open: bool,
+	scale: f32,
+	// Degrees
+	yaw: f32,
+	pitch: f32,
+	roll: f32,
+	offset: Vec3,
+	// 0 for X
+	mirror_axis: usize,
+	// Flip U and V when mirroring
+	mirror_uv: [bool; 2],
+}
+
+impl Default for TransformSettings {
+	fn default() -> Self {
+		Self {
+			open: false, scale: 1., yaw: 0., pitch: 0., roll: 0., offset: Vec3::ZERO,
+			mirror_axis: 1, mirror_uv: [false; 2],
+		}
+	}
+}
+
+impl TransformSettings {
+	fn transform(&self) -> ModelTransform {
+		let [yaw, pitch, roll] = [self.yaw, self.pitch, self.roll].map(f32::to_radians);
+		ModelTransform {
+			scale: self.scale,
+			rotation: Quat::from_euler(EulerRot::ZYX, yaw, pitch, roll),
+			offset: self.offset,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+struct LodWindow {
+	open: bool,
+	// How much of each surface is kept for model_1.md3 and model_2.md3
+	percent: [f32; 2],
+	// 0 is the full model
+	preview: usize,
+	lods: Option<[MD3Model; 2]>,
+}
+
+impl Default for LodWindow {
+	fn default() -> Self {
+		Self { open: false, percent: [50., 25.], preview: 0, lods: None }
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OptimizeWindow {
+	open: bool,
+	settings: OptimizeSettings,
+	report: Option<OptimizeReport>,
+	// In the 1/64 unit steps vertices are stored in
+	snap_grid: u16,
+	snap_report: Option<SnapReport>,
+}
+
+impl Default for OptimizeWindow {
+	fn default() -> Self {
+		Self {
+			open: false,
+			settings: OptimizeSettings::default(),
+			report: None,
+			snap_grid: 1,
+			snap_report: None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FrameEditor {
+	open: bool,
+	first: usize,
+	last: usize,
+	// Where the frames are moved to
+	before: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct NormalRecalcSettings {
+	open: bool,
+	weighting: NormalWeighting,
+	gzdoom: bool,
+}
+
+impl Default for NormalInspector {
+	fn default() -> Self {
+		Self { open: false, threshold: 15., all: false, gzdoom: None, lines: None }
+	}
+}
+
+impl EntityLight {
+	// Unit vector towards the light, in model space
+	fn direction(&self) -> Vec3 {
+		let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+		Vec3::new(yaw.cos() * pitch.cos(), yaw.sin() * pitch.cos(), pitch.sin())
+	}
+}
+
+// Find and replace in the shader names of all surfaces
+#[derive(Debug, Clone, Default)]
+struct ShaderReplaceSettings {
+	open: bool,
+	find: String,
+	replace: String,
+	regex: bool,
+}
+
+impl ShaderReplaceSettings {
+	// Make a function which returns the new name of a shader, or None if the
+	// name doesn't change
+	fn renamer(&self) -> Result<impl Fn(&str) -> Option<String> + '_, regex::Error> {
+		let regex = if self.regex { Some(Regex::new(&self.find)?) } else { None };
+		Ok(move |name: &str| {
+			if self.find.is_empty() { return None; }
+			let new_name = match regex.as_ref() {
+				Some(regex) => regex.replace_all(name, self.replace.as_str()).into_owned(),
+				None => name.replace(&self.find, &self.replace),
+			};
+			(new_name != name).then_some(new_name)
+		})
+	}
+}
+
+// What to do once a model has been loaded
+enum AfterLoad {
+	Nothing,
+	Project(Box<Project>),
+	// Keep the view, surface settings, bookmarks and markers
+	Reload {
+		camera: OrbitCamera,
+		current_frame: f32,
+		tag_mode: bool,
+		surfaces: Vec<SurfaceSettings>,
+		bookmarks: BTreeSet<usize>,
+		markers: Vec<Marker>,
+	},
+}
+
+// Something which would throw away unsaved changes to the model, waiting for
+// the user to say that's alright
+enum DiscardChanges {
+	Load(PathBuf, AfterLoad),
+	Quit,
+}
+
+// Another model in the scene, either at one of the tags of the main model, or
+// standing on its own if the tag is empty
+struct Attachment {
+	path: PathBuf,
+	model: Box<MD3Model>,
+	tag: String,
+	hidden: bool,
+	// Relative to the tag
+	transform: DisplayTransform,
+	// Image used for all surfaces instead of their shaders
+	texture: Option<PathBuf>,
+	models: Vec<(usize, BasicModel<u32, UniformsMD3, UniformsMD3Locations>)>,
+}
+
+impl Attachment {
+	// Where the attachment is in the main model's space
+	fn placement(&self, model: &MD3Model, frame: f32) -> Option<Mat4> {
+		let tag = match self.tag.is_empty() {
+			true => Mat4::IDENTITY,
+			false => Mat4::from(model.tag_transform(&self.tag, frame)?),
+		};
+		Some(tag * self.transform.matrix())
+	}
 }
 
 struct App {
+	#[cfg(not(target_arch = "wasm32"))]
 	open_file_dialog: FileDialog,
+	// The browser's file picker, which can pick the model's textures too
+	#[cfg(target_arch = "wasm32")]
+	open_file_dialog: web::FilePicker,
+	file_dialog: Option<(DialogPurpose, FileDialog)>,
 	model_data: Option<Box<MD3Model>>,
+	// The model has been changed since it was opened or saved
+	model_modified: bool,
+	discard_changes: Option<DiscardChanges>,
+	model_path: Option<PathBuf>,
+	loading: Option<(Loading, AfterLoad)>,
+	surface_settings: Vec<SurfaceSettings>,
 	current_frame: f32,
 	anim_playing: bool,
 	anim_start_time: Instant,
 	anim_start_frame: f32,
+	// Multiplies the speed of animation playback
+	anim_speed: f32,
+	// Frame shown while hovering over the frame slider with Shift held
+	scrub_frame: Option<f32>,
+	// When the frame being drawn started, for animation playback and the
+	// turntable
+	clock: Instant,
+	turntable: Turntable,
+	display: DisplayTransform,
+	display_open: bool,
+	model_hidden: bool,
+	outliner: Outliner,
+	// Text in the Paste view window, if it's open
+	pasted_view: Option<String>,
 	frame_range: Option<RangeInclusive<f32>>,
-	error_log: Option<String>,
-	models: Vec<BasicModel<u32, UniformsMD3, UniformsMD3Locations>>,
+	// Frames the animation loops over instead of the whole range
+	play_section: Option<RangeInclusive<usize>>,
+	bookmarks: BTreeSet<usize>,
+	frames_open: bool,
+	// Animation events, from the model's sidecar file
+	markers: Vec<Marker>,
+	new_marker_name: String,
+	notes: Vec<Note>,
+	notes_window: NotesWindow,
+	problems: Vec<Problem>,
+	log_open: bool,
+	// Least important messages shown in the log window
+	log_level: log::Level,
+	// Surface index, and the model used to render it
+	models: Vec<(usize, BasicModel<u32, UniformsMD3, UniformsMD3Locations>)>,
+	md3_shader: Rc<ShaderProgram<UniformsMD3Locations>>,
 	axes: BasicModel<u8, UniformsRes, UniformsResLocations>,
 	tag_axes: BasicModel<u8, UniformsRes, UniformsResLocations>,
+	frustum: BasicModel<u8, UniformsRes, UniformsResLocations>,
+	bounds: BasicModel<u8, UniformsRes, UniformsResLocations>,
+	// Lines following the origin of each tag over the whole animation
+	tag_paths: Option<BasicModel<u32, UniformsRes, UniformsResLocations>>,
+	sky_shader: Rc<ShaderProgram<UniformsSkyLocations>>,
+	skybox: Option<BasicModel<u8, UniformsSky, UniformsSkyLocations>>,
+	skybox_path: Option<PathBuf>,
 	camera: OrbitCamera,
+	debug_camera: OrbitCamera,
 	controls: AppControls,
 	texture_cache: TextureCache,
+	settings: Settings,
+	model_watcher: Option<(RecommendedWatcher, Receiver<notify::Result<notify::Event>>)>,
+	// When the model file was last changed
+	model_changed: Option<Instant>,
+	// The folder the shaders were read from, to rebuild them when they're
+	// edited
+	assets_path: Option<PathBuf>,
+	shader_watcher: Option<(RecommendedWatcher, Receiver<notify::Result<notify::Event>>)>,
+	shaders_changed: Option<Instant>,
+	uv_export: UvExportSettings,
+	vertex_export: VertexExportSettings,
+	print_export: PrintExport,
+	camera_path: Vec<CameraKeyframe>,
+	path_render: PathRenderSettings,
+	#[cfg(not(target_arch = "wasm32"))]
+	video_export: VideoExportSettings,
+	render_job: Option<RenderJob>,
+	sprite_export: SpriteExport,
+	frame_bounds_open: bool,
+	merge_open: bool,
+	surface_split: SurfaceSplit,
+	attachments: Vec<Attachment>,
+	shader_replace: ShaderReplaceSettings,
+	shortcuts_open: bool,
+	// The action which the next key pressed will be bound to
+	capturing_shortcut: Option<Action>,
+	// Size of the window in physical pixels, for screenshots
+	viewport: (u32, u32),
+	// Positions of the fingers on a touch screen, for pinching to zoom
+	touches: HashMap<u64, Vec2>,
+	// Camera transition, and when it started
+	camera_tween: Option<(CameraTween, Instant)>,
+	view_settings_open: bool,
+	statistics_open: bool,
+	entity_light: EntityLight,
+	comparison: Option<Comparison>,
+	normal_inspector: NormalInspector,
+	motion_trail: MotionTrail,
+	voxels: VoxelPreview,
+	uv_checker: UvChecker,
+	flipped: Option<FlippedTriangles>,
+	// The headers of the loaded model's file, as they are on disk
+	internals: Option<layout::Layout>,
+	// Masks of the fullbright texels of 8-bit textures
+	fullbright_masks: HashMap<PathBuf, Option<Rc<Texture>>>,
+	normal_recalc: NormalRecalcSettings,
+	tag_editor: TagEditor,
+	name_edit: Option<NameEdit>,
+	frame_editor: FrameEditor,
+	transform: TransformSettings,
+	optimize: OptimizeWindow,
+	lod: LodWindow,
 }
 
 impl App {
-	fn new(res: &AppResources, glc: &Arc<GLContext>) -> Self {
+	fn new(res: &AppResources, glc: &Arc<GLContext>, md3_shader: Rc<ShaderProgram<UniformsMD3Locations>>, settings: Settings) -> Result<Self, AError> {
 		let axes_shader = {
 			let sp = ShaderProgramBuilder::new()
 				.add_shader(ShaderStage::Vertex, &res.res_vertex_shader)
@@ -140,19 +1169,54 @@ impl App {
 				.build(Arc::clone(&glc)).unwrap();
 			Rc::new(sp)
 		};
-		App {
+		let sky_shader = {
+			let sp = ShaderProgramBuilder::new()
+				.add_shader(ShaderStage::Vertex, &res.sky_vertex_shader)
+				.add_shader(ShaderStage::Fragment, &res.sky_pixel_shader)
+				.build(Arc::clone(glc)).context("sky.vert or sky.frag")?;
+			Rc::new(sp)
+		};
+		Ok(App {
+			#[cfg(not(target_arch = "wasm32"))]
 			open_file_dialog: FileDialog::open_file(None)
 				.show_rename(false)
 				.show_new_folder(false)
 				.filter(String::from("md3")),
+			#[cfg(target_arch = "wasm32")]
+			open_file_dialog: web::FilePicker::new(".md3,.png,.tga,.jpg,.jpeg"),
+			file_dialog: None,
 			model_data: None,
+			model_modified: false,
+			discard_changes: None,
+			model_path: None,
+			loading: None,
+			surface_settings: vec![],
 			current_frame: 0.,
 			anim_playing: false,
 			anim_start_time: Instant::now(),
 			anim_start_frame: 0.,
+			anim_speed: 1.,
+			scrub_frame: None,
+			clock: Instant::now(),
+			turntable: Turntable::default(),
+			display: DisplayTransform::default(),
+			display_open: false,
+			model_hidden: false,
+			outliner: Outliner::default(),
+			pasted_view: None,
 			frame_range: None,
-			error_log: None,
+			play_section: None,
+			bookmarks: BTreeSet::new(),
+			frames_open: false,
+			markers: vec![],
+			new_marker_name: String::new(),
+			notes: vec![],
+			notes_window: NotesWindow::default(),
+			problems: vec![],
+			log_open: false,
+			log_level: log::Level::Info,
 			models: vec![],
+			md3_shader,
 			axes: BasicModel {
 				vertex: VertexBuffer::new(Arc::clone(glc), Box::new(res::AXES_V)),
 				index: IndexBuffer::new(Arc::clone(glc), Vec::from(res::AXES_I)),
@@ -165,58 +1229,2902 @@ impl App {
 				shader: Rc::clone(&axes_shader),
 				uniforms: UniformsRes::default(),
 			},
-			controls: AppControls::default(),
+			frustum: BasicModel {
+				vertex: VertexBuffer::new(Arc::clone(glc), Box::new(res::FRUSTUM_V)),
+				index: IndexBuffer::new(Arc::clone(glc), Vec::from(res::CUBE_LINES_I)),
+				shader: Rc::clone(&axes_shader),
+				uniforms: UniformsRes::default(),
+			},
+			bounds: BasicModel {
+				vertex: VertexBuffer::new(Arc::clone(glc), Box::new(res::BOUNDS_V)),
+				index: IndexBuffer::new(Arc::clone(glc), Vec::from(res::CUBE_LINES_I)),
+				shader: Rc::clone(&axes_shader),
+				uniforms: UniformsRes::default(),
+			},
+			tag_paths: None,
+			sky_shader,
+			skybox: None,
+			skybox_path: None,
+			controls: AppControls { focused: true, ..Default::default() },
 			camera: OrbitCamera::default(),
-			texture_cache: TextureCache::new(Arc::clone(glc), &res.null_surface),
+			debug_camera: OrbitCamera::default(),
+			texture_cache: TextureCache::new(Arc::clone(glc), &res.null_surface, settings.nearest_filtering),
+			settings,
+			model_watcher: None,
+			model_changed: None,
+			assets_path: res.path.clone(),
+			shader_watcher: res.path.as_ref().and_then(|directory| {
+				let (sender, changes) = mpsc::channel();
+				let mut watcher = watch_files(sender).ok()?;
+				watcher.watch(directory, RecursiveMode::NonRecursive)
+					.map_err(|e| log::warn!("Could not watch {}: {}", directory.display(), e)).ok()?;
+				Some((watcher, changes))
+			}),
+			shaders_changed: None,
+			uv_export: UvExportSettings::default(),
+			vertex_export: VertexExportSettings::default(),
+			print_export: PrintExport::default(),
+			camera_path: vec![],
+			path_render: PathRenderSettings::default(),
+			#[cfg(not(target_arch = "wasm32"))]
+			video_export: VideoExportSettings::default(),
+			render_job: None,
+			sprite_export: SpriteExport::default(),
+			frame_bounds_open: false,
+			merge_open: false,
+			surface_split: SurfaceSplit::default(),
+			shader_replace: ShaderReplaceSettings::default(),
+			shortcuts_open: false,
+			capturing_shortcut: None,
+			viewport: (1, 1),
+			touches: HashMap::new(),
+			camera_tween: None,
+			view_settings_open: false,
+			statistics_open: false,
+			entity_light: EntityLight::default(),
+			comparison: None,
+			normal_inspector: NormalInspector::default(),
+			motion_trail: MotionTrail::default(),
+			voxels: VoxelPreview::default(),
+			uv_checker: UvChecker::default(),
+			flipped: None,
+			internals: None,
+			fullbright_masks: HashMap::default(),
+			normal_recalc: NormalRecalcSettings::default(),
+			tag_editor: TagEditor { all_frames: true, ..Default::default() },
+			name_edit: None,
+			frame_editor: FrameEditor::default(),
+			transform: TransformSettings::default(),
+			optimize: OptimizeWindow::default(),
+			lod: LodWindow::default(),
+			attachments: vec![],
+		})
+	}
+	// The camera the scene is viewed from
+	fn view_camera(&self) -> OrbitCamera {
+		if self.controls.debug_camera { self.debug_camera } else { self.camera }
+	}
+	fn save_settings(&mut self) {
+		if let Err(e) = self.settings.save() {
+			self.log_error(&format!("Could not save settings:\n{}", e));
 		}
 	}
-}
-
-const MOUSE_FACTOR: f32 = 0.0078125; // 1./128
-const LOOK_LIMIT: f32 = {
-	use std::mem;
-	let v = unsafe{mem::transmute::<f32, u32>(FRAC_PI_2)};
-	// It's a pain in the butt having to generate this code... But it's all
-	// done at compile time, so there are no runtime costs.
-	/* 
-	Shell (zsh) code used to generate this mess:
-	(bits=32
-	for bit in {0..$((bits-1))}; do
-		print -v hxb -f "%0$((bits/4))X" $((1 << bit))
-		if ((bit > 0)); then
-			print -n "else "
-		fi
-		print "if v & $hxb != 0 { $hxb }"
-	done
-	print "else { 0 };")
-	 */
-	let lowest_bit = if v & 00000001 != 0 { 00000001 }
-	else if v & 00000002 != 0 { 00000002 }
-	else if v & 00000004 != 0 { 00000004 }
-	else if v & 00000008 != 0 { 00000008 }
-	else if v & 00000010 != 0 { 00000010 }
-	else if v & 00000020 != 0 { 00000020 }
-	else if v & 00000040 != 0 { 00000040 }
-	else if v & 00000080 != 0 { 00000080 }
-	else if v & 00000100 != 0 { 00000100 }
-	else if v & 00000200 != 0 { 00000200 }
-	else if v & 00000400 != 0 { 00000400 }
-	else if v & 00000800 != 0 { 00000800 }
-	else if v & 00001000 != 0 { 00001000 }
-	else if v & 00002000 != 0 { 00002000 }
-	else if v & 00004000 != 0 { 00004000 }
-	else if v & 00008000 != 0 { 00008000 }
-	else if v & 00010000 != 0 { 00010000 }
-	else if v & 00020000 != 0 { 00020000 }
-	else if v & 00040000 != 0 { 00040000 }
-	else if v & 00080000 != 0 { 00080000 }
-	else if v & 00100000 != 0 { 00100000 }
-	else if v & 00200000 != 0 { 00200000 }
-	else if v & 00400000 != 0 { 00400000 }
-	else if v & 00800000 != 0 { 00800000 }
-	else if v & 01000000 != 0 { 01000000 }
-	else if v & 02000000 != 0 { 02000000 }
-	else if v & 04000000 != 0 { 04000000 }
+	fn log_error(&mut self, message: &str) {
+		self.report(Problem::error(Source::Other, message));
+	}
+	fn report(&mut self, problem: Problem) {
+		match problem.severity {
+			Severity::Error => log::error!("{}", problem.message),
+			Severity::Warning => log::warn!("{}", problem.message),
+		}
+		self.problems.push(problem);
+	}
+	fn retry(&mut self, glc: &Arc<GLContext>, retry: Retry) {
+		match retry {
+			Retry::LoadModel(path) => self.start_loading(&path, AfterLoad::Nothing),
+			Retry::ReloadModel => if let Err(e) = self.reload_model() {
+				self.log_error(&e.to_string());
+			},
+			Retry::FragmentShader => self.update_fragment_shader(glc),
+			Retry::SurfaceTextures(surface) => {
+				self.update_surface_texture(glc, surface);
+				if let Some(settings) = self.surface_settings.get(surface) {
+					let (normal_map, specular_map) = (settings.normal_map.clone(), settings.specular_map.clone());
+					self.set_surface_map(glc, surface, TextureMap::Normal, normal_map);
+					self.set_surface_map(glc, surface, TextureMap::Specular, specular_map);
+				}
+			},
+		}
+	}
+	// Read the model on another thread, and do something with it once it's
+	// loaded
+	fn start_loading(&mut self, fpath: &Path, then: AfterLoad) {
+		// Even opening the same model again would lose the changes
+		if self.model_modified {
+			self.discard_changes = Some(DiscardChanges::Load(fpath.to_path_buf(), then));
+			return;
+		}
+		// Textures chosen for this model in previous sessions, or in the project
+		let textures: Vec<PathBuf> = match &then {
+			AfterLoad::Project(project) => project.textures().cloned().collect(),
+			_ => self.settings.model(fpath).map(|m| m.textures.values().cloned().collect()).unwrap_or_default(),
+		};
+		self.loading = Some((Loading::start(fpath, textures, self.settings.tolerant_parsing), then));
+	}
+	// Upload one of the textures which have been decoded, and use it for the
+	// surfaces which need it
+	fn upload_textures(&mut self, glc: &Arc<GLContext>) {
+		if !self.texture_cache.is_streaming() { return; }
+		// Visible surfaces' textures first, then the normal and specular maps
+		let mut priorities: HashMap<PathBuf, usize> = HashMap::new();
+		self.surface_settings.iter().enumerate().for_each(|(surface, settings)| {
+			let mut set = |path: PathBuf, priority: usize| {
+				let p = priorities.entry(path).or_insert(priority);
+				*p = (*p).min(priority);
+			};
+			if let Some(path) = self.surface_texture_path(surface) {
+				set(path, if settings.hidden { 1 } else { 0 });
+			}
+			[&settings.normal_map, &settings.specular_map].into_iter().flatten()
+				.for_each(|path| set(path.clone(), 2));
+		});
+		let priority = |path: &Path| priorities.get(path).copied().unwrap_or(3);
+		let path = match self.texture_cache.upload_next(Arc::clone(glc), priority) {
+			Some(path) => path,
+			None => return,
+		};
+		(0..self.surface_settings.len()).for_each(|surface| {
+			if self.surface_texture_path(surface).is_some_and(|p| p == path) {
+				self.update_surface_texture(glc, surface);
+			}
+			let settings = &self.surface_settings[surface];
+			[(TextureMap::Normal, settings.normal_map.clone()), (TextureMap::Specular, settings.specular_map.clone())]
+				.into_iter().filter(|(_, map)| map.as_ref() == Some(&path))
+				.for_each(|(map, path)| self.set_surface_map(glc, surface, map, path));
+		});
+	}
+	fn poll_loading(&mut self, glc: &Arc<GLContext>) {
+		let result = match self.loading.as_ref().and_then(|(loading, _)| loading.poll()) {
+			Some(result) => result,
+			None => return,
+		};
+		let (loading, then) = match self.loading.take() {
+			Some(loading) => loading,
+			None => return,
+		};
+		let result = result.and_then(|loaded| self.load_model(glc, loaded));
+		if let Err(e) = result {
+			let retry = match then {
+				AfterLoad::Reload { .. } => Retry::ReloadModel,
+				_ => Retry::LoadModel(loading.path.clone()),
+			};
+			let hint = match self.settings.tolerant_parsing {
+				true => "",
+				false => "\nTurn on File > Tolerant parsing to load what can be read.",
+			};
+			self.report(Problem::error(Source::Parser, format!("Error reading file {}:\n{}{}", loading.path.display(), e, hint))
+				.path(loading.path).retry(retry));
+			return;
+		}
+		match then {
+			AfterLoad::Nothing => self.restore_surfaces(glc, None),
+			AfterLoad::Project(project) => self.apply_project(glc, &project),
+			AfterLoad::Reload { camera, current_frame, tag_mode, surfaces, bookmarks, markers } => {
+				self.camera = camera;
+				self.current_frame = self.frame_range.as_ref()
+					.map(|range| current_frame.min(*range.end())).unwrap_or(0.);
+				self.controls.tag_mode = tag_mode;
+				self.apply_surface_settings(glc, &surfaces);
+				// The file may have fewer frames now
+				let num_frames = self.model_data.as_ref().map_or(0, |m| m.frames.len());
+				self.bookmarks = bookmarks.into_iter().filter(|&f| f < num_frames).collect();
+				self.markers = markers.into_iter().filter(|m| m.frame < num_frames).collect();
+			},
+		}
+	}
+	fn load_model(&mut self, glc: &Arc<GLContext>, loaded: LoadedModel) -> Result<(), AError> {
+		let LoadedModel { path, model, recovered, markers, notes, shaders, textures, images } = loaded;
+		let fpath = path.as_path();
+		let num_frames = model.frames.len();
+		self.frame_range = if num_frames > 1 {
+			Some(0.0..=(num_frames - 1) as f32)
+		} else {
+			None
+		};
+		self.texture_cache.clear();
+		self.anim_playing = false;
+		self.current_frame = 0.;
+		self.play_section = None;
+		self.scrub_frame = None;
+		self.bookmarks.clear();
+		self.comparison = None;
+		self.normal_inspector.gzdoom = None;
+		self.normal_inspector.lines = None;
+		self.motion_trail.built = None;
+		self.voxels.grid = None;
+		self.voxels.mesh = None;
+		self.surface_split.surface = 0;
+		self.surface_split.selected.clear();
+		self.surface_split.built = None;
+		self.flipped = None;
+		self.internals = None;
+		self.model_modified = false;
+		self.fullbright_masks.clear();
+		self.tag_editor.selected = None;
+		self.tag_editor.hidden_labels.clear();
+		self.outliner.retain(|node| matches!(node, SceneNode::Attachment(_) | SceneNode::Light));
+		self.name_edit = None;
+		self.optimize.report = None;
+		self.optimize.snap_report = None;
+		self.lod.lods = None;
+		self.lod.preview = 0;
+		if self.controls.view_mode == ViewMode::Deviation {
+			self.controls.view_mode = ViewMode::Textured;
+		}
+		self.camera.distance = home_distance(&model);
+		self.camera.target = Vec3::ZERO;
+		self.camera_tween = None;
+		self.surface_settings = vec![SurfaceSettings::default(); model.surfaces.len()];
+		let mut errors: Vec<Problem> = model.warnings().iter()
+			.map(|w| Problem::warning(Source::Parser, w).path(fpath)).collect();
+		errors.extend(limits::check_limits(&model, self.settings.engine_profile).iter()
+			.map(|w| limit_problem(w, fpath)));
+		errors.extend(recovered.iter().map(|e| Problem::warning(Source::Parser,
+			format!("Loaded anyway, the model may be incomplete: {}", e)).path(fpath)));
+		self.markers = markers.unwrap_or_else(|e| {
+			errors.push(Problem::error(Source::Parser, format!("Could not read animation markers:\n{}", e))
+				.path(markers::sidecar_path(fpath)).retry(Retry::ReloadModel));
+			vec![]
+		});
+		self.notes = notes.unwrap_or_else(|e| {
+			errors.push(Problem::error(Source::Parser, format!("Could not read notes:\n{}", e))
+				.path(notes::sidecar_path(fpath)).retry(Retry::ReloadModel));
+			vec![]
+		});
+		self.notes_window.placing = false;
+		// Surfaces with "cull none" in the game's shader scripts
+		match shaders {
+			Some(Ok(shaders)) => model.surfaces.iter().zip(self.surface_settings.iter_mut())
+				.for_each(|(surface, settings)| {
+					settings.two_sided = surface.shaders.first()
+						.and_then(|s| shaders.get(&q3shader::shader_key(&String::from_utf8_stop(&s.name))))
+						.is_some_and(|s| s.two_sided());
+				}),
+			Some(Err(e)) => errors.push(Problem::error(Source::Parser, format!("Could not read shader scripts: {}", e))
+				.retry(Retry::ReloadModel)),
+			None => (),
+		}
+		self.uv_export.surfaces = vec![true; model.surfaces.len()];
+		self.vertex_export.surface = 0;
+		self.vertex_export.first_frame = 0;
+		self.vertex_export.last_frame = num_frames.saturating_sub(1);
+		self.sprite_export.first_frame = 0;
+		self.sprite_export.last_frame = num_frames.saturating_sub(1).min(export::MAX_SPRITE_FRAMES - 1);
+		let stem = fpath.file_stem().unwrap_or_default().to_string_lossy().to_uppercase();
+		let prefix: String = stem.chars().filter(char::is_ascii_alphanumeric).take(4).collect();
+		self.sprite_export.prefix = format!("{:X<4}", prefix);
+		self.texture_cache.stream(textures, images);
+		let mut surface_errors = vec![];
+		self.models = self.surface_models(glc, &model, fpath, &mut surface_errors);
+		errors.extend(surface_errors.into_iter().map(|problem| match (problem.source, problem.surface) {
+			(Source::Texture, Some(surface)) => problem.retry(Retry::SurfaceTextures(surface)),
+			_ => problem,
+		}));
+		// Normal and specular maps are found using the names of the textures
+		self.models.iter_mut().for_each(|(index, surface_model)| {
+			let texture_path = shader_texture_path(fpath, model.surfaces[*index].shaders.first());
+			let settings = &mut self.surface_settings[*index];
+			settings.normal_map = detect_texture_map(&texture_path, "_n");
+			settings.specular_map = detect_texture_map(&texture_path, "_s");
+			let mut load_map = |path: Option<&PathBuf>| {
+				// Normal maps are left out until they're uploaded
+				if self.texture_cache.is_pending(path?) { return None; }
+				let (texture, error) = self.texture_cache.get(Arc::clone(glc), path?);
+				match error {
+					Some(e) => {
+						errors.push(Problem::error(Source::Texture, e).path(path?).surface(*index)
+							.retry(Retry::SurfaceTextures(*index)));
+						None
+					},
+					None => Some(texture),
+				}
+			};
+			surface_model.uniforms.normalMap = load_map(settings.normal_map.as_ref());
+			surface_model.uniforms.specularMap = load_map(settings.specular_map.as_ref());
+		});
+		// Tag-only models are useless without tag mode
+		self.controls.tag_mode = model.surfaces.is_empty() && model.num_tags > 0;
+		self.model_data = Some(Box::new(model));
+		self.model_path = Some(fpath.to_path_buf());
+		self.update_tag_paths(glc);
+		if self.fragment_shader_path().is_some() {
+			self.update_fragment_shader(glc);
+		}
+		self.model_changed = None;
+		self.model_watcher = fpath.parent().and_then(|directory| {
+			let (sender, changes) = mpsc::channel();
+			let mut watcher = watch_files(sender).ok()?;
+			watcher.watch(directory, RecursiveMode::NonRecursive)
+				.map_err(|e| log::warn!("Could not watch {}: {}", directory.display(), e)).ok()?;
+			Some((watcher, changes))
+		});
+		errors.into_iter().for_each(|e| self.report(e));
+		(0..self.surface_settings.len()).for_each(|surface| self.recheck_texture(surface));
+		Ok(())
+	}
+	// Create the GPU resources needed to draw each surface of a model, using
+	// the first shader of each surface as its texture.
+	fn surface_models(&mut self, glc: &Arc<GLContext>, model: &MD3Model, fpath: &Path, errors: &mut Vec<Problem>) -> Vec<(usize, BasicModel<u32, UniformsMD3, UniformsMD3Locations>)> {
+		model.surfaces.iter().enumerate().filter_map(|(index, surf)| {
+			let vb = VertexBuffer::from_surface(Arc::clone(glc), surf);
+			let ib = IndexBuffer::from_surface(Arc::clone(glc), surf);
+			let an = Texture::try_from_md3(Arc::clone(glc), surf)
+				.map_err(|e| errors.push(Problem::error(Source::Graphics, e).path(fpath).surface(index))).ok()?;
+			let texture_path = shader_texture_path(fpath, surf.shaders.first());
+			let (texture, error) = self.texture_cache.get(Arc::clone(glc), &texture_path);
+			if let Some(e) = error {
+				errors.push(Problem::error(Source::Texture, e).path(texture_path).surface(index));
+			}
+			Some((index, BasicModel {
+				vertex: vb,
+				index: ib,
+				shader: Rc::clone(&self.md3_shader),
+				uniforms: UniformsMD3 {
+					tex: texture,
+					anim: Rc::new(an),
+					gzdoom: Default::default(),
+					eye: Default::default(),
+					model: Mat4::from_scale(MD3_MODEL_SCALE),
+					frame: Default::default(),
+					mode: Default::default(),
+					numVerts: surf.num_verts as i32,
+					envmap: Default::default(),
+					viewOrigin: Default::default(),
+					sky: None,
+					skyReflection: Default::default(),
+					normalMap: None,
+					specularMap: None,
+					alphaThreshold: Default::default(),
+					entityLight: Default::default(),
+					ambientLight: Default::default(),
+					directedLight: Default::default(),
+					lightDirection: Default::default(),
+					deviation: None,
+					maxDeviation: Default::default(),
+					normalError: None,
+					normalThreshold: Default::default(),
+					checker: None,
+					fullbright: None,
+					outline: Default::default(),
+					opaque: true,
+				}
+			}))
+		}).collect()
+	}
+	// Draw the surface with another of its shaders, without remembering it.
+	// Returns whether the surface has that shader.
+	fn use_surface_shader(&mut self, glc: &Arc<GLContext>, surface: usize, shader: usize) -> bool {
+		let exists = self.model_data.as_ref().and_then(|m| m.surfaces.get(surface))
+			.is_some_and(|s| shader < s.shaders.len());
+		if !exists { return false; }
+		self.surface_settings[surface].shader = shader;
+		self.update_surface_texture(glc, surface);
+		true
+	}
+	// Same, but remembered for the next time the model is opened
+	fn set_surface_shader(&mut self, glc: &Arc<GLContext>, surface: usize, shader: usize) {
+		if !self.use_surface_shader(glc, surface, shader) { return; }
+		if let Some(fpath) = self.model_path.as_ref() {
+			let shaders = &mut self.settings.model_mut(fpath).shaders;
+			match shader {
+				0 => { shaders.remove(&surface); },
+				_ => { shaders.insert(surface, shader); },
+			}
+			self.save_settings();
+		}
+	}
+	// Use an image instead of the surface's shader, or go back to the shader
+	// if there is no image. Returns whether the surface exists.
+	fn use_surface_texture(&mut self, glc: &Arc<GLContext>, surface: usize, texture: Option<PathBuf>) -> bool {
+		let settings = match self.surface_settings.get_mut(surface) {
+			Some(s) => s,
+			None => return false,
+		};
+		settings.texture = texture;
+		self.update_surface_texture(glc, surface);
+		true
+	}
+	// Same, but remembered for the next time the model is opened
+	fn set_surface_texture(&mut self, glc: &Arc<GLContext>, surface: usize, texture: Option<PathBuf>) {
+		if !self.use_surface_texture(glc, surface, texture.clone()) { return; }
+		if let Some(fpath) = self.model_path.as_ref() {
+			let textures = &mut self.settings.model_mut(fpath).textures;
+			match texture {
+				Some(texture) => { textures.insert(surface, texture); },
+				None => { textures.remove(&surface); },
+			}
+			self.save_settings();
+		}
+	}
+	fn fragment_shader_path(&self) -> Option<PathBuf> {
+		let fpath = self.model_path.as_ref()?;
+		self.settings.model(fpath)?.fragment_shader.clone()
+	}
+	fn set_fragment_shader(&mut self, glc: &Arc<GLContext>, path: Option<PathBuf>) {
+		if let Some(fpath) = self.model_path.clone() {
+			self.settings.model_mut(&fpath).fragment_shader = path;
+			self.save_settings();
+		}
+		self.update_fragment_shader(glc);
+	}
+	// Draw the model with the chosen fragment shader, or the built in one
+	fn update_fragment_shader(&mut self, glc: &Arc<GLContext>) {
+		let path = self.fragment_shader_path();
+		let shader = match path.as_ref().map(|path| self.build_fragment_shader(glc, path)) {
+			Some(Ok(shader)) => Rc::new(shader),
+			Some(Err(e)) => {
+				self.report(Problem::error(Source::Graphics, format!("Could not use the fragment shader:\n{}", e))
+					.path(path.unwrap_or_default()).retry(Retry::FragmentShader));
+				Rc::clone(&self.md3_shader)
+			},
+			None => Rc::clone(&self.md3_shader),
+		};
+		self.models.iter_mut().for_each(|(_, model)| model.shader = Rc::clone(&shader));
+	}
+	fn build_fragment_shader(&self, glc: &Arc<GLContext>, path: &Path) -> Result<ShaderProgram<UniformsMD3Locations>, AError> {
+		let res = AppResources::try_load(self.assets_path.as_ref())?;
+		let source = fs::read_to_string(path)?;
+		ShaderProgramBuilder::new()
+			.add_shader(ShaderStage::Vertex, &res.md3_vertex_shader)
+			.add_shader(ShaderStage::Fragment, &source)
+			.build(Arc::clone(glc))
+	}
+	// Use another palette for 8-bit textures, or look for one near each
+	// texture if there's None
+	fn set_palette(&mut self, glc: &Arc<GLContext>, path: Option<PathBuf>) {
+		palette::set_user_palette(path.clone());
+		self.settings.palette = path;
+		self.save_settings();
+		let errors: Vec<AError> = self.texture_cache.reload_palettized().into_iter()
+			.filter_map(Result::err).collect();
+		errors.into_iter().for_each(|e| self.report(Problem::error(Source::Texture, e)));
+		// Textures which couldn't be read without a palette
+		let is_palettized = |problem: &Problem| problem.source == Source::Texture &&
+			problem.path.as_deref().is_some_and(palette::is_palettized);
+		let retries: Vec<Retry> = self.problems.iter().filter(|p| is_palettized(p))
+			.filter_map(|p| p.retry.clone()).collect();
+		self.problems.retain(|p| !is_palettized(p));
+		retries.into_iter().for_each(|retry| self.retry(glc, retry));
+	}
+	fn update_surface_texture(&mut self, glc: &Arc<GLContext>, surface: usize) {
+		let path = self.surface_texture_path(surface).unwrap_or_default();
+		let (texture, error) = self.texture_cache.get(Arc::clone(glc), &path);
+		if let Some((_, model)) = self.models.iter_mut().find(|(i, _)| *i == surface) {
+			model.uniforms.tex = texture;
+		}
+		if let Some(e) = error {
+			self.report(Problem::error(Source::Texture, e).path(path).surface(surface)
+				.retry(Retry::SurfaceTextures(surface)));
+		}
+		self.recheck_texture(surface);
+	}
+	// Warn about the surface's texture if the engine would resample it, or if
+	// it's the wrong shape for the UVs
+	fn recheck_texture(&mut self, surface: usize) {
+		self.problems.retain(|p| !(p.source == Source::Texture && p.severity == Severity::Warning &&
+			p.surface == Some(surface)));
+		self.texture_problems(surface).into_iter().for_each(|p| self.report(p));
+	}
+	fn texture_problems(&self, surface: usize) -> Vec<Problem> {
+		let surf = self.model_data.as_ref().and_then(|m| m.surfaces.get(surface));
+		let (path, surf) = match (self.surface_texture_path(surface), surf) {
+			(Some(path), Some(surf)) => (path, surf),
+			_ => return vec![],
+		};
+		// Textures which can't be read are reported when they're loaded
+		let (width, height) = match texture_check::image_size(&path) {
+			Ok(size) => size,
+			Err(_) => return vec![],
+		};
+		let limits = self.settings.engine_profile.limits();
+		let fitted = texture_check::fitted_size(width, height, &limits);
+		texture_check::check(width, height, surf.uv_span(0), &limits).into_iter().map(|warning| {
+			let problem = Problem::warning(Source::Texture, &warning).path(&path).surface(surface);
+			match warning {
+				TextureWarning::NotPowerOfTwo(..) | TextureWarning::TooBig { .. } if fitted != (width, height) =>
+					problem.fix(Fix::ResizeTexture { width: fitted.0, height: fitted.1 }),
+				_ => problem,
+			}
+		}).collect()
+	}
+	fn fix_problem(&mut self, problem: Problem) -> Result<(), AError> {
+		let path = problem.path.as_ref().ok_or_else(|| AError::msg("The problem isn't about a file"))?;
+		match problem.fix {
+			Some(Fix::ResizeTexture { width, height }) => {
+				texture_check::resize(path, width, height, &self.settings.backups)
+					.with_context(|| format!("Could not resize {}", path.display()))?;
+				if let Some(Err(e)) = self.texture_cache.reload(path) {
+					self.report(Problem::error(Source::Texture, e).path(path));
+				}
+			},
+			None => (),
+		}
+		if let Some(surface) = problem.surface {
+			self.recheck_texture(surface);
+		}
+		Ok(())
+	}
+	// Set or clear the normal map or specular map of a surface
+	fn set_surface_map(&mut self, glc: &Arc<GLContext>, surface: usize, map: TextureMap, path: Option<PathBuf>) {
+		if surface >= self.surface_settings.len() {
+			return;
+		}
+		let texture = match path.as_ref() {
+			Some(path) if self.texture_cache.is_pending(path) => None,
+			Some(path) => match self.texture_cache.get(Arc::clone(glc), path) {
+				(_, Some(e)) => {
+					self.report(Problem::error(Source::Texture, e).path(path).surface(surface)
+						.retry(Retry::SurfaceTextures(surface)));
+					return;
+				},
+				(texture, None) => Some(texture),
+			},
+			None => None,
+		};
+		let settings = &mut self.surface_settings[surface];
+		let model = self.models.iter_mut().find(|(i, _)| *i == surface);
+		match map {
+			TextureMap::Normal => {
+				settings.normal_map = path;
+				if let Some((_, model)) = model { model.uniforms.normalMap = texture; }
+			},
+			TextureMap::Specular => {
+				settings.specular_map = path;
+				if let Some((_, model)) = model { model.uniforms.specularMap = texture; }
+			},
+		}
+	}
+	fn surface_texture_path(&self, surface: usize) -> Option<PathBuf> {
+		let settings = self.surface_settings.get(surface)?;
+		if let Some(texture) = settings.texture.as_ref() {
+			return Some(texture.clone());
+		}
+		let model = self.model_data.as_ref()?;
+		let fpath = self.model_path.as_ref()?;
+		let shader = settings.shader;
+		let sdr = model.surfaces.get(surface)?.shaders.get(shader)?;
+		Some(shader_texture_path(fpath, Some(sdr)))
+	}
+	fn export_uv_layout(&self, path: &Path) -> Result<(), AError> {
+		let model = self.model_data.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let selected = &self.uv_export.surfaces;
+		let surfaces: Vec<&MD3Surface> = model.surfaces.iter().zip(selected.iter())
+			.filter_map(|(surf, &sel)| sel.then_some(surf)).collect();
+		let background = if self.uv_export.over_texture {
+			let texture = selected.iter().position(|&sel| sel)
+				.and_then(|surface| self.surface_texture_path(surface));
+			match texture {
+				Some(texture) => Some(image::open(&texture)
+					.with_context(|| format!("Could not load texture {}", texture.display()))?
+					.into_rgba8()),
+				None => None,
+			}
+		} else { None };
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("png");
+		}
+		export::uv_layout(&surfaces, self.uv_export.size, background.as_ref())
+			.save(&path)?;
+		Ok(())
+	}
+	fn export_vertex_data(&self, path: &Path) -> Result<(), AError> {
+		let model = self.model_data.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let settings = &self.vertex_export;
+		let surf = model.surfaces.get(settings.surface)
+			.ok_or_else(|| AError::msg("Surface does not exist"))?;
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension(match settings.format {
+				VertexDataFormat::Csv => "csv",
+				VertexDataFormat::Json => "json",
+			});
+		}
+		let mut writer = BufWriter::new(File::create(&path)?);
+		export::vertex_data(surf, settings.first_frame..=settings.last_frame,
+			self.controls.gzdoom_normals, settings.format, &mut writer)?;
+		writer.flush()?;
+		Ok(())
+	}
+	fn export_print_mesh(&self, path: &Path) -> Result<(), AError> {
+		let model = self.model_data.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let settings = &self.print_export;
+		let mut path = path.to_path_buf();
+		let format = match MeshFormat::from_path(&path) {
+			Some(format) => format,
+			None => {
+				path.set_extension(settings.format.extension());
+				settings.format
+			},
+		};
+		let frame = (self.current_frame.floor() as usize).min(model.frames.len().saturating_sub(1));
+		let mut writer = BufWriter::new(File::create(&path)?);
+		export::print_mesh(model, frame, settings.scale, format, &mut writer)?;
+		writer.flush()?;
+		Ok(())
+	}
+	// Add a model to the scene, at a tag of the main model if `attach` is set
+	fn attach_model(&mut self, glc: &Arc<GLContext>, fpath: &Path, attach: bool) -> Result<(), AError> {
+		let model = File::open(fpath).map_err(AError::from)
+			.and_then(|mut f| md3::read_md3(&mut f).map_err(AError::from))?;
+		let tag_names: Vec<String> = self.model_data.as_ref().filter(|_| attach)
+			.map(|m| m.tag_names().map(String::from).collect()).unwrap_or_default();
+		let tag = tag_names.iter().find(|&n| n == "tag_weapon")
+			.or(tag_names.first()).cloned().unwrap_or_default();
+		let mut errors = vec![];
+		let models = self.surface_models(glc, &model, fpath, &mut errors);
+		self.attachments.push(Attachment {
+			path: fpath.to_path_buf(),
+			model: Box::new(model),
+			tag,
+			hidden: false,
+			transform: DisplayTransform::default(),
+			texture: None,
+			models,
+		});
+		errors.into_iter().for_each(|e| self.report(e));
+		Ok(())
+	}
+	fn set_attachment_texture(&mut self, glc: &Arc<GLContext>, attachment: usize, texture: Option<PathBuf>) {
+		let attachment = match self.attachments.get_mut(attachment) {
+			Some(a) => a,
+			None => return,
+		};
+		attachment.texture = texture;
+		let mut errors = vec![];
+		attachment.models.iter_mut().for_each(|(index, model)| {
+			let path = attachment.texture.clone().unwrap_or_else(|| shader_texture_path(
+				&attachment.path, attachment.model.surfaces[*index].shaders.first()));
+			let (texture, error) = self.texture_cache.get(Arc::clone(glc), &path);
+			model.uniforms.tex = texture;
+			errors.extend(error.map(|e| Problem::error(Source::Texture, e).path(path)));
+		});
+		errors.into_iter().for_each(|e| self.report(e));
+	}
+	// Replace the warnings about the previous engine's limits with ones about
+	// the engine which was chosen
+	fn recheck_limits(&mut self, previous: EngineProfile) {
+		let (model, path) = match (self.model_data.as_ref(), self.model_path.as_ref()) {
+			(Some(model), Some(path)) => (model, path),
+			_ => return,
+		};
+		let problems = |engine| limits::check_limits(model, engine).iter()
+			.map(|w| limit_problem(w, path)).collect::<Vec<_>>();
+		let old = problems(previous);
+		let new = problems(self.settings.engine_profile);
+		self.problems.retain(|p| !old.contains(p));
+		new.into_iter().for_each(|p| self.report(p));
+		(0..self.surface_settings.len()).for_each(|surface| self.recheck_texture(surface));
+	}
+	fn export_frame_bounds(&self, path: &Path) -> Result<(), AError> {
+		let model = self.model_data.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("json");
+		}
+		let mut writer = BufWriter::new(File::create(&path)?);
+		export::frame_bounds(model, &mut writer)?;
+		writer.flush()?;
+		Ok(())
+	}
+	// Read the headers of the loaded model's file again, to show them
+	fn open_internals(&mut self) {
+		let path = match self.model_path.clone() {
+			Some(p) => p,
+			None => return,
+		};
+		match fs::read(&path) {
+			Ok(data) => self.internals = Some(layout::read_layout(&data)),
+			Err(e) => self.report(Problem::error(Source::Parser, format!("Could not read {}:\n{}", path.display(), e))
+				.path(path)),
+		}
+	}
+	fn export_structure(&self, path: &Path, full: bool) -> Result<(), AError> {
+		let model = self.model_data.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("json");
+		}
+		let mut writer = BufWriter::new(File::create(&path)?);
+		export::model_structure(model, full, &mut writer)?;
+		writer.flush()?;
+		Ok(())
+	}
+	// Compare the vertices of the loaded model with another model which has
+	// the same topology, and show the differences as a heatmap
+	fn compare_with(&mut self, glc: &Arc<GLContext>, fpath: &Path) -> Result<(), AError> {
+		let model = self.model_data.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let other = File::open(fpath).map_err(AError::from)
+			.and_then(|mut f| md3::read_md3(&mut f).map_err(AError::from))?;
+		let diff = compare::compare(model, &other)?;
+		let max_deviation = diff.max();
+		self.models.iter_mut().try_for_each(|(index, surface_model)| -> Result<(), AError> {
+			let texture = Texture::try_from_vertex_values(Arc::clone(glc), &diff.surfaces[*index])?;
+			surface_model.uniforms.deviation = Some(Rc::new(texture));
+			surface_model.uniforms.maxDeviation = max_deviation;
+			Ok(())
+		})?;
+		self.comparison = Some(Comparison { path: fpath.to_path_buf(), diff, open: true });
+		self.controls.view_mode = ViewMode::Deviation;
+		Ok(())
+	}
+	fn toggle_playback(&mut self) {
+		self.anim_playing = !self.anim_playing;
+		if self.anim_playing {
+			self.anim_start_time = Instant::now();
+			self.anim_start_frame = self.current_frame;
+		}
+	}
+	// Loop the animation over some of the frames
+	fn play_section(&mut self, frames: RangeInclusive<usize>) {
+		self.anim_playing = true;
+		self.anim_start_time = Instant::now();
+		self.anim_start_frame = *frames.start() as f32;
+		self.play_section = Some(frames);
+	}
+	fn set_playback_speed(&mut self, speed: f32) {
+		// Carry on from the current frame at the new speed
+		self.update_playback();
+		self.anim_start_time = self.clock;
+		self.anim_start_frame = self.current_frame;
+		self.anim_speed = speed;
+	}
+	// The frames of the section being played, or all of them
+	fn animation_range(&self) -> Option<RangeInclusive<f32>> {
+		match (self.play_section.as_ref(), self.frame_range.as_ref()) {
+			(Some(section), _) => Some(*section.start() as f32..=*section.end() as f32),
+			(None, range) => range.cloned(),
+		}
+	}
+	fn update_playback(&mut self) {
+		if !self.anim_playing { return; }
+		let (start, end) = match self.animation_range() {
+			Some(range) => range.into_inner(),
+			None => return,
+		};
+		let time = self.clock.saturating_duration_since(self.anim_start_time).as_secs_f32() * self.anim_speed
+			+ self.anim_start_frame;
+		self.current_frame = if end > start { start + (time - start).rem_euclid(end - start) } else { start };
+	}
+	fn add_marker(&mut self, frame: usize, name: String) {
+		let index = self.markers.partition_point(|m| m.frame <= frame);
+		self.markers.insert(index, Marker { frame, name });
+		self.save_markers();
+	}
+	fn remove_marker(&mut self, index: usize) {
+		self.markers.remove(index);
+		self.save_markers();
+	}
+	fn save_markers(&mut self) {
+		let result = match self.model_path.as_ref() {
+			Some(path) => markers::write(path, &self.markers),
+			None => return,
+		};
+		if let Err(e) = result {
+			self.log_error(&format!("Could not save animation markers:\n{}", e));
+		}
+	}
+	fn add_note(&mut self, note: Note) {
+		self.notes.push(note);
+		self.save_notes();
+	}
+	fn remove_note(&mut self, index: usize) {
+		self.notes.remove(index);
+		self.save_notes();
+	}
+	fn save_notes(&mut self) {
+		let result = match self.model_path.as_ref() {
+			Some(path) => notes::write(path, &self.notes),
+			None => return,
+		};
+		if let Err(e) = result {
+			self.log_error(&format!("Could not save notes:\n{}", e));
+		}
+	}
+	fn toggle_bookmark(&mut self, frame: usize) {
+		if !self.bookmarks.remove(&frame) {
+			self.bookmarks.insert(frame);
+		}
+	}
+	// Go to the given frame, and stop playing the animation
+	fn seek_frame(&mut self, frame: f32) {
+		if let Some(range) = self.frame_range.as_ref() {
+			self.anim_playing = false;
+			self.current_frame = frame.clamp(*range.start(), *range.end());
+		}
+	}
+	// Go exactly one frame back or forward, from between frames too
+	fn step_frame(&mut self, forward: bool) {
+		match forward {
+			true => self.seek_frame(self.current_frame.floor() + 1.),
+			false => self.seek_frame(self.current_frame.ceil() - 1.),
+		}
+	}
+	// Go to the first or last frame of the section being played
+	fn seek_end(&mut self, last: bool) {
+		if let Some(range) = self.animation_range() {
+			self.seek_frame(if last { *range.end() } else { *range.start() });
+		}
+	}
+	fn open_project_dialog(&mut self) {
+		let mut dialog = FileDialog::open_file(None)
+			.show_rename(false)
+			.show_new_folder(false)
+			.filter(String::from("json"));
+		dialog.open();
+		self.file_dialog = Some((DialogPurpose::OpenProject, dialog));
+	}
+	fn save_project_dialog(&mut self) {
+		if self.model_data.is_none() { return; }
+		let mut dialog = FileDialog::save_file(None)
+			.filter(String::from("json"));
+		dialog.open();
+		self.file_dialog = Some((DialogPurpose::SaveProject, dialog));
+	}
+	// The camera which is moved by the mouse and keyboard
+	fn active_camera_mut(&mut self) -> &mut OrbitCamera {
+		match self.controls.debug_camera {
+			true => &mut self.debug_camera,
+			false => &mut self.camera,
+		}
+	}
+	// Move the active camera smoothly to a new view
+	fn move_camera(&mut self, change: impl FnOnce(&mut OrbitCamera)) {
+		let from = *self.active_camera_mut();
+		let mut to = from;
+		change(&mut to);
+		self.camera_tween = Some((CameraTween::new(from, to, CAMERA_TWEEN_DURATION), Instant::now()));
+	}
+	// Whether the view changes by itself, so it needs to be drawn every frame
+	fn animating(&self) -> bool {
+		self.anim_playing || self.camera_tween.is_some() || self.loading.is_some()
+			|| self.texture_cache.is_streaming() || self.turntable.enabled
+	}
+	// Spin the camera, except while it's being dragged or moved to a new view
+	fn update_turntable(&mut self) {
+		let controls = &self.controls;
+		let moving = controls.lmb_dragging || controls.rmb_dragging || controls.mmb_dragging
+			|| self.camera_tween.is_some();
+		if !self.turntable.enabled || moving {
+			self.turntable.start = None;
+			return;
+		}
+		let (clock, speed) = (self.clock, self.turntable.speed.to_radians());
+		let longtude = self.active_camera_mut().longtude;
+		let (start, start_longtude) = match self.turntable.start {
+			Some(start) if longtude == self.turntable.longtude => start,
+			_ => (clock, longtude),
+		};
+		let longtude = start_longtude + clock.saturating_duration_since(start).as_secs_f32() * speed;
+		self.active_camera_mut().longtude = longtude;
+		self.turntable.start = Some((start, start_longtude));
+		self.turntable.longtude = longtude;
+	}
+	fn update_camera_tween(&mut self) {
+		if let Some((tween, start)) = self.camera_tween {
+			let time = start.elapsed().as_secs_f32();
+			tween.apply(self.active_camera_mut(), time);
+			if tween.finished(time) {
+				self.camera_tween = None;
+			}
+		}
+	}
+	// Move the camera with the mouse. delta is in logical pixels.
+	fn mouse_dragged(&mut self, delta: Vec2, view_height: f32) {
+		let controls = self.controls;
+		if controls.lmb_dragging || controls.rmb_dragging || controls.mmb_dragging {
+			self.camera_tween = None;
+		}
+		let sensitivity = self.settings.sensitivity.mouse;
+		let camera = self.active_camera_mut();
+		if controls.lmb_dragging {
+			let delta = delta * MOUSE_FACTOR * sensitivity;
+			camera.longtude += delta.x;
+			camera.latitude = (camera.latitude - delta.y).clamp(-LOOK_LIMIT, LOOK_LIMIT);
+		}
+		if controls.rmb_dragging {
+			camera.distance += delta.y * sensitivity * MOUSE_FACTOR * camera.distance.max(1.);
+		}
+		if controls.mmb_dragging {
+			// Keep the target under the cursor
+			let half_height = view_height * 0.5;
+			camera.pan(delta.x / half_height, delta.y / half_height);
+		}
+	}
+	// Move the camera closer by the given number of steps, or further away
+	// if steps is negative
+	fn zoom(&mut self, steps: f32) {
+		let factor = (1. - self.settings.sensitivity.zoom).powf(steps);
+		self.camera_tween = None;
+		let camera = self.active_camera_mut();
+		camera.distance = (camera.distance * factor).max(camera.near);
+	}
+	// Orbit by the given number of key presses
+	fn orbit(&mut self, right: f32, up: f32) {
+		let step = self.settings.sensitivity.orbit.to_radians();
+		self.camera_tween = None;
+		let camera = self.active_camera_mut();
+		camera.longtude += right * step;
+		camera.latitude = (camera.latitude + up * step).clamp(-LOOK_LIMIT, LOOK_LIMIT);
+	}
+	fn touch(&mut self, touch: Touch) {
+		use winit::event::TouchPhase;
+		let pos = Vec2::new(touch.location.x as f32, touch.location.y as f32);
+		match touch.phase {
+			TouchPhase::Started => { self.touches.insert(touch.id, pos); },
+			TouchPhase::Moved => {
+				let spread = |touches: &HashMap<u64, Vec2>| {
+					let mut fingers = touches.values();
+					match (fingers.next(), fingers.next(), fingers.next()) {
+						(Some(a), Some(b), None) => Some(a.distance(*b)),
+						_ => None,
+					}
+				};
+				let before = spread(&self.touches);
+				self.touches.insert(touch.id, pos);
+				if let (Some(before), Some(after)) = (before, spread(&self.touches)) {
+					if after > 0. {
+						self.camera_tween = None;
+						let camera = self.active_camera_mut();
+						camera.distance = (camera.distance * before / after).max(camera.near);
+					}
+				}
+			},
+			TouchPhase::Ended | TouchPhase::Cancelled => { self.touches.remove(&touch.id); },
+		}
+	}
+	// Back to the angle and distance the camera has when a model is opened
+	fn reset_camera(&mut self) {
+		let distance = self.model_data.as_ref().map(|m| home_distance(m)).unwrap_or(32.);
+		self.move_camera(|camera| {
+			camera.longtude = 0.;
+			camera.latitude = 0.;
+			camera.distance = distance;
+			camera.target = Vec3::ZERO;
+		});
+	}
+	// Look at the whole model as it is in the current frame
+	fn frame_model(&mut self) {
+		let frame = self.current_frame as usize;
+		let bounds = self.model_data.as_ref().and_then(|m| m.frames.get(frame))
+			.map(|f| (f.min, f.max));
+		if let Some((min, max)) = bounds {
+			self.focus_bounds(min, max);
+		}
+	}
+	fn focus_surface(&mut self, surface: usize) {
+		let frame = self.current_frame as usize;
+		let bounds = self.model_data.as_ref().and_then(|m| m.surfaces.get(surface))
+			.and_then(|s| s.bounds(frame));
+		if let Some((min, max)) = bounds {
+			self.focus_bounds(min, max);
+		}
+	}
+	fn update_tag_paths(&mut self, glc: &Arc<GLContext>) {
+		self.motion_trail.built = None;
+		self.tag_paths = self.model_data.as_ref().and_then(|m| tag_paths(m))
+			.map(|(vertices, indices)| BasicModel {
+				vertex: VertexBuffer::new(Arc::clone(glc), vertices),
+				index: IndexBuffer::new(Arc::clone(glc), indices),
+				shader: Rc::clone(&self.axes.shader),
+				uniforms: UniformsRes::default(),
+			});
+	}
+	fn edit_tag(&mut self, glc: &Arc<GLContext>, edit: TagEdit) {
+		let model = match self.model_data.as_mut() {
+			Some(m) => m,
+			None => return,
+		};
+		let frame = self.current_frame.floor() as usize;
+		let all_frames = self.tag_editor.all_frames;
+		// Change the tag in the current frame, or in every frame
+		let mut transform = |index: usize, transform: &dyn Fn(&mut MD3FrameTag)| {
+			model.tag_frames_mut(index).enumerate()
+				.filter(|(f, _)| all_frames || *f == frame)
+				.for_each(|(_, tag)| transform(tag));
+			Ok(())
+		};
+		let result: Result<(), MD3EditError> = match edit {
+			TagEdit::Rename(index, name) => {
+				let old_name = model.tag_names().nth(index).map(String::from);
+				let result = model.rename_tag(index, &name);
+				// Keep attachments and notes on the same tag
+				if let (Ok(()), Some(old_name)) = (&result, old_name) {
+					self.attachments.iter_mut().filter(|a| a.tag == old_name)
+						.for_each(|a| a.tag = name.clone());
+					let renamed = self.notes.iter_mut()
+						.filter(|n| n.anchor == NoteAnchor::Tag(old_name.clone()))
+						.map(|n| n.anchor = NoteAnchor::Tag(name.clone()))
+						.count();
+					if renamed > 0 {
+						self.save_notes();
+					}
+				}
+				result
+			},
+			TagEdit::Add(name) => model.add_tag(&name, Vec3::ZERO, Mat3::IDENTITY),
+			TagEdit::Duplicate(index, name) => model.duplicate_tag(index, &name),
+			TagEdit::Remove(index) => {
+				self.tag_editor.selected = None;
+				self.tag_editor.hidden_labels = self.tag_editor.hidden_labels.iter()
+					.filter(|&&i| i != index).map(|&i| if i > index { i - 1 } else { i }).collect();
+				self.outliner.retain(|node| !matches!(node, SceneNode::Tag(_)));
+				model.remove_tag(index)
+			},
+			TagEdit::CopyToAllFrames(index) => {
+				let tag = model.tags.get(frame * model.num_tags + index).cloned();
+				if let Some(tag) = tag {
+					model.tag_frames_mut(index).for_each(|t| *t = tag.clone());
+				}
+				Ok(())
+			},
+			TagEdit::Move(index, offset) => transform(index, &|tag| tag.origin += offset),
+			TagEdit::Rotate(index, rotation) => transform(index, &|tag| tag.axes = rotation * tag.axes),
+			TagEdit::MoveLocal(index, axis, distance) => transform(index, &|tag| {
+				tag.origin += tag.axes.col(axis).normalize_or_zero() * distance;
+			}),
+			TagEdit::TurnLocal(index, axis, angle) => transform(index, &|tag| {
+				tag.axes *= Mat3::from_axis_angle(Vec3::AXES[axis], angle);
+			}),
+		};
+		match result {
+			Ok(()) => {
+				self.model_edited();
+				self.update_tag_paths(glc);
+			},
+			Err(e) => self.log_error(&e.to_string()),
+		}
+	}
+	fn focus_tag(&mut self, name: &str) {
+		let transform = self.model_data.as_ref()
+			.and_then(|m| m.tag_transform(name, self.current_frame));
+		if let Some(transform) = transform {
+			let target = self.model_matrix().transform_point3(Vec3::from(transform.translation));
+			self.move_camera(|camera| camera.target = target);
+		}
+	}
+	// Model space to world space
+	fn model_matrix(&self) -> Mat4 {
+		Mat4::from_scale(MD3_MODEL_SCALE) * self.display.matrix()
+	}
+	// Frame a bounding box in model space
+	fn focus_bounds(&mut self, min: Vec3, max: Vec3) {
+		let model_matrix = self.model_matrix();
+		let corners = (0..8).map(|corner| model_matrix.transform_point3(Vec3::select(
+			glam::BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0), max, min)));
+		let (a, b) = corners.fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+			|(a, b), corner| (a.min(corner), b.max(corner)));
+		self.move_camera(|camera| camera.frame(a, b));
+	}
+	// Keyboard shortcuts. These are only used when egui doesn't want the
+	// keyboard, i.e. when none of its widgets have focus.
+	fn key_pressed(&mut self, key: VirtualKeyCode) -> Option<Action> {
+		self.settings.keymap.action(Chord::pressed(key, self.controls.modifiers))
+	}
+	fn set_view_mode(&mut self, mode: ViewMode) {
+		// There's nothing to show in deviation mode without a comparison
+		if mode != ViewMode::Deviation || self.comparison.is_some() {
+			self.controls.view_mode = mode;
+		}
+	}
+	fn view_state(&self) -> ViewState {
+		ViewState {
+			camera: CameraSettings::from(&self.camera),
+			frame: self.current_frame,
+			view_mode: self.controls.view_mode as u32,
+		}
+	}
+	// Look at the model the same way as a copied view
+	fn paste_view(&mut self, text: &str) -> Result<(), AError> {
+		let view: ViewState = text.parse()?;
+		self.move_camera(|camera| view.camera.apply(camera));
+		self.seek_frame(view.frame);
+		if let Some(&mode) = ViewMode::ALL.get(view.view_mode as usize) {
+			self.set_view_mode(mode);
+		}
+		Ok(())
+	}
+	fn run_action(&mut self, action: Action) {
+		use Action::*;
+		if let Some(mode) = Action::VIEW_MODES.iter().position(|&a| a == action) {
+			self.set_view_mode(ViewMode::ALL[mode]);
+			return;
+		}
+		if let Some(surface) = Action::TOGGLE_SURFACES.iter().position(|&a| a == action) {
+			if let Some(settings) = self.surface_settings.get_mut(surface) {
+				settings.hidden = !settings.hidden;
+			}
+			return;
+		}
+		// Canonical views, like Blender. Models face +X, and Y is flipped
+		// when they are drawn.
+		let view = match action {
+			FrontView => Some((PI, 0.)),
+			BackView => Some((0., 0.)),
+			RightView => Some((-FRAC_PI_2, 0.)),
+			LeftView => Some((FRAC_PI_2, 0.)),
+			TopView => Some((PI, -LOOK_LIMIT)),
+			BottomView => Some((PI, LOOK_LIMIT)),
+			_ => None,
+		};
+		if let Some((longtude, latitude)) = view {
+			self.move_camera(|camera| {
+				camera.longtude = longtude;
+				camera.latitude = latitude;
+			});
+			return;
+		}
+		match action {
+			Open => self.open_file_dialog.open(),
+			OpenProject => self.open_project_dialog(),
+			SaveProject => self.save_project_dialog(),
+			Screenshot => self.screenshot_dialog(),
+			PlayPause if self.frame_range.is_some() => self.toggle_playback(),
+			PreviousFrame => self.step_frame(false),
+			NextFrame => self.step_frame(true),
+			FirstFrame => self.seek_end(false),
+			LastFrame => self.seek_end(true),
+			Shortcuts => self.shortcuts_open = !self.shortcuts_open,
+			ToggleHud => {
+				self.settings.show_hud = !self.settings.show_hud;
+				self.save_settings();
+			},
+			FrameModel => self.frame_model(),
+			ResetCamera => self.reset_camera(),
+			OrbitLeft => self.orbit(-1., 0.),
+			OrbitRight => self.orbit(1., 0.),
+			OrbitUp => self.orbit(0., 1.),
+			OrbitDown => self.orbit(0., -1.),
+			ZoomIn => self.zoom(1.),
+			ZoomOut => self.zoom(-1.),
+			Orthographic => {
+				let camera = self.active_camera_mut();
+				camera.orthographic = !camera.orthographic;
+			},
+			// Quitting is done by the event loop
+			_ => (),
+		}
+	}
+	// Bind another key to an action, or stop waiting for one if it's Escape
+	fn bind_key(&mut self, key: VirtualKeyCode) {
+		let action = match self.capturing_shortcut.take() {
+			Some(action) => action,
+			None => return,
+		};
+		if key == VirtualKeyCode::Escape { return; }
+		let chord = Chord::pressed(key, self.controls.modifiers);
+		let mut bindings = self.settings.keymap.bindings(action).into_owned();
+		if !bindings.contains(&chord) {
+			bindings.push(chord);
+		}
+		self.settings.keymap.set_bindings(action, bindings);
+		self.save_settings();
+	}
+	fn screenshot_dialog(&mut self) {
+		let mut dialog = FileDialog::save_file(self.model_path.as_ref().map(|path| path.with_extension("png")))
+			.filter(String::from("png"));
+		dialog.open();
+		self.file_dialog = Some((DialogPurpose::Screenshot, dialog));
+	}
+	// Clear to transparent black, and hide the skybox, for rendering images
+	// which are put on top of something else. Returns whether the skybox was
+	// shown, for end_transparent.
+	fn begin_transparent(&mut self, glc: &Arc<GLContext>) -> bool {
+		unsafe { glc.clear_color(0., 0., 0., 0.); }
+		std::mem::replace(&mut self.controls.show_skybox, false)
+	}
+	fn end_transparent(&mut self, glc: &Arc<GLContext>, show_skybox: bool) {
+		unsafe { glc.clear_color(0., 0., 0., 1.); }
+		self.controls.show_skybox = show_skybox;
+	}
+	// Render the view without the UI at the size of the window
+	fn save_screenshot(&mut self, glc: &Arc<GLContext>, path: &Path) -> Result<(), AError> {
+		let (width, height) = self.viewport;
+		let renderer = Renderer::new(Arc::clone(glc), &RenderPass::SCENE);
+		let mut framebuffer = Framebuffer::new(Arc::clone(glc), width, height)?;
+		let show_skybox = self.controls.show_skybox;
+		if self.settings.transparent_screenshots {
+			self.begin_transparent(glc);
+		}
+		framebuffer.bind();
+		let camera = self.view_camera();
+		renderer.render(self, &View::orbit(&camera, Vec2::new(width as f32, height as f32)));
+		let image = framebuffer.read_image();
+		framebuffer.unbind();
+		self.end_transparent(glc, show_skybox);
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("png");
+		}
+		image.save(&path)?;
+		log::info!("Saved a screenshot to {}", path.display());
+		Ok(())
+	}
+	fn replace_shader_paths(&mut self, glc: &Arc<GLContext>) -> Result<(), AError> {
+		let model = self.model_data.as_mut()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let renamer = self.shader_replace.renamer()?;
+		if model.rename_shaders(renamer) > 0 {
+			self.model_edited();
+		}
+		let num_surfaces = self.model_data.as_ref().map_or(0, |m| m.surfaces.len());
+		(0..num_surfaces).for_each(|surface| self.update_surface_texture(glc, surface));
+		Ok(())
+	}
+	// Replace the normals of every surface with smooth normals, and upload
+	// the new vertex data
+	fn recalculate_normals(&mut self, glc: &Arc<GLContext>) -> Result<(), AError> {
+		let model = self.model_data.as_mut()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let settings = self.normal_recalc;
+		model.surfaces.iter_mut()
+			.for_each(|surface| surface.recalculate_normals(settings.weighting, settings.gzdoom));
+		self.controls.gzdoom_normals = settings.gzdoom;
+		self.model_edited();
+		self.upload_vertices(glc)
+	}
+	// Upload the vertices of the model again after they've been edited
+	fn upload_vertices(&mut self, glc: &Arc<GLContext>) -> Result<(), AError> {
+		// The levels of detail were made from the old vertices
+		self.lod.lods = None;
+		self.lod.preview = 0;
+		self.upload_lod(glc)
+	}
+	// Show the full model, or one of its levels of detail
+	fn preview_lod(&mut self, glc: &Arc<GLContext>, level: usize) -> Result<(), AError> {
+		self.lod.preview = level;
+		if level > 0 && self.lod.lods.is_none() {
+			self.generate_lods()?;
+		}
+		self.upload_lod(glc)
+	}
+	fn generate_lods(&mut self) -> Result<(), AError> {
+		let model = self.model_data.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		self.lod.lods = Some(self.lod.percent.map(|percent| lod::decimate(model, percent / 100.)));
+		Ok(())
+	}
+	fn upload_lod(&mut self, glc: &Arc<GLContext>) -> Result<(), AError> {
+		let model = match (self.lod.preview, self.lod.lods.as_ref()) {
+			(0, _) | (_, None) => self.model_data.as_deref(),
+			(level, Some(lods)) => lods.get(level - 1),
+		}.ok_or_else(|| AError::msg("No model is loaded"))?;
+		self.models.iter_mut().try_for_each(|(index, surface_model)| -> Result<(), AError> {
+			let surface = &model.surfaces[*index];
+			let anim = Texture::try_from_md3(Arc::clone(glc), surface)?;
+			surface_model.vertex = VertexBuffer::from_surface(Arc::clone(glc), surface);
+			surface_model.index = IndexBuffer::from_surface(Arc::clone(glc), surface);
+			surface_model.uniforms.anim = Rc::new(anim);
+			surface_model.uniforms.numVerts = surface.num_verts as i32;
+			Ok(())
+		})?;
+		self.normal_inspector.gzdoom = None;
+		self.normal_inspector.lines = None;
+		self.motion_trail.built = None;
+		self.surface_split.built = None;
+		self.flipped = None;
+		Ok(())
+	}
+	// After an edit which moves vertices or changes the frames, which leaves
+	// the model unsaved and no longer matching the model it was compared with
+	fn invalidate_comparison(&mut self) {
+		self.comparison = None;
+		self.models.iter_mut().for_each(|(_, m)| m.uniforms.deviation = None);
+		if self.controls.view_mode == ViewMode::Deviation {
+			self.controls.view_mode = ViewMode::Textured;
+		}
+		self.model_edited();
+	}
+	// Every edit to the model in memory ends up here, so that it isn't closed
+	// without asking
+	fn model_edited(&mut self) {
+		self.model_modified = true;
+	}
+	// Whether the program can quit without losing changes. If it can't, the
+	// user is asked first.
+	fn can_quit(&mut self) -> bool {
+		if self.model_modified {
+			self.discard_changes = Some(DiscardChanges::Quit);
+		}
+		!self.model_modified
+	}
+	fn optimize_model(&mut self, glc: &Arc<GLContext>) -> Result<(), AError> {
+		let model = self.model_data.as_mut()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		self.optimize.report = Some(optimize::optimize(model, &self.optimize.settings));
+		self.invalidate_comparison();
+		self.upload_vertices(glc)
+	}
+	fn snap_to_grid(&mut self, glc: &Arc<GLContext>) -> Result<(), AError> {
+		let model = self.model_data.as_mut()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		self.optimize.snap_report = Some(optimize::snap_to_grid(model, self.optimize.snap_grid));
+		self.invalidate_comparison();
+		self.upload_vertices(glc)
+	}
+	fn bake_transform(&mut self, glc: &Arc<GLContext>) -> Result<(), AError> {
+		let model = self.model_data.as_mut()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let clamped = model.bake_transform(&self.transform.transform(), self.controls.gzdoom_normals);
+		self.invalidate_comparison();
+		self.update_tag_paths(glc);
+		self.upload_vertices(glc)?;
+		if clamped > 0 {
+			self.report(Problem::warning(Source::Other,
+				format!("{} vertices were too far from the origin, and were moved closer", clamped)));
+		}
+		Ok(())
+	}
+	fn mirror_model(&mut self, glc: &Arc<GLContext>) -> Result<(), AError> {
+		let model = self.model_data.as_mut()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		model.mirror(self.transform.mirror_axis, self.transform.mirror_uv, self.controls.gzdoom_normals);
+		self.invalidate_comparison();
+		self.update_tag_paths(glc);
+		self.upload_vertices(glc)
+	}
+	// Delete, copy or move frames of the animation. Bookmarks and markers
+	// stay on the same frames.
+	fn edit_frames(&mut self, glc: &Arc<GLContext>, edit: FrameEdit) -> Result<(), AError> {
+		let model = self.model_data.as_mut()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let order = edit.order(model.frames.len());
+		if order.is_empty() {
+			return Err(AError::msg("The model needs at least one frame"));
+		}
+		model.reorder_frames(&order);
+		let num_frames = order.len();
+		let new_frame = |frame: usize| order.iter().position(|&f| f == frame);
+		self.bookmarks = self.bookmarks.iter().filter_map(|&f| new_frame(f)).collect();
+		self.markers = std::mem::take(&mut self.markers).into_iter()
+			.filter_map(|m| Some(Marker { frame: new_frame(m.frame)?, ..m }))
+			.collect();
+		self.markers.sort_by_key(|m| m.frame);
+		self.notes = std::mem::take(&mut self.notes).into_iter()
+			.filter_map(|n| match n.frame {
+				Some(frame) => Some(Note { frame: Some(new_frame(frame)?), ..n }),
+				None => Some(n),
+			})
+			.collect();
+		self.frame_range = (num_frames > 1).then(|| 0.0..=(num_frames - 1) as f32);
+		self.current_frame = self.current_frame.min((num_frames - 1) as f32).floor();
+		self.anim_playing = false;
+		self.play_section = None;
+		self.invalidate_comparison();
+		self.update_tag_paths(glc);
+		self.upload_vertices(glc)
+	}
+	fn rename(&mut self, glc: &Arc<GLContext>, target: NameTarget, name: &str) -> Result<(), AError> {
+		let model = self.model_data.as_mut()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		match target {
+			NameTarget::Surface(surface) => model.rename_surface(surface, name)?,
+			NameTarget::Shader(surface, shader) => {
+				model.set_shader_name(surface, shader, name)?;
+				self.update_surface_texture(glc, surface);
+			},
+		}
+		self.model_edited();
+		Ok(())
+	}
+	// Replace the bounds the exporter wrote with ones fitted to the vertices
+	fn fit_frame_bounds(&mut self) -> Result<(), AError> {
+		let model = self.model_data.as_mut()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		if model.fit_frame_bounds() > 0 {
+			self.model_edited();
+		}
+		Ok(())
+	}
+	fn save_model(&mut self, path: &Path) -> Result<(), AError> {
+		let model = self.model_data.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("md3");
+		}
+		backup::backup(&path, &self.settings.backups)
+			.with_context(|| format!("Could not back up {}", path.display()))?;
+		let mut writer = BufWriter::new(File::create(&path)?);
+		md3::write_md3(model, &mut writer)?;
+		writer.flush()?;
+		self.model_modified = false;
+		Ok(())
+	}
+	// Merge the surfaces which use the same shaders, save the result, and open
+	// it with the same view and surface settings
+	fn merge_surfaces(&mut self, path: &Path) -> Result<(), AError> {
+		let mut model = self.model_data.as_deref().cloned()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let plan = optimize::merge_surfaces(&mut model, &self.settings.engine_profile.limits());
+		let surfaces = (0..model.surfaces.len()).map(|merged| plan.iter().position(|&p| p == merged)
+			.and_then(|surface| self.surface_settings.get(surface).cloned())
+			.unwrap_or_default()).collect();
+		self.save_and_reopen(&model, path, surfaces)
+	}
+	// Split the chosen surface by UV island, save the result, and open it.
+	// The new surfaces start with the settings of the surface they came from.
+	fn split_surface(&mut self, path: &Path) -> Result<(), AError> {
+		let mut model = self.model_data.as_deref().cloned()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let split = &self.surface_split;
+		let islands = model.surfaces.get(split.surface).map(|s| s.uv_islands()).unwrap_or_default();
+		let groups: Vec<usize> = match split.each {
+			true => islands,
+			false => islands.iter().map(|island| split.selected.contains(island) as usize).collect(),
+		};
+		let added = model.split_surface(split.surface, &groups)?;
+		let mut surfaces = self.surface_settings.clone();
+		let settings = surfaces.get(split.surface).cloned().unwrap_or_default();
+		surfaces.splice(split.surface + 1..split.surface + 1, added.iter().map(|_| settings.clone()));
+		self.save_and_reopen(&model, path, surfaces)
+	}
+	// Save an edited copy of the model, and open it with the same view
+	fn save_and_reopen(&mut self, model: &MD3Model, path: &Path, surfaces: Vec<SurfaceSettings>) -> Result<(), AError> {
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("md3");
+		}
+		backup::backup(&path, &self.settings.backups)
+			.with_context(|| format!("Could not back up {}", path.display()))?;
+		let mut writer = BufWriter::new(File::create(&path)?);
+		md3::write_md3(model, &mut writer)?;
+		writer.flush()?;
+		// The earlier changes were saved along with this one
+		self.model_modified = false;
+		let then = self.reload_state(surfaces);
+		self.start_loading(&path, then);
+		Ok(())
+	}
+	// Saves model_1.md3 and model_2.md3, named after the path
+	fn save_lods(&mut self, path: &Path) -> Result<(), AError> {
+		if self.lod.lods.is_none() {
+			self.generate_lods()?;
+		}
+		let lods = self.lod.lods.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("md3");
+		}
+		let name = path.file_name().map(|n| n.to_string_lossy().to_string())
+			.ok_or_else(|| AError::msg("No file name"))?;
+		lods.iter().enumerate().try_for_each(|(index, model)| -> Result<(), AError> {
+			let path = path.with_file_name(lod::lod_file_name(&name, index + 1));
+			backup::backup(&path, &self.settings.backups)
+				.with_context(|| format!("Could not back up {}", path.display()))?;
+			let mut writer = BufWriter::new(File::create(&path)?);
+			md3::write_md3(model, &mut writer)?;
+			writer.flush()?;
+			Ok(())
+		})
+	}
+	fn save_project(&self, path: &Path) -> Result<(), AError> {
+		let project = Project {
+			model: self.model_path.clone(),
+			surfaces: self.surface_settings.clone(),
+			camera_path: self.camera_path.clone(),
+			bookmarks: self.bookmarks.iter().copied().collect(),
+			attachments: self.attachments.iter().map(|a| AttachmentSettings {
+				path: a.path.clone(),
+				tag: a.tag.clone(),
+				texture: a.texture.clone(),
+				hidden: a.hidden,
+				transform: a.transform,
+			}).collect(),
+			camera: Some(CameraSettings::from(&self.camera)),
+			light: Some(LightSettings {
+				enabled: self.entity_light.enabled,
+				ambient: self.entity_light.ambient,
+				directed: self.entity_light.directed,
+				yaw: self.entity_light.yaw,
+				pitch: self.entity_light.pitch,
+			}),
+			skybox: self.skybox_path.clone(),
+			show_skybox: self.controls.show_skybox,
+			sky_reflection: self.controls.sky_reflection,
+			frame: self.current_frame,
+			display: self.display,
+		};
+		project.write(path)
+	}
+	fn open_project(&mut self, path: &Path) -> Result<(), AError> {
+		let project = Project::read(path)?;
+		let model_path = project.model.clone()
+			.ok_or_else(|| AError::msg("Project does not have a model"))?;
+		self.start_loading(&model_path, AfterLoad::Project(Box::new(project)));
+		Ok(())
+	}
+	// Everything in a project except the model, once the model is loaded
+	fn apply_project(&mut self, glc: &Arc<GLContext>, project: &Project) {
+		self.camera_path = project.camera_path.clone();
+		let num_frames = self.model_data.as_ref().map(|m| m.frames.len()).unwrap_or(0);
+		self.bookmarks = project.bookmarks.iter().copied().filter(|&f| f < num_frames).collect();
+		self.restore_surfaces(glc, Some(project));
+		self.current_frame = self.frame_range.as_ref()
+			.map(|range| project.frame.clamp(*range.start(), *range.end())).unwrap_or(0.);
+		if let Some(camera) = project.camera.as_ref() {
+			camera.apply(&mut self.camera);
+		}
+		self.display = project.display;
+		if let Some(light) = project.light {
+			self.entity_light.enabled = light.enabled;
+			self.entity_light.ambient = light.ambient;
+			self.entity_light.directed = light.directed;
+			self.entity_light.yaw = light.yaw;
+			self.entity_light.pitch = light.pitch;
+		}
+		// The rest of the scene is still worth seeing if some files are missing
+		if let Some(skybox) = project.skybox.as_ref() {
+			if let Err(e) = self.load_skybox(glc, skybox) {
+				self.report(Problem::error(Source::Texture, format!("Could not load skybox {}: {}", skybox.display(), e))
+					.path(skybox));
+			}
+		}
+		self.controls.show_skybox = project.show_skybox && self.skybox.is_some();
+		self.controls.sky_reflection = project.sky_reflection;
+		self.attachments.clear();
+		self.outliner.retain(|node| !matches!(node, SceneNode::Attachment(_)));
+		project.attachments.iter().for_each(|settings| {
+			if let Err(e) = self.attach_model(glc, &settings.path, false) {
+				self.report(Problem::error(Source::Parser, format!("Could not attach {}: {}", settings.path.display(), e))
+					.path(&settings.path));
+				return;
+			}
+			let index = self.attachments.len() - 1;
+			self.attachments[index].tag = settings.tag.clone();
+			self.attachments[index].hidden = settings.hidden;
+			self.attachments[index].transform = settings.transform;
+			if settings.texture.is_some() {
+				self.set_attachment_texture(glc, index, settings.texture.clone());
+			}
+		});
+	}
+	// Shaders and textures chosen for this model in previous sessions, or
+	// everything saved in the project
+	fn restore_surfaces(&mut self, glc: &Arc<GLContext>, project: Option<&Project>) {
+		let remembered = self.model_path.as_deref().and_then(|fpath| self.settings.model(fpath));
+		let surfaces = project::restored_surfaces(&self.surface_settings, remembered, project);
+		self.apply_surface_settings(glc, &surfaces);
+	}
+	fn apply_surface_settings(&mut self, glc: &Arc<GLContext>, surfaces: &[SurfaceSettings]) {
+		surfaces.iter().enumerate().for_each(|(surface, sp)| {
+			if sp.shader != 0 {
+				self.use_surface_shader(glc, surface, sp.shader);
+			}
+			if sp.texture.is_some() {
+				self.use_surface_texture(glc, surface, sp.texture.clone());
+			}
+			if let Some(settings) = self.surface_settings.get_mut(surface) {
+				settings.envmap = sp.envmap;
+				settings.hidden = sp.hidden;
+				settings.blend = sp.blend;
+				settings.two_sided = sp.two_sided;
+			}
+			// Maps found next to the textures are already in use
+			if self.surface_settings.get(surface).is_some_and(|s| s.normal_map != sp.normal_map) {
+				self.set_surface_map(glc, surface, TextureMap::Normal, sp.normal_map.clone());
+			}
+			if self.surface_settings.get(surface).is_some_and(|s| s.specular_map != sp.specular_map) {
+				self.set_surface_map(glc, surface, TextureMap::Specular, sp.specular_map.clone());
+			}
+		});
+	}
+	// Reload the current model, keeping the view and surface settings
+	fn reload_model(&mut self) -> Result<(), AError> {
+		let fpath = self.model_path.clone()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let then = self.reload_state(self.surface_settings.clone());
+		self.start_loading(&fpath, then);
+		Ok(())
+	}
+	// What to keep when the model is opened again
+	fn reload_state(&self, surfaces: Vec<SurfaceSettings>) -> AfterLoad {
+		AfterLoad::Reload {
+			camera: self.camera,
+			current_frame: self.current_frame,
+			tag_mode: self.controls.tag_mode,
+			surfaces,
+			bookmarks: self.bookmarks.clone(),
+			markers: self.markers.clone(),
+		}
+	}
+	// Check whether the model file has been changed by another program, and
+	// reload it once it hasn't been written to for a while.
+	fn reload_model_if_changed(&mut self) {
+		if let Some((_, changes)) = self.model_watcher.as_ref() {
+			let model_path = self.model_path.as_ref();
+			// All of the pending events are consumed
+			let changed = changes.try_iter().filter_map(Result::ok)
+				.filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+				.filter(|event| event.paths.iter().any(|p| Some(p) == model_path))
+				.count() > 0;
+			if changed {
+				self.model_changed = Some(Instant::now());
+			}
+		}
+		match self.model_changed {
+			// Reloading would throw away the changes, so the user is told
+			// instead, and can reload it after being asked
+			Some(time) if time.elapsed() >= MODEL_RELOAD_DELAY && self.model_modified => {
+				self.model_changed = None;
+				if let Some(path) = self.model_path.clone() {
+					self.report(Problem::warning(Source::Other, format!(
+						"{} was changed by another program, but it wasn't reloaded, since the model has unsaved changes",
+						path.display())).path(path).retry(Retry::ReloadModel));
+				}
+			},
+			Some(time) if time.elapsed() >= MODEL_RELOAD_DELAY => {
+				self.model_changed = None;
+				if let Err(e) = self.reload_model() {
+					let problem = Problem::error(Source::Parser, format!("Error reloading model:\n{}", e));
+					self.report(match self.model_path.clone() {
+						Some(path) => problem.path(path).retry(Retry::ReloadModel),
+						None => problem,
+					});
+				}
+			},
+			_ => (),
+		}
+	}
+	// Rebuild the shaders once they haven't been written to for a while.
+	// Returns whether they were rebuilt.
+	fn reload_shaders_if_changed(&mut self, glc: &Arc<GLContext>) -> bool {
+		if let Some((_, changes)) = self.shader_watcher.as_ref() {
+			let changed = changes.try_iter().filter_map(Result::ok)
+				.filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+				.flat_map(|event| event.paths)
+				.any(|path| matches!(path.extension().and_then(|e| e.to_str()), Some("vert" | "frag")));
+			if changed {
+				self.shaders_changed = Some(Instant::now());
+			}
+		}
+		match self.shaders_changed {
+			Some(time) if time.elapsed() >= MODEL_RELOAD_DELAY => {
+				self.shaders_changed = None;
+				let result = self.reload_shaders(glc);
+				if let Err(e) = result.as_ref() {
+					let problem = Problem::error(Source::Graphics, format!("Could not rebuild the shaders:\n{}", e));
+					self.report(match self.assets_path.clone() {
+						Some(path) => problem.path(path),
+						None => problem,
+					});
+				}
+				result.is_ok()
+			},
+			_ => false,
+		}
+	}
+	// The old shaders are kept if any of the new ones don't compile
+	fn reload_shaders(&mut self, glc: &Arc<GLContext>) -> Result<(), AError> {
+		let res = AppResources::try_load(self.assets_path.as_ref())?;
+		let md3_shader = ShaderProgramBuilder::new()
+			.add_shader(ShaderStage::Vertex, &res.md3_vertex_shader)
+			.add_shader(ShaderStage::Fragment, &res.md3_pixel_shader)
+			.build(Arc::clone(glc)).context("md3.vert or md3.frag")?;
+		let res_shader = ShaderProgramBuilder::new()
+			.add_shader(ShaderStage::Vertex, &res.res_vertex_shader)
+			.add_shader(ShaderStage::Fragment, &res.res_pixel_shader)
+			.build(Arc::clone(glc)).context("res.vert or res.frag")?;
+		let sky_shader = ShaderProgramBuilder::new()
+			.add_shader(ShaderStage::Vertex, &res.sky_vertex_shader)
+			.add_shader(ShaderStage::Fragment, &res.sky_pixel_shader)
+			.build(Arc::clone(glc)).context("sky.vert or sky.frag")?;
+		self.md3_shader.replace(md3_shader);
+		self.axes.shader.replace(res_shader);
+		self.sky_shader.replace(sky_shader);
+		log::info!("Rebuilt the shaders");
+		Ok(())
+	}
+	// Render each frame of the camera path to a PNG image in the given directory
+	fn render_camera_path(&mut self, glc: &Arc<GLContext>, directory: &Path) -> Result<(), AError> {
+		self.camera_path.sort_by(|a, b| a.time.total_cmp(&b.time));
+		let duration = self.camera_path.last().map(|kf| kf.time)
+			.ok_or_else(|| AError::msg("The camera path has no keyframes"))?;
+		let PathRenderSettings { width, height, fps, .. } = self.path_render;
+		let shots = RenderShots::CameraPath { keyframes: self.camera_path.clone(), camera: self.camera };
+		let output = RenderOutput::Images(directory.to_path_buf());
+		let frames = (duration * fps as f32).floor() as usize + 1;
+		self.start_render_job(glc, shots, output, frames, fps, (width, height), self.settings.transparent_screenshots)
+	}
+	// Render the animation or a turntable from the current view, and encode it
+	// as a WebM or MP4 video
+	#[cfg(not(target_arch = "wasm32"))]
+	fn export_video(&mut self, glc: &Arc<GLContext>, path: &Path) -> Result<(), AError> {
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("webm");
+		}
+		let VideoExportSettings { source, width, height, fps, anim_fps, turntable_seconds, transparent, quantizer, .. } = self.video_export;
+		let (start, end) = self.animation_range().map(RangeInclusive::into_inner).unwrap_or((0., 0.));
+		let camera = OrbitCamera { aspect: width as f32 / height as f32, ..self.view_camera() };
+		let (shots, frames) = match source {
+			VideoSource::Animation => (RenderShots::Animation { camera, start, end, anim_fps },
+				((end - start) / anim_fps * fps as f32).floor() as usize + 1),
+			VideoSource::Turntable => (RenderShots::Turntable(camera),
+				(turntable_seconds * fps as f32).round().max(1.) as usize),
+		};
+		let encoder = video::VideoEncoder::start(&path, width, height, fps, transparent, quantizer)?;
+		let output = RenderOutput::Video(path, video::Encoding::start(encoder));
+		self.start_render_job(glc, shots, output, frames, fps, (width, height), transparent)
+	}
+	#[allow(clippy::too_many_arguments)]
+	fn start_render_job(&mut self, glc: &Arc<GLContext>, shots: RenderShots, output: RenderOutput, frames: usize, fps: u32, (width, height): (u32, u32), transparent: bool) -> Result<(), AError> {
+		if self.render_job.is_some() {
+			return Err(AError::msg("Something else is being rendered"));
+		}
+		let renderer = Renderer::new(Arc::clone(glc), &RenderPass::SCENE);
+		let framebuffer = Framebuffer::new(Arc::clone(glc), width, height)?;
+		self.render_job = Some(RenderJob { shots, output, frames, next: 0, fps, transparent, renderer, framebuffer });
+		Ok(())
+	}
+	// Render the next frame of the camera path or video, if the encoder is
+	// ready for it, and report how it went once it's finished
+	fn step_render_job(&mut self, glc: &Arc<GLContext>) {
+		let Some(mut job) = self.render_job.take() else { return; };
+		match self.render_next_frame(glc, &mut job) {
+			Ok(true) => self.render_job = Some(job),
+			Ok(false) => log::info!("Saved {} frames to {}", job.frames, job.path().display()),
+			Err(e) => {
+				let path = job.path().to_path_buf();
+				job.cancel();
+				self.report(Problem::error(Source::Other, format!("Error writing or reading {}:\n{}", path.display(), e))
+					.path(path));
+			},
+		}
+	}
+	// Whether there's more to do
+	fn render_next_frame(&mut self, glc: &Arc<GLContext>, job: &mut RenderJob) -> Result<bool, AError> {
+		#[cfg(not(target_arch = "wasm32"))]
+		if let RenderOutput::Video(_, encoding) = &mut job.output {
+			if let Some(result) = encoding.poll() {
+				return result.map(|()| false);
+			}
+			if job.next == job.frames {
+				encoding.finish();
+			}
+			if !encoding.ready() {
+				return Ok(true);
+			}
+		}
+		if job.next == job.frames {
+			return Ok(false);
+		}
+		let (width, height) = (job.framebuffer.width, job.framebuffer.height);
+		let size = Vec2::new(width as f32, height as f32);
+		let time = job.next as f32 / job.fps as f32;
+		let (current_frame, show_skybox) = (self.current_frame, self.controls.show_skybox);
+		let view = match &job.shots {
+			RenderShots::CameraPath { keyframes, camera } => {
+				let (position, target) = eye::sample_camera_path(keyframes, time).unwrap_or_default();
+				View::look_at(&LookAtCamera {
+					position: position * MD3_MODEL_SCALE,
+					target: target * MD3_MODEL_SCALE,
+					fov: camera.fov,
+					aspect: width as f32 / height as f32,
+					near: camera.near,
+					far: camera.far,
+				}, size)
+			},
+			#[cfg(not(target_arch = "wasm32"))]
+			RenderShots::Animation { camera, start, end, anim_fps } => {
+				self.current_frame = (start + time * anim_fps).min(*end);
+				View::orbit(camera, size)
+			},
+			#[cfg(not(target_arch = "wasm32"))]
+			RenderShots::Turntable(camera) => View::orbit(&OrbitCamera {
+				longtude: camera.longtude + TAU * job.next as f32 / job.frames as f32,
+				..*camera
+			}, size),
+		};
+		if job.transparent {
+			self.begin_transparent(glc);
+		}
+		job.framebuffer.bind();
+		job.renderer.render(self, &view);
+		let image = job.framebuffer.read_image();
+		job.framebuffer.unbind();
+		self.end_transparent(glc, show_skybox);
+		self.current_frame = current_frame;
+		match &mut job.output {
+			RenderOutput::Images(directory) => image.save(directory.join(format!("frame_{:04}.png", job.next)))?,
+			#[cfg(not(target_arch = "wasm32"))]
+			RenderOutput::Video(_, encoding) => encoding.send(image),
+		}
+		job.next += 1;
+		Ok(true)
+	}
+	// Render the model from each rotation in each of the chosen frames, and
+	// save them packed into one image, with a JSON file of where each sprite
+	// is and its offsets
+	fn export_sprites(&mut self, glc: &Arc<GLContext>, path: &Path) -> Result<(), AError> {
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("png");
+		}
+		let model = self.model_data.as_ref()
+			.ok_or_else(|| AError::msg("No model is loaded"))?;
+		let settings = &self.sprite_export;
+		let frames: Vec<usize> = settings.frames().filter(|&f| f < model.frames.len()).collect();
+		if frames.len() > export::MAX_SPRITE_FRAMES {
+			return Err(AError::msg(format!("Sprites can only have {} frames. Use a bigger step, or fewer frames.",
+				export::MAX_SPRITE_FRAMES)));
+		}
+		// Every sprite is drawn at the same scale, around the origin, which is
+		// on the ground
+		let display = self.display.matrix();
+		let extent = frames.iter().flat_map(|&frame| model.surfaces.iter().flat_map(move |surf| {
+			surf.vertices.get(frame * surf.num_verts..(frame + 1) * surf.num_verts).unwrap_or_default()
+		})).map(|v| display.transform_point3(v.position()))
+			.fold(0f32, |extent, p| extent.max(p.truncate().length()).max(p.z.abs()));
+		let half = extent * 1.05 + 1.;
+		let size = ((half * 2. * settings.scale).ceil() as u32).clamp(8, 4096);
+		let origin = (size as i32 / 2, size as i32 / 2);
+		let (prefix, rotations) = (settings.prefix.clone(), settings.rotations);
+		let camera = OrbitCamera {
+			latitude: 0.,
+			distance: half / (self.camera.fov * 0.5).tan(),
+			aspect: 1.,
+			orthographic: true,
+			target: Vec3::ZERO,
+			..self.camera
+		};
+		let camera = OrbitCamera { far: (camera.distance + half) * 2., ..camera };
+		let renderer = Renderer::new(Arc::clone(glc), &[RenderPass::Opaque, RenderPass::Transparent]);
+		let mut framebuffer = Framebuffer::new(Arc::clone(glc), size, size)?;
+		let current_frame = self.current_frame;
+		let show_skybox = self.begin_transparent(glc);
+		framebuffer.bind();
+		let sprites: Vec<export::Sprite> = frames.iter().enumerate().flat_map(|(letter, &frame)| {
+			(0..rotations).map(move |rotation| (letter, frame, rotation))
+		}).filter_map(|(letter, frame, rotation)| {
+			self.current_frame = frame as f32;
+			// Rotation 1 is in front of the model, and the rest go around
+			// anticlockwise seen from above, like in Doom
+			let camera = OrbitCamera { longtude: PI - TAU * rotation as f32 / rotations as f32, ..camera };
+			renderer.render(self, &View::orbit(&camera, Vec2::splat(size as f32)));
+			let name = export::sprite_name(&prefix, letter, rotation, rotations)?;
+			Some(export::Sprite::trimmed(name, &framebuffer.read_image(), origin))
+		}).collect();
+		framebuffer.unbind();
+		self.end_transparent(glc, show_skybox);
+		self.current_frame = current_frame;
+		let (sheet, placements) = export::sprite_sheet(&sprites);
+		sheet.save(&path)?;
+		let mut writer = BufWriter::new(File::create(path.with_extension("json"))?);
+		export::sprite_offsets(&placements, &mut writer)?;
+		writer.flush()?;
+		Ok(())
+	}
+	// Fill the voxels which the surfaces of the current frame go through,
+	// coloured by their textures
+	fn voxelize(&mut self, glc: &Arc<GLContext>) {
+		let model = match self.model_data.as_ref() {
+			Some(m) => m,
+			None => return,
+		};
+		let frame = (self.current_frame.floor() as usize).min(model.frames.len().saturating_sub(1));
+		let textures: Vec<Option<Surface>> = (0..model.surfaces.len()).map(|surface| {
+			let path = self.surface_texture_path(surface)?;
+			Surface::read_image(&path)
+				.map_err(|e| log::warn!("Could not read {} to colour the voxels: {:?}", path.display(), e))
+				.ok()
+		}).collect();
+		let grid = voxel::voxelize(model, frame, self.voxels.resolution, |surface, uv| {
+			textures[surface].as_ref().and_then(|t| t.texel(uv))
+				.map_or([160; 3], |[r, g, b, _]| [r, g, b])
+		});
+		self.voxels.mesh = voxel_mesh(glc, &grid, Rc::clone(&self.axes.shader));
+		self.voxels.grid = Some(grid);
+		self.voxels.frame = frame;
+	}
+	fn export_vox(&self, path: &Path) -> Result<(), AError> {
+		let mut path = path.to_path_buf();
+		if path.extension().is_none() {
+			path.set_extension("vox");
+		}
+		let grid = self.voxels.grid.as_ref()
+			.ok_or_else(|| AError::msg("The model hasn't been voxelized"))?;
+		let mut writer = BufWriter::new(File::create(&path)?);
+		voxel::write_vox(grid, &mut writer)?;
+		writer.flush()?;
+		Ok(())
+	}
+	// Render the first frame of a model to the bound square framebuffer,
+	// looking at its bounds from the front left
+	fn render_preview(&mut self, glc: &Arc<GLContext>, renderer: &Renderer, path: &Path, errors: &mut Vec<Problem>) -> Result<(), AError> {
+		renderer.clear();
+		RenderPass::Opaque.begin(glc);
+		let model = File::open(path).map_err(AError::from)
+			.and_then(|mut f| md3::read_md3(&mut f).map_err(AError::from))?;
+		let (centre, radius) = model.frames.first()
+			.map(|f| ((f.min + f.max) * 0.5, (f.max - f.min).length() * 0.5))
+			.unwrap_or((Vec3::ZERO, 0.));
+		let radius = radius.max(1.);
+		let distance = radius / (self.camera.fov * 0.5).sin();
+		let direction = Vec3::new(1., 0.6, 0.5).normalize();
+		let camera = LookAtCamera {
+			position: (centre + direction * distance) * MD3_MODEL_SCALE,
+			target: centre * MD3_MODEL_SCALE,
+			fov: self.camera.fov,
+			aspect: 1.,
+			near: self.camera.near,
+			far: self.camera.far.max(distance + radius),
+		};
+		let eye = camera.view_projection();
+		self.surface_models(glc, &model, path, errors).iter_mut()
+			.for_each(|(_, surface_model)| {
+				if let Err(e) = surface_model.render(glc, |uniforms| {
+					uniforms.eye = eye;
+				}) {
+					log::error!("{:?}", e);
+				}
+			});
+		Ok(())
+	}
+	// Render a thumbnail of every model in the directory and the folders in
+	// it, next to each model or in the same folders under `output`, and make
+	// an index.html page showing them all. Returns the number of thumbnails
+	// and the models which couldn't be rendered.
+	fn batch_thumbnails(&mut self, glc: &Arc<GLContext>, directory: &Path, output: Option<&Path>) -> Result<(usize, usize), AError> {
+		let output = output.unwrap_or(directory);
+		let models = find_models(directory)?;
+		let renderer = Renderer::new(Arc::clone(glc), &[RenderPass::Opaque]);
+		let mut framebuffer = Framebuffer::new(Arc::clone(glc), BATCH_THUMBNAIL_SIZE, BATCH_THUMBNAIL_SIZE)?;
+		let mut thumbnails = vec![];
+		let mut failed = vec![];
+		framebuffer.bind();
+		models.iter().for_each(|path| {
+			let relative = path.strip_prefix(directory).unwrap_or(path);
+			let name = relative.to_string_lossy().replace('\\', "/");
+			let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+			let thumbnail = output.join(relative).with_file_name(format!("{}_thumb.png", stem));
+			let mut errors = vec![];
+			let result = self.render_preview(glc, &renderer, path, &mut errors)
+				.and_then(|_| {
+					fs::create_dir_all(thumbnail.parent().unwrap_or(output))?;
+					Ok(framebuffer.read_image().save(&thumbnail)?)
+				});
+			// Missing textures don't stop the thumbnail from being useful
+			errors.iter().for_each(|e| log::warn!("{}: {}", name, e));
+			match result {
+				Ok(_) => {
+					log::info!("Rendered a thumbnail of {}", name);
+					let image = thumbnail.strip_prefix(output).unwrap_or(&thumbnail)
+						.to_string_lossy().replace('\\', "/");
+					thumbnails.push((name, image));
+				},
+				Err(e) => {
+					log::error!("Could not render a thumbnail of {}: {}", name, e);
+					failed.push((name, e.to_string()));
+				},
+			}
+			self.texture_cache.evict(self.settings.texture_budget.bytes());
+		});
+		framebuffer.unbind();
+		let mut index = BufWriter::new(File::create(output.join("index.html"))?);
+		export::thumbnail_index(&thumbnails, &failed, &mut index)?;
+		index.flush()?;
+		Ok((thumbnails.len(), failed.len()))
+	}
+	// Render the first frame of every model in the directory, and save them
+	// all to a single image in the same directory.
+	fn render_contact_sheet(&mut self, glc: &Arc<GLContext>, directory: &Path) -> Result<(), AError> {
+		let mut paths: Vec<PathBuf> = fs::read_dir(directory)?
+			.filter_map(|entry| entry.ok().map(|e| e.path()))
+			.filter(|path| path.extension().is_some_and(|e| e.eq_ignore_ascii_case("md3")))
+			.collect();
+		if paths.is_empty() {
+			return Err(AError::msg("There are no MD3 files in this folder"));
+		}
+		paths.sort();
+		let fonts = egui::FontDefinitions::default();
+		let font = FontRef::try_from_slice(&fonts.font_data["Ubuntu-Light"].font)?;
+		let renderer = Renderer::new(Arc::clone(glc), &[RenderPass::Opaque]);
+		let mut framebuffer = Framebuffer::new(Arc::clone(glc),
+			CONTACT_SHEET_PREVIEW_SIZE, CONTACT_SHEET_PREVIEW_SIZE)?;
+		let mut errors = vec![];
+		framebuffer.bind();
+		let previews: Vec<(String, RgbaImage)> = paths.iter().map(|path| {
+			let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+			if let Err(e) = self.render_preview(glc, &renderer, path, &mut errors) {
+				errors.push(Problem::error(Source::Parser, format!("Error reading file {}:\n{}", path.display(), e))
+					.path(path));
+			}
+			(name, framebuffer.read_image())
+		}).collect();
+		framebuffer.unbind();
+		errors.into_iter().for_each(|e| self.report(e));
+		export::contact_sheet(&previews, &font).save(directory.join("contact_sheet.png"))?;
+		Ok(())
+	}
+	fn draw_sky(&mut self, glc: &Arc<GLContext>, view: &View) {
+		let inv_sky_eye = (view.rotation_projection * Mat4::from_scale(MD3_MODEL_SCALE)).inverse();
+		if let Some(skybox) = self.skybox.as_mut().filter(|_| self.controls.show_skybox) {
+			if let Err(e) = skybox.render(glc, |uniforms| {
+				uniforms.invEye = inv_sky_eye;
+			}) {
+				log::error!("{:?}", e);
+			}
+		}
+	}
+	// Draw the opaque surfaces of the model, and the other models
+	fn draw_models(&mut self, glc: &Arc<GLContext>, view: &View) {
+		self.update_normal_errors(glc);
+		self.update_flipped_triangles();
+		self.update_fullbright(glc);
+		let model_matrix = self.model_matrix();
+		let eye = view.view_projection * model_matrix;
+		let display = self.display.matrix();
+		let show_voxels = self.voxels.show && self.voxels.mesh.is_some();
+		let opaque: Vec<usize> = self.models.iter().enumerate()
+			.filter(|_| !self.model_hidden && !show_voxels)
+			.filter(|(_, (index, _))| !self.surface_settings[*index].blend.transparent())
+			.map(|(model, _)| model)
+			.collect();
+		self.draw_surfaces(glc, view, &opaque);
+		if let Some(mesh) = self.voxels.mesh.as_mut().filter(|_| show_voxels && !self.model_hidden) {
+			if let Err(e) = mesh.render(glc, |uniforms| {
+				uniforms.eye = eye;
+				uniforms.shaded = true;
+			}) {
+				log::error!("{:?}", e);
+			}
+		}
+		let checker = self.checker_texture(glc);
+		let model = match self.model_data.as_ref() {
+			Some(m) => m,
+			None => return,
+		};
+		// Models without any drawable surfaces would otherwise look unreadable
+		if self.models.is_empty() && !self.model_hidden {
+			if let Some(frame) = model.frames.get(self.current_frame.floor() as usize) {
+				let mvp = eye * Mat4::from_translation(frame.min) * Mat4::from_scale(frame.max - frame.min);
+				if let Err(e) = self.bounds.render_lines(glc, |uniforms| {
+					uniforms.eye = mvp;
+					uniforms.shaded = false;
+				}) {
+					log::error!("{:?}", e);
+				}
+			}
+		}
+		self.attachments.iter_mut().filter(|a| !a.hidden).for_each(|attachment| {
+			let tag = match attachment.placement(model, self.current_frame) {
+				Some(t) => t,
+				None => return,
+			};
+			// Attachments are animated along with the main model, as long as
+			// they have enough frames
+			let last_frame = attachment.model.frames.len().saturating_sub(1) as f32;
+			let frame = self.current_frame.min(last_frame);
+			let attachment_matrix = model_matrix * tag;
+			let view_origin = attachment_matrix.inverse().transform_point3(view.position);
+			let light_direction = (display * tag).inverse().transform_vector3(self.entity_light.direction()).normalize();
+			let outlines = outline_passes(self.controls.view_mode, view,
+				attachment_matrix.transform_point3(Vec3::ZERO), self.display.scale * attachment.transform.scale);
+			attachment.models.iter_mut().for_each(|(_, model)| outlines.iter().for_each(|&outline| {
+				renderer::set_cull_front(glc, outline > 0.);
+				if let Err(e) = model.render(glc, |uniforms| {
+					uniforms.eye = view.view_projection;
+					uniforms.model = attachment_matrix;
+					uniforms.frame = frame;
+					uniforms.mode = self.controls.view_mode as u32;
+					uniforms.gzdoom = self.controls.gzdoom_normals;
+					uniforms.viewOrigin = view_origin;
+					uniforms.entityLight = self.entity_light.enabled;
+					uniforms.ambientLight = Vec3::from(self.entity_light.ambient);
+					uniforms.directedLight = Vec3::from(self.entity_light.directed);
+					uniforms.lightDirection = light_direction;
+					uniforms.checker = checker.clone();
+					uniforms.outline = outline;
+				}) {
+					log::error!("{:?}", e);
+				}
+			}));
+			renderer::set_cull_front(glc, false);
+		});
+	}
+	// Draw the transparent surfaces of the model, from back to front
+	fn draw_transparent(&mut self, glc: &Arc<GLContext>, view: &View) {
+		let frame = self.current_frame.floor() as usize;
+		let model_matrix = self.model_matrix();
+		let show_voxels = self.voxels.show && self.voxels.mesh.is_some();
+		let mut transparent: Vec<(usize, f32)> = self.models.iter().enumerate()
+			.filter(|_| !self.model_hidden && !show_voxels)
+			.filter(|(_, (index, _))| self.surface_settings[*index].blend.transparent())
+			.map(|(model, (index, _))| {
+				let centre = self.model_data.as_ref()
+					.and_then(|m| m.surfaces[*index].bounds(frame))
+					.map(|(min, max)| (min + max) * 0.5)
+					.unwrap_or_default();
+				let depth = (model_matrix.transform_point3(centre) - view.position).dot(view.forward);
+				(model, depth)
+			})
+			.collect();
+		transparent.sort_by(|a, b| b.1.total_cmp(&a.1));
+		let transparent: Vec<usize> = transparent.into_iter().map(|(model, _)| model).collect();
+		self.draw_surfaces(glc, view, &transparent);
+	}
+	// Make the textures with the normal error of each vertex, if they're
+	// needed and the normal encoding has changed
+	fn update_normal_errors(&mut self, glc: &Arc<GLContext>) {
+		let needed = self.controls.view_mode == ViewMode::NormalCheck || self.normal_inspector.open;
+		let gzdoom = self.controls.gzdoom_normals;
+		if !needed || self.normal_inspector.gzdoom == Some(gzdoom) { return; }
+		let model = match self.model_data.as_ref() {
+			Some(m) => m,
+			None => return,
+		};
+		self.models.iter_mut().for_each(|(index, surface_model)| {
+			let surface = &model.surfaces[*index];
+			let texture = Texture::try_from_vertex_values(Arc::clone(glc), &surface.normal_errors(gzdoom));
+			surface_model.uniforms.normalError = texture
+				.map_err(|e| log::error!("{:?}", e)).ok().map(Rc::new);
+		});
+		self.normal_inspector.gzdoom = Some(gzdoom);
+	}
+	// Decode the normals of the current frame, and make lines showing the
+	// bad ones, or all of them
+	fn update_normal_lines(&mut self, glc: &Arc<GLContext>) {
+		let model = match self.model_data.as_ref() {
+			Some(m) => m,
+			None => return,
+		};
+		let inspector = &self.normal_inspector;
+		let frame = (self.current_frame.floor() as usize).min(model.frames.len().saturating_sub(1));
+		let gzdoom = self.controls.gzdoom_normals;
+		let up_to_date = inspector.lines.as_ref().is_some_and(|l| l.frame == frame &&
+			l.gzdoom == gzdoom && l.threshold == inspector.threshold && l.all == inspector.all);
+		if up_to_date { return; }
+		let normals: Vec<VertexNormal> = model.surfaces.iter().enumerate().flat_map(|(surface, surf)| {
+			let start = frame * surf.num_verts;
+			let vertices = surf.vertices.get(start..start + surf.num_verts).unwrap_or_default();
+			surf.smooth_normals(frame, NormalWeighting::Area).into_iter().zip(vertices).enumerate()
+				.map(move |(vertex, (smooth, v))| {
+					let stored = v.normal(gzdoom);
+					VertexNormal {
+						surface, vertex, smooth, stored,
+						position: v.position(),
+						lat_long: v.lat_long(),
+						error: if smooth == Vec3::ZERO { 0. } else {
+							smooth.dot(stored).clamp(-1., 1.).acos().to_degrees()
+						},
+					}
+				})
+		}).collect();
+		let length = (model.max_radius() * 0.05).max(1.);
+		let vertices: Box<[VertexRes]> = normals.iter()
+			.filter(|n| !self.surface_settings[n.surface].hidden)
+			.filter(|n| inspector.all || n.error > inspector.threshold)
+			.flat_map(|n| [
+				(n.position, n.stored, NORMAL_STORED_COLOUR),
+				(n.position, n.smooth, NORMAL_SMOOTH_COLOUR),
+			]).flat_map(|(position, normal, colour)| [
+				VertexRes { position, colour, normal: Vec3::ZERO },
+				VertexRes { position: position + normal * length, colour, normal: Vec3::ZERO },
+			]).collect();
+		let lines = (!vertices.is_empty()).then(|| BasicModel {
+			index: IndexBuffer::new(Arc::clone(glc), (0..vertices.len() as u32).collect()),
+			vertex: VertexBuffer::new(Arc::clone(glc), vertices),
+			shader: Rc::clone(&self.axes.shader),
+			uniforms: UniformsRes::default(),
+		});
+		self.normal_inspector.lines = Some(NormalLines {
+			frame, gzdoom, normals, lines,
+			threshold: inspector.threshold,
+			all: inspector.all,
+		});
+	}
+	// Outline the selected islands of the surface being split in orange, and
+	// the hovered one in white
+	fn update_split_outline(&mut self, glc: &Arc<GLContext>) {
+		let split = &mut self.surface_split;
+		let frame = self.current_frame.floor() as usize;
+		let key = (split.surface, frame, split.selected.iter().copied().collect(), split.hovered);
+		if split.built.as_ref() == Some(&key) { return; }
+		let surf = match self.model_data.as_ref().and_then(|m| m.surfaces.get(split.surface)) {
+			Some(s) => s,
+			None => return,
+		};
+		let start = frame.min(surf.num_frames.saturating_sub(1)) * surf.num_verts;
+		let verts = surf.vertices.get(start..start + surf.num_verts).unwrap_or_default();
+		let islands = surf.uv_islands();
+		let vertices: Box<[VertexRes]> = surf.triangles.iter().zip(islands).filter_map(|(tri, island)| {
+			let colour = match (split.hovered == Some(island), split.selected.contains(&island)) {
+				(true, _) => Vec3::ONE,
+				(false, true) => Vec3::new(1., 0.5, 0.),
+				(false, false) => return None,
+			};
+			let corners = tri.0.map(|v| verts.get(v as usize).map(|v| v.position()));
+			Some((0..3).filter_map(move |i| Some([corners[i]?, corners[(i + 1) % 3]?]))
+				.flatten().map(move |position| VertexRes { position, colour, normal: Vec3::ZERO }))
+		}).flatten().collect();
+		split.outline = (!vertices.is_empty()).then(|| BasicModel {
+			index: IndexBuffer::new(Arc::clone(glc), (0..vertices.len() as u32).collect()),
+			vertex: VertexBuffer::new(Arc::clone(glc), vertices),
+			shader: Rc::clone(&self.axes.shader),
+			uniforms: UniformsRes::default(),
+		});
+		split.built = Some(key);
+	}
+	fn update_motion_trail(&mut self, glc: &Arc<GLContext>) {
+		let trail = &mut self.motion_trail;
+		if trail.built == trail.target && trail.built.is_some() { return; }
+		trail.points = match (self.model_data.as_ref(), trail.target) {
+			(Some(model), Some(target)) => trail_points(model, target),
+			_ => vec![],
+		};
+		trail.built = trail.target;
+		// Blue at the first frame, red at the last
+		let last = trail.points.len().saturating_sub(1).max(1) as f32;
+		let vertices: Box<[VertexRes]> = trail.points.iter().enumerate().map(|(frame, &position)| VertexRes {
+			position,
+			colour: Vec3::new(0.2, 0.4, 1.).lerp(Vec3::new(1., 0.2, 0.2), frame as f32 / last),
+			normal: Vec3::ZERO,
+		}).collect();
+		let indices: Vec<u32> = (1..vertices.len() as u32).flat_map(|i| [i - 1, i]).collect();
+		trail.lines = (!indices.is_empty()).then(|| BasicModel {
+			vertex: VertexBuffer::new(Arc::clone(glc), vertices),
+			index: IndexBuffer::new(Arc::clone(glc), indices),
+			shader: Rc::clone(&self.axes.shader),
+			uniforms: UniformsRes::default(),
+		});
+	}
+	// Where a note is on the model in the current frame
+	fn note_position(&self, note: &Note) -> Option<Vec3> {
+		match &note.anchor {
+			NoteAnchor::Position(position) => Some(*position),
+			NoteAnchor::Tag(tag) => self.model_data.as_ref()?.tag_transform(tag, self.current_frame)
+				.map(|transform| transform.translation.into()),
+		}
+	}
+	// The vertex of a visible surface closest to where the view was clicked,
+	// in points from the top left
+	fn pick_vertex(&self, pos: Vec2, screen: Vec2) -> Option<TrailTarget> {
+		let model = self.model_data.as_ref()?;
+		let frame = self.current_frame.floor() as usize;
+		let mvp = self.view_camera().view_projection() * self.model_matrix();
+		let mut nearest: Option<(f32, TrailTarget)> = None;
+		model.surfaces.iter().enumerate()
+			.filter(|(surface, _)| !self.surface_settings.get(*surface).is_some_and(|s| s.hidden))
+			.for_each(|(surface, surf)| {
+				let start = frame.min(surf.num_frames.saturating_sub(1)) * surf.num_verts;
+				let vertices = surf.vertices.get(start..start + surf.num_verts).unwrap_or_default();
+				vertices.iter().enumerate().for_each(|(vertex, v)| {
+					let clip = mvp * v.position().extend(1.);
+					if clip.w <= 0. { return; }
+					let ndc = clip.truncate() / clip.w;
+					let on_screen = Vec2::new(ndc.x + 1., 1. - ndc.y) * 0.5 * screen;
+					let distance = on_screen.distance(pos);
+					if distance < nearest.map_or(PICK_RADIUS, |(d, _)| d) {
+						nearest = Some((distance, TrailTarget::Vertex { surface, vertex }));
+					}
+				});
+			});
+		nearest.map(|(_, target)| target)
+	}
+	// Find the triangles which are wound the wrong way round in the current
+	// frame, for the back face view mode
+	fn update_flipped_triangles(&mut self) {
+		if self.controls.view_mode != ViewMode::Backfaces { return; }
+		let model = match self.model_data.as_ref() {
+			Some(m) => m,
+			None => return,
+		};
+		let frame = (self.current_frame.floor() as usize).min(model.frames.len().saturating_sub(1));
+		let gzdoom = self.controls.gzdoom_normals;
+		if matches!(&self.flipped, Some(f) if f.frame == frame && f.gzdoom == gzdoom) { return; }
+		let triangles = model.surfaces.iter().enumerate()
+			.flat_map(|(surface, surf)| surf.flipped_triangles(frame, gzdoom).into_iter()
+				.map(move |triangle| (surface, triangle)))
+			.collect();
+		self.flipped = Some(FlippedTriangles { frame, gzdoom, triangles });
+	}
+	// Use the fullbright masks of the surfaces with 8-bit textures, if
+	// fullbright texels are being shown
+	fn update_fullbright(&mut self, glc: &Arc<GLContext>) {
+		if !self.controls.fullbright {
+			self.models.iter_mut().for_each(|(_, model)| model.uniforms.fullbright = None);
+			return;
+		}
+		let surfaces: Vec<usize> = self.models.iter().map(|(index, _)| *index).collect();
+		let masks: Vec<Option<Rc<Texture>>> = surfaces.iter().map(|&surface| {
+			let path = self.surface_texture_path(surface).filter(|p| palette::is_palettized(p))?;
+			let nearest = self.settings.nearest_filtering;
+			self.fullbright_masks.entry(path).or_insert_with_key(|path| {
+				let mask = Surface::read_fullbright_mask(path)
+					.and_then(|mask| mask.map(|m| Texture::try_from_surface(Arc::clone(glc), &m)).transpose());
+				match mask {
+					Ok(mask) => mask.map(|m| {
+						m.set_nearest(nearest);
+						Rc::new(m)
+					}),
+					Err(e) => {
+						log::warn!("Could not read the fullbright texels of {}: {:?}", path.display(), e);
+						None
+					},
+				}
+			}).clone()
+		}).collect();
+		self.models.iter_mut().zip(masks).for_each(|((_, model), mask)| model.uniforms.fullbright = mask);
+	}
+	// Make the checkerboard for the UV checker view mode, if it's being used
+	// and the number of squares has changed
+	fn checker_texture(&mut self, glc: &Arc<GLContext>) -> Option<Rc<Texture>> {
+		if self.controls.view_mode != ViewMode::UvChecker { return None; }
+		let cells = self.uv_checker.cells;
+		if !matches!(&self.uv_checker.texture, Some((c, _)) if *c == cells) {
+			self.uv_checker.texture = Texture::try_from_surface(Arc::clone(glc), &Surface::checkerboard(cells))
+				.map_err(|e| log::error!("Could not make the checkerboard texture: {:?}", e))
+				.ok().map(|texture| (cells, Rc::new(texture)));
+		}
+		self.uv_checker.texture.as_ref().map(|(_, texture)| Rc::clone(texture))
+	}
+	// Draw some of the model's surfaces, in the given order. The surfaces are
+	// indices into self.models.
+	fn draw_surfaces(&mut self, glc: &Arc<GLContext>, view: &View, surfaces: &[usize]) {
+		let model_matrix = self.model_matrix();
+		let view_origin = model_matrix.inverse().transform_point3(view.position);
+		let light_direction = self.display.matrix().inverse()
+			.transform_vector3(self.entity_light.direction()).normalize();
+		let checker = self.checker_texture(glc);
+		let sky_texture = self.skybox.as_ref().filter(|_| self.controls.show_skybox)
+			.map(|skybox| Rc::clone(&skybox.uniforms.sky));
+		let outlines = outline_passes(self.controls.view_mode, view,
+			model_matrix.transform_point3(Vec3::ZERO), self.display.scale);
+		surfaces.iter().for_each(|&surface| {
+			let (index, model) = &mut self.models[surface];
+			let settings = &self.surface_settings[*index];
+			if settings.hidden { return; }
+			settings.blend.apply(glc);
+			outlines.iter().for_each(|&outline| {
+				match outline > 0. {
+					true => renderer::set_cull_front(glc, true),
+					false => renderer::set_two_sided(glc, settings.two_sided || self.controls.view_mode == ViewMode::Backfaces),
+				}
+				if let Err(e) = model.render(glc, |uniforms| {
+					uniforms.eye = view.view_projection;
+					uniforms.model = model_matrix;
+					uniforms.frame = self.current_frame;
+					uniforms.mode = self.controls.view_mode as u32;
+					uniforms.gzdoom = self.controls.gzdoom_normals;
+					uniforms.envmap = settings.envmap;
+					uniforms.viewOrigin = view_origin;
+					uniforms.sky = sky_texture.clone();
+					uniforms.skyReflection = self.controls.sky_reflection && sky_texture.is_some();
+					uniforms.alphaThreshold = settings.blend.alpha_threshold();
+					uniforms.entityLight = self.entity_light.enabled;
+					uniforms.ambientLight = Vec3::from(self.entity_light.ambient);
+					uniforms.directedLight = Vec3::from(self.entity_light.directed);
+					uniforms.lightDirection = light_direction;
+					uniforms.normalThreshold = self.normal_inspector.threshold;
+					uniforms.checker = checker.clone();
+					uniforms.outline = outline;
+					uniforms.opaque = !settings.blend.transparent();
+				}) {
+					log::error!("{:?}", e);
+				}
+				renderer::set_cull_front(glc, false);
+			});
+		});
+		renderer::set_two_sided(glc, false);
+	}
+	fn draw_tag_gizmos(&mut self, glc: &Arc<GLContext>, view: &View) {
+		let model_matrix = self.model_matrix();
+		let eye = view.view_projection * model_matrix;
+		let view_origin = model_matrix.inverse().transform_point3(view.position);
+		if let Some(model) = self.model_data.as_ref() {
+			let current_frame = self.current_frame.floor() as usize;
+			let next_frame = self.current_frame.ceil() as usize;
+			let lerp_factor = self.current_frame.fract();
+			// Models without frames don't have any tags either
+			let num_tags = if model.tags.is_empty() { 0 } else { model.num_tags };
+			(0..num_tags).for_each(|tag_index| {
+				let tag_a = tag_index + num_tags * current_frame;
+				let tag_b = tag_index + num_tags * next_frame;
+				let tag_a = &model.tags[tag_a];
+				let tag_b = &model.tags[tag_b];
+				let tag_axes = lerp(tag_a.axes, tag_b.axes, lerp_factor);
+				let tag_origin = lerp(tag_a.origin, tag_b.origin, lerp_factor);
+				let gizmo_scale = if self.controls.tag_mode { 64. } else { 256. };
+				let tag_distance = view_origin.distance(tag_origin) / gizmo_scale;
+				let mvp = eye * Affine3A::from_mat3_translation(tag_axes, tag_origin) *
+					Mat4::from_scale(Vec3::splat(tag_distance));
+
+				if let Err(e) = self.tag_axes.render(glc, |uniforms| {
+					uniforms.eye = mvp;
+					uniforms.shaded = true;
+				}) {
+					log::error!("{:?}", e);
+				}
+			});
+		}
+		if let Some(tag_paths) = self.tag_paths.as_mut().filter(|_| self.controls.tag_mode) {
+			if let Err(e) = tag_paths.render_lines(glc, |uniforms| {
+				uniforms.eye = eye;
+				uniforms.shaded = false;
+			}) {
+				log::error!("{:?}", e);
+			}
+		}
+	}
+	// Small axes in the top right corner, which show the view direction
+	fn draw_world_axes(&mut self, glc: &Arc<GLContext>, view: &View) {
+		let mvp = {
+			let eye = view.forward * -60.;
+			// 160 pixels left from top right corner, 80 pixels down from top right corner
+			let trans = Mat4::from_translation(Vec3::new(1.0 - (320./view.size.x), 1.0 - (160./view.size.y), 0.));
+			let scale = Mat4::from_scale(Vec3::new(0.125, 0.125, 0.125));
+			let look = Mat4::look_at_lh(eye, Vec3::ZERO, Vec3::Z);
+			let proj = Mat4::perspective_lh(view.fov, view.aspect, 0.25, 512.);
+			trans * proj * look * scale * Mat4::from_scale(MD3_MODEL_SCALE)
+		};
+		if let Err(e) = self.axes.render(glc, |uniforms| {
+			uniforms.eye = mvp;
+			uniforms.shaded = false;
+		}) {
+			log::error!("{:?}", e);
+		}
+	}
+	// The bounding box of what's selected in the outliner, and the matrix
+	// from its space to world space
+	fn selection_bounds(&self) -> Option<(Mat4, Vec3, Vec3)> {
+		let model = self.model_data.as_ref()?;
+		let frame = self.current_frame.floor() as usize;
+		match self.outliner.selected.filter(|_| self.outliner.open)? {
+			SceneNode::Model => model.frames.get(frame).map(|f| (self.model_matrix(), f.min, f.max)),
+			SceneNode::Surface(index) => model.surfaces.get(index)?.bounds(frame)
+				.map(|(min, max)| (self.model_matrix(), min, max)),
+			SceneNode::Attachment(index) => {
+				let attachment = self.attachments.get(index)?;
+				let placement = attachment.placement(model, self.current_frame)?;
+				let last_frame = attachment.model.frames.len().checked_sub(1)?;
+				let bounds = &attachment.model.frames[frame.min(last_frame)];
+				Some((self.model_matrix() * placement, bounds.min, bounds.max))
+			},
+			SceneNode::Tag(_) | SceneNode::Light => None,
+		}
+	}
+	fn draw_overlays(&mut self, glc: &Arc<GLContext>, view: &View) {
+		if let Some((matrix, min, max)) = self.selection_bounds() {
+			let mvp = view.view_projection * matrix * Mat4::from_translation(min) * Mat4::from_scale(max - min);
+			if let Err(e) = self.bounds.render_lines(glc, |uniforms| {
+				uniforms.eye = mvp;
+				uniforms.shaded = false;
+			}) {
+				log::error!("{:?}", e);
+			}
+		}
+		if self.controls.debug_camera {
+			// Transform the clip space volume of the main camera to world space
+			let frustum_eye = view.view_projection * self.camera.view_projection().inverse();
+			if let Err(e) = self.frustum.render_lines(glc, |uniforms| {
+				uniforms.eye = frustum_eye;
+				uniforms.shaded = false;
+			}) {
+				log::error!("{:?}", e);
+			}
+		}
+		if self.normal_inspector.open {
+			self.update_normal_lines(glc);
+			let eye = view.view_projection * self.model_matrix();
+			let lines = self.normal_inspector.lines.as_mut().and_then(|l| l.lines.as_mut());
+			if let Some(Err(e)) = lines.map(|lines| lines.render_lines(glc, |uniforms| {
+				uniforms.eye = eye;
+				uniforms.shaded = false;
+			})) {
+				log::error!("{:?}", e);
+			}
+		}
+		if self.surface_split.open {
+			self.update_split_outline(glc);
+			let eye = view.view_projection * self.model_matrix();
+			if let Some(Err(e)) = self.surface_split.outline.as_mut().map(|outline| outline.render_lines(glc, |uniforms| {
+				uniforms.eye = eye;
+				uniforms.shaded = false;
+			})) {
+				log::error!("{:?}", e);
+			}
+		}
+		if self.motion_trail.open {
+			self.update_motion_trail(glc);
+			let eye = view.view_projection * self.model_matrix();
+			if let Some(Err(e)) = self.motion_trail.lines.as_mut().map(|lines| lines.render_lines(glc, |uniforms| {
+				uniforms.eye = eye;
+				uniforms.shaded = false;
+			})) {
+				log::error!("{:?}", e);
+			}
+		}
+	}
+	fn load_skybox(&mut self, glc: &Arc<GLContext>, path: &Path) -> Result<(), AError> {
+		let faces = Surface::read_skybox(path)?;
+		let sky = Texture::try_from_cube_faces(Arc::clone(glc), &faces)?;
+		self.skybox = Some(BasicModel {
+			vertex: VertexBuffer::new(Arc::clone(glc), Box::new(render::SCREEN_TRIANGLE_V)),
+			index: IndexBuffer::new(Arc::clone(glc), Vec::from(render::SCREEN_TRIANGLE_I)),
+			shader: Rc::clone(&self.sky_shader),
+			uniforms: UniformsSky {
+				invEye: Mat4::IDENTITY,
+				sky: Rc::new(sky),
+			},
+		});
+		self.skybox_path = Some(path.to_path_buf());
+		self.controls.show_skybox = true;
+		Ok(())
+	}
+}
+
+// Textures are looked up relative to the directory the model is in
+impl Scene for App {
+	fn draw(&mut self, glc: &Arc<GLContext>, pass: RenderPass, view: &View) {
+		match pass {
+			RenderPass::Sky => self.draw_sky(glc, view),
+			RenderPass::Opaque => self.draw_models(glc, view),
+			RenderPass::Transparent => self.draw_transparent(glc, view),
+			RenderPass::TagGizmos => self.draw_tag_gizmos(glc, view),
+			RenderPass::WorldAxes => self.draw_world_axes(glc, view),
+			RenderPass::Overlays => self.draw_overlays(glc, view),
+		}
+	}
+}
+
+fn limit_problem(warning: &LimitWarning, path: &Path) -> Problem {
+	let problem = Problem::warning(Source::Parser, warning).path(path);
+	match warning.surface() {
+		Some(surface) => problem.surface(surface),
+		None => problem,
+	}
+}
+
+// How far the camera is from a model when it's opened. Empty models have a
+// radius of 0, which would put the camera inside them.
+fn home_distance(model: &MD3Model) -> f32 {
+	model.max_radius().max(16.) * 2.
+}
+
+const TAG_PATH_COLOURS: [Vec3; 6] = [
+	Vec3::new(1., 1., 0.),
+	Vec3::new(0., 1., 1.),
+	Vec3::new(1., 0., 1.),
+	Vec3::new(1., 0.5, 0.),
+	Vec3::new(0.5, 1., 0.),
+	Vec3::new(0.5, 0.5, 1.),
+];
+
+// Line segments connecting the origins of each tag in consecutive frames
+fn tag_paths(model: &MD3Model) -> Option<(Box<[VertexRes]>, Vec<u32>)> {
+	let num_tags = model.num_tags;
+	let num_frames = model.frames.len();
+	if num_tags == 0 || num_frames < 2 || model.tags.len() < num_tags * num_frames {
+		return None;
+	}
+	// Tags are stored frame by frame
+	let vertices = model.tags.iter().enumerate().map(|(index, tag)| VertexRes {
+		position: tag.origin,
+		colour: TAG_PATH_COLOURS[(index % num_tags) % TAG_PATH_COLOURS.len()],
+		normal: Vec3::ZERO,
+	}).collect();
+	let indices = (0..num_frames - 1).flat_map(|frame| {
+		(0..num_tags).flat_map(move |tag| {
+			let index = (frame * num_tags + tag) as u32;
+			[index, index + num_tags as u32]
+		})
+	}).collect();
+	Some((vertices, indices))
+}
+
+// How close a click has to be to a vertex to pick it, in points
+const PICK_RADIUS: f32 = 12.;
+
+// Where a tag's origin or a vertex is in each frame
+fn trail_points(model: &MD3Model, target: TrailTarget) -> Vec<Vec3> {
+	match target {
+		TrailTarget::Tag(tag) if tag < model.num_tags => model.tags.iter()
+			.skip(tag).step_by(model.num_tags).map(|t| t.origin).collect(),
+		TrailTarget::Tag(_) => vec![],
+		TrailTarget::Vertex { surface, vertex } => match model.surfaces.get(surface) {
+			Some(surf) if vertex < surf.num_verts => surf.vertices.iter()
+				.skip(vertex).step_by(surf.num_verts).map(|v| v.position()).collect(),
+			_ => vec![],
+		},
+	}
+}
+
+// The faces of the voxels which aren't next to another voxel
+fn voxel_mesh(glc: &Arc<GLContext>, grid: &VoxelGrid, shader: Rc<ShaderProgram<UniformsResLocations>>)
+	-> Option<BasicModel<u32, UniformsRes, UniformsResLocations>> {
+	let mut vertices: Vec<VertexRes> = vec![];
+	let mut indices: Vec<u32> = vec![];
+	grid.voxels.iter().for_each(|(&cell, colour)| {
+		let colour = Vec3::from(colour.map(|c| c as f32 / 255.));
+		let corner = grid.origin + Vec3::from(cell.map(|c| c as f32)) * grid.voxel_size;
+		(0..3).for_each(|axis| [-1i64, 1].into_iter().for_each(|side| {
+			let mut neighbour = cell.map(i64::from);
+			neighbour[axis] += side;
+			if grid.is_filled(neighbour[0], neighbour[1], neighbour[2]) { return; }
+			let normal = Vec3::AXES[axis] * side as f32;
+			let (u, v) = (Vec3::AXES[(axis + 1) % 3], Vec3::AXES[(axis + 2) % 3]);
+			let base = if side > 0 { Vec3::AXES[axis] } else { Vec3::ZERO };
+			// Clockwise seen from outside, like MD3 triangles
+			let corners = match side > 0 {
+				true => [Vec3::ZERO, v, u + v, u],
+				false => [Vec3::ZERO, u, u + v, v],
+			};
+			let first = vertices.len() as u32;
+			vertices.extend(corners.map(|c| VertexRes {
+				position: corner + (base + c) * grid.voxel_size,
+				colour, normal,
+			}));
+			indices.extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+		}));
+	});
+	(!indices.is_empty()).then(|| BasicModel {
+		vertex: VertexBuffer::new(Arc::clone(glc), vertices.into_boxed_slice()),
+		index: IndexBuffer::new(Arc::clone(glc), indices),
+		shader,
+		uniforms: UniformsRes::default(),
+	})
+}
+
+// The lines of the HUD: the frame, frame rate, visible triangles and the
+// camera's angles
+fn hud_text(app: &App, frame_times: &FrameTimes) -> String {
+	let mut lines = vec![];
+	if let Some(model) = app.model_data.as_ref() {
+		let frame = app.current_frame.floor() as usize;
+		let name = model.frames.get(frame).map(|f| f.name()).unwrap_or_default();
+		lines.push(format!("Frame {}/{} {}", frame, model.frames.len().saturating_sub(1), name));
+		let visible: Vec<&MD3Surface> = model.surfaces.iter().enumerate()
+			.filter(|(surface, _)| !app.model_hidden && !app.surface_settings.get(*surface).is_some_and(|s| s.hidden))
+			.map(|(_, surf)| surf).collect();
+		lines.push(format!("{} triangles in {} of {} surfaces", visible.iter().map(|s| s.triangles.len()).sum::<usize>(),
+			visible.len(), model.surfaces.len()));
+	}
+	let average = frame_times.average().as_secs_f32();
+	if average > 0. {
+		lines.push(format!("{:.0} FPS", 1. / average));
+	}
+	let camera = app.view_camera();
+	lines.push(format!("Camera: yaw {:.0}°, pitch {:.0}°{}", camera.longtude.to_degrees().rem_euclid(360.),
+		camera.latitude.to_degrees(), if camera.orthographic { ", orthographic" } else { "" }));
+	lines.join("\n")
+}
+
+// Milliseconds per frame, newest on the right, with lines at 60 and 30 FPS
+fn frame_time_graph(ui: &mut egui::Ui, frame_times: &FrameTimes) {
+	let ms = |time: Duration| time.as_secs_f32() * 1000.;
+	let size = egui::vec2(frame_times::HISTORY as f32, 80.);
+	let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+	let painter = ui.painter_at(rect);
+	painter.rect_filled(rect, 2., Color32::from_black_alpha(160));
+	let scale = ms(frame_times.max()).max(40.);
+	let y = |ms: f32| rect.bottom() - rect.height() * (ms / scale).min(1.);
+	[(1000. / 60., Color32::DARK_GREEN), (1000. / 30., Color32::DARK_RED)].into_iter()
+		.for_each(|(ms, colour)| painter.hline(rect.x_range(), y(ms), (1., colour)));
+	let count = frame_times.times().count();
+	let points: Vec<Pos2> = frame_times.times().enumerate()
+		.map(|(index, time)| Pos2::new(rect.right() - (count - index) as f32, y(ms(time))))
+		.collect();
+	painter.add(egui::Shape::line(points, (1., Color32::WHITE)));
+	painter.text(rect.left_top() + egui::vec2(4., 2.), egui::Align2::LEFT_TOP,
+		format!("{:.1} ms average, {:.1} ms max", ms(frame_times.average()), ms(frame_times.max())),
+		TextStyle::Small.resolve(ui.style()), Color32::WHITE);
+}
+
+// A node in the outliner, with a visibility checkbox if it can be hidden, a
+// lock, and its name
+fn outliner_row(ui: &mut egui::Ui, outliner: &mut Outliner, node: SceneNode, name: &str, hidden: Option<&mut bool>) {
+	ui.horizontal(|ui| outliner_header(ui, outliner, node, name, hidden));
+}
+
+// A node with other nodes under it
+fn outliner_parent(ui: &mut egui::Ui, outliner: &mut Outliner, node: SceneNode, name: &str,
+	hidden: Option<&mut bool>, children: impl FnOnce(&mut egui::Ui, &mut Outliner)) {
+	let id = ui.make_persistent_id(("outliner", node));
+	egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, node == SceneNode::Model)
+		.show_header(ui, |ui| outliner_header(ui, outliner, node, name, hidden))
+		.body(|ui| children(ui, outliner));
+}
+
+fn outliner_header(ui: &mut egui::Ui, outliner: &mut Outliner, node: SceneNode, name: &str, hidden: Option<&mut bool>) {
+	let mut locked = outliner.is_locked(node);
+	if let Some(hidden) = hidden {
+		let mut visible = !*hidden;
+		if ui.add_enabled(!locked, egui::Checkbox::new(&mut visible, "")).on_hover_text("Visible").changed() {
+			*hidden = !visible;
+		}
+	}
+	if ui.toggle_value(&mut locked, "🔒").on_hover_text("Locked").changed() {
+		match locked {
+			true => outliner.locked.insert(node),
+			false => outliner.locked.remove(&node),
+		};
+	}
+	if ui.selectable_label(outliner.selected == Some(node), name).clicked() {
+		outliner.selected = Some(node);
+	}
+}
+
+fn attachment_row(ui: &mut egui::Ui, outliner: &mut Outliner, index: usize, attachment: &mut Attachment) {
+	let name = attachment.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+	outliner_row(ui, outliner, SceneNode::Attachment(index), &name, Some(&mut attachment.hidden));
+}
+
+// Fields for the offset, rotation and scale of a transform
+fn display_transform_grid(ui: &mut egui::Ui, id: impl std::hash::Hash, transform: &mut DisplayTransform) {
+	egui::Grid::new(id).num_columns(4).show(ui, |ui| {
+		ui.label("Offset");
+		transform.offset.as_mut().iter_mut().for_each(|v| { ui.add(egui::DragValue::new(v).speed(0.25)); });
+		ui.end_row();
+		ui.label("Yaw, pitch, roll");
+		transform.rotation.as_mut().iter_mut().for_each(|v| {
+			ui.add(egui::DragValue::new(v).speed(1.).clamp_range(-180.0..=180.0).suffix("°"));
+		});
+		ui.end_row();
+		ui.label("Scale");
+		ui.add(egui::DragValue::new(&mut transform.scale).speed(0.01).clamp_range(0.01..=100.0));
+		ui.end_row();
+	});
+}
+
+// How far to push the vertices out for each time a model is drawn. In the
+// silhouette view mode, the outline is drawn first, and it's about the same
+// width on the screen wherever the model is.
+fn outline_passes(mode: ViewMode, view: &View, origin: Vec3, scale: f32) -> Vec<f32> {
+	if mode != ViewMode::Silhouette {
+		return vec![0.];
+	}
+	let pixel = 2. * (view.fov * 0.5).tan() * view.position.distance(origin) / view.size.y;
+	vec![OUTLINE_WIDTH * pixel / scale, 0.]
+}
+
+// Handles for moving a tag along its axes, and turning it around them
+fn tag_gizmo(ctx: &egui::Context, painter: &egui::Painter, index: usize, tag: &MD3FrameTag,
+	view_projection: Mat4, view_origin: Vec3, screen_size: Vec2) -> Option<TagEdit> {
+	const HANDLE_RADIUS: f32 = 6.;
+	let colours = [Color32::RED, Color32::GREEN, Color32::from_rgb(64, 128, 255)];
+	let length = view_origin.distance(tag.origin) * 0.15;
+	let project = |point: Vec3| gizmo::project(view_projection, point, screen_size);
+	let axes = [0, 1, 2].map(|axis| tag.axes.col(axis).normalize_or_zero());
+	let origin = project(tag.origin)?;
+	let tips = axes.map(|axis| project(tag.origin + axis * length));
+	let pos2 = |v: Vec2| Pos2::new(v.x, v.y);
+	let vec2 = |v: egui::Vec2| Vec2::new(v.x, v.y);
+	let handle = |id: (&str, usize), centre: Vec2, colour: Color32, round: bool| {
+		let corner = centre - Vec2::splat(HANDLE_RADIUS);
+		egui::Area::new(Id::new(id)).fixed_pos(pos2(corner)).order(Order::Foreground).show(ctx, |ui| {
+			let size = egui::Vec2::splat(HANDLE_RADIUS * 2.);
+			let (rect, response) = ui.allocate_exact_size(size, egui::Sense::drag());
+			let colour = if response.hovered() || response.dragged() { Color32::WHITE } else { colour };
+			if round {
+				ui.painter().circle_filled(rect.center(), HANDLE_RADIUS, colour);
+			} else {
+				ui.painter().rect_filled(rect.shrink(1.), 1., colour);
+			}
+			response
+		}).inner
+	};
+	let mut edit = None;
+	(0..3).for_each(|axis| {
+		let tip = match tips[axis] {
+			Some(t) => t,
+			None => return,
+		};
+		painter.line_segment([pos2(origin), pos2(tip)], egui::Stroke::new(2., colours[axis]));
+		let response = handle(("tag_gizmo_move", axis), tip, colours[axis], true);
+		if response.dragged() {
+			let distance = gizmo::axis_drag(origin, tip, vec2(response.drag_delta()));
+			edit = Some(TagEdit::MoveLocal(index, axis, distance * length));
+		}
+		// Turning around an axis moves the other two
+		let (j, k) = ((axis + 1) % 3, (axis + 2) % 3);
+		let (tip_j, tip_k) = match (tips[j], tips[k]) {
+			(Some(j), Some(k)) => (j, k),
+			_ => return,
+		};
+		let centre = origin + ((tip_j - origin) + (tip_k - origin)) * 0.5;
+		let response = handle(("tag_gizmo_turn", axis), centre, colours[axis], false);
+		if let (true, Some(to)) = (response.dragged(), response.interact_pointer_pos()) {
+			let to = Vec2::new(to.x, to.y);
+			let from = to - vec2(response.drag_delta());
+			edit = Some(TagEdit::TurnLocal(index, axis, gizmo::rotation_drag(origin, from, to, tip_j, tip_k)));
+		}
+	});
+	edit
+}
+
+// Lines showing the normal stored in the model, and the calculated normal
+const NORMAL_STORED_COLOUR: Vec3 = Vec3::new(0., 1., 1.);
+const NORMAL_SMOOTH_COLOUR: Vec3 = Vec3::new(1., 1., 0.);
+// Logical pixels
+const OUTLINE_WIDTH: f32 = 2.;
+// Quake coordinates are right-handed, but the camera matrices are left-handed
+const MD3_MODEL_SCALE: Vec3 = Vec3::new(1., -1., 1.);
+// Exporters may write the model in several steps
+const MODEL_RELOAD_DELAY: Duration = Duration::from_millis(250);
+// How often to look for changed files while nothing is happening
+const FILE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+const RENDER_JOB_INTERVAL: Duration = Duration::from_millis(10);
+// Seconds
+const CAMERA_TWEEN_DURATION: f32 = 0.25;
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+// Depth precision gets very bad with anything closer
+const MIN_NEAR_PLANE: f32 = 0.001;
+const CONTACT_SHEET_PREVIEW_SIZE: u32 = 128;
+const BATCH_THUMBNAIL_SIZE: u32 = 256;
+const MOUSE_FACTOR: f32 = 0.0078125; // 1./128
+// Touchpads scroll by pixels instead of wheel notches
+const PIXELS_PER_WHEEL_STEP: f32 = 40.;
+// Shortcuts which aren't in the keymap, so they can't be changed
+const FIXED_SHORTCUTS: &[(&str, &str)] = &[
+	("Double click", "Reset the camera"),
+	("Ctrl+C / Ctrl+V", "Copy or paste the view"),
+	("Tab / Shift+Tab", "Move focus between controls"),
+	("Enter / Space", "Press the focused control"),
+	("Arrow keys (with focus)", "Change the focused slider or value"),
+	("Escape", "Give focus back to the 3D view"),
+];
+const LOOK_LIMIT: f32 = {
+	use std::mem;
+	let v = unsafe{mem::transmute::<f32, u32>(FRAC_PI_2)};
+	// It's a pain in the butt having to generate this code... But it's all
+	// done at compile time, so there are no runtime costs.
+	/* 
+	Shell (zsh) code used to generate this mess:
+	(bits=32
+	for bit in {0..$((bits-1))}; do
+		print -v hxb -f "%0$((bits/4))X" $((1 << bit))
+		if ((bit > 0)); then
+			print -n "else "
+		fi
+		print "if v & $hxb != 0 { $hxb }"
+	done
+	print "else { 0 };")
+	 */
+	let lowest_bit = if v & 00000001 != 0 { 00000001 }
+	else if v & 00000002 != 0 { 00000002 }
+	else if v & 00000004 != 0 { 00000004 }
+	else if v & 00000008 != 0 { 00000008 }
+	else if v & 00000010 != 0 { 00000010 }
+	else if v & 00000020 != 0 { 00000020 }
+	else if v & 00000040 != 0 { 00000040 }
+	else if v & 00000080 != 0 { 00000080 }
+	else if v & 00000100 != 0 { 00000100 }
+	else if v & 00000200 != 0 { 00000200 }
+	else if v & 00000400 != 0 { 00000400 }
+	else if v & 00000800 != 0 { 00000800 }
+	else if v & 00001000 != 0 { 00001000 }
+	else if v & 00002000 != 0 { 00002000 }
+	else if v & 00004000 != 0 { 00004000 }
+	else if v & 00008000 != 0 { 00008000 }
+	else if v & 00010000 != 0 { 00010000 }
+	else if v & 00020000 != 0 { 00020000 }
+	else if v & 00040000 != 0 { 00040000 }
+	else if v & 00080000 != 0 { 00080000 }
+	else if v & 00100000 != 0 { 00100000 }
+	else if v & 00200000 != 0 { 00200000 }
+	else if v & 00400000 != 0 { 00400000 }
+	else if v & 00800000 != 0 { 00800000 }
+	else if v & 01000000 != 0 { 01000000 }
+	else if v & 02000000 != 0 { 02000000 }
+	else if v & 04000000 != 0 { 04000000 }
 	else if v & 08000000 != 0 { 08000000 }
 	else if v & 10000000 != 0 { 10000000 }
 	else if v & 20000000 != 0 { 20000000 }
@@ -226,377 +4134,3131 @@ const LOOK_LIMIT: f32 = {
 	unsafe{mem::transmute::<u32, f32>(v ^ lowest_bit)}
 };
 
-fn main() -> Result<(), AError> {
-	let app_res = AppResources::try_load(env::var("ASSETS_PATH").ok())
-		.context("Failed to load app resources!")?;
-	let el = EventLoopBuilder::new().build();
-	let (wc, glc) = window::create_window(&el, None);
-	let glc = Arc::new(glc);
-	let mut egui_glow = egui_glow::EguiGlow::new(&el, Arc::clone(&glc));
-	let mut app = App::new(&app_res, &glc);
-	let md3_shader = Rc::new({
-		let sdr = ShaderProgramBuilder::new()
-			.add_shader(ShaderStage::Vertex, &app_res.md3_vertex_shader)
-			.add_shader(ShaderStage::Fragment, &app_res.md3_pixel_shader)
-			.build(Arc::clone(&glc))?;
-		sdr
+// Print the differences between two models, for comparing exporters
+fn compare_command(a: &Path, b: &Path) -> Result<(), AError> {
+	let read = |path: &Path| File::open(path).map_err(AError::from)
+		.and_then(|mut f| md3::read_md3(&mut f).map_err(AError::from))
+		.with_context(|| format!("Could not read {}", path.display()));
+	let diff = compare::compare(&read(a)?, &read(b)?)?;
+	println!("{}", diff);
+	Ok(())
+}
+
+// The MD3 files in a folder and the folders in it
+fn find_models(directory: &Path) -> Result<Vec<PathBuf>, AError> {
+	let mut models = vec![];
+	for entry in fs::read_dir(directory)? {
+		let entry = entry?;
+		let path = entry.path();
+		// Symbolic links to folders aren't followed, in case they loop
+		if entry.file_type()?.is_dir() {
+			models.extend(find_models(&path)?);
+		} else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("md3")) {
+			models.push(path);
+		}
+	}
+	models.sort();
+	Ok(models)
+}
+
+// Print the structure of a model as JSON, for other tools
+fn dump_command(path: &Path, full: bool) -> Result<(), AError> {
+	let model = File::open(path).map_err(AError::from)
+		.and_then(|mut f| md3::read_md3(&mut f).map_err(AError::from))
+		.with_context(|| format!("Could not read {}", path.display()))?;
+	let stdout = std::io::stdout();
+	let mut writer = BufWriter::new(stdout.lock());
+	export::model_structure(&model, full, &mut writer)?;
+	writeln!(writer)?;
+	Ok(())
+}
+
+fn main() -> Result<(), AError> {
+	// rustmd3view compare a.md3 b.md3
+	let args: Vec<String> = env::args().skip(1).collect();
+	if let [command, a, b] = args.as_slice() {
+		if command == "compare" {
+			return compare_command(Path::new(a), Path::new(b));
+		}
+	}
+	// rustmd3view dump model.md3 [--full]
+	match args.as_slice() {
+		[command, path] if command == "dump" => return dump_command(Path::new(path), false),
+		[command, path, full] if command == "dump" && full == "--full" => return dump_command(Path::new(path), true),
+		_ => (),
+	}
+	// rustmd3view --check model.md3 folder ...
+	if let [flag, paths @ ..] = args.as_slice() {
+		if flag == "--check" {
+			let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+			let report = check::check(&paths, Settings::load().engine_profile);
+			println!("{}", serde_json::to_string_pretty(&report)?);
+			if !report.passed() {
+				std::process::exit(1);
+			}
+			return Ok(());
+		}
+	}
+	// rustmd3view --batch-thumbs folder [--output folder]
+	let batch_thumbs = match args.as_slice() {
+		[flag, directory] if flag == "--batch-thumbs" => Some((PathBuf::from(directory), None)),
+		[flag, directory, output_flag, output] if flag == "--batch-thumbs" && output_flag == "--output" =>
+			Some((PathBuf::from(directory), Some(PathBuf::from(output)))),
+		_ => None,
+	};
+	// Panics go to the browser's console
+	#[cfg(target_arch = "wasm32")]
+	console_error_panic_hook::set_once();
+	console::init();
+	let app_res = AppResources::try_load(env::var("ASSETS_PATH").ok())
+		.context("Failed to load app resources!")?;
+	let settings = Settings::load();
+	palette::set_user_palette(settings.palette.clone());
+	let el = EventLoopBuilder::new().build();
+	let (wc, glc) = window::create_window(&el, None, settings.frame_rate.vsync, batch_thumbs.is_none());
+	// egui_glow takes an Arc, even though WebGL contexts can't be sent to
+	// other threads
+	#[cfg_attr(target_arch = "wasm32", allow(clippy::arc_with_non_send_sync))]
+	let glc = Arc::new(glc);
+	let mut egui_glow = window::EguiGlow::new(&el, Arc::clone(&glc));
+	// Make the widget with keyboard focus stand out
+	egui_glow.egui_ctx.set_visuals({
+		let mut visuals = egui::Visuals::dark();
+		visuals.widgets.active.bg_stroke = egui::Stroke::new(2., visuals.selection.stroke.color);
+		visuals
+	});
+	let md3_shader = Rc::new({
+		let sdr = ShaderProgramBuilder::new()
+			.add_shader(ShaderStage::Vertex, &app_res.md3_vertex_shader)
+			.add_shader(ShaderStage::Fragment, &app_res.md3_pixel_shader)
+			.build(Arc::clone(&glc))?;
+		sdr
+	});
+	let mut app = App::new(&app_res, &glc, md3_shader, settings)?;
+	app.camera.aspect = {
+		let logical_size = wc.window().inner_size().to_logical::<f32>(wc.window().scale_factor());
+		logical_size.width / logical_size.height
+	};
+	let mut window_size = wc.window().inner_size().to_logical::<f32>(wc.window().scale_factor());
+	app.viewport = wc.window().inner_size().into();
+	let md3_model_scale = MD3_MODEL_SCALE;
+	let renderer = Renderer::new(Arc::clone(&glc), &RenderPass::ALL);
+	unsafe {
+		glc.clear_color(0., 0., 0., 1.);
+		match render::MAX_TEXTURE_UNITS.set(
+			Box::new(glc.get_parameter_i32(glow::MAX_TEXTURE_IMAGE_UNITS)
+				.try_into().unwrap_or(u8::MAX))
+		).map_err(|_| format!("Maximum number of texture units already set!")) {
+			Ok(_) => log::info!("Maximum texture units: {}",
+				render::MAX_TEXTURE_UNITS.get().copied().unwrap()),
+			Err(e) => log::warn!("{}", e),
+		}
+		log::info!("OpenGL version: {}", glc.get_parameter_string(glow::VERSION));
+		if render::buffer_textures() {
+			match render::MAX_TEXTURE_BUFFER_SIZE.set(
+				Box::new(glc.get_parameter_i32(glow::MAX_TEXTURE_BUFFER_SIZE).max(0) as usize)
+			).map_err(|_| "Maximum texture buffer size already set!") {
+				Ok(_) => log::info!("Maximum texture buffer size: {}",
+					render::MAX_TEXTURE_BUFFER_SIZE.get().copied().unwrap()),
+				Err(e) => log::warn!("{}", e),
+			}
+		} else {
+			log::info!("Buffer textures are unavailable, so vertex data is put in 2D textures");
+		}
+	}
+	// The site the viewer is embedded in says which model to show
+	#[cfg(target_arch = "wasm32")]
+	if let Some(path) = web::model_url() {
+		app.start_loading(&path, AfterLoad::Nothing);
+	}
+	if let Some((directory, output)) = batch_thumbs {
+		let (rendered, failed) = app.batch_thumbnails(&glc, &directory, output.as_deref())?;
+		println!("Rendered {} thumbnails, {} models could not be rendered", rendered, failed);
+		return Ok(());
+	}
+	// When egui asked to be drawn again
+	let mut repaint_at: Option<Instant> = None;
+	let mut frame_times = FrameTimes::default();
+	el.run(move |event, _window, control_flow| {
+		match event {
+			Event::WindowEvent { window_id: _, event } => {
+				use winit::event::{
+					WindowEvent::*,
+					MouseButton,
+					ElementState,
+				};
+				// Input may change the camera or the UI
+				wc.window().request_redraw();
+				// Keys being bound in the keyboard shortcuts window aren't
+				// given to egui
+				if let (KeyboardInput { input, .. }, true) = (&event, app.capturing_shortcut.is_some()) {
+					if let (ElementState::Pressed, Some(key)) = (input.state, input.virtual_keycode) {
+						if !Chord::is_modifier(key) {
+							app.bind_key(key);
+						}
+					}
+					return;
+				}
+				if egui_glow.on_event(&event) {
+					return ();
+				}
+				match event {
+					CloseRequested if app.can_quit() => {
+						*control_flow = ControlFlow::ExitWithCode(0);
+					},
+					Resized(new_size) => {
+						wc.resize(new_size);
+						window_size = new_size.to_logical::<f32>(wc.window().scale_factor());
+						app.viewport = new_size.into();
+						app.camera.aspect = window_size.width / window_size.height;
+						app.debug_camera.aspect = app.camera.aspect;
+					},
+					// The window was moved to a monitor with a different DPI
+					ScaleFactorChanged { scale_factor, new_inner_size } => {
+						wc.resize(*new_inner_size);
+						window_size = new_inner_size.to_logical::<f32>(scale_factor);
+						app.viewport = (*new_inner_size).into();
+						app.camera.aspect = window_size.width / window_size.height;
+						app.debug_camera.aspect = app.camera.aspect;
+					},
+					MouseInput {state, button, .. } => {
+						// egui may have used the cursor since it was last moved
+						if state == ElementState::Pressed {
+							app.controls.cursor = None;
+						}
+						match button {
+							MouseButton::Left => {
+							if state == ElementState::Pressed {
+								let now = Instant::now();
+								match app.controls.last_click.take() {
+									Some(last) if now - last < DOUBLE_CLICK_TIME => app.reset_camera(),
+									_ => app.controls.last_click = Some(now),
+								}
+							}
+							app.controls.lmb_dragging = match state {
+								ElementState::Pressed => true,
+								ElementState::Released => false,
+							};
+							},
+							MouseButton::Right => {
+							app.controls.rmb_dragging = match state {
+								ElementState::Pressed => true,
+								ElementState::Released => false,
+							};
+							},
+							MouseButton::Middle => {
+							app.controls.mmb_dragging = match state {
+								ElementState::Pressed => true,
+								ElementState::Released => false,
+							};
+							},
+							_ => (),
+						}
+					},
+					CursorLeft{..} => {
+						app.controls.lmb_dragging = false;
+						app.controls.rmb_dragging = false;
+						app.controls.mmb_dragging = false;
+						app.controls.cursor = None;
+					},
+					CursorMoved { position, .. } => {
+						let position = Vec2::new(position.x as f32, position.y as f32);
+						let last = app.controls.cursor.replace(position);
+						if let (true, Some(last)) = (app.settings.pointer_acceleration, last) {
+							let scale = wc.window().scale_factor() as f32;
+							app.mouse_dragged((position - last) / scale, window_size.height);
+						}
+					},
+					Focused(focused) => {
+						app.controls.focused = focused;
+						if !focused {
+							app.controls.lmb_dragging = false;
+							app.controls.rmb_dragging = false;
+							app.controls.mmb_dragging = false;
+						}
+					},
+					MouseWheel { delta, .. } => {
+						use winit::event::MouseScrollDelta;
+						let steps = match delta {
+							MouseScrollDelta::LineDelta(_, y) => y,
+							MouseScrollDelta::PixelDelta(pos) => (pos.y / wc.window().scale_factor()) as f32 / PIXELS_PER_WHEEL_STEP,
+						};
+						app.zoom(steps);
+					},
+					Touch(touch) => app.touch(touch),
+					ModifiersChanged(modifiers) => {
+						app.controls.modifiers = modifiers;
+					},
+					KeyboardInput { input, .. } => {
+						if let (ElementState::Pressed, Some(key)) = (input.state, input.virtual_keycode) {
+							match app.key_pressed(key) {
+								Some(Action::Quit) if app.can_quit() => *control_flow = ControlFlow::ExitWithCode(0),
+								Some(Action::Quit) => (),
+								Some(action) => app.run_action(action),
+								None => (),
+							}
+						}
+					},
+					_ => (),
+				}
+			},
+			// Raw mouse motion is only used by the focused window
+			Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta: (dx, dy) }, .. }
+				if !app.settings.pointer_acceleration && app.controls.focused => {
+				let controls = app.controls;
+				if controls.lmb_dragging || controls.rmb_dragging || controls.mmb_dragging {
+					wc.window().request_redraw();
+				}
+				let scale = wc.window().scale_factor();
+				app.mouse_dragged(Vec2::new((dx / scale) as f32, (dy / scale) as f32),
+					window_size.height);
+			}
+			Event::MainEventsCleared => {
+// RELOAD CHANGED TEXTURES AND MODEL
+// ==================================================================
+let was_animating = app.animating();
+let reloaded = app.texture_cache.reload_changed();
+reloaded.iter().filter_map(|r| r.as_ref().err())
+	.for_each(|e| log::error!("{}", e));
+app.texture_cache.evict(app.settings.texture_budget.bytes());
+app.reload_model_if_changed();
+let shaders_reloaded = app.reload_shaders_if_changed(&glc);
+app.poll_loading(&glc);
+app.upload_textures(&glc);
+let rendering = app.render_job.is_some();
+app.step_render_job(&glc);
+let logged = console::changed() && app.log_open;
+// Only draw when something has changed
+let now = Instant::now();
+if was_animating || app.animating() || !reloaded.is_empty() || logged || shaders_reloaded || rendering
+	|| repaint_at.is_some_and(|time| time <= now) {
+	wc.window().request_redraw();
+}
+// Wake up now and then to check for changed files, or when egui wants to
+// be drawn again. Frames are rendered offscreen more often.
+if !matches!(*control_flow, ControlFlow::ExitWithCode(_)) {
+	let wake = now + if app.render_job.is_some() { RENDER_JOB_INTERVAL } else { FILE_CHECK_INTERVAL };
+	*control_flow = ControlFlow::WaitUntil(repaint_at.map_or(wake, |time| time.min(wake)));
+}
+			},
+			Event::RedrawRequested(_) => {
+// Draw the frame later if it's too soon after the last one
+let frame_start = Instant::now();
+if let Some(next) = frame_times.next_frame(app.settings.frame_rate.limit) {
+	if next > frame_start {
+		repaint_at = Some(next);
+		if !matches!(*control_flow, ControlFlow::ExitWithCode(_)) {
+			*control_flow = ControlFlow::WaitUntil(next);
+		}
+		return;
+	}
+}
+// DRAW SCENE
+// ==================================================================
+app.clock = frame_start;
+app.update_camera_tween();
+app.update_turntable();
+let camera = app.view_camera();
+// The frame being scrubbed is only shown, so playback carries on from where
+// it was
+let current_frame = app.current_frame;
+if let Some(frame) = app.scrub_frame {
+	app.current_frame = frame;
+}
+// egui leaves its own viewport set, which is out of date right after the
+// window is resized or moved to a screen with a different scale
+unsafe {
+	glc.viewport(0, 0, app.viewport.0 as i32, app.viewport.1 as i32);
+}
+renderer.render(&mut app, &View::orbit(&camera, Vec2::new(window_size.width, window_size.height)));
+app.current_frame = current_frame;
+
+// DRAW EGUI
+// ==================================================================
+// Setting the scale on the context would only take effect on the next frame,
+// so the tag labels would be put in the wrong place for a frame
+egui_glow.egui_winit.set_pixels_per_point(wc.window().scale_factor() as f32 * app.settings.ui_scale.unwrap_or(1.));
+let repaint_after = egui_glow.run(wc.window(), |ctx| {
+	// Ctrl+C and Ctrl+V copy and paste the view, unless a text field has focus
+	if !ctx.wants_keyboard_input() {
+		let events = ctx.input().events.clone();
+		events.iter().for_each(|event| match event {
+			egui::Event::Copy => ctx.output().copied_text = app.view_state().to_string(),
+			egui::Event::Paste(text) => if let Err(e) = app.paste_view(text) {
+				app.log_error(&format!("Could not paste the view: {}", e));
+			},
+			_ => (),
+		});
+	}
+	egui::TopBottomPanel::top("menu_bar").show(&ctx, |ui| {
+		egui::menu::bar(ui, |ui| {
+			ui.menu_button("File", |ui| {
+				if ui.button("Open").clicked() {
+					app.open_file_dialog.open();
+					ui.close_menu();
+				}
+				if ui.button("Open project").clicked() {
+					app.open_project_dialog();
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Save project")).clicked() {
+					app.save_project_dialog();
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Save model as")).clicked() {
+					let mut dialog = FileDialog::save_file(app.model_path.clone())
+						.filter(String::from("md3"));
+					dialog.open();
+					app.file_dialog = Some((DialogPurpose::SaveModel, dialog));
+					ui.close_menu();
+				}
+				if ui.button("Save screenshot")
+					.on_hover_text(app.settings.keymap.describe(Action::Screenshot)).clicked() {
+					app.screenshot_dialog();
+					ui.close_menu();
+				}
+				if ui.checkbox(&mut app.settings.transparent_screenshots, "Transparent screenshots")
+					.on_hover_text("Leave the background of screenshots and camera path images transparent").clicked() {
+					app.save_settings();
+				}
+				if ui.checkbox(&mut app.settings.tolerant_parsing, "Tolerant parsing")
+					.on_hover_text("Load what can be read from broken models, instead of refusing them")
+					.clicked() {
+					app.save_settings();
+					ui.close_menu();
+				}
+				ui.separator();
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Export UV layout")).clicked() {
+					app.uv_export.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Export vertex data")).clicked() {
+					app.vertex_export.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Export for 3D printing")).clicked() {
+					app.print_export.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Frame bounds")).clicked() {
+					app.frame_bounds_open = true;
+					ui.close_menu();
+				}
+				ui.add_enabled_ui(app.model_data.is_some(), |ui| {
+					ui.menu_button("Export structure as JSON", |ui| {
+						let export = [("Without geometry", false), ("With triangles and vertices", true)].into_iter()
+							.find(|(label, _)| ui.button(*label).clicked());
+						if let Some((_, full)) = export {
+							let mut dialog = FileDialog::save_file(None)
+								.filter(String::from("json"));
+							dialog.open();
+							app.file_dialog = Some((DialogPurpose::ExportStructure { full }, dialog));
+							ui.close_menu();
+						}
+					});
+				});
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Compare with...")).clicked() {
+					let mut dialog = FileDialog::open_file(app.model_path.clone())
+						.show_rename(false)
+						.show_new_folder(false)
+						.filter(String::from("md3"));
+					dialog.open();
+					app.file_dialog = Some((DialogPurpose::CompareModel, dialog));
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Render camera path")).clicked() {
+					app.path_render.open = true;
+					ui.close_menu();
+				}
+				// Browsers can't start threads to encode them
+				#[cfg(not(target_arch = "wasm32"))]
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Export video")).clicked() {
+					app.video_export.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Export sprites")).clicked() {
+					app.sprite_export.open = true;
+					ui.close_menu();
+				}
+				if ui.button("Make contact sheet of folder").clicked() {
+					let mut dialog = FileDialog::select_folder(None);
+					dialog.open();
+					app.file_dialog = Some((DialogPurpose::ContactSheet, dialog));
+					ui.close_menu();
+				}
+				ui.menu_button("Backups", |ui| {
+					let backups = &mut app.settings.backups;
+					let mut save = ui.checkbox(&mut backups.enabled, "Back up models before saving over them")
+						.changed();
+					ui.add_enabled_ui(backups.enabled, |ui| {
+						save |= ui.checkbox(&mut backups.in_folder, "Put backups in a \"backups\" folder")
+							.changed();
+						ui.horizontal(|ui| {
+							ui.label("Backups to keep (0 for all)");
+							let response = ui.add(egui::DragValue::new(&mut backups.keep)
+								.clamp_range(0..=1000));
+							save |= response.drag_released() || response.changed() && !response.dragged();
+						});
+					});
+					if save {
+						app.save_settings();
+					}
+				});
+				ui.separator();
+				if ui.button("Quit").clicked() {
+					ui.close_menu();
+					if app.can_quit() {
+						*control_flow = ControlFlow::ExitWithCode(0);
+					}
+				}
+			});
+			ui.menu_button("Edit", |ui| {
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Replace shader paths")).clicked() {
+					app.shader_replace.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Optimize mesh")).clicked() {
+					app.optimize.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Merge surfaces")).clicked() {
+					app.merge_open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Split surface")).clicked() {
+					app.surface_split.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Levels of detail")).clicked() {
+					app.lod.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Transform model")).clicked() {
+					app.transform.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Edit frames")).clicked() {
+					app.frame_editor.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Edit tags")).clicked() {
+					app.tag_editor.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Recalculate normals")).clicked() {
+					app.normal_recalc.open = true;
+					app.normal_recalc.gzdoom = app.controls.gzdoom_normals;
+					ui.close_menu();
+				}
+			});
+			ui.menu_button("View", |ui| {
+				if ui.radio_value(&mut app.controls.view_mode,
+					ViewMode::Textured, "Textured").clicked() ||
+					ui.radio_value(&mut app.controls.view_mode,
+						ViewMode::Untextured, "Untextured").clicked() ||
+					ui.radio_value(&mut app.controls.view_mode,
+						ViewMode::Normals, "Normals").clicked() ||
+					ui.radio_value(&mut app.controls.view_mode,
+						ViewMode::Lit, "Lit").clicked() ||
+					ui.add_enabled_ui(app.comparison.is_some(), |ui| ui.radio_value(
+						&mut app.controls.view_mode, ViewMode::Deviation, "Deviation")).inner.clicked() ||
+					ui.radio_value(&mut app.controls.view_mode,
+						ViewMode::NormalCheck, "Normal check").clicked() ||
+					ui.radio_value(&mut app.controls.view_mode,
+						ViewMode::UvChecker, "UV checker").clicked() ||
+					ui.radio_value(&mut app.controls.view_mode,
+						ViewMode::Backfaces, "Back faces").clicked() ||
+					ui.radio_value(&mut app.controls.view_mode,
+						ViewMode::Silhouette, "Silhouette").clicked()
+				{ ui.close_menu(); }
+				if app.controls.view_mode == ViewMode::UvChecker {
+					ui.add(egui::Slider::new(&mut app.uv_checker.cells, 1..=CHECKER_MAX_CELLS)
+						.text("Checker squares"));
+				}
+				ui.checkbox(&mut app.turntable.enabled, "Auto-rotate")
+					.on_hover_text("Spin the camera around the model, except while it's being dragged");
+				if app.turntable.enabled {
+					ui.add(egui::Slider::new(&mut app.turntable.speed, -90.0..=90.0)
+						.text("Degrees per second"));
+				}
+				if ui.checkbox(&mut app.controls.gzdoom_normals, "GZDoom normals").clicked() { ui.close_menu(); }
+				if ui.checkbox(&mut app.controls.tag_mode, "Tag mode").clicked() { ui.close_menu(); }
+				if ui.checkbox(&mut app.frames_open, "Frames").clicked() { ui.close_menu(); }
+				if ui.checkbox(&mut app.settings.show_hud, "Model info over the view")
+					.on_hover_text(app.settings.keymap.describe(Action::ToggleHud)).clicked() {
+					app.save_settings();
+					ui.close_menu();
+				}
+				if ui.checkbox(&mut app.active_camera_mut().orthographic, "Orthographic").clicked() { ui.close_menu(); }
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Frame model")).clicked() {
+					app.frame_model();
+					ui.close_menu();
+				}
+				if ui.button("Copy view").on_hover_text(
+					"Copy the camera, frame and view mode, to look at the same thing in another viewer").clicked() {
+					ui.output().copied_text = app.view_state().to_string();
+					ui.close_menu();
+				}
+				if ui.button("Paste view...").clicked() {
+					app.pasted_view = Some(String::new());
+					ui.close_menu();
+				}
+				let tag_names: Vec<String> = app.model_data.as_ref()
+					.map(|m| m.tag_names().map(String::from).collect()).unwrap_or_default();
+				ui.add_enabled_ui(!tag_names.is_empty(), |ui| {
+					ui.menu_button("Focus on tag", |ui| {
+						tag_names.iter().for_each(|name| {
+							if ui.button(name).clicked() {
+								app.focus_tag(name);
+								ui.close_menu();
+							}
+						});
+					});
+				});
+				ui.separator();
+				if ui.button("Load skybox").clicked() {
+					let mut dialog = FileDialog::open_file(None)
+						.show_rename(false)
+						.show_new_folder(false);
+					dialog.open();
+					app.file_dialog = Some((DialogPurpose::LoadSkybox, dialog));
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.skybox.is_some(), egui::Checkbox::new(
+					&mut app.controls.show_skybox, "Show skybox")).clicked() { ui.close_menu(); }
+				if ui.checkbox(&mut app.controls.sky_reflection, "Reflect skybox").clicked() { ui.close_menu(); }
+				if ui.checkbox(&mut app.settings.nearest_filtering, "Nearest-neighbour skin filtering").clicked() {
+					app.texture_cache.set_nearest(app.settings.nearest_filtering);
+					app.fullbright_masks.values().flatten()
+						.for_each(|mask| mask.set_nearest(app.settings.nearest_filtering));
+					app.save_settings();
+					ui.close_menu();
+				}
+				ui.add_enabled_ui(app.model_data.is_some(), |ui| {
+					ui.menu_button("Fragment shader", |ui| {
+						let path = app.fragment_shader_path();
+						ui.label(match path.as_ref() {
+							Some(path) => format!("Using {}", path.display()),
+							None => String::from("Using the built in shader"),
+						});
+						if ui.button("Choose...").clicked() {
+							let mut dialog = FileDialog::open_file(path.clone())
+								.show_rename(false)
+								.show_new_folder(false)
+								.filter(String::from("frag"));
+							dialog.open();
+							app.file_dialog = Some((DialogPurpose::FragmentShader, dialog));
+							ui.close_menu();
+						}
+						if ui.add_enabled(path.is_some(), egui::Button::new("Reload")).clicked() {
+							app.update_fragment_shader(&glc);
+							ui.close_menu();
+						}
+						if ui.add_enabled(path.is_some(), egui::Button::new("Use built in shader")).clicked() {
+							app.set_fragment_shader(&glc, None);
+							ui.close_menu();
+						}
+					}).response.on_hover_text("A fragment shader for this model, with the same uniforms and inputs as md3.frag");
+				});
+				ui.menu_button("Palette", |ui| {
+					let path = app.settings.palette.clone();
+					ui.label(match path.as_ref() {
+						Some(path) => format!("Using {}", path.display()),
+						None => String::from("Using the game's palette, found near each texture"),
+					});
+					if ui.button("Choose...").clicked() {
+						let mut dialog = FileDialog::open_file(path.clone())
+							.show_rename(false)
+							.show_new_folder(false);
+						dialog.open();
+						app.file_dialog = Some((DialogPurpose::Palette, dialog));
+						ui.close_menu();
+					}
+					if ui.add_enabled(path.is_some(), egui::Button::new("Find automatically")).clicked() {
+						app.set_palette(&glc, None);
+						ui.close_menu();
+					}
+					ui.checkbox(&mut app.controls.fullbright, "Fullbright texels")
+						.on_hover_text(format!("Texels using colours {} to {} of the palette aren't affected by lighting, like in Quake",
+							palette::FULLBRIGHT.start(), palette::FULLBRIGHT.end()));
+				}).response.on_hover_text("The palette for PCX, WAL and LMP textures without their own, e.g. \
+					pics/colormap.pcx, gfx/palette.lmp or PLAYPAL");
+				ui.separator();
+				if ui.checkbox(&mut app.controls.debug_camera, "Debug camera").clicked() {
+					if app.controls.debug_camera {
+						// Start out behind the main camera, far enough away to see
+						// its whole frustum
+						app.debug_camera = OrbitCamera {
+							distance: app.camera.distance.max(1.) * 4.,
+							far: (app.camera.far + app.camera.distance) * 4.,
+							..app.camera
+						};
+					}
+					ui.close_menu();
+				}
+				if ui.button("View settings").clicked() {
+					app.view_settings_open = true;
+					ui.close_menu();
+				}
+				if ui.button("Statistics").clicked() {
+					app.statistics_open = true;
+					ui.close_menu();
+				}
+				if ui.button("Log").clicked() {
+					app.log_open = true;
+					ui.close_menu();
+				}
+				if ui.button("Entity light").clicked() {
+					app.entity_light.open = true;
+					ui.close_menu();
+				}
+				if ui.button("Display transform").clicked() {
+					app.display_open = true;
+					ui.close_menu();
+				}
+				if ui.button("Outliner").clicked() {
+					app.outliner.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Normal inspector")).clicked() {
+					app.normal_inspector.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Motion trail")).clicked() {
+					app.motion_trail.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_path.is_some(),
+					egui::Button::new("Notes")).clicked() {
+					app.notes_window.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_data.is_some(),
+					egui::Button::new("Voxelize (experimental)")).clicked() {
+					app.voxels.open = true;
+					ui.close_menu();
+				}
+				if ui.add_enabled(app.model_path.is_some(),
+					egui::Button::new("MD3 internals")).clicked() {
+					app.open_internals();
+					ui.close_menu();
+				}
+				ui.horizontal(|ui| {
+					ui.label("UI scale");
+					let mut scale = app.settings.ui_scale.unwrap_or(1.);
+					let response = ui.add(egui::Slider::new(&mut scale, 0.5..=4.0)
+						.logarithmic(true).fixed_decimals(2).suffix("×"));
+					if response.changed() {
+						app.settings.ui_scale = Some(scale);
+					}
+					let reset = ui.add_enabled(app.settings.ui_scale.is_some(),
+						egui::Button::new("Reset")).clicked();
+					if reset {
+						app.settings.ui_scale = None;
+					}
+					// Don't save the settings on every frame while dragging
+					if reset || response.drag_released() || response.changed() && !response.dragged() {
+						app.save_settings();
+					}
+				});
+				ui.menu_button("Sensitivity", |ui| {
+					let sensitivity = &mut app.settings.sensitivity;
+					let mut save = false;
+					egui::Grid::new("sensitivity").num_columns(2).show(ui, |ui| {
+						[("Mouse", &mut sensitivity.mouse, 0.1..=10.0),
+						("Zoom step", &mut sensitivity.zoom, 0.01..=0.5),
+						("Orbit step (degrees)", &mut sensitivity.orbit, 0.5..=45.0)]
+						.into_iter().for_each(|(label, value, range)| {
+							ui.label(label);
+							let response = ui.add(egui::DragValue::new(value)
+								.speed(0.01).clamp_range(range));
+							save |= response.drag_released() || response.changed() && !response.dragged();
+							ui.end_row();
+						});
+					});
+					if ui.button("Reset").clicked() {
+						*sensitivity = Default::default();
+						save = true;
+					}
+					save |= ui.checkbox(&mut app.settings.pointer_acceleration, "Pointer acceleration")
+						.on_hover_text("Use the system's pointer movement instead of raw mouse movement")
+						.changed();
+					if save {
+						app.save_settings();
+					}
+				});
+				ui.menu_button("Frame rate", |ui| {
+					let frame_rate = &mut app.settings.frame_rate;
+					let mut save = ui.checkbox(&mut frame_rate.vsync, "Vsync")
+						.on_hover_text("Takes effect after restarting")
+						.changed();
+					ui.horizontal(|ui| {
+						let mut limited = frame_rate.limit.is_some();
+						if ui.checkbox(&mut limited, "Limit to").changed() {
+							frame_rate.limit = limited.then_some(60);
+							save = true;
+						}
+						let mut limit = frame_rate.limit.unwrap_or(60);
+						let response = ui.add_enabled(limited, egui::DragValue::new(&mut limit)
+							.clamp_range(1..=1000).suffix(" FPS"));
+						if limited {
+							frame_rate.limit = Some(limit);
+						}
+						save |= response.drag_released() || response.changed() && !response.dragged();
+					});
+					save |= ui.checkbox(&mut frame_rate.show_frame_times, "Show frame times").changed();
+					if save {
+						app.save_settings();
+					}
+				});
+				ui.menu_button("Units", |ui| {
+					let units = app.settings.units;
+					let number_format = app.settings.number_format;
+					ui.radio_value(&mut app.settings.units, UnitSystem::Units, "Map units");
+					ui.radio_value(&mut app.settings.units, UnitSystem::Metric, "Metric");
+					ui.radio_value(&mut app.settings.units, UnitSystem::Imperial, "Imperial");
+					ui.separator();
+					ui.radio_value(&mut app.settings.number_format, None, "System number format");
+					[NumberFormat::ENGLISH, NumberFormat::GERMAN, NumberFormat::FRENCH,
+					NumberFormat::PLAIN].into_iter().for_each(|format| {
+						ui.radio_value(&mut app.settings.number_format, Some(format),
+							format.format(1234.5, 1));
+					});
+					if app.settings.units != units || app.settings.number_format != number_format {
+						app.save_settings();
+					}
+				});
+			});
+			ui.menu_button("Help", |ui| {
+				if ui.button("Keyboard shortcuts").clicked() {
+					app.shortcuts_open = true;
+					ui.close_menu();
+				}
+			});
+			if let Some((loading, _)) = app.loading.as_ref() {
+				ui.separator();
+				ui.add(egui::Spinner::new());
+				let name = loading.path.file_name().unwrap_or(loading.path.as_os_str());
+				ui.label(format!("Loading {}", name.to_string_lossy()));
+			} else if app.texture_cache.is_streaming() {
+				ui.separator();
+				ui.add(egui::Spinner::new());
+				ui.label(format!("Loading textures ({} left)", app.texture_cache.pending.len()));
+			}
+		});
+	});
+	egui::TopBottomPanel::bottom("frame_bar").show(&ctx, |ui| {
+		let play_button_text = match app.anim_playing {
+			true => "⏸",
+			false => "▶",
+		};
+		// let time = (Instant::now() - app_start).as_secs_f32();
+		if let Some(range) = app.frame_range.clone() {
+			ui.horizontal(|ui| {
+				let keys = |action| app.settings.keymap.describe(action);
+				let first = ui.button("|<").on_hover_text(format!("First frame ({})", keys(Action::FirstFrame))).clicked();
+				let previous = ui.button("<").on_hover_text(format!("Previous frame ({})", keys(Action::PreviousFrame))).clicked();
+				let play = ui.button(play_button_text).on_hover_text(format!("Play/pause ({})", keys(Action::PlayPause))).clicked();
+				let next = ui.button(">").on_hover_text(format!("Next frame ({})", keys(Action::NextFrame))).clicked();
+				let last = ui.button(">|").on_hover_text(format!("Last frame ({})", keys(Action::LastFrame))).clicked();
+				match (first, previous, play, next, last) {
+					(true, ..) => app.seek_end(false),
+					(_, true, ..) => app.step_frame(false),
+					(_, _, true, ..) => app.toggle_playback(),
+					(.., true, _) => app.step_frame(true),
+					(.., true) => app.seek_end(true),
+					_ => (),
+				}
+				app.update_playback();
+				ui.spacing_mut().slider_width = 400.;
+				let slider = ui.add(egui::Slider::new(&mut app.current_frame, range.clone()));
+				let mut speed = app.anim_speed;
+				let response = ui.scope(|ui| {
+					ui.spacing_mut().slider_width = 100.;
+					ui.add(egui::Slider::new(&mut speed, 0.1..=4.0)
+						.logarithmic(true).max_decimals(2).suffix("×"))
+				}).inner.on_hover_text("Playback speed. Double click for normal speed.");
+				if response.double_clicked() {
+					speed = 1.;
+				}
+				if speed != app.anim_speed {
+					app.set_playback_speed(speed);
+				}
+				// Marker ticks, placed the same way as the slider's handle
+				let rect = slider.rect;
+				let handle_radius = rect.height() / 2.5;
+				let rail = (rect.left() + handle_radius)..=(rect.left() + 400. - handle_radius);
+				app.markers.iter().enumerate().for_each(|(index, marker)| {
+					let f = (marker.frame as f32 - range.start()) / (range.end() - range.start());
+					let x = egui::lerp(rail.clone(), f.clamp(0., 1.));
+					let tick = egui::Rect::from_x_y_ranges(x - 2.0..=x + 2.0, rect.y_range());
+					ui.painter().vline(x, rect.y_range(), egui::Stroke::new(2., Color32::GOLD));
+					ui.interact(tick, ui.id().with(("marker", index)), egui::Sense::hover())
+						.on_hover_text(format!("{}: {}", marker.frame, marker.name));
+				});
+				// Show the frame under the pointer while Shift is held,
+				// without going to it
+				let pointer = ui.input().pointer.hover_pos();
+				let shift = ui.input().modifiers.shift;
+				let slider_rect = egui::Rect::from_x_y_ranges(rect.left()..=rect.left() + 400., rect.y_range());
+				app.scrub_frame = pointer.filter(|pos| shift && slider_rect.contains(*pos)).map(|pos| {
+					let f = egui::remap_clamp(pos.x, rail.clone(), 0.0..=1.0);
+					egui::lerp(range.clone(), f).round()
+				});
+				if let Some(frame) = app.scrub_frame {
+					egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("scrub_frame"), |ui| {
+						ui.label(format!("Frame {}", frame));
+					});
+				}
+				let mut remove = None;
+				let mut add = None;
+				let mut seek = None;
+				ui.menu_button("Markers", |ui| {
+					app.markers.iter().enumerate().for_each(|(index, marker)| {
+						ui.horizontal(|ui| {
+							if ui.button(format!("{}: {}", marker.frame, marker.name)).clicked() {
+								seek = Some(marker.frame);
+							}
+							if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+								remove = Some(index);
+							}
+						});
+					});
+					ui.separator();
+					ui.horizontal(|ui| {
+						ui.text_edit_singleline(&mut app.new_marker_name);
+						let frame = app.current_frame.round() as usize;
+						if ui.add_enabled(!app.new_marker_name.trim().is_empty(),
+							egui::Button::new(format!("Add at frame {}", frame))).clicked() {
+							add = Some((frame, app.new_marker_name.trim().to_string()));
+						}
+					});
+				});
+				if let Some(frame) = seek {
+					app.seek_frame(frame as f32);
+				}
+				if let Some(index) = remove {
+					app.remove_marker(index);
+				}
+				if let Some((frame, name)) = add {
+					app.add_marker(frame, name);
+				}
+				if let Some(section) = app.play_section.clone() {
+					ui.label(format!("Looping frames {} to {}", section.start(), section.end()));
+					if ui.button("Play all").clicked() {
+						app.play_section = None;
+					}
+				}
+			});
+		}
+	});
+	if let Some(model) = app.model_data.as_ref().filter(|_| app.frames_open) {
+		let mut seek = None;
+		let mut play = None;
+		let mut bookmark = None;
+		let current = app.current_frame.floor() as usize;
+		egui::SidePanel::left("frames").show(ctx, |ui| {
+			ui.heading("Frames");
+			egui::ScrollArea::vertical().show(ui, |ui| {
+				let mut frame_row = |ui: &mut egui::Ui, frame: usize| {
+					ui.horizontal(|ui| {
+						let bookmarked = app.bookmarks.contains(&frame);
+						if ui.selectable_label(bookmarked, if bookmarked { "★" } else { "☆" })
+							.on_hover_text("Bookmark").clicked() {
+							bookmark = Some(frame);
+						}
+						let name = model.frames[frame].name();
+						if ui.selectable_label(frame == current, format!("{}: {}", frame, name)).clicked() {
+							seek = Some(frame);
+						}
+					});
+				};
+				if !app.bookmarks.is_empty() {
+					egui::CollapsingHeader::new("Bookmarks").default_open(true).show(ui, |ui| {
+						app.bookmarks.iter().for_each(|&frame| frame_row(ui, frame));
+					});
+				}
+				model.frame_sections().into_iter().for_each(|section| {
+					let (start, end) = (*section.frames.start(), *section.frames.end());
+					if start == end {
+						frame_row(ui, start);
+						return;
+					}
+					egui::CollapsingHeader::new(format!("{} ({}-{})", section.name, start, end))
+						.id_source(("frame_section", start))
+						.show(ui, |ui| {
+							if ui.button("▶ Play section").clicked() {
+								play = Some(section.frames.clone());
+							}
+							section.frames.for_each(|frame| frame_row(ui, frame));
+						});
+				});
+			});
+		});
+		if let Some(frame) = seek {
+			app.seek_frame(frame as f32);
+		}
+		if let Some(frames) = play {
+			app.play_section(frames);
+		}
+		if let Some(frame) = bookmark {
+			app.toggle_bookmark(frame);
+		}
+	}
+	egui::SidePanel::right("infoz").show(ctx, |ui| {
+		ui.heading("Shaders");
+		let mut shader_change = None;
+		let mut map_change = None;
+		let mut texture_change = None;
+		let mut focus = None;
+		let mut rename = None;
+		let mut cancel_rename = false;
+		if let Some(model) = app.model_data.as_ref() {
+			// Names in the model can be edited one at a time
+			let mut name_field = |ui: &mut egui::Ui, target: NameTarget, name: &[u8]| {
+				let name = String::from_utf8_stop(name);
+				match app.name_edit.as_mut().filter(|e| e.target == target) {
+					Some(edit) => {
+						let response = ui.text_edit_singleline(&mut edit.text);
+						// The last byte is the terminating 0
+						let fits = edit.text.len() < 64;
+						ui.horizontal(|ui| {
+							let colour = if fits { ui.visuals().text_color() } else { Color32::RED };
+							ui.colored_label(colour, format!("{}/63 bytes", edit.text.len()));
+							let enter = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+							if ui.add_enabled(fits, egui::Button::new("Apply")).clicked() || enter && fits {
+								rename = Some((target, edit.text.clone()));
+							}
+							if ui.button("Cancel").clicked() {
+								cancel_rename = true;
+							}
+						});
+					},
+					None => {
+						ui.horizontal(|ui| {
+							ui.label(name.as_ref());
+							if ui.small_button("✏").on_hover_text("Edit").clicked() {
+								app.name_edit = Some(NameEdit { target, text: name.to_string() });
+							}
+						});
+					},
+				}
+			};
+			model.surfaces.iter().enumerate().for_each(|(index, surf)| {
+				egui::CollapsingHeader::new(format!("Surface {}: {}", index, String::from_utf8_stop(&surf.name)))
+					.id_source(("surface", index)).show(ui, |ui| {
+					ui.set_enabled(!app.outliner.is_locked(SceneNode::Surface(index)));
+					ui.label("Name");
+					name_field(ui, NameTarget::Surface(index), &surf.name);
+					ui.label("Shaders");
+					// Some exporters put skin variants in the other shaders
+					surf.shaders.iter().enumerate().for_each(|(shader, sdr)| {
+						ui.horizontal(|ui| {
+							let used = app.surface_settings[index].shader == shader;
+							if ui.radio(used, format!("Shader {}", shader))
+								.on_hover_text("Use this shader's texture").clicked() && !used {
+								shader_change = Some((index, shader));
+							}
+							ui.weak(format!("index {}", sdr.index));
+						});
+						name_field(ui, NameTarget::Shader(index, shader), &sdr.name);
+					});
+					ui.horizontal(|ui| {
+						ui.label("Texture");
+						if ui.button("Browse...").clicked() {
+							texture_change = Some((index, true));
+						}
+						if ui.add_enabled(app.surface_settings[index].texture.is_some(),
+							egui::Button::new("Clear")).clicked() {
+							texture_change = Some((index, false));
+						}
+					});
+					if let Some(name) = app.surface_settings[index].texture.as_ref()
+						.and_then(|p| p.file_name()) {
+						ui.label(name.to_string_lossy());
+					}
+					ui.checkbox(&mut app.surface_settings[index].hidden, "Hidden");
+					ui.checkbox(&mut app.surface_settings[index].envmap, "Environment mapped");
+					ui.checkbox(&mut app.surface_settings[index].two_sided, "Two-sided");
+					let blend = &mut app.surface_settings[index].blend;
+					ui.horizontal(|ui| {
+						ui.label("Blending");
+						let threshold = match *blend {
+							BlendMode::AlphaTest(threshold) => threshold,
+							_ => 0.5,
+						};
+						egui::ComboBox::from_id_source(("surface_blend", index))
+							.selected_text(blend.name())
+							.show_ui(ui, |ui| {
+								[BlendMode::Opaque, BlendMode::AlphaTest(threshold),
+								BlendMode::AlphaBlend, BlendMode::Additive].into_iter()
+								.for_each(|mode| {
+									ui.selectable_value(blend, mode, mode.name());
+								});
+							});
+					});
+					if let BlendMode::AlphaTest(threshold) = blend {
+						ui.add(egui::Slider::new(threshold, 0.0..=1.0).text("Alpha threshold"));
+					}
+					if ui.button("Focus").clicked() {
+						focus = Some(index);
+					}
+					let settings = &app.surface_settings[index];
+					[(TextureMap::Normal, "Normal map", &settings.normal_map),
+					(TextureMap::Specular, "Specular map", &settings.specular_map)]
+					.into_iter().for_each(|(map, label, path)| {
+						ui.horizontal(|ui| {
+							ui.label(label);
+							if ui.button("Browse").clicked() {
+								map_change = Some((index, map, true));
+							}
+							if ui.add_enabled(path.is_some(), egui::Button::new("Clear")).clicked() {
+								map_change = Some((index, map, false));
+							}
+						});
+						if let Some(name) = path.as_ref().and_then(|p| p.file_name()) {
+							ui.label(name.to_string_lossy());
+						}
+					});
+				});
+			});
+		}
+		if cancel_rename {
+			app.name_edit = None;
+		}
+		if let Some((target, name)) = rename {
+			app.name_edit = None;
+			if let Err(e) = app.rename(&glc, target, &name) {
+				app.log_error(&e.to_string());
+			}
+		}
+		if let Some((surface, shader)) = shader_change {
+			app.set_surface_shader(&glc, surface, shader);
+		}
+		if let Some(surface) = focus {
+			app.focus_surface(surface);
+		}
+		match texture_change {
+			Some((surface, true)) => {
+				let mut dialog = FileDialog::open_file(None)
+					.show_rename(false)
+					.show_new_folder(false);
+				dialog.open();
+				app.file_dialog = Some((DialogPurpose::SurfaceTexture(surface), dialog));
+			},
+			Some((surface, false)) => app.set_surface_texture(&glc, surface, None),
+			None => (),
+		}
+		match map_change {
+			Some((surface, map, true)) => {
+				let mut dialog = FileDialog::open_file(None)
+					.show_rename(false)
+					.show_new_folder(false);
+				dialog.open();
+				let purpose = match map {
+					TextureMap::Normal => DialogPurpose::NormalMap(surface),
+					TextureMap::Specular => DialogPurpose::SpecularMap(surface),
+				};
+				app.file_dialog = Some((purpose, dialog));
+			},
+			Some((surface, map, false)) => app.set_surface_map(&glc, surface, map, None),
+			None => (),
+		}
+		ui.separator();
+		ui.heading("Other models");
+		let tag_names: Vec<String> = app.model_data.as_ref()
+			.map(|m| m.tag_names().map(String::from).collect()).unwrap_or_default();
+		let mut remove = None;
+		let mut texture_change = None;
+		app.attachments.iter_mut().enumerate().for_each(|(index, attachment)| {
+			let name = attachment.path.file_name().unwrap_or_default().to_string_lossy();
+			egui::CollapsingHeader::new(name).id_source(("attachment", index)).show(ui, |ui| {
+				ui.set_enabled(!app.outliner.is_locked(SceneNode::Attachment(index)));
+				ui.checkbox(&mut attachment.hidden, "Hidden");
+				let tag = match attachment.tag.is_empty() {
+					true => String::from("(none)"),
+					false => attachment.tag.clone(),
+				};
+				egui::ComboBox::from_id_source(("attachment_tag", index))
+					.selected_text(tag)
+					.show_ui(ui, |ui| {
+						ui.selectable_value(&mut attachment.tag, String::new(), "(none)");
+						tag_names.iter().for_each(|tag| {
+							ui.selectable_value(&mut attachment.tag, tag.clone(), tag);
+						});
+					});
+				display_transform_grid(ui, ("attachment_transform", index), &mut attachment.transform);
+				ui.horizontal(|ui| {
+					ui.label("Texture");
+					if ui.button("Browse...").clicked() {
+						texture_change = Some((index, true));
+					}
+					if ui.add_enabled(attachment.texture.is_some(),
+						egui::Button::new("Clear")).clicked() {
+						texture_change = Some((index, false));
+					}
+				});
+				if ui.button("Remove").clicked() {
+					remove = Some(index);
+				}
+			});
+		});
+		if ui.add_enabled(!tag_names.is_empty(), egui::Button::new("Attach model...")).clicked() {
+			let mut dialog = FileDialog::open_file(None)
+				.show_rename(false)
+				.show_new_folder(false)
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::AttachModel, dialog));
+		}
+		if ui.add_enabled(app.model_data.is_some(), egui::Button::new("Add model...")).on_hover_text(
+			"Add a model which isn't at any tag, e.g. the other parts of a map object").clicked() {
+			let mut dialog = FileDialog::open_file(None)
+				.show_rename(false)
+				.show_new_folder(false)
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::AddModel, dialog));
+		}
+		match texture_change {
+			Some((attachment, true)) => {
+				let mut dialog = FileDialog::open_file(None)
+					.show_rename(false)
+					.show_new_folder(false);
+				dialog.open();
+				app.file_dialog = Some((DialogPurpose::AttachmentTexture(attachment), dialog));
+			},
+			Some((attachment, false)) => app.set_attachment_texture(&glc, attachment, None),
+			None => (),
+		}
+		if let Some(index) = remove {
+			app.attachments.remove(index);
+			app.outliner.remove_attachment(index);
+		}
 	});
-	app.camera.aspect = {
-		let logical_size = wc.window().inner_size().to_logical::<f32>(wc.window().scale_factor());
-		logical_size.width / logical_size.height
-	};
-	let mut window_size = wc.window().inner_size().to_logical::<f32>(wc.window().scale_factor());
-	let md3_model_scale = Vec3::new(1., -1., 1.);
-	let md3_model_matrix = Mat4::from_scale(md3_model_scale);
-	unsafe {
-		glc.clear_color(0., 0., 0., 1.);
-		match render::MAX_TEXTURE_UNITS.set(
-			Box::new(glc.get_parameter_i32(glow::MAX_TEXTURE_IMAGE_UNITS)
-				.try_into().unwrap_or(u8::MAX))
-		).map_err(|_| format!("Maximum number of texture units already set!")) {
-			Ok(_) => println!("Maximum texture units: {}",
-				render::MAX_TEXTURE_UNITS.get().copied().unwrap()),
-			Err(e) => println!("{}", e),
+	let mut entity_light_open = app.entity_light.open;
+	egui::Window::new("Entity light").open(&mut entity_light_open).show(ctx, |ui| {
+		// Preview how the model looks in dark or coloured parts of a map
+		ui.set_enabled(!app.outliner.is_locked(SceneNode::Light));
+		let light = &mut app.entity_light;
+		ui.checkbox(&mut light.enabled, "Use entity light");
+		ui.add_enabled_ui(light.enabled, |ui| {
+			egui::Grid::new("entity_light").num_columns(2).show(ui, |ui| {
+				ui.label("Ambient");
+				egui::color_picker::color_edit_button_rgb(ui, &mut light.ambient);
+				ui.end_row();
+				ui.label("Directed");
+				egui::color_picker::color_edit_button_rgb(ui, &mut light.directed);
+				ui.end_row();
+				ui.label("Yaw");
+				ui.add(egui::Slider::new(&mut light.yaw, -180.0..=180.0).suffix("°"));
+				ui.end_row();
+				ui.label("Pitch");
+				ui.add(egui::Slider::new(&mut light.pitch, -90.0..=90.0).suffix("°"));
+				ui.end_row();
+			});
+		});
+		if ui.button("Reset").clicked() {
+			*light = EntityLight { open: true, enabled: light.enabled, ..Default::default() };
+		}
+	});
+	app.entity_light.open = entity_light_open;
+	egui::Window::new("Display transform").open(&mut app.display_open).show(ctx, |ui| {
+		// Only changes how the model is shown; use Transform model to change
+		// the model itself
+		ui.set_enabled(!app.outliner.is_locked(SceneNode::Model));
+		let display = &mut app.display;
+		display_transform_grid(ui, "display_transform", display);
+		if ui.add_enabled(!display.is_identity(), egui::Button::new("Reset")).clicked() {
+			*display = DisplayTransform::default();
+		}
+	});
+	if let Some(mut text) = app.pasted_view.take() {
+		let mut open = true;
+		let mut apply = false;
+		egui::Window::new("Paste view").open(&mut open).show(ctx, |ui| {
+			ui.label("Paste a view copied with View > Copy view");
+			let response = ui.text_edit_singleline(&mut text);
+			let enter = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+			apply = ui.add_enabled(!text.is_empty(), egui::Button::new("Apply")).clicked() || enter;
+		});
+		match (open, apply) {
+			(true, true) => match app.paste_view(&text) {
+				Ok(()) => (),
+				Err(e) => {
+					app.log_error(&format!("Could not paste the view: {}", e));
+					app.pasted_view = Some(text);
+				},
+			},
+			(true, false) => app.pasted_view = Some(text),
+			(false, _) => (),
+		}
+	}
+	let mut outliner_open = app.outliner.open;
+	egui::Window::new("Outliner").open(&mut outliner_open).vscroll(true).show(ctx, |ui| {
+		let outliner = &mut app.outliner;
+		let selected = outliner.selected;
+		let tag_names: Vec<String> = app.model_data.as_ref()
+			.map(|m| m.tag_names().map(String::from).collect()).unwrap_or_default();
+		if let Some(model) = app.model_data.as_ref() {
+			let name = app.model_path.as_ref().and_then(|p| p.file_name())
+				.map(|n| n.to_string_lossy()).unwrap_or_default();
+			let (surfaces, attachments) = (&mut app.surface_settings, &mut app.attachments);
+			let hidden_labels = &mut app.tag_editor.hidden_labels;
+			outliner_parent(ui, outliner, SceneNode::Model, &name, Some(&mut app.model_hidden), |ui, outliner| {
+				model.surfaces.iter().zip(surfaces.iter_mut()).enumerate().for_each(|(index, (surface, settings))| {
+					let name = format!("Surface {}: {}", index, String::from_utf8_stop(&surface.name));
+					outliner_row(ui, outliner, SceneNode::Surface(index), &name, Some(&mut settings.hidden));
+				});
+				// Attachments are under their tags
+				// Hiding a tag hides its name label
+				tag_names.iter().enumerate().for_each(|(index, tag)| {
+					let node = SceneNode::Tag(index);
+					let mut hidden = hidden_labels.contains(&index);
+					match attachments.iter().any(|a| &a.tag == tag) {
+						true => outliner_parent(ui, outliner, node, tag, Some(&mut hidden), |ui, outliner| {
+							attachments.iter_mut().enumerate().filter(|(_, a)| &a.tag == tag)
+								.for_each(|(index, attachment)| attachment_row(ui, outliner, index, attachment));
+						}),
+						false => outliner_row(ui, outliner, node, tag, Some(&mut hidden)),
+					}
+					match hidden {
+						true => hidden_labels.insert(index),
+						false => hidden_labels.remove(&index),
+					};
+				});
+			});
+		}
+		// Models which aren't at any tag
+		app.attachments.iter_mut().enumerate().filter(|(_, a)| !tag_names.contains(&a.tag))
+			.for_each(|(index, attachment)| attachment_row(ui, outliner, index, attachment));
+		let mut light_hidden = !app.entity_light.enabled;
+		outliner_row(ui, outliner, SceneNode::Light, "Entity light", Some(&mut light_hidden));
+		app.entity_light.enabled = !light_hidden;
+		// Selecting a tag or light here selects it in its own window too
+		if outliner.selected != selected {
+			match outliner.selected {
+				Some(SceneNode::Tag(index)) => {
+					app.tag_editor.selected = Some(index);
+					app.tag_editor.rename = tag_names[index].clone();
+				},
+				Some(SceneNode::Light) => app.entity_light.open = true,
+				_ => (),
+			}
+		}
+	});
+	app.outliner.open = outliner_open;
+	let mut normal_inspector_open = app.normal_inspector.open;
+	egui::Window::new("Normal inspector").open(&mut normal_inspector_open).show(ctx, |ui| {
+		let inspector = &mut app.normal_inspector;
+		ui.add(egui::Slider::new(&mut inspector.threshold, 0.0..=180.0).text("Threshold").suffix("°"));
+		ui.checkbox(&mut app.controls.gzdoom_normals, "GZDoom encoding");
+		ui.checkbox(&mut inspector.all, "Show all normals");
+		let colour = |c: Vec3| Color32::from_rgb((c.x * 255.) as u8, (c.y * 255.) as u8, (c.z * 255.) as u8);
+		ui.horizontal(|ui| {
+			ui.colored_label(colour(NORMAL_STORED_COLOUR), "Stored");
+			ui.colored_label(colour(NORMAL_SMOOTH_COLOUR), "Calculated");
+		});
+		let lines = match inspector.lines.as_ref() {
+			Some(l) => l,
+			None => return,
+		};
+		let bad: Vec<&VertexNormal> = lines.normals.iter()
+			.filter(|n| n.error > inspector.threshold).collect();
+		ui.label(format!("{} of {} vertices in frame {} are off by more than {}°",
+			bad.len(), lines.normals.len(), lines.frame, inspector.threshold));
+		let vector = |v: Vec3| format!("{:.3} {:.3} {:.3}", v.x, v.y, v.z);
+		egui::ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+			egui::Grid::new("bad_normals").striped(true).show(ui, |ui| {
+				["Surface", "Vertex", "Lat", "Long", "Stored", "Calculated", "Angle"]
+					.into_iter().for_each(|heading| { ui.strong(heading); });
+				ui.end_row();
+				bad.iter().for_each(|n| {
+					ui.label(n.surface.to_string());
+					ui.label(n.vertex.to_string());
+					ui.label(n.lat_long.0.to_string());
+					ui.label(n.lat_long.1.to_string());
+					ui.label(vector(n.stored));
+					ui.label(vector(n.smooth));
+					ui.label(format!("{:.1}°", n.error));
+					ui.end_row();
+				});
+			});
+		});
+	});
+	app.normal_inspector.open = normal_inspector_open;
+	if app.motion_trail.picking && !ctx.is_pointer_over_area() && ctx.input().pointer.primary_clicked() {
+		let screen = ctx.input().screen_rect.size();
+		let picked = ctx.input().pointer.interact_pos()
+			.and_then(|pos| app.pick_vertex(Vec2::new(pos.x, pos.y), Vec2::new(screen.x, screen.y)));
+		if picked.is_some() {
+			app.motion_trail.target = picked;
+			app.motion_trail.picking = false;
+		}
+	}
+	let mut motion_trail_open = app.motion_trail.open;
+	let mut trail_seek = None;
+	egui::Window::new("Motion trail").open(&mut motion_trail_open).show(ctx, |ui| {
+		let model = match app.model_data.as_ref() {
+			Some(m) => m,
+			None => return,
+		};
+		let trail = &mut app.motion_trail;
+		let mut target = trail.target;
+		ui.horizontal(|ui| {
+			ui.label("Tag");
+			let selected = match target {
+				Some(TrailTarget::Tag(tag)) => model.tags.get(tag).map(|t| String::from_utf8_stop(&t.name).into_owned()),
+				_ => None,
+			};
+			egui::ComboBox::from_id_source("trail_tag")
+				.selected_text(selected.unwrap_or_default())
+				.show_ui(ui, |ui| {
+					model.tags.iter().take(model.num_tags).enumerate().for_each(|(index, tag)| {
+						ui.selectable_value(&mut target, Some(TrailTarget::Tag(index)), String::from_utf8_stop(&tag.name));
+					});
+				});
+		});
+		ui.horizontal(|ui| {
+			let (mut surface, mut vertex) = match target {
+				Some(TrailTarget::Vertex { surface, vertex }) => (surface, vertex),
+				_ => (0, 0),
+			};
+			ui.label("Surface");
+			let mut changed = ui.add(egui::DragValue::new(&mut surface)
+				.clamp_range(0..=model.surfaces.len().saturating_sub(1))).changed();
+			ui.label("Vertex");
+			let num_verts = model.surfaces.get(surface).map_or(0, |s| s.num_verts);
+			changed |= ui.add(egui::DragValue::new(&mut vertex)
+				.clamp_range(0..=num_verts.saturating_sub(1))).changed();
+			if ui.button("Follow").clicked() || changed {
+				target = Some(TrailTarget::Vertex { surface, vertex });
+			}
+		});
+		ui.toggle_value(&mut trail.picking, "Pick in view")
+			.on_hover_text("Click on a vertex of the model to follow it");
+		trail.target = target;
+		// How far the target moves between each frame and the next
+		let steps: Vec<f32> = trail.points.windows(2).map(|p| p[0].distance(p[1])).collect();
+		if steps.is_empty() {
+			ui.label("Choose a tag or vertex which moves in the animation");
+			return;
+		}
+		let (largest, jump) = steps.iter().copied().enumerate()
+			.fold((0, 0.), |max, step| if step.1 > max.1 { step } else { max });
+		ui.label(format!("Largest jump: {} between frames {} and {}",
+			app.settings.length(jump), largest, largest + 1));
+		let size = egui::vec2(ui.available_width().max(200.), 80.);
+		let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+		let painter = ui.painter_at(rect);
+		painter.rect_filled(rect, 2., Color32::from_black_alpha(160));
+		let width = rect.width() / steps.len() as f32;
+		let frame = app.current_frame.floor() as usize;
+		steps.iter().enumerate().for_each(|(index, &step)| {
+			let top = rect.bottom() - rect.height() * step / jump.max(f32::EPSILON);
+			let bar = egui::Rect::from_x_y_ranges(
+				rect.left() + width * index as f32..=rect.left() + width * (index + 1) as f32,
+				top..=rect.bottom());
+			let colour = match index {
+				_ if index == largest => Color32::RED,
+				_ if index + 1 == frame => Color32::WHITE,
+				_ => Color32::LIGHT_BLUE,
+			};
+			painter.rect_filled(bar, 0., colour);
+		});
+		let hovered = response.hover_pos()
+			.map(|pos| (((pos.x - rect.left()) / width) as usize).min(steps.len() - 1));
+		if response.clicked() {
+			trail_seek = hovered.map(|index| index + 1);
+		}
+		if let Some(index) = hovered {
+			response.on_hover_text(format!("Frames {} to {}: {}",
+				index, index + 1, app.settings.length(steps[index])));
+		}
+	});
+	app.motion_trail.open = motion_trail_open;
+	app.motion_trail.picking &= motion_trail_open;
+	if let Some(frame) = trail_seek {
+		app.seek_frame(frame as f32);
+	}
+	if app.notes_window.placing && !ctx.is_pointer_over_area() && ctx.input().pointer.primary_clicked() {
+		let screen = ctx.input().screen_rect.size();
+		let frame = app.current_frame.floor() as usize;
+		let position = ctx.input().pointer.interact_pos()
+			.and_then(|pos| app.pick_vertex(Vec2::new(pos.x, pos.y), Vec2::new(screen.x, screen.y)))
+			.and_then(|target| match target {
+				TrailTarget::Vertex { surface, vertex } => {
+					let surf = app.model_data.as_ref()?.surfaces.get(surface)?;
+					let start = frame.min(surf.num_frames.saturating_sub(1)) * surf.num_verts;
+					surf.vertices.get(start + vertex).map(|v| v.position())
+				},
+				TrailTarget::Tag(_) => None,
+			});
+		if let Some(position) = position {
+			let notes = &mut app.notes_window;
+			let note = Note {
+				anchor: NoteAnchor::Position(position),
+				frame: notes.this_frame.then_some(frame),
+				text: std::mem::take(&mut notes.text).trim().to_string(),
+			};
+			notes.placing = false;
+			app.add_note(note);
+		}
+	}
+	let mut notes_open = app.notes_window.open;
+	let mut add_note = None;
+	let mut remove_note = None;
+	let mut note_seek = None;
+	egui::Window::new("Notes").open(&mut notes_open).show(ctx, |ui| {
+		let model = match app.model_data.as_ref().filter(|_| app.model_path.is_some()) {
+			Some(m) => m,
+			None => return,
+		};
+		let window = &mut app.notes_window;
+		let mut shown = !window.hidden;
+		ui.checkbox(&mut shown, "Show notes over the view");
+		window.hidden = !shown;
+		egui::ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+			app.notes.iter().enumerate().for_each(|(index, note)| {
+				ui.horizontal(|ui| {
+					let place = match &note.anchor {
+						NoteAnchor::Position(p) => format!("({:.1}, {:.1}, {:.1})", p.x, p.y, p.z),
+						NoteAnchor::Tag(tag) => tag.clone(),
+					};
+					match note.frame {
+						Some(frame) => if ui.small_button(format!("Frame {}", frame)).clicked() {
+							note_seek = Some(frame);
+						},
+						None => { ui.label("Every frame"); },
+					}
+					ui.label(&place);
+					if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+						remove_note = Some(index);
+					}
+				});
+				ui.label(&note.text);
+				ui.separator();
+			});
+		});
+		ui.text_edit_multiline(&mut window.text);
+		let frame = app.current_frame.floor() as usize;
+		ui.checkbox(&mut window.this_frame, format!("Only on frame {}", frame));
+		let valid = !window.text.trim().is_empty();
+		ui.horizontal(|ui| {
+			ui.label("Tag");
+			egui::ComboBox::from_id_source("note_tag")
+				.selected_text(window.tag.clone().unwrap_or_default())
+				.show_ui(ui, |ui| {
+					model.tag_names().for_each(|tag| {
+						ui.selectable_value(&mut window.tag, Some(tag.to_string()), tag.as_ref());
+					});
+				});
+			if ui.add_enabled(valid && window.tag.is_some(), egui::Button::new("Add on tag")).clicked() {
+				add_note = window.tag.clone().map(|tag| Note {
+					anchor: NoteAnchor::Tag(tag),
+					frame: window.this_frame.then_some(frame),
+					text: window.text.trim().to_string(),
+				});
+			}
+		});
+		if ui.add_enabled(valid, egui::SelectableLabel::new(window.placing, "Place in view"))
+			.on_hover_text("Click on a vertex of the model to put the note there").clicked() {
+			window.placing = !window.placing;
+		}
+		window.placing &= valid;
+	});
+	app.notes_window.open = notes_open;
+	app.notes_window.placing &= notes_open;
+	if let Some(index) = remove_note {
+		app.remove_note(index);
+	}
+	if let Some(note) = add_note {
+		app.notes_window.text.clear();
+		app.add_note(note);
+	}
+	if let Some(frame) = note_seek {
+		app.seek_frame(frame as f32);
+	}
+	let mut internals_open = app.internals.is_some();
+	if let Some(internals) = app.internals.as_ref() {
+		egui::Window::new("MD3 internals").open(&mut internals_open).show(ctx, |ui| {
+			let header = &internals.header;
+			let hex = |offset: u64| format!("{:#x}", offset);
+			internals.issues.iter().for_each(|issue| {
+				ui.colored_label(Color32::from_rgb(255, 96, 96), issue.to_string());
+			});
+			if internals.issues.is_empty() {
+				ui.label("Nothing overlaps or is out of order");
+			}
+			egui::ScrollArea::vertical().max_height(400.).show(ui, |ui| {
+				ui.collapsing("Header", |ui| {
+					egui::Grid::new("internals_header").striped(true).show(ui, |ui| {
+						[
+							("Ident", String::from_utf8_lossy(&header.ident).into_owned()),
+							("Version", header.version.to_string()),
+							("Name", header.name.clone()),
+							("Flags", hex(header.flags as u32 as u64)),
+							("Frames", header.num_frames.to_string()),
+							("Tags", header.num_tags.to_string()),
+							("Surfaces", header.num_surfaces.to_string()),
+							("Skins", header.num_skins.to_string()),
+							("Frames offset", hex(header.offset_frames as u64)),
+							("Tags offset", hex(header.offset_tags as u64)),
+							("Surfaces offset", hex(header.offset_surfaces as u64)),
+							("End offset", hex(header.offset_end as u64)),
+							("File size", hex(internals.len)),
+						].into_iter().for_each(|(name, value)| {
+							ui.label(name);
+							ui.monospace(value);
+							ui.end_row();
+						});
+					});
+				});
+				internals.surfaces.iter().enumerate().for_each(|(index, surface)| {
+					ui.collapsing(format!("Surface {}: {}", index, surface.name), |ui| {
+						egui::Grid::new(("internals_surface", index)).striped(true).show(ui, |ui| {
+							[
+								("Start", hex(surface.start)),
+								("Ident", String::from_utf8_lossy(&surface.ident).into_owned()),
+								("Flags", hex(surface.flags as u32 as u64)),
+								("Frames", surface.num_frames.to_string()),
+								("Shaders", surface.num_shaders.to_string()),
+								("Vertices", surface.num_verts.to_string()),
+								("Triangles", surface.num_triangles.to_string()),
+								("Triangles offset", hex(surface.offset_triangles as u64)),
+								("Shaders offset", hex(surface.offset_shaders as u64)),
+								("Texture coordinates offset", hex(surface.offset_texcoords as u64)),
+								("Vertices offset", hex(surface.offset_vertices as u64)),
+								("End offset", hex(surface.offset_end as u64)),
+							].into_iter().for_each(|(name, value)| {
+								ui.label(name);
+								ui.monospace(value);
+								ui.end_row();
+							});
+						});
+					});
+				});
+				ui.collapsing("Sections", |ui| {
+					egui::Grid::new("internals_sections").striped(true).show(ui, |ui| {
+						["Section", "Start", "End", "Size"].into_iter().for_each(|heading| { ui.strong(heading); });
+						ui.end_row();
+						internals.sections.iter().for_each(|section| {
+							ui.label(&section.name);
+							ui.monospace(hex(section.start));
+							ui.monospace(hex(section.end));
+							ui.monospace((section.end - section.start).to_string());
+							ui.end_row();
+						});
+					});
+				});
+			});
+		});
+	}
+	if !internals_open {
+		app.internals = None;
+	}
+	let mut backfaces = app.controls.view_mode == ViewMode::Backfaces;
+	if let Some(flipped) = app.flipped.as_ref().filter(|_| backfaces && app.model_data.is_some()) {
+		egui::Window::new("Flipped triangles").open(&mut backfaces).show(ctx, |ui| {
+			ui.label(format!("{} triangles in frame {} face away from their vertex normals",
+				flipped.triangles.len(), flipped.frame));
+			egui::ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+				egui::Grid::new("flipped_triangles").striped(true).show(ui, |ui| {
+					ui.strong("Surface");
+					ui.strong("Triangle");
+					ui.end_row();
+					flipped.triangles.iter().for_each(|(surface, triangle)| {
+						ui.label(surface.to_string());
+						ui.label(triangle.to_string());
+						ui.end_row();
+					});
+				});
+			});
+		});
+		if !backfaces {
+			app.controls.view_mode = ViewMode::Textured;
+		}
+	}
+	egui::Window::new("View settings").open(&mut app.view_settings_open).show(ctx, |ui| {
+		let camera = &mut app.camera;
+		egui::Grid::new("view_settings").num_columns(2).show(ui, |ui| {
+			ui.label("Field of view");
+			let mut fov = camera.fov.to_degrees();
+			if ui.add(egui::Slider::new(&mut fov, 10.0..=150.0).suffix("°")).changed() {
+				camera.fov = fov.to_radians();
+			}
+			ui.end_row();
+			// Small gib models need a close near plane, and map objects need
+			// a far away far plane
+			ui.label("Near plane");
+			ui.add(egui::Slider::new(&mut camera.near, MIN_NEAR_PLANE..=64.0).logarithmic(true));
+			ui.end_row();
+			ui.label("Far plane");
+			ui.add(egui::Slider::new(&mut camera.far, 64.0..=1048576.0).logarithmic(true));
+			ui.end_row();
+		});
+		camera.far = camera.far.max(camera.near * 2.);
+		if ui.button("Reset").clicked() {
+			let OrbitCamera { fov, near, far, .. } = OrbitCamera::default();
+			camera.fov = fov;
+			camera.near = near;
+			camera.far = far;
+		}
+	});
+	let mut statistics_open = app.statistics_open;
+	let mut previous_engine = None;
+	egui::Window::new("Statistics").open(&mut statistics_open).show(ctx, |ui| {
+		let megabytes = |bytes: usize| format!("{:.1} MB", bytes as f32 / (1024. * 1024.));
+		let engine = app.settings.engine_profile;
+		egui::ComboBox::from_label("Engine")
+			.selected_text(engine.to_string())
+			.show_ui(ui, |ui| {
+				EngineProfile::ALL.into_iter().for_each(|profile| {
+					ui.selectable_value(&mut app.settings.engine_profile, profile, profile.to_string());
+				});
+			});
+		if app.settings.engine_profile != engine {
+			previous_engine = Some(engine);
+		}
+		let limits = app.settings.engine_profile.limits();
+		// Red if it's more than the engine can load
+		let count = |ui: &mut egui::Ui, text: String, count: usize, max: Option<usize>| {
+			match max.is_some_and(|max| count > max) {
+				true => ui.colored_label(Color32::RED, text),
+				false => ui.label(text),
+			}
+		};
+		egui::Grid::new("statistics").num_columns(2).show(ui, |ui| {
+			if let Some(model) = app.model_data.as_ref() {
+				let most = |f: fn(&MD3Surface) -> usize| model.surfaces.iter().map(f).max().unwrap_or(0);
+				ui.label("Surfaces");
+				count(ui, model.surfaces.len().to_string(), model.surfaces.len(), limits.surfaces);
+				ui.end_row();
+				ui.label("Triangles");
+				let triangles = most(|s| s.triangles.len());
+				count(ui, format!("{} (up to {} per surface)",
+					model.surfaces.iter().map(|s| s.triangles.len()).sum::<usize>(), triangles),
+					triangles, limits.triangles);
+				ui.end_row();
+				ui.label("Vertices");
+				let verts = most(|s| s.num_verts);
+				count(ui, format!("{} (up to {} per surface)",
+					model.surfaces.iter().map(|s| s.num_verts).sum::<usize>(), verts),
+					verts, limits.verts);
+				ui.end_row();
+				ui.label("Shaders");
+				let shaders = most(|s| s.shaders.len());
+				count(ui, format!("Up to {} per surface", shaders), shaders, limits.shaders);
+				ui.end_row();
+				ui.label("Frames");
+				count(ui, model.frames.len().to_string(), model.frames.len(), limits.frames);
+				ui.end_row();
+				ui.label("Tags");
+				count(ui, model.num_tags.to_string(), model.num_tags, limits.tags);
+				ui.end_row();
+			}
+			let memory = app.texture_cache.memory();
+			ui.label("Cached textures");
+			ui.label(memory.count.to_string());
+			ui.end_row();
+			ui.label("Texture memory");
+			ui.label(format!("{} ({} in use)", megabytes(memory.total), megabytes(memory.in_use)));
+			ui.end_row();
+			ui.label("Texture budget");
+			let response = ui.add(egui::DragValue::new(&mut app.settings.texture_budget.megabytes)
+				.clamp_range(16..=16384).suffix(" MB"));
+			if response.drag_released() || response.changed() && !response.dragged() {
+				app.save_settings();
+			}
+			ui.end_row();
+		});
+		ui.label("Textures which aren't in use are thrown away, oldest first, when there are too many.");
+	});
+	app.statistics_open = statistics_open;
+	if let Some(previous) = previous_engine {
+		app.save_settings();
+		app.recheck_limits(previous);
+	}
+	if app.settings.frame_rate.show_frame_times {
+		egui::Area::new("frame_times")
+			.anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8., 32.))
+			.interactable(false)
+			.show(ctx, |ui| frame_time_graph(ui, &frame_times));
+	}
+	if app.settings.show_hud {
+		let painter = ctx.layer_painter(LayerId { order: Order::Foreground, id: Id::new("hud") });
+		let text = hud_text(&app, &frame_times);
+		let font = TextStyle::Monospace.resolve(&ctx.style());
+		let galley = painter.layout_no_wrap(text, font, Color32::WHITE);
+		let pos = ctx.available_rect().left_top() + egui::vec2(8., 8.);
+		painter.rect_filled(egui::Rect::from_min_size(pos, galley.size()).expand(4.), 2., Color32::from_black_alpha(160));
+		painter.galley(pos, galley);
+	}
+	let mut shortcuts_open = app.shortcuts_open;
+	let mut binding_change = None;
+	let mut reset_bindings = false;
+	egui::Window::new("Keyboard shortcuts").open(&mut shortcuts_open).vscroll(true).show(ctx, |ui| {
+		ui.label("Click a key to remove it, or + to add one");
+		egui::Grid::new("keyboard_shortcuts").num_columns(3).striped(true).show(ui, |ui| {
+			let keymap = &app.settings.keymap;
+			Action::ALL.into_iter().for_each(|action| {
+				ui.label(action.description());
+				ui.horizontal(|ui| {
+					let bindings = keymap.bindings(action);
+					bindings.iter().enumerate().for_each(|(index, chord)| {
+						// Keys bound to more than one action only do the first one
+						let (text, hint) = match keymap.action(*chord) == Some(action) {
+							true => (egui::RichText::new(chord.to_string()), "Remove"),
+							false => (egui::RichText::new(chord.to_string()).color(Color32::RED),
+								"Remove. This key is used by another action before this one."),
+						};
+						if ui.small_button(text).on_hover_text(hint).clicked() {
+							let mut bindings = bindings.to_vec();
+							bindings.remove(index);
+							binding_change = Some((action, bindings));
+						}
+					});
+					match app.capturing_shortcut == Some(action) {
+						true => { ui.label("Press a key, or Escape"); },
+						false => if ui.small_button("+").on_hover_text("Add a key").clicked() {
+							app.capturing_shortcut = Some(action);
+						},
+					}
+				});
+				if ui.add_enabled(!keymap.is_default(action), egui::Button::new("Reset").small()).clicked() {
+					binding_change = Some((action, action.default_bindings()));
+				}
+				ui.end_row();
+			});
+			FIXED_SHORTCUTS.iter().for_each(|(keys, action)| {
+				ui.label(*action);
+				ui.label(*keys);
+				ui.end_row();
+			});
+		});
+		reset_bindings = ui.button("Reset all").clicked();
+	});
+	app.shortcuts_open = shortcuts_open;
+	if !shortcuts_open {
+		app.capturing_shortcut = None;
+	}
+	if let Some((action, bindings)) = binding_change {
+		app.settings.keymap.set_bindings(action, bindings);
+		app.save_settings();
+	}
+	if reset_bindings {
+		app.settings.keymap.reset();
+		app.save_settings();
+	}
+	let mut log_open = app.log_open;
+	egui::Window::new("Log").open(&mut log_open)
+		.default_height(300.).show(ctx, |ui| {
+		let level = &mut app.log_level;
+		ui.horizontal(|ui| {
+			egui::ComboBox::from_id_source("log_level")
+				.selected_text(level.as_str())
+				.show_ui(ui, |ui| {
+					[log::Level::Error, log::Level::Warn, log::Level::Info, log::Level::Debug]
+						.into_iter().for_each(|l| { ui.selectable_value(level, l, l.as_str()); });
+				});
+			if ui.button("Copy").clicked() {
+				ui.output().copied_text = console::with_entries(|entries| entries.iter()
+					.filter(|entry| entry.level <= *level)
+					.map(|entry| format!("{}\n", entry))
+					.collect()).unwrap_or_default();
+			}
+			if ui.button("Clear").clicked() {
+				console::clear();
+			}
+		});
+		if let Some(path) = console::path() {
+			ui.label(format!("Also written to {}", path.display()));
+		}
+		if !err_util::debug_output() {
+			ui.label("The OpenGL driver doesn't support KHR_debug, so its messages aren't shown.");
+		}
+		ui.separator();
+		egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+			console::with_entries(|entries| entries.iter()
+				.filter(|entry| entry.level <= *level)
+				.for_each(|entry| {
+					let colour = match entry.level {
+						log::Level::Error => Color32::LIGHT_RED,
+						log::Level::Warn => Color32::GOLD,
+						_ => ui.visuals().text_color(),
+					};
+					ui.colored_label(colour, entry.to_string());
+				}));
+		});
+	});
+	app.log_open = log_open;
+	if !app.problems.is_empty() {
+		let mut dismiss = None;
+		let mut retry = None;
+		let mut fix = None;
+		let mut clear = false;
+		egui::Window::new("Problems").default_height(300.).vscroll(true).show(ctx, |ui| {
+			let surface_names: Vec<String> = app.model_data.as_ref()
+				.map(|model| model.surfaces.iter().map(|s| String::from_utf8_stop(&s.name).into_owned()).collect())
+				.unwrap_or_default();
+			ui.horizontal(|ui| {
+				let errors = app.problems.iter().filter(|p| p.severity == Severity::Error).count();
+				ui.label(format!("{} errors, {} warnings", errors, app.problems.len() - errors));
+				clear = ui.button("Clear").clicked();
+			});
+			app.problems.iter().enumerate().for_each(|(index, problem)| {
+				let colour = match problem.severity {
+					Severity::Error => Color32::LIGHT_RED,
+					Severity::Warning => Color32::GOLD,
+				};
+				let title = egui::RichText::new(format!("{}: {}", problem.source, problem.summary())).color(colour);
+				egui::CollapsingHeader::new(title).id_source(("problem", index)).show(ui, |ui| {
+					ui.label(&problem.message);
+					if let Some(surface) = problem.surface {
+						match surface_names.get(surface) {
+							Some(name) => ui.label(format!("Surface {}: {}", surface, name)),
+							None => ui.label(format!("Surface {}", surface)),
+						};
+					}
+					if let Some(path) = problem.path.as_ref() {
+						ui.label(format!("File: {}", path.display()));
+					}
+					ui.horizontal(|ui| {
+						if let Some(path) = problem.path.as_ref() {
+							if ui.button("Open containing folder").clicked() {
+								if let Err(e) = problems::open_containing_folder(path) {
+									log::error!("Could not open the folder of {}: {}", path.display(), e);
+								}
+							}
+						}
+						if problem.retry.is_some() && ui.button("Retry").clicked() {
+							retry = Some(index);
+						}
+						if let Some(label) = problem.fix.as_ref().map(ToString::to_string) {
+							if ui.button(label).clicked() {
+								fix = Some(index);
+							}
+						}
+						if ui.button("Dismiss").clicked() {
+							dismiss = Some(index);
+						}
+					});
+				});
+			});
+		});
+		if clear {
+			app.problems.clear();
+		} else if let Some(index) = retry {
+			// Problems come back if they haven't been fixed
+			let problem = app.problems.remove(index);
+			if let Some(retry) = problem.retry {
+				app.retry(&glc, retry);
+			}
+		} else if let Some(index) = fix {
+			let problem = app.problems.remove(index);
+			if let Err(e) = app.fix_problem(problem) {
+				app.log_error(&format!("{:#}", e));
+			}
+		} else if let Some(index) = dismiss {
+			app.problems.remove(index);
+		}
+	}
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.uv_export.open;
+		let mut export = false;
+		egui::Window::new("Export UV layout").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.uv_export;
+			ui.horizontal(|ui| {
+				ui.label("Size");
+				ui.add(egui::DragValue::new(&mut settings.size).clamp_range(16..=8192));
+			});
+			ui.checkbox(&mut settings.over_texture, "Draw over texture");
+			ui.label("Surfaces");
+			model.surfaces.iter().zip(settings.surfaces.iter_mut())
+				.for_each(|(surf, sel)| {
+					ui.checkbox(sel, String::from_utf8_stop(&surf.name));
+				});
+			if ui.add_enabled(settings.surfaces.iter().any(|&s| s),
+				egui::Button::new("Export")).clicked() {
+				export = true;
+			}
+		});
+		app.uv_export.open = open;
+		if export {
+			let mut dialog = FileDialog::save_file(None)
+				.filter(String::from("png"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::ExportUvLayout, dialog));
+		}
+	}
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.vertex_export.open;
+		let mut export = false;
+		egui::Window::new("Export vertex data").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.vertex_export;
+			let last_frame = model.frames.len().saturating_sub(1);
+			egui::ComboBox::from_label("Surface")
+				.selected_text(model.surfaces.get(settings.surface)
+					.map(|s| String::from_utf8_stop(&s.name)).unwrap_or_default())
+				.show_ui(ui, |ui| {
+					model.surfaces.iter().enumerate().for_each(|(index, surf)| {
+						ui.selectable_value(&mut settings.surface, index,
+							String::from_utf8_stop(&surf.name));
+					});
+				});
+			ui.horizontal(|ui| {
+				ui.label("Frames");
+				ui.add(egui::DragValue::new(&mut settings.first_frame)
+					.clamp_range(0..=settings.last_frame));
+				ui.label("to");
+				ui.add(egui::DragValue::new(&mut settings.last_frame)
+					.clamp_range(settings.first_frame..=last_frame));
+			});
+			ui.horizontal(|ui| {
+				ui.radio_value(&mut settings.format, VertexDataFormat::Csv, "CSV");
+				ui.radio_value(&mut settings.format, VertexDataFormat::Json, "JSON");
+			});
+			if ui.add_enabled(!model.surfaces.is_empty(),
+				egui::Button::new("Export")).clicked() {
+				export = true;
+			}
+		});
+		app.vertex_export.open = open;
+		if export {
+			let mut dialog = FileDialog::save_file(None);
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::ExportVertexData, dialog));
+		}
+	}
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.print_export.open;
+		let mut export = false;
+		egui::Window::new("Export for 3D printing").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.print_export;
+			let frame = (app.current_frame.floor() as usize).min(model.frames.len().saturating_sub(1));
+			ui.label(format!("Frame {}, with the vertices of every surface welded together", frame));
+			ui.horizontal(|ui| {
+				ui.radio_value(&mut settings.format, MeshFormat::Stl, "STL");
+				ui.radio_value(&mut settings.format, MeshFormat::Ply, "PLY");
+			});
+			ui.horizontal(|ui| {
+				ui.label("Millimetres per unit");
+				ui.add(egui::DragValue::new(&mut settings.scale).speed(0.01).clamp_range(0.001..=1000.0));
+			});
+			let bounds = model.surfaces.iter().filter_map(|s| s.bounds(frame))
+				.reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)));
+			if let Some((min, max)) = bounds {
+				let size = (max - min) * settings.scale;
+				ui.label(format!("{:.1} × {:.1} × {:.1} mm", size.x, size.y, size.z));
+			}
+			export = ui.button("Export...").clicked();
+		});
+		app.print_export.open = open;
+		if export {
+			let mut dialog = FileDialog::save_file(None)
+				.filter(String::from(app.print_export.format.extension()));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::ExportPrintMesh, dialog));
+		}
+	}
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.frame_bounds_open;
+		let mut export = false;
+		let mut fit = false;
+		let mut save = false;
+		egui::Window::new("Frame bounds").open(&mut open).show(ctx, |ui| {
+			use egui::plot::{Legend, Line, Plot, PlotPoints, VLine};
+			let radius: Vec<[f64; 2]> = model.frames.iter().enumerate()
+				.map(|(frame, f)| [frame as f64, f.radius as f64]).collect();
+			// The radius is measured from the frame's origin, so it may be
+			// bigger than it needs to be
+			let half_diagonal: Vec<[f64; 2]> = model.frames.iter().enumerate()
+				.map(|(frame, f)| [frame as f64, ((f.max - f.min).length() * 0.5) as f64]).collect();
+			ui.label(format!("Maximum radius: {}", app.settings.length(model.max_radius())));
+			if let Some(frame) = model.frames.get(app.current_frame as usize) {
+				let size = frame.max - frame.min;
+				ui.label(format!("Frame size: {} × {} × {}", app.settings.length(size.x),
+					app.settings.length(size.y), app.settings.length(size.z)));
+			}
+			Plot::new("frame_bounds_plot").height(200.).legend(Legend::default()).show(ui, |plot_ui| {
+				plot_ui.line(Line::new(PlotPoints::new(radius)).name("Radius"));
+				plot_ui.line(Line::new(PlotPoints::new(half_diagonal)).name("Half diagonal"));
+				plot_ui.vline(VLine::new(app.current_frame));
+			});
+			if ui.button("Export JSON").clicked() {
+				export = true;
+			}
+			egui::CollapsingHeader::new("Recompute from vertices").show(ui, |ui| {
+				let fitted: Vec<(usize, &md3::MD3Frame, md3::MD3Frame)> = model.frames.iter().enumerate()
+					.filter_map(|(frame, stored)| model.fitted_frame(frame)
+						.filter(|fitted| fitted != stored)
+						.map(|fitted| (frame, stored, fitted)))
+					.collect();
+				ui.label(format!("The bounds of {} of {} frames don't match their vertices",
+					fitted.len(), model.frames.len()));
+				let vector = |v: Vec3| format!("{:.2} {:.2} {:.2}", v.x, v.y, v.z);
+				egui::ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+					egui::Grid::new("fitted_bounds").striped(true).show(ui, |ui| {
+						["Frame", "", "Min", "Max", "Origin", "Radius"]
+							.into_iter().for_each(|heading| { ui.strong(heading); });
+						ui.end_row();
+						fitted.iter().for_each(|(frame, stored, fitted)| {
+							[("Stored", *stored), ("Fitted", fitted)].into_iter().for_each(|(label, bounds)| {
+								ui.label(if label == "Stored" { frame.to_string() } else { String::new() });
+								ui.label(label);
+								ui.label(vector(bounds.min));
+								ui.label(vector(bounds.max));
+								ui.label(vector(bounds.origin));
+								ui.label(app.settings.length(bounds.radius));
+								ui.end_row();
+							});
+						});
+					});
+				});
+				ui.horizontal(|ui| {
+					if ui.add_enabled(!fitted.is_empty(), egui::Button::new("Recompute bounds")).clicked() {
+						fit = true;
+					}
+					if ui.button("Save model as...").clicked() {
+						save = true;
+					}
+				});
+				if app.model_modified {
+					ui.label("The model has unsaved changes");
+				}
+			});
+		});
+		app.frame_bounds_open = open;
+		if fit {
+			if let Err(e) = app.fit_frame_bounds() {
+				app.log_error(&e.to_string());
+			}
+		}
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SaveModel, dialog));
+		}
+		if export {
+			let mut dialog = FileDialog::save_file(None)
+				.filter(String::from("json"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::ExportFrameBounds, dialog));
+		}
+	}
+	if let Some(comparison) = app.comparison.as_mut() {
+		let mut open = comparison.open;
+		let mut seek = None;
+		let file_name = comparison.path.file_name().unwrap_or_default().to_string_lossy();
+		egui::Window::new(format!("Comparison with {}", file_name)).id(Id::new("comparison"))
+			.open(&mut open).show(ctx, |ui| {
+			let diff = &comparison.diff;
+			ui.label(format!("Maximum deviation: {}", app.settings.length(diff.max())));
+			ui.label(format!("Mean deviation: {}", app.settings.length(diff.mean())));
+			egui::ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+				egui::Grid::new("comparison_frames").striped(true).show(ui, |ui| {
+					ui.strong("Frame");
+					ui.strong("Max");
+					ui.strong("Mean");
+					ui.end_row();
+					diff.frames.iter().enumerate().for_each(|(frame, deviation)| {
+						if ui.selectable_label(app.current_frame as usize == frame, frame.to_string()).clicked() {
+							seek = Some(frame);
+						}
+						ui.label(app.settings.length(deviation.max));
+						ui.label(app.settings.length(deviation.mean));
+						ui.end_row();
+					});
+				});
+			});
+		});
+		comparison.open = open;
+		if let Some(frame) = seek {
+			app.seek_frame(frame as f32);
+		}
+	}
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.shader_replace.open;
+		let mut apply = false;
+		let mut save = false;
+		egui::Window::new("Replace shader paths").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.shader_replace;
+			egui::Grid::new("shader_replace_fields").num_columns(2).show(ui, |ui| {
+				ui.label("Find");
+				ui.text_edit_singleline(&mut settings.find);
+				ui.end_row();
+				ui.label("Replace with");
+				ui.text_edit_singleline(&mut settings.replace);
+				ui.end_row();
+			});
+			ui.checkbox(&mut settings.regex, "Regular expression");
+			ui.separator();
+			let renames: Vec<(String, String)> = match settings.renamer() {
+				Ok(renamer) => model.surfaces.iter()
+					.flat_map(|surf| surf.shaders.iter())
+					.filter_map(|shader| {
+						let name = String::from_utf8_stop(&shader.name);
+						renamer(&name).map(|new_name| (name.into_owned(), new_name))
+					}).collect(),
+				Err(e) => {
+					ui.colored_label(Color32::RED, e.to_string());
+					vec![]
+				},
+			};
+			egui::ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+				renames.iter().for_each(|(name, new_name)| {
+					ui.label(format!("{} → {}", name, new_name));
+				});
+			});
+			ui.label(format!("{} shader(s) will be renamed", renames.len()));
+			ui.horizontal(|ui| {
+				if ui.add_enabled(!renames.is_empty(), egui::Button::new("Replace")).clicked() {
+					apply = true;
+				}
+				if ui.button("Save model as...").clicked() {
+					save = true;
+				}
+			});
+		});
+		app.shader_replace.open = open;
+		if apply {
+			if let Err(e) = app.replace_shader_paths(&glc) {
+				app.log_error(&e.to_string());
+			}
+		}
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SaveModel, dialog));
+		}
+	}
+	if app.model_data.is_some() {
+		let mut open = app.optimize.open;
+		let mut apply = false;
+		let mut snap = false;
+		let mut save = false;
+		egui::Window::new("Optimize mesh").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.optimize.settings;
+			ui.checkbox(&mut settings.weld, "Weld vertices with the same positions and UVs");
+			ui.add_enabled_ui(settings.weld, |ui| {
+				ui.checkbox(&mut settings.match_normals, "Only if their normals are the same");
+			});
+			ui.checkbox(&mut settings.remove_degenerate, "Remove triangles with no area");
+			ui.checkbox(&mut settings.remove_duplicates, "Remove duplicate triangles");
+			ui.checkbox(&mut settings.remove_unused, "Remove unused vertices");
+			ui.horizontal(|ui| {
+				if ui.button("Optimize").clicked() {
+					apply = true;
+				}
+				if ui.button("Save model as...").clicked() {
+					save = true;
+				}
+			});
+			if let Some(report) = app.optimize.report.as_ref() {
+				ui.separator();
+				ui.label(report.to_string());
+			}
+			ui.separator();
+			ui.horizontal(|ui| {
+				ui.label("Grid");
+				ui.add(egui::DragValue::new(&mut app.optimize.snap_grid).clamp_range(1..=256).suffix("/64"));
+				ui.label(app.settings.length(app.optimize.snap_grid as f32 / 64.));
+				snap = ui.button("Snap vertices to grid").clicked();
+			});
+			if let Some(report) = app.optimize.snap_report.as_ref() {
+				ui.label(report.to_string());
+			}
+		});
+		app.optimize.open = open;
+		if apply {
+			if let Err(e) = app.optimize_model(&glc) {
+				app.log_error(&e.to_string());
+			}
+		}
+		if snap {
+			if let Err(e) = app.snap_to_grid(&glc) {
+				app.log_error(&e.to_string());
+			}
+		}
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SaveModel, dialog));
+		}
+	}
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.merge_open;
+		let mut save = false;
+		egui::Window::new("Merge surfaces").open(&mut open).show(ctx, |ui| {
+			let engine = app.settings.engine_profile;
+			let plan = optimize::merge_plan(model, &engine.limits());
+			let merged = plan.iter().max().map_or(0, |&last| last + 1);
+			ui.label("Surfaces which use the same shaders are merged into one, so the engine draws them together.");
+			ui.label("Skins choose textures by surface name, so each merged surface is named after its first surface.");
+			ui.separator();
+			(0..merged).for_each(|index| {
+				let names: Vec<String> = plan.iter().zip(model.surfaces.iter())
+					.filter(|(&p, _)| p == index)
+					.map(|(_, surf)| String::from_utf8_stop(&surf.name).to_string()).collect();
+				if names.len() > 1 {
+					ui.label(format!("{} ← {}", names[0], names.join(", ")));
+				}
+			});
+			ui.label(format!("{} surfaces → {}, within the limits of {}", model.surfaces.len(), merged, engine));
+			save = ui.add_enabled(merged < model.surfaces.len(),
+				egui::Button::new("Merge and save as...")).clicked();
+		});
+		app.merge_open = open;
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::MergeSurfaces, dialog));
 		}
-		match render::MAX_TEXTURE_POT.set({
-			let max_texture_size = glc.get_parameter_i32(glow::MAX_TEXTURE_SIZE);
-			let max_texture_pot = (max_texture_size as f32).log2().floor()
-				.to_int_unchecked();
-			Box::new(max_texture_pot)
+	}
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.surface_split.open;
+		let mut save = false;
+		egui::Window::new("Split surface").open(&mut open).show(ctx, |ui| {
+			let split = &mut app.surface_split;
+			let previous = split.surface;
+			egui::ComboBox::from_label("Surface")
+				.selected_text(model.surfaces.get(split.surface)
+					.map(|s| String::from_utf8_stop(&s.name)).unwrap_or_default())
+				.show_ui(ui, |ui| {
+					model.surfaces.iter().enumerate().for_each(|(index, surf)| {
+						ui.selectable_value(&mut split.surface, index, String::from_utf8_stop(&surf.name));
+					});
+				});
+			if split.surface != previous {
+				split.selected.clear();
 			}
-		).map_err(|_| format!("Maximum texture size already set!")) {
-			Ok(_) => println!("Maximum texture size: {}",
-				2i32.pow(render::MAX_TEXTURE_POT.get().copied().unwrap())),
-			Err(e) => println!("{}", e),
+			let islands = model.surfaces.get(split.surface).map(|s| s.uv_islands()).unwrap_or_default();
+			let count = islands.iter().max().map_or(0, |&last| last + 1);
+			ui.label(format!("{} triangles in {} UV islands", islands.len(), count));
+			ui.radio_value(&mut split.each, false, "Move the selected islands to a new surface");
+			ui.radio_value(&mut split.each, true, "Give every island its own surface");
+			split.hovered = None;
+			ui.add_enabled_ui(!split.each, |ui| {
+				egui::ScrollArea::vertical().max_height(240.).show(ui, |ui| {
+					(0..count).for_each(|island| {
+						let triangles = islands.iter().filter(|&&i| i == island).count();
+						let mut selected = split.selected.contains(&island);
+						let response = ui.checkbox(&mut selected, format!("Island {}: {} triangles", island + 1, triangles));
+						if response.changed() {
+							match selected {
+								true => split.selected.insert(island),
+								false => split.selected.remove(&island),
+							};
+						}
+						if response.hovered() {
+							split.hovered = Some(island);
+						}
+					});
+				});
+			});
+			ui.label("The new surfaces use the same shaders, so a skin can give them different textures.");
+			let valid = match split.each {
+				true => count > 1,
+				false => !split.selected.is_empty() && split.selected.len() < count,
+			};
+			save = ui.add_enabled(valid, egui::Button::new("Split and save as...")).clicked();
+		});
+		app.surface_split.open = open;
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SplitSurface, dialog));
 		}
 	}
-	el.run(move |event, _window, control_flow| {
-		match event {
-			Event::WindowEvent { window_id: _, event } => {
-				use glutin::event::{
-					WindowEvent::*,
-					MouseButton,
-					ElementState,
-				};
-				if egui_glow.on_event(&event) {
-					return ();
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.lod.open;
+		let mut preview = None;
+		let mut save = false;
+		let triangles = |model: &MD3Model| model.surfaces.iter().map(|s| s.triangles.len()).sum::<usize>();
+		egui::Window::new("Levels of detail").open(&mut open).show(ctx, |ui| {
+			let window = &mut app.lod;
+			ui.label(format!("The full model has {} triangles", triangles(model)));
+			(0..window.percent.len()).for_each(|index| {
+				ui.horizontal(|ui| {
+					ui.label(format!("LOD {}", index + 1));
+					let response = ui.add(egui::Slider::new(&mut window.percent[index], 1.0..=100.0).suffix("%"));
+					if response.changed() {
+						window.lods = None;
+					}
+					if window.preview > 0 && (response.drag_released() || response.changed() && !response.dragged()) {
+						preview = Some(window.preview);
+					}
+					match window.lods.as_ref() {
+						Some(lods) => ui.label(format!("{} triangles", triangles(&lods[index]))),
+						None => ui.label(format!("about {} triangles", model.surfaces.iter()
+							.map(|s| lod::target_triangles(s.triangles.len(), window.percent[index] / 100.))
+							.sum::<usize>())),
+					};
+				});
+			});
+			ui.horizontal(|ui| {
+				ui.label("Show");
+				let mut level = window.preview;
+				ui.radio_value(&mut level, 0, "Full model");
+				(1..lod::LOD_LEVELS).for_each(|l| { ui.radio_value(&mut level, l, format!("LOD {}", l)); });
+				if level != window.preview {
+					preview = Some(level);
 				}
-				match event {
-					CloseRequested => {
-						*control_flow = ControlFlow::ExitWithCode(0);
-					},
-					Resized(new_size) => {
-						window_size = new_size.to_logical::<f32>(wc.window().scale_factor());
-						app.camera.aspect = window_size.width / window_size.height;
-					},
-					MouseInput {state, button, .. } => {
-						match button {
-							MouseButton::Left => {
-							app.controls.lmb_dragging = match state {
-								ElementState::Pressed => true,
-								ElementState::Released => false,
-							};
-							},
-							MouseButton::Right => {
-							app.controls.rmb_dragging = match state {
-								ElementState::Pressed => true,
-								ElementState::Released => false,
-							};
-							},
-							_ => (),
-						}
-					},
-					CursorLeft{..} => {
-						app.controls.lmb_dragging = false;
-						app.controls.rmb_dragging = false;
-					},
-					_ => (),
+			});
+			if ui.button("Save LODs as...").clicked() {
+				save = true;
+			}
+			ui.label("Saving model.md3 saves model_1.md3 and model_2.md3");
+		});
+		// Show the full model again when the window is closed
+		if !open && app.lod.preview > 0 {
+			preview = Some(0);
+		}
+		app.lod.open = open;
+		if let Some(level) = preview {
+			if let Err(e) = app.preview_lod(&glc, level) {
+				app.log_error(&e.to_string());
+			}
+		}
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SaveLods, dialog));
+		}
+	}
+	if app.model_data.is_some() {
+		let mut open = app.transform.open;
+		let mut apply = false;
+		let mut mirror = false;
+		let mut save = false;
+		egui::Window::new("Transform model").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.transform;
+			egui::Grid::new("model_transform").num_columns(2).show(ui, |ui| {
+				ui.label("Scale");
+				ui.add(egui::DragValue::new(&mut settings.scale).speed(0.01).clamp_range(0.001..=1000.0));
+				ui.end_row();
+				// Yaw turns around Z, pitch around Y, and roll around X
+				[("Yaw", &mut settings.yaw), ("Pitch", &mut settings.pitch), ("Roll", &mut settings.roll)]
+					.into_iter().for_each(|(label, angle)| {
+						ui.label(label);
+						ui.horizontal(|ui| {
+							ui.add(egui::DragValue::new(angle).speed(0.5).suffix("°").clamp_range(-180.0..=180.0));
+							if ui.small_button("-90°").clicked() { *angle = (*angle - 90.).max(-180.); }
+							if ui.small_button("+90°").clicked() { *angle = (*angle + 90.).min(180.); }
+						});
+						ui.end_row();
+					});
+				ui.label("Offset");
+				ui.horizontal(|ui| {
+					ui.add(egui::DragValue::new(&mut settings.offset.x).speed(0.1));
+					ui.add(egui::DragValue::new(&mut settings.offset.y).speed(0.1));
+					ui.add(egui::DragValue::new(&mut settings.offset.z).speed(0.1));
+				});
+				ui.end_row();
+			});
+			ui.label(format!("Offset: {} × {} × {}", app.settings.length(settings.offset.x),
+				app.settings.length(settings.offset.y), app.settings.length(settings.offset.z)));
+			ui.collapsing("Mirror", |ui| {
+				// Y is left and right, for making left-handed weapons
+				ui.horizontal(|ui| {
+					ui.label("Across");
+					["X", "Y", "Z"].iter().enumerate().for_each(|(axis, label)| {
+						ui.radio_value(&mut settings.mirror_axis, axis, *label);
+					});
+				});
+				ui.horizontal(|ui| {
+					ui.checkbox(&mut settings.mirror_uv[0], "Flip U");
+					ui.checkbox(&mut settings.mirror_uv[1], "Flip V");
+				});
+				mirror = ui.button("Mirror").clicked();
+			});
+			ui.horizontal(|ui| {
+				if ui.button("Apply").clicked() {
+					apply = true;
 				}
-			},
-			Event::DeviceEvent {event, ..} => {
-				use glutin::event::DeviceEvent::*;
-				if app.controls.lmb_dragging {
-				match event {
-					MouseMotion { delta: (dx, dy) } => {
-						let dx = dx as f32 * MOUSE_FACTOR;
-						let dy = dy as f32 * MOUSE_FACTOR;
-						app.camera.longtude += dx;
-						app.camera.latitude -= dy;
-						app.camera.latitude = app.camera.latitude.clamp(-LOOK_LIMIT, LOOK_LIMIT);
-					},
-					_ => ()
+				if ui.button("Reset").clicked() {
+					*settings = TransformSettings { open: true, ..Default::default() };
 				}
+				if ui.button("Save model as...").clicked() {
+					save = true;
 				}
-				if app.controls.rmb_dragging {
-				match event {
-					MouseMotion { delta: (_dx, dy) } => {
-						let dy = dy as f32 * MOUSE_FACTOR * app.camera.distance.max(1.);
-						app.camera.distance += dy;
-					},
-					_ => (),
+			});
+		});
+		app.transform.open = open;
+		if apply {
+			if let Err(e) = app.bake_transform(&glc) {
+				app.log_error(&e.to_string());
+			}
+		}
+		if mirror {
+			if let Err(e) = app.mirror_model(&glc) {
+				app.log_error(&e.to_string());
+			}
+		}
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SaveModel, dialog));
+		}
+	}
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.frame_editor.open;
+		let mut edit = None;
+		let mut save = false;
+		let num_frames = model.frames.len();
+		let last_frame = num_frames.saturating_sub(1);
+		egui::Window::new("Edit frames").open(&mut open).show(ctx, |ui| {
+			let editor = &mut app.frame_editor;
+			ui.label(format!("The model has {} frames", num_frames));
+			ui.horizontal(|ui| {
+				ui.label("Frames");
+				ui.add(egui::DragValue::new(&mut editor.first).clamp_range(0..=last_frame));
+				ui.label("to");
+				ui.add(egui::DragValue::new(&mut editor.last).clamp_range(0..=last_frame));
+				if let Some(section) = app.play_section.as_ref() {
+					if ui.button("Looping frames").clicked() {
+						(editor.first, editor.last) = (*section.start(), *section.end());
+					}
+				}
+			});
+			editor.last = editor.last.max(editor.first);
+			let frames = editor.first..=editor.last;
+			ui.horizontal(|ui| {
+				// There has to be at least one frame left
+				let all = editor.first == 0 && editor.last == last_frame;
+				if ui.add_enabled(!all, egui::Button::new("Delete")).clicked() {
+					edit = Some(FrameEdit::Delete(frames.clone()));
+				}
+				if ui.button("Duplicate").clicked() {
+					edit = Some(FrameEdit::Duplicate(frames.clone()));
+				}
+			});
+			ui.horizontal(|ui| {
+				if ui.button("Move").clicked() {
+					edit = Some(FrameEdit::Move(frames.clone(), editor.before));
 				}
+				ui.label("to before frame");
+				ui.add(egui::DragValue::new(&mut editor.before).clamp_range(0..=num_frames));
+				if editor.before == num_frames {
+					ui.label("(the end)");
 				}
+			});
+			if ui.button("Save model as...").clicked() {
+				save = true;
 			}
-			Event::MainEventsCleared => {
-// CLEAR SCREEN BEFORE DRAWING ANYTHING
-// ==================================================================
-unsafe {
-	glc.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
-	glc.enable(glow::DEPTH_TEST);
-}
-// DRAW MODELS
-// ==================================================================
-unsafe {
-	glc.depth_func(glow::LESS);
-	glc.enable(glow::CULL_FACE);
-	glc.cull_face(glow::BACK);
-}
-app.models.iter_mut().for_each(|model| {
-	if let Err(e) = model.render(&glc, |uniforms| {
-		uniforms.eye = app.camera.view_projection() * md3_model_matrix;
-		uniforms.frame = app.current_frame;
-		uniforms.mode = app.controls.view_mode as u32;
-		uniforms.gzdoom = app.controls.gzdoom_normals;
-	}) {
-		eprintln!("{:?}", e);
+		});
+		app.frame_editor.open = open;
+		if let Some(edit) = edit {
+			if let Err(e) = app.edit_frames(&glc, edit) {
+				app.log_error(&e.to_string());
+			}
+		}
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SaveModel, dialog));
+		}
 	}
-});
-
-// DRAW TAG AXES
-// ==================================================================
-
-app.tag_axes.shader.activate().unwrap();
-if let Some(model) = app.model_data.as_ref() {
-	let current_frame = app.current_frame.floor() as usize;
-	let next_frame = app.current_frame.ceil() as usize;
-	let lerp_factor = app.current_frame.fract();
-	let num_tags = model.num_tags;
-	(0..num_tags).for_each(|tag_index| {
-		let tag_a = tag_index + num_tags * current_frame;
-		let tag_b = tag_index + num_tags * next_frame;
-		let tag_a = &model.tags[tag_a];
-		let tag_b = &model.tags[tag_b];
-		let tag_axes = lerp(tag_a.axes, tag_b.axes, lerp_factor);
-		let tag_origin = lerp(tag_a.origin, tag_b.origin, lerp_factor);
-		let tag_distance = (app.camera.position() * md3_model_scale).distance(tag_origin) / 256.;
-		let mvp = app.camera.view_projection() * md3_model_matrix *
-			Affine3A::from_mat3_translation(tag_axes, tag_origin) *
-			Mat4::from_scale(Vec3::splat(tag_distance));
-
-		if let Err(e) = app.tag_axes.render(&glc, |uniforms| {
-			uniforms.eye = mvp;
-			uniforms.shaded = true;
-		}) {
-			eprintln!("{:?}", e);
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.tag_editor.open;
+		let mut edit = None;
+		let mut save = false;
+		let frame = app.current_frame.floor() as usize;
+		egui::Window::new("Tags").open(&mut open).show(ctx, |ui| {
+			let editor = &mut app.tag_editor;
+			egui::ScrollArea::vertical().max_height(150.).show(ui, |ui| {
+				model.tag_names().enumerate().for_each(|(index, name)| {
+					if ui.selectable_label(editor.selected == Some(index), name.as_ref()).clicked() {
+						editor.selected = Some(index);
+						editor.rename = name.into_owned();
+						app.outliner.selected = Some(SceneNode::Tag(index));
+					}
+				});
+			});
+			ui.horizontal(|ui| {
+				ui.text_edit_singleline(&mut editor.new_name);
+				let valid = !editor.new_name.is_empty();
+				if ui.add_enabled(valid, egui::Button::new("Add")).clicked() {
+					edit = Some(TagEdit::Add(editor.new_name.clone()));
+				}
+				if ui.add_enabled(valid && editor.selected.is_some(), egui::Button::new("Duplicate")).clicked() {
+					edit = editor.selected.map(|index| TagEdit::Duplicate(index, editor.new_name.clone()));
+				}
+			});
+			let index = match editor.selected.filter(|&i| i < model.num_tags) {
+				Some(i) => i,
+				None => return,
+			};
+			ui.separator();
+			ui.set_enabled(!app.outliner.is_locked(SceneNode::Tag(index)));
+			ui.horizontal(|ui| {
+				ui.text_edit_singleline(&mut editor.rename);
+				if ui.add_enabled(!editor.rename.is_empty(), egui::Button::new("Rename")).clicked() {
+					edit = Some(TagEdit::Rename(index, editor.rename.clone()));
+				}
+				if ui.button("Remove").clicked() {
+					edit = Some(TagEdit::Remove(index));
+				}
+			});
+			ui.checkbox(&mut editor.all_frames, "Change every frame");
+			let mut show_label = !editor.hidden_labels.contains(&index);
+			if ui.checkbox(&mut show_label, "Show name over the view").changed() {
+				match show_label {
+					true => editor.hidden_labels.remove(&index),
+					false => editor.hidden_labels.insert(index),
+				};
+			}
+			let tag = match model.tags.get(frame * model.num_tags + index) {
+				Some(t) => t,
+				None => return,
+			};
+			egui::Grid::new("tag_transform").num_columns(4).show(ui, |ui| {
+				ui.label("Origin");
+				let mut origin = tag.origin;
+				let changed = [&mut origin.x, &mut origin.y, &mut origin.z].into_iter()
+					.map(|v| ui.add(egui::DragValue::new(v).speed(0.1)).changed())
+					.fold(false, |changed, c| changed | c);
+				if changed {
+					edit = Some(TagEdit::Move(index, origin - tag.origin));
+				}
+				ui.end_row();
+				// Yaw turns around Z, pitch around Y, and roll around X
+				ui.label("Rotation");
+				let (yaw, pitch, roll) = Quat::from_mat3(&tag.axes).to_euler(EulerRot::ZYX);
+				let mut angles = [yaw, pitch, roll].map(f32::to_degrees);
+				let changed = angles.iter_mut()
+					.map(|a| ui.add(egui::DragValue::new(a).speed(0.5).suffix("°")).changed())
+					.fold(false, |changed, c| changed | c);
+				if changed {
+					let [yaw, pitch, roll] = angles.map(f32::to_radians);
+					let axes = Mat3::from_euler(EulerRot::ZYX, yaw, pitch, roll);
+					edit = Some(TagEdit::Rotate(index, axes * tag.axes.inverse()));
+				}
+				ui.end_row();
+			});
+			ui.horizontal(|ui| {
+				if ui.button("Copy this frame to every frame").clicked() {
+					edit = Some(TagEdit::CopyToAllFrames(index));
+				}
+				if ui.button("Save model as...").clicked() {
+					save = true;
+				}
+			});
+		});
+		app.tag_editor.open = open;
+		if let Some(edit) = edit {
+			app.edit_tag(&glc, edit);
 		}
-	});
-}
-
-// DRAW AXES
-// ==================================================================
-unsafe {
-	glc.depth_func(glow::ALWAYS);
-}
-app.axes.shader.activate().unwrap();
-let mvp = {
-	let eye = Vec3::new(
-		app.camera.longtude.cos() * app.camera.latitude.cos(),
-		app.camera.longtude.sin() * app.camera.latitude.cos(),
-		app.camera.latitude.sin(),
-	) * -60.;
-	// 160 pixels left from top right corner, 80 pixels down from top right corner
-	let trans = Mat4::from_translation(Vec3::new(1.0 - (320./window_size.width), 1.0 - (160./window_size.height), 0.));
-	let scale = Mat4::from_scale(Vec3::new(0.125, 0.125, 0.125));
-	let view = Mat4::look_at_lh(eye, Vec3::ZERO, Vec3::Z);
-	let proj = Mat4::perspective_lh(app.camera.fov, app.camera.aspect, 0.25, 512.);
-	trans * proj * view * scale * md3_model_matrix
-};
-
-if let Err(e) = app.axes.render(&glc, |uniforms| {
-	uniforms.eye = mvp;
-	uniforms.shaded = false;
-}) {
-	eprintln!("{:?}", e);
-}
-
-// DRAW EGUI
-// ==================================================================
-egui_glow.run(wc.window(), |ctx| {
-	egui::TopBottomPanel::top("menu_bar").show(&ctx, |ui| {
-		egui::menu::bar(ui, |ui| {
-			ui.menu_button("File", |ui| {
-				if ui.button("Open").clicked() {
-					app.open_file_dialog.open();
-					ui.close_menu();
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SaveModel, dialog));
+		}
+	}
+	if app.model_data.is_some() {
+		let mut open = app.normal_recalc.open;
+		let mut apply = false;
+		let mut save = false;
+		egui::Window::new("Recalculate normals").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.normal_recalc;
+			ui.horizontal(|ui| {
+				ui.label("Weighting");
+				ui.radio_value(&mut settings.weighting, NormalWeighting::Area, "Area");
+				ui.radio_value(&mut settings.weighting, NormalWeighting::Angle, "Angle");
+			});
+			ui.checkbox(&mut settings.gzdoom, "GZDoom encoding");
+			ui.label("Vertices in the same place get the same normal, and vertices \
+				which aren't part of a triangle are left alone.");
+			ui.horizontal(|ui| {
+				if ui.button("Recalculate").clicked() {
+					apply = true;
 				}
-				if ui.button("Quit").clicked() {
-					ui.close_menu();
-					*control_flow = ControlFlow::ExitWithCode(0);
+				if ui.button("Save model as...").clicked() {
+					save = true;
 				}
 			});
-			ui.menu_button("View", |ui| {
-				if ui.radio_value(&mut app.controls.view_mode,
-					ViewMode::Textured, "Textured").clicked() ||
-					ui.radio_value(&mut app.controls.view_mode,
-						ViewMode::Untextured, "Untextured").clicked() ||
-					ui.radio_value(&mut app.controls.view_mode,
-						ViewMode::Normals, "Normals").clicked()
-				{ ui.close_menu(); }
-				if ui.checkbox(&mut app.controls.gzdoom_normals, "GZDoom normals").clicked() { ui.close_menu(); }
+		});
+		app.normal_recalc.open = open;
+		if apply {
+			if let Err(e) = app.recalculate_normals(&glc) {
+				app.log_error(&e.to_string());
+			}
+		}
+		if save {
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SaveModel, dialog));
+		}
+	}
+	#[cfg(not(target_arch = "wasm32"))]
+	if app.model_data.is_some() {
+		let mut open = app.video_export.open;
+		let mut export = false;
+		let rendering = app.render_job.is_some();
+		egui::Window::new("Export video").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.video_export;
+			ui.horizontal(|ui| {
+				ui.radio_value(&mut settings.source, VideoSource::Animation, "Animation");
+				ui.radio_value(&mut settings.source, VideoSource::Turntable, "Turntable");
+			});
+			egui::Grid::new("video_export").num_columns(2).show(ui, |ui| {
+				ui.label("Size");
+				ui.horizontal(|ui| {
+					// Most video encoders need an even size
+					ui.add(egui::DragValue::new(&mut settings.width).clamp_range(16..=8192));
+					ui.label("x");
+					ui.add(egui::DragValue::new(&mut settings.height).clamp_range(16..=8192));
+				});
+				settings.width &= !1;
+				settings.height &= !1;
+				ui.end_row();
+				ui.label("Frames per second");
+				ui.add(egui::DragValue::new(&mut settings.fps).clamp_range(1..=240));
+				ui.end_row();
+				match settings.source {
+					VideoSource::Animation => {
+						ui.label("Model frames per second");
+						ui.add(egui::DragValue::new(&mut settings.anim_fps).speed(0.1).clamp_range(0.1..=240.0));
+					},
+					VideoSource::Turntable => {
+						ui.label("Seconds per turn");
+						ui.add(egui::DragValue::new(&mut settings.turntable_seconds).speed(0.1).clamp_range(0.5..=120.0));
+					},
+				}
+				ui.end_row();
+				ui.label("Quantizer");
+				ui.add(egui::DragValue::new(&mut settings.quantizer).clamp_range(0..=255))
+					.on_hover_text("0 is lossless, which is the quickest to encode but makes the biggest videos. \
+						The higher it is, the smaller and blurrier the video, but every other value is much slower to encode.");
+				ui.end_row();
 			});
+			ui.checkbox(&mut settings.transparent, "Transparent background")
+				.on_hover_text("Only WebM videos can be transparent");
+			if cfg!(feature = "ffmpeg") {
+				ui.weak("MP4 videos are encoded with ffmpeg, which has to be installed");
+			} else {
+				ui.weak("Videos are saved as WebM");
+			}
+			export = ui.add_enabled(!rendering, egui::Button::new("Export...")).clicked();
 		});
-	});
-	egui::TopBottomPanel::bottom("frame_bar").show(&ctx, |ui| {
-		let play_button_text = match app.anim_playing {
-			true => "⏸",
-			false => "▶",
-		};
-		// let time = (Instant::now() - app_start).as_secs_f32();
-		match app.frame_range {
-			Some(ref range) => {
+		app.video_export.open = open;
+		if export {
+			let mut dialog = FileDialog::save_file(None);
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::ExportVideo, dialog));
+		}
+	}
+	if let Some(model) = app.model_data.as_ref() {
+		let mut open = app.sprite_export.open;
+		let mut export = false;
+		egui::Window::new("Export sprites").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.sprite_export;
+			let last_frame = model.frames.len().saturating_sub(1);
+			egui::Grid::new("sprite_export").num_columns(2).show(ui, |ui| {
+				ui.label("Name");
+				let response = ui.add(egui::TextEdit::singleline(&mut settings.prefix).desired_width(60.));
+				if response.changed() {
+					settings.prefix = settings.prefix.to_uppercase().chars()
+						.filter(char::is_ascii_alphanumeric).take(4).collect();
+				}
+				ui.end_row();
+				ui.label("Rotations");
 				ui.horizontal(|ui| {
-					if ui.button(play_button_text).clicked() {
-						app.anim_playing = !app.anim_playing;
-						if app.anim_playing {
-							app.anim_start_time = Instant::now();
-							app.anim_start_frame = app.current_frame;
-						}
-					}
-					if app.anim_playing {
-						app.current_frame = if let Bound::Included(&fc) = range.end_bound() {((Instant::now() - app.anim_start_time).as_secs_f32() + app.anim_start_frame) % fc} else {0.};
-					}
-					ui.spacing_mut().slider_width = 400.;
-					ui.add(egui::Slider::new(&mut app.current_frame, range.clone()));
+					ui.radio_value(&mut settings.rotations, 8, "8");
+					ui.radio_value(&mut settings.rotations, 16, "16 (GZDoom)");
 				});
-			},
-			None => ()
+				ui.end_row();
+				ui.label("Frames");
+				ui.horizontal(|ui| {
+					ui.add(egui::DragValue::new(&mut settings.first_frame)
+						.clamp_range(0..=settings.last_frame));
+					ui.label("to");
+					ui.add(egui::DragValue::new(&mut settings.last_frame)
+						.clamp_range(settings.first_frame..=last_frame));
+					ui.label("every");
+					ui.add(egui::DragValue::new(&mut settings.step).clamp_range(1..=last_frame.max(1)));
+				});
+				ui.end_row();
+				ui.label("Pixels per unit");
+				ui.add(egui::DragValue::new(&mut settings.scale).speed(0.05).clamp_range(0.1..=16.0));
+				ui.end_row();
+			});
+			let frames = settings.frames().count();
+			let valid = settings.prefix.len() == 4 && frames <= export::MAX_SPRITE_FRAMES;
+			match valid {
+				true => ui.label(format!("{} sprites", frames * settings.rotations)),
+				false => ui.colored_label(Color32::RED, format!(
+					"The name has to be 4 letters, and there can be up to {} frames", export::MAX_SPRITE_FRAMES)),
+			};
+			export = ui.add_enabled(valid, egui::Button::new("Export...")).clicked();
+		});
+		app.sprite_export.open = open;
+		if export {
+			let mut dialog = FileDialog::save_file(None)
+				.filter(String::from("png"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::ExportSprites, dialog));
 		}
-	});
-	let error_window = egui::Window::new("Error")
-		.default_height(200.).vscroll(true);
-	{
-		let el = &mut app.error_log;
-		if el.is_some() {
-			error_window.show(ctx, |ui| {
+	}
+	if app.model_data.is_some() {
+		let mut open = app.voxels.open;
+		let mut voxelize = false;
+		let mut export = false;
+		egui::Window::new("Voxelize (experimental)").open(&mut open).show(ctx, |ui| {
+			let preview = &mut app.voxels;
+			ui.horizontal(|ui| {
+				ui.label("Voxels along the longest side");
+				ui.add(egui::DragValue::new(&mut preview.resolution).clamp_range(1..=voxel::MAX_VOX_SIZE));
+			});
+			voxelize = ui.button("Voxelize current frame").clicked();
+			ui.checkbox(&mut preview.show, "Show voxels instead of the model");
+			if let Some(grid) = preview.grid.as_ref() {
+				ui.label(format!("Frame {}: {} voxels, {}×{}×{}, each {:.3} units across", preview.frame,
+					grid.voxels.len(), grid.size.x, grid.size.y, grid.size.z, grid.voxel_size));
+			}
+			export = ui.add_enabled(preview.grid.is_some(), egui::Button::new("Export .vox...")).clicked();
+		});
+		app.voxels.open = open;
+		if voxelize {
+			app.voxelize(&glc);
+		}
+		if export {
+			let mut dialog = FileDialog::save_file(None)
+				.filter(String::from("vox"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::ExportVox, dialog));
+		}
+	}
+	if app.model_data.is_some() {
+		let mut open = app.path_render.open;
+		let mut render = false;
+		egui::Window::new("Camera path").open(&mut open).show(ctx, |ui| {
+			let settings = &mut app.path_render;
+			ui.horizontal(|ui| {
+				ui.label("Size");
+				ui.add(egui::DragValue::new(&mut settings.width).clamp_range(16..=8192));
+				ui.label("x");
+				ui.add(egui::DragValue::new(&mut settings.height).clamp_range(16..=8192));
+			});
+			ui.horizontal(|ui| {
+				ui.label("Frames per second");
+				ui.add(egui::DragValue::new(&mut settings.fps).clamp_range(1..=240));
+			});
+			ui.separator();
+			let mut remove = None;
+			egui::Grid::new("camera_keyframes").show(ui, |ui| {
+				ui.label("Time");
+				ui.label("Position");
+				ui.label("Target");
+				ui.end_row();
+				app.camera_path.iter_mut().enumerate().for_each(|(index, kf)| {
+					ui.add(egui::DragValue::new(&mut kf.time).speed(0.05)
+						.clamp_range(0.0..=f32::MAX));
+					ui.label(format!("{:.1} {:.1} {:.1}", kf.position.x, kf.position.y, kf.position.z));
+					ui.label(format!("{:.1} {:.1} {:.1}", kf.target.x, kf.target.y, kf.target.z));
+					if ui.button("Remove").clicked() {
+						remove = Some(index);
+					}
+					ui.end_row();
+				});
+			});
+			if let Some(index) = remove {
+				app.camera_path.remove(index);
+			}
+			ui.horizontal(|ui| {
+				if ui.button("Add current view").clicked() {
+					let time = app.camera_path.last().map(|kf| kf.time + 1.).unwrap_or(0.);
+					app.camera_path.push(CameraKeyframe {
+						time,
+						position: app.camera.position() * md3_model_scale,
+						target: app.camera.target * md3_model_scale,
+					});
+				}
 				if ui.button("Clear").clicked() {
-					*el = None;
-				} else {
-					ui.label(el.as_ref().unwrap());
+					app.camera_path.clear();
 				}
 			});
+			if ui.add_enabled(!app.camera_path.is_empty() && app.render_job.is_none(),
+				egui::Button::new("Render")).clicked() {
+				render = true;
+			}
+		});
+		app.path_render.open = open;
+		if render {
+			let mut dialog = FileDialog::select_folder(None);
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::RenderCameraPath, dialog));
+		}
+	}
+	if let Some(job) = app.render_job.as_ref() {
+		let mut cancel = false;
+		egui::Window::new("Rendering").collapsible(false).resizable(false).show(ctx, |ui| {
+			let name = job.path().file_name().unwrap_or(job.path().as_os_str());
+			ui.label(format!("Saving {}", name.to_string_lossy()));
+			let done = job.done();
+			ui.add(egui::ProgressBar::new(done as f32 / job.frames as f32)
+				.text(format!("{} of {} frames", done, job.frames)));
+			cancel = ui.button("Cancel").clicked();
+		});
+		if cancel {
+			if let Some(job) = app.render_job.take() {
+				log::info!("Stopped saving {}", job.path().display());
+				job.cancel();
+			}
 		}
 	}
 	app.open_file_dialog.show(&ctx);
 	if app.open_file_dialog.selected() {
 		if let Some(fpath) = app.open_file_dialog.path() {
-			if let Err(e) = File::open(&fpath)
-				.map_err(AError::from).and_then(|mut f| {
-				md3::read_md3(&mut f).map_err(AError::from)
-			}).and_then(|model| {
-				let num_frames = model.frames.len();
-				app.frame_range = if num_frames > 1 {
-					Some(0.0..=(num_frames - 1) as f32)
-				} else {
-					None
+			app.start_loading(&fpath, AfterLoad::Nothing);
+		}
+	}
+	if let Some((purpose, dialog)) = app.file_dialog.as_mut() {
+		let purpose = *purpose;
+		if dialog.show(&ctx).selected() {
+			let fpath = dialog.path();
+			app.file_dialog = None;
+			if let Some(fpath) = fpath {
+				let result = match purpose {
+					DialogPurpose::OpenProject => app.open_project(&fpath),
+					DialogPurpose::SaveProject => app.save_project(&fpath),
+					DialogPurpose::ExportUvLayout => app.export_uv_layout(&fpath),
+					DialogPurpose::LoadSkybox => app.load_skybox(&glc, &fpath),
+					DialogPurpose::ExportVertexData => app.export_vertex_data(&fpath),
+					DialogPurpose::ExportPrintMesh => app.export_print_mesh(&fpath),
+					DialogPurpose::NormalMap(surface) => {
+						app.set_surface_map(&glc, surface, TextureMap::Normal, Some(fpath.clone()));
+						Ok(())
+					},
+					DialogPurpose::SpecularMap(surface) => {
+						app.set_surface_map(&glc, surface, TextureMap::Specular, Some(fpath.clone()));
+						Ok(())
+					},
+					DialogPurpose::RenderCameraPath => app.render_camera_path(&glc, &fpath),
+					#[cfg(not(target_arch = "wasm32"))]
+					DialogPurpose::ExportVideo => app.export_video(&glc, &fpath),
+					DialogPurpose::ExportSprites => app.export_sprites(&glc, &fpath),
+					DialogPurpose::ExportVox => app.export_vox(&fpath),
+					DialogPurpose::ContactSheet => app.render_contact_sheet(&glc, &fpath),
+					DialogPurpose::ExportFrameBounds => app.export_frame_bounds(&fpath),
+					DialogPurpose::ExportStructure { full } => app.export_structure(&fpath, full),
+					DialogPurpose::Screenshot => app.save_screenshot(&glc, &fpath),
+					DialogPurpose::SaveModel => app.save_model(&fpath),
+					DialogPurpose::AttachModel => app.attach_model(&glc, &fpath, true),
+					DialogPurpose::AddModel => app.attach_model(&glc, &fpath, false),
+					DialogPurpose::CompareModel => app.compare_with(&glc, &fpath),
+					DialogPurpose::SaveLods => app.save_lods(&fpath),
+					DialogPurpose::MergeSurfaces => app.merge_surfaces(&fpath),
+					DialogPurpose::SplitSurface => app.split_surface(&fpath),
+					DialogPurpose::Palette => palette::read_palette(&fpath)
+						.map(|_| app.set_palette(&glc, Some(fpath.clone()))),
+					DialogPurpose::FragmentShader => {
+						app.set_fragment_shader(&glc, Some(fpath.clone()));
+						Ok(())
+					},
+					DialogPurpose::AttachmentTexture(attachment) => {
+						app.set_attachment_texture(&glc, attachment, Some(fpath.clone()));
+						Ok(())
+					},
+					DialogPurpose::SurfaceTexture(surface) => {
+						app.set_surface_texture(&glc, surface, Some(fpath.clone()));
+						Ok(())
+					},
 				};
-				app.texture_cache.clear();
-				app.anim_playing = false;
-				app.current_frame = 0.;
-				app.model_data = Some(Box::new(model));
-				app.camera.distance = app.model_data.as_ref().unwrap().max_radius() * 2.;
-				app.models = app.model_data.as_ref().unwrap().surfaces
-				.iter().filter_map(|surf| {
-					let vb = VertexBuffer::from_surface(Arc::clone(&glc), surf);
-					let ib = IndexBuffer::from_surface(Arc::clone(&glc), surf);
-					let (an, rows_per_frame) = Texture::try_from_md3(Arc::clone(&glc), &surf).map_err(|e| {
-						let el = app.error_log.get_or_insert(String::new());
-						if !el.is_empty() { el.push('\n'); }
-						el.push_str(&e.to_string()); e}).ok()?;
-					Some(BasicModel {
-						vertex: vb,
-						index: ib,
-						shader: Rc::clone(&md3_shader),
-						uniforms: UniformsMD3 {
-							tex: {
-let (texture, error) = app.texture_cache.get(Arc::clone(&glc), &surf.shaders.get(0).map(|s|
-	Cow::from(OsString::from(fpath.parent().unwrap_or(&fpath).join(
-	String::from_utf8_stop(&s.name)
-	.trim_matches(|c| c == char::from_u32(0).unwrap())
-	.trim())))
-).unwrap_or(Cow::from(OsString::new())));
-if let Some(e) = error {
-	let el = app.error_log.get_or_insert(String::new());
-	if !el.is_empty() { el.push('\n'); }
-	el.push_str(&e.to_string());
-}
-texture
-							},
-							anim: Rc::new(an),
-							gzdoom: Default::default(),
-							eye: Default::default(),
-							frame: Default::default(),
-							mode: Default::default(),
-							rowsPerFrame: rows_per_frame as i32,
-						}
-					})
-				}).collect();
-				Ok(())
-			}) {
-				let el = app.error_log.get_or_insert(String::new());
-				if !el.is_empty() { el.push('\n'); }
-				el.push_str(&format!("Error reading file {}:\n{}", fpath.display(), e));
+				if let Err(e) = result {
+					app.report(Problem::error(Source::Other, format!("Error writing or reading {}:\n{}", fpath.display(), e))
+						.path(fpath));
+				}
 			}
+		} else if !dialog.visible() {
+			app.file_dialog = None;
 		}
 	}
-	egui::SidePanel::right("infoz").show(ctx, |ui| {
-		ui.heading("Shaders");
-		if let Some(model) = app.model_data.as_ref() {
-			model.surfaces.iter().enumerate().for_each(|(index, surf)| {
-				egui::CollapsingHeader::new(format!("Surface {}", index)).show(ui, |ui| {
-					surf.shaders.iter().for_each(|sdr| {
-						ui.label(String::from_utf8_stop(&sdr.name));
-					});
-				});
+	if let Some(discard) = app.discard_changes.as_ref() {
+		let mut save = false;
+		let mut answer = None;
+		egui::Window::new("Unsaved changes").collapsible(false).resizable(false)
+			.anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO).show(ctx, |ui| {
+			let (message, button) = match discard {
+				DiscardChanges::Load(..) => ("The changes to the model will be lost if a model is opened.", "Open anyway"),
+				DiscardChanges::Quit => ("The changes to the model will be lost if you quit.", "Quit without saving"),
+			};
+			ui.label(message);
+			ui.horizontal(|ui| {
+				save = ui.button("Save model as...").clicked();
+				if ui.button(button).clicked() {
+					answer = Some(true);
+				}
+				if ui.button("Cancel").clicked() {
+					answer = Some(false);
+				}
 			});
+		});
+		if save {
+			app.discard_changes = None;
+			let mut dialog = FileDialog::save_file(app.model_path.clone())
+				.filter(String::from("md3"));
+			dialog.open();
+			app.file_dialog = Some((DialogPurpose::SaveModel, dialog));
 		}
-	});
+		match (answer, app.discard_changes.take()) {
+			(Some(true), Some(DiscardChanges::Load(path, then))) => {
+				app.model_modified = false;
+				app.start_loading(&path, then);
+			},
+			(Some(true), Some(DiscardChanges::Quit)) => *control_flow = ControlFlow::ExitWithCode(0),
+			(Some(_), _) => (),
+			(None, discard) => app.discard_changes = discard,
+		}
+	}
 	// DRAW TAG NAMES AT TAG POSITIONS
 	// ==================================================================
-	if !app.open_file_dialog.visible(){
+	if !app.open_file_dialog.visible() && app.file_dialog.is_none() {
 	let painter = ctx.layer_painter(
 		LayerId { order: Order::Foreground, id: Id::new("tag_name_overlays") });
+	// The UI scale may be overridden, so this isn't always the window size
+	let screen_size = ctx.input().screen_rect().size();
+	let model_matrix = app.model_matrix();
 	if let Some(model) = app.model_data.as_ref() {
 		let current_frame = app.current_frame.floor() as usize;
 		let next_frame = app.current_frame.ceil() as usize;
 		let lerp_factor = app.current_frame.fract();
-		let num_tags = model.num_tags;
-		(0..num_tags).for_each(|tag_index| {
-			let tag_a = tag_index + num_tags * current_frame;
-			let tag_b = tag_index + num_tags * next_frame;
-			let tag_a = &model.tags[tag_a];
-			let tag_b = &model.tags[tag_b];
-			let tag_origin = lerp(tag_a.origin, tag_b.origin, lerp_factor);
-			let tag_name = String::from_utf8_stop(&tag_a.name).to_string();
-			let font = egui::style::default_text_styles()[&TextStyle::Small].clone();
-			let galley = painter.layout_no_wrap(tag_name, font, Color32::WHITE);
+		let num_tags = if model.tags.is_empty() { 0 } else { model.num_tags };
+		let view_projection = camera.view_projection() * model_matrix;
+		let view_origin = model_matrix.inverse().transform_point3(camera.position());
+		let mut labels: Vec<_> = (0..num_tags).filter(|i| !app.tag_editor.hidden_labels.contains(i))
+			.filter_map(|tag_index| {
+				let tag_a = &model.tags[tag_index + num_tags * current_frame];
+				let tag_b = &model.tags[tag_index + num_tags * next_frame];
+				let tag_origin = lerp(tag_a.origin, tag_b.origin, lerp_factor);
+				let clip = view_projection * tag_origin.extend(1.);
+				// Behind the camera
+				if clip.w <= 0. { return None; }
+				Some((clip.w, tag_origin, String::from_utf8_stop(&tag_a.name).to_string()))
+			}).collect();
+		// Nearest first, so that they're the ones which are drawn clearly
+		labels.sort_by(|a, b| a.0.total_cmp(&b.0));
+		let mut drawn: Vec<egui::Rect> = vec![];
+		labels.into_iter().for_each(|(_, tag_origin, tag_name)| {
+			// Tags often sit inside the model, so hidden ones are faded
+			// instead of left out
+			let occluded = model.surfaces.iter().enumerate()
+				.filter(|(surface, _)| !app.model_hidden && !app.surface_settings.get(*surface).is_some_and(|s| s.hidden))
+				.any(|(_, surf)| surf.blocks_segment(
+					current_frame.min(surf.num_frames.saturating_sub(1)), view_origin, tag_origin));
+			let (style, colour) = match app.controls.tag_mode {
+				true => (TextStyle::Heading, Color32::YELLOW),
+				false => (TextStyle::Small, Color32::WHITE),
+			};
+			let font = egui::style::default_text_styles()[&style].clone();
 			let pos = {
-				let pos = (app.camera.view_projection() * md3_model_matrix).project_point3(tag_origin);
+				let pos = view_projection.project_point3(tag_origin);
 				let Vec3 {x, y, ..} = pos;
-				let x = x.mul_add(0.5, 0.5) * window_size.width;
+				let x = x.mul_add(0.5, 0.5) * screen_size.x;
 				// In OpenGL NDC, +y is up and -y is down
-				let y = (-y).mul_add(0.5, 0.5) * window_size.height;
+				let y = (-y).mul_add(0.5, 0.5) * screen_size.y;
 				Pos2 {x, y}
 			};
+			let size = painter.layout_no_wrap(tag_name.clone(), font.clone(), colour).size();
+			let rect = egui::Rect::from_min_size(pos, size).expand(2.);
+			// Labels covered by nearer ones are faded too
+			let overlapping = drawn.iter().any(|r| r.intersects(rect));
+			drawn.push(rect);
+			let opacity = match (occluded, overlapping) {
+				(false, false) => 1.,
+				(true, true) => 0.15,
+				_ => 0.35,
+			};
+			let galley = painter.layout_no_wrap(tag_name, font, colour.linear_multiply(opacity));
+			if app.controls.tag_mode {
+				painter.rect_filled(rect, 2., Color32::from_black_alpha((160. * opacity) as u8));
+			}
+			painter.galley(pos, galley);
+		});
+		// Notes are drawn like sticky notes, a little above and to the right
+		// of where they are
+		let notes = app.notes.iter().filter(|_| !app.notes_window.hidden)
+			.filter(|n| n.frame.is_none_or(|f| f == current_frame));
+		notes.for_each(|note| {
+			let position = match app.note_position(note) {
+				Some(p) => p,
+				None => return,
+			};
+			let clip = view_projection * position.extend(1.);
+			if clip.w <= 0. { return; }
+			let ndc = clip.truncate() / clip.w;
+			let anchor = Pos2::new(ndc.x.mul_add(0.5, 0.5) * screen_size.x, (-ndc.y).mul_add(0.5, 0.5) * screen_size.y);
+			let font = egui::style::default_text_styles()[&TextStyle::Body].clone();
+			let galley = painter.layout(note.text.clone(), font, Color32::BLACK, 240.);
+			let pos = anchor + egui::vec2(12., -12. - galley.size().y);
+			let paper = Color32::from_rgb(255, 232, 128);
+			painter.line_segment([anchor, pos + egui::vec2(0., galley.size().y)], egui::Stroke::new(1., paper));
+			painter.circle_filled(anchor, 3., paper);
+			painter.rect_filled(egui::Rect::from_min_size(pos, galley.size()).expand(4.), 2., paper);
 			painter.galley(pos, galley);
 		});
+		// Tags selected in the outliner can be moved too
+		let outliner_tag = match app.outliner.selected.filter(|_| app.outliner.open) {
+			Some(SceneNode::Tag(index)) => Some(index),
+			_ => None,
+		};
+		let selected = app.tag_editor.selected.filter(|_| app.tag_editor.open).or(outliner_tag)
+			.filter(|&index| !app.outliner.is_locked(SceneNode::Tag(index)));
+		let tag = selected.and_then(|index| model.tags.get(current_frame * model.num_tags + index));
+		if let (Some(index), Some(tag)) = (selected, tag) {
+			let screen_size = Vec2::new(screen_size.x, screen_size.y);
+			if let Some(edit) = tag_gizmo(ctx, &painter, index, tag, view_projection,
+				view_origin, screen_size) {
+				app.edit_tag(&glc, edit);
+			}
+		}
 	}}
 });
 egui_glow.paint(wc.window());
 // SWAP BUFFERS
 // ==================================================================
 if let Err(e) = wc.swap_buffers() {
-	eprintln!("{:?}", e);
+	log::error!("{:?}", e);
+}
+frame_times.record(frame_start, Instant::now());
+// Keep drawing while the animation is playing or the camera is moving
+if app.animating() || repaint_after.is_zero() {
+	wc.window().request_redraw();
 }
+// Duration::MAX if egui doesn't need to be drawn again
+repaint_at = Instant::now().checked_add(repaint_after);
 			},
 			_ => ()
 		}
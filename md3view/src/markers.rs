@@ -0,0 +1,67 @@
+use anyhow::Error;
+use serde::{Serialize, Deserialize};
+use std::{
+	fs::{self, File},
+	io::{BufReader, BufWriter, ErrorKind},
+	path::{Path, PathBuf},
+};
+
+// A named point in a model's animation, like a footstep or a muzzle flash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Marker {
+	pub frame: usize,
+	pub name: String,
+}
+
+// Markers are kept beside the model, so model.md3 has model.markers.json
+pub fn sidecar_path(model: &Path) -> PathBuf {
+	model.with_extension("markers.json")
+}
+
+// The markers for a model, sorted by frame. Models without a sidecar file
+// don't have any markers, and neither do models opened in a browser, which
+// can't read files.
+pub fn read(model: &Path) -> Result<Vec<Marker>, Error> {
+	let file = match File::open(sidecar_path(model)) {
+		Ok(file) => file,
+		Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::Unsupported) => return Ok(vec![]),
+		Err(e) => return Err(e.into()),
+	};
+	let mut markers: Vec<Marker> = serde_json::from_reader(BufReader::new(file))?;
+	markers.sort_by_key(|m| m.frame);
+	Ok(markers)
+}
+
+// Save the markers for a model, or remove the sidecar file if there aren't any
+pub fn write(model: &Path, markers: &[Marker]) -> Result<(), Error> {
+	let path = sidecar_path(model);
+	if markers.is_empty() {
+		return match fs::remove_file(path) {
+			Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+			_ => Ok(()),
+		};
+	}
+	let writer = BufWriter::new(File::create(path)?);
+	serde_json::to_writer_pretty(writer, markers)?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sidecar_round_trip() {
+		let directory = std::env::temp_dir().join(format!("rustmd3view-markers-{}", std::process::id()));
+		fs::create_dir_all(&directory).unwrap();
+		let model = directory.join("lower.md3");
+		assert_eq!(sidecar_path(&model), directory.join("lower.markers.json"));
+		assert_eq!(read(&model).unwrap(), vec![]);
+		let marker = |frame, name: &str| Marker { frame, name: name.to_string() };
+		write(&model, &[marker(12, "footstep"), marker(3, "footstep")]).unwrap();
+		assert_eq!(read(&model).unwrap(), vec![marker(3, "footstep"), marker(12, "footstep")]);
+		write(&model, &[]).unwrap();
+		assert!(!sidecar_path(&model).exists());
+		fs::remove_dir_all(&directory).unwrap();
+	}
+}
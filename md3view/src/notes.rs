@@ -0,0 +1,79 @@
+use anyhow::Error;
+use glam::Vec3;
+use serde::{Serialize, Deserialize};
+use std::{
+	fs::{self, File},
+	io::{BufReader, BufWriter, ErrorKind},
+	path::{Path, PathBuf},
+};
+
+// Where a note is on the model. Positions are in model units, and notes on
+// tags follow them as they move.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NoteAnchor {
+	Position(Vec3),
+	Tag(String),
+}
+
+// Feedback from a reviewer, like "clipping here on frame 14"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+	pub anchor: NoteAnchor,
+	// Notes about one frame are only shown on that frame
+	#[serde(default)]
+	pub frame: Option<usize>,
+	pub text: String,
+}
+
+// Notes are kept beside the model, so model.md3 has model.notes.json
+pub fn sidecar_path(model: &Path) -> PathBuf {
+	model.with_extension("notes.json")
+}
+
+// Models without a sidecar file don't have any notes, like models opened in
+// a browser
+pub fn read(model: &Path) -> Result<Vec<Note>, Error> {
+	let file = match File::open(sidecar_path(model)) {
+		Ok(file) => file,
+		Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::Unsupported) => return Ok(vec![]),
+		Err(e) => return Err(e.into()),
+	};
+	Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+// Save the notes for a model, or remove the sidecar file if there aren't any
+pub fn write(model: &Path, notes: &[Note]) -> Result<(), Error> {
+	let path = sidecar_path(model);
+	if notes.is_empty() {
+		return match fs::remove_file(path) {
+			Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+			_ => Ok(()),
+		};
+	}
+	let writer = BufWriter::new(File::create(path)?);
+	serde_json::to_writer_pretty(writer, notes)?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sidecar_round_trip() {
+		let directory = std::env::temp_dir().join(format!("rustmd3view-notes-{}", std::process::id()));
+		fs::create_dir_all(&directory).unwrap();
+		let model = directory.join("upper.md3");
+		assert_eq!(sidecar_path(&model), directory.join("upper.notes.json"));
+		assert_eq!(read(&model).unwrap(), vec![]);
+		let notes = vec![
+			Note { anchor: NoteAnchor::Position(Vec3::new(1., 2., 3.)), frame: Some(14), text: String::from("clipping here") },
+			Note { anchor: NoteAnchor::Tag(String::from("tag_weapon")), frame: None, text: String::from("too low") },
+		];
+		write(&model, &notes).unwrap();
+		assert_eq!(read(&model).unwrap(), notes);
+		write(&model, &[]).unwrap();
+		assert!(!sidecar_path(&model).exists());
+		fs::remove_dir_all(&directory).unwrap();
+	}
+}
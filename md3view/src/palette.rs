@@ -0,0 +1,265 @@
+// 8-bit images from older id Tech games, which need a palette to be shown:
+// PCX (which usually has its own palette), Quake 2 WAL, Quake gfx lumps, and
+// Doom flats and patches. Models converted from those games often still have
+// their skins in these formats.
+use anyhow::Error;
+use once_cell::sync::Lazy;
+use std::{
+	fs,
+	ops::RangeInclusive,
+	path::{Path, PathBuf},
+	sync::RwLock,
+};
+
+const EXTENSIONS: [&str; 3] = ["pcx", "wal", "lmp"];
+
+// The last two rows of Quake's palette aren't affected by lighting
+pub const FULLBRIGHT: RangeInclusive<u8> = 224..=255;
+
+// Files the palette is usually in, relative to the game's base folder
+const PALETTE_FILES: [&str; 6] = [
+	"pics/colormap.pcx",
+	"gfx/palette.lmp",
+	"playpal.lmp",
+	"PLAYPAL.lmp",
+	"palette.lmp",
+	"palette.pal",
+];
+
+// The palette chosen by the user, which is used instead of looking for one
+static USER_PALETTE: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(None));
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette(pub [[u8; 3]; 256]);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedImage {
+	pub width: u32,
+	pub height: u32,
+	// None where the image is transparent
+	pub pixels: Vec<Option<u8>>,
+	// The image's own palette
+	pub palette: Option<Palette>,
+}
+
+impl IndexedImage {
+	pub fn has_fullbright(&self) -> bool {
+		self.pixels.iter().flatten().any(|p| FULLBRIGHT.contains(p))
+	}
+}
+
+pub fn is_palettized(path: &Path) -> bool {
+	path.extension().map(|e| e.to_string_lossy().to_lowercase())
+		.is_some_and(|e| EXTENSIONS.contains(&e.as_str()))
+}
+
+pub fn user_palette() -> Option<PathBuf> {
+	USER_PALETTE.read().ok()?.clone()
+}
+
+pub fn set_user_palette(path: Option<PathBuf>) {
+	if let Ok(mut palette) = USER_PALETTE.write() {
+		*palette = path;
+	}
+}
+
+pub fn read(path: &Path) -> Result<IndexedImage, Error> {
+	let data = fs::read(path)?;
+	let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+	match extension.as_str() {
+		"pcx" => decode_pcx(&data),
+		"wal" => decode_wal(&data),
+		_ => decode_lump(&data),
+	}
+}
+
+// The palette to use for an image without its own: the one chosen by the
+// user, or one in the folder the image is in or any folder above it
+pub fn find(image: &Path) -> Result<Palette, Error> {
+	if let Some(path) = user_palette() {
+		return read_palette(&path);
+	}
+	image.ancestors().skip(1)
+		.flat_map(|folder| PALETTE_FILES.iter().map(move |file| folder.join(file)))
+		.find(|path| path.is_file())
+		.ok_or_else(|| Error::msg(format!("Could not find a palette for {}. Choose one in View > Palette.", image.display())))
+		.and_then(|path| read_palette(&path))
+}
+
+// A PCX image's palette, 768 bytes of RGB (like palette.lmp and PLAYPAL, of
+// which the first palette is used), or a JASC palette
+pub fn read_palette(path: &Path) -> Result<Palette, Error> {
+	let data = fs::read(path)?;
+	if data.starts_with(b"JASC-PAL") {
+		return parse_jasc(&String::from_utf8_lossy(&data));
+	}
+	if data.first() == Some(&0x0a) && data.len() > 128 + 769 {
+		return pcx_palette(&data).ok_or_else(|| Error::msg(format!("{} has no palette", path.display())));
+	}
+	palette_from_bytes(&data).ok_or_else(|| Error::msg(format!("{} is not a palette", path.display())))
+}
+
+fn palette_from_bytes(data: &[u8]) -> Option<Palette> {
+	let data = data.get(..768)?;
+	let mut palette = [[0; 3]; 256];
+	palette.iter_mut().zip(data.chunks_exact(3)).for_each(|(colour, rgb)| colour.copy_from_slice(rgb));
+	Some(Palette(palette))
+}
+
+fn parse_jasc(text: &str) -> Result<Palette, Error> {
+	let mut palette = [[0; 3]; 256];
+	let colours: Vec<[u8; 3]> = text.lines().skip(3)
+		.filter_map(|line| {
+			let rgb: Vec<u8> = line.split_whitespace().filter_map(|c| c.parse().ok()).collect();
+			rgb.get(..3).map(|rgb| [rgb[0], rgb[1], rgb[2]])
+		}).collect();
+	if colours.is_empty() {
+		return Err(Error::msg("The JASC palette has no colours"));
+	}
+	palette.iter_mut().zip(colours).for_each(|(colour, rgb)| *colour = rgb);
+	Ok(Palette(palette))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+	data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+	data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// Version 5 PCX images have a 256 colour palette after the image data
+fn pcx_palette(data: &[u8]) -> Option<Palette> {
+	let start = data.len().checked_sub(769)?;
+	if data[start] != 0x0c { return None; }
+	palette_from_bytes(&data[start + 1..])
+}
+
+fn decode_pcx(data: &[u8]) -> Result<IndexedImage, Error> {
+	let invalid = || Error::msg("Invalid PCX image");
+	if data.len() < 128 || data[0] != 0x0a {
+		return Err(invalid());
+	}
+	let (bits, planes) = (data[3], data[65]);
+	if bits != 8 || planes != 1 {
+		return Err(Error::msg("Only 8-bit PCX images with a palette are supported"));
+	}
+	let (xmin, ymin) = (u16_at(data, 4).ok_or_else(invalid)?, u16_at(data, 6).ok_or_else(invalid)?);
+	let (xmax, ymax) = (u16_at(data, 8).ok_or_else(invalid)?, u16_at(data, 10).ok_or_else(invalid)?);
+	let width = xmax.checked_sub(xmin).ok_or_else(invalid)? as usize + 1;
+	let height = ymax.checked_sub(ymin).ok_or_else(invalid)? as usize + 1;
+	let line = (u16_at(data, 66).ok_or_else(invalid)? as usize).max(width);
+	let encoded = match data[2] {
+		1 => &data[128..],
+		_ => return Err(Error::msg("Only run length encoded PCX images are supported")),
+	};
+	// Runs can go over the end of a line, so the whole image is decoded
+	// before being cut into lines
+	let mut decoded = Vec::with_capacity(line * height);
+	let mut bytes = encoded.iter();
+	while decoded.len() < line * height {
+		let byte = *bytes.next().ok_or_else(invalid)?;
+		match byte {
+			0xc0.. => {
+				let value = *bytes.next().ok_or_else(invalid)?;
+				decoded.extend(std::iter::repeat_n(value, (byte & 0x3f) as usize));
+			},
+			_ => decoded.push(byte),
+		}
+	}
+	let pixels = decoded.chunks_exact(line).take(height)
+		.flat_map(|row| row[..width].iter().map(|&p| Some(p)))
+		.collect();
+	Ok(IndexedImage { width: width as u32, height: height as u32, pixels, palette: pcx_palette(data) })
+}
+
+// Quake 2 textures, which use the palette in pics/colormap.pcx
+fn decode_wal(data: &[u8]) -> Result<IndexedImage, Error> {
+	let invalid = || Error::msg("Invalid WAL texture");
+	let width = u32_at(data, 32).ok_or_else(invalid)?;
+	let height = u32_at(data, 36).ok_or_else(invalid)?;
+	let offset = u32_at(data, 40).ok_or_else(invalid)? as usize;
+	let size = width as usize * height as usize;
+	let pixels = data.get(offset..offset + size).ok_or_else(invalid)?;
+	Ok(IndexedImage { width, height, pixels: pixels.iter().map(|&p| Some(p)).collect(), palette: None })
+}
+
+// Quake gfx lumps have the size first, Doom flats are 64x64 with no header,
+// and anything else is tried as a Doom patch
+fn decode_lump(data: &[u8]) -> Result<IndexedImage, Error> {
+	let opaque = |width: u32, height: u32, pixels: &[u8]| IndexedImage {
+		width, height, pixels: pixels.iter().map(|&p| Some(p)).collect(), palette: None,
+	};
+	if let (Some(width), Some(height)) = (u32_at(data, 0), u32_at(data, 4)) {
+		if (width as u64 * height as u64) + 8 == data.len() as u64 {
+			return Ok(opaque(width, height, &data[8..]));
+		}
+	}
+	if data.len() == 64 * 64 {
+		return Ok(opaque(64, 64, data));
+	}
+	decode_patch(data)
+}
+
+// Columns of runs of pixels, with gaps where the patch is transparent
+fn decode_patch(data: &[u8]) -> Result<IndexedImage, Error> {
+	let invalid = || Error::msg("Not a Quake lump, Doom flat or Doom patch");
+	let width = u16_at(data, 0).ok_or_else(invalid)? as usize;
+	let height = u16_at(data, 2).ok_or_else(invalid)? as usize;
+	if width == 0 || height == 0 || width > 4096 || height > 4096 {
+		return Err(invalid());
+	}
+	let mut pixels = vec![None; width * height];
+	for column in 0..width {
+		let mut offset = u32_at(data, 8 + column * 4).ok_or_else(invalid)? as usize;
+		// Posts are in order from the top, ending with 0xff
+		loop {
+			let top = *data.get(offset).ok_or_else(invalid)?;
+			if top == 0xff { break; }
+			let length = *data.get(offset + 1).ok_or_else(invalid)? as usize;
+			let post = data.get(offset + 3..offset + 3 + length).ok_or_else(invalid)?;
+			post.iter().enumerate()
+				.filter(|(row, _)| top as usize + row < height)
+				.for_each(|(row, &p)| pixels[(top as usize + row) * width + column] = Some(p));
+			offset += length + 4;
+		}
+	}
+	Ok(IndexedImage { width: width as u32, height: height as u32, pixels, palette: None })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decode() {
+		// A 3x2 PCX image, with a run of 3 pixels and a run of 2
+		let mut pcx = vec![0; 128];
+		pcx[..4].copy_from_slice(&[0x0a, 5, 1, 8]);
+		pcx[8..12].copy_from_slice(&[2, 0, 1, 0]);
+		pcx[65] = 1;
+		pcx[66] = 3;
+		pcx.extend([0xc3, 7, 0xc2, 9, 4]);
+		pcx.push(0x0c);
+		pcx.extend((0..=255).flat_map(|i| [i, i, i]));
+		let image = decode_pcx(&pcx).unwrap();
+		assert_eq!((image.width, image.height), (3, 2));
+		assert_eq!(image.pixels, [7, 7, 7, 9, 9, 4].map(Some));
+		assert!(!image.has_fullbright());
+		assert_eq!(image.palette.unwrap().0[9], [9, 9, 9]);
+
+		// A 2x3 Doom patch with a gap in the second column
+		let mut patch = vec![2, 0, 3, 0, 0, 0, 0, 0, 16, 0, 0, 0, 24, 0, 0, 0];
+		patch.extend([0, 3, 0, 1, 2, 3, 0, 0xff]);
+		patch.extend([2, 1, 0, 4, 0, 0xff]);
+		let image = decode_lump(&patch).unwrap();
+		assert_eq!((image.width, image.height), (2, 3));
+		assert_eq!(image.pixels, [Some(1), None, Some(2), None, Some(3), Some(4)]);
+
+		// A Quake gfx lump
+		let image = decode_lump(&[1, 0, 0, 0, 2, 0, 0, 0, 5, 6]).unwrap();
+		assert_eq!(image.pixels, [Some(5), Some(6)]);
+		assert!(is_palettized(Path::new("skin.PCX")));
+		assert!(!is_palettized(Path::new("skin.tga")));
+	}
+}
@@ -0,0 +1,130 @@
+// Errors and warnings for the problems panel, with what they were about, so
+// they can be dealt with one by one instead of being piled into one message
+use std::{
+	fmt,
+	io,
+	path::{Path, PathBuf},
+	process::Command,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+	// Reading models, shader scripts and marker files
+	Parser,
+	Texture,
+	Graphics,
+	Other,
+}
+
+impl fmt::Display for Source {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Source::Parser => "Parser",
+			Source::Texture => "Texture",
+			Source::Graphics => "Graphics",
+			Source::Other => "Other",
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+// What to do again to try to fix the problem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Retry {
+	LoadModel(PathBuf),
+	ReloadModel,
+	// The texture, normal map and specular map of a surface of the model
+	SurfaceTextures(usize),
+	FragmentShader,
+}
+
+// A change to the files which makes the problem go away
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+	// Scale the texture to a size the engine can use, and save it
+	ResizeTexture { width: u32, height: u32 },
+}
+
+impl fmt::Display for Fix {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Fix::ResizeTexture { width, height } => write!(f, "Resize to {}×{} and save", width, height),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+	pub source: Source,
+	pub severity: Severity,
+	pub message: String,
+	// The file which couldn't be read or written
+	pub path: Option<PathBuf>,
+	// Index of the surface of the model
+	pub surface: Option<usize>,
+	pub retry: Option<Retry>,
+	pub fix: Option<Fix>,
+}
+
+impl Problem {
+	pub fn error(source: Source, message: impl fmt::Display) -> Self {
+		Self {
+			source,
+			severity: Severity::Error,
+			message: message.to_string(),
+			path: None,
+			surface: None,
+			retry: None,
+			fix: None,
+		}
+	}
+	pub fn warning(source: Source, message: impl fmt::Display) -> Self {
+		Self { severity: Severity::Warning, ..Self::error(source, message) }
+	}
+	pub fn path(self, path: impl Into<PathBuf>) -> Self {
+		Self { path: Some(path.into()), ..self }
+	}
+	pub fn surface(self, surface: usize) -> Self {
+		Self { surface: Some(surface), ..self }
+	}
+	pub fn retry(self, retry: Retry) -> Self {
+		Self { retry: Some(retry), ..self }
+	}
+	pub fn fix(self, fix: Fix) -> Self {
+		Self { fix: Some(fix), ..self }
+	}
+	// The first line of the message
+	pub fn summary(&self) -> &str {
+		self.message.lines().next().unwrap_or_default()
+	}
+}
+
+impl fmt::Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.severity == Severity::Warning {
+			write!(f, "Warning: ")?;
+		}
+		write!(f, "{}", self.message)
+	}
+}
+
+// Show the folder a file is in with the system's file manager
+pub fn open_containing_folder(path: &Path) -> io::Result<()> {
+	let folder = match path.is_dir() {
+		true => path,
+		false => path.parent().unwrap_or(path),
+	};
+	let program = if cfg!(windows) {
+		"explorer"
+	} else if cfg!(target_os = "macos") {
+		"open"
+	} else {
+		"xdg-open"
+	};
+	Command::new(program).arg(folder).spawn().map(|_| ())
+}
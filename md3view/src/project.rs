@@ -0,0 +1,319 @@
+use anyhow::Error;
+use crate::eye::{CameraKeyframe, OrbitCamera};
+use crate::renderer::BlendMode;
+use crate::settings::ModelSettings;
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use serde::{Serialize, Deserialize};
+use std::{
+	fmt,
+	fs::File,
+	io::{BufReader, BufWriter},
+	path::{Path, PathBuf},
+	str::FromStr,
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Project {
+	pub model: Option<PathBuf>,
+	#[serde(default)]
+	pub surfaces: Vec<SurfaceSettings>,
+	#[serde(default)]
+	pub camera_path: Vec<CameraKeyframe>,
+	// Bookmarked frames
+	#[serde(default)]
+	pub bookmarks: Vec<usize>,
+	#[serde(default)]
+	pub attachments: Vec<AttachmentSettings>,
+	#[serde(default)]
+	pub camera: Option<CameraSettings>,
+	#[serde(default)]
+	pub light: Option<LightSettings>,
+	#[serde(default)]
+	pub skybox: Option<PathBuf>,
+	#[serde(default)]
+	pub show_skybox: bool,
+	#[serde(default)]
+	pub sky_reflection: bool,
+	#[serde(default)]
+	pub frame: f32,
+	#[serde(default)]
+	pub display: DisplayTransform,
+}
+
+// Moves the model for viewing, without changing the model itself, e.g. to
+// stand a lower.md3 on the grid. In model space, before the Y axis is flipped.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DisplayTransform {
+	pub offset: Vec3,
+	// Yaw, pitch and roll in degrees, around Z, Y and X
+	pub rotation: Vec3,
+	pub scale: f32,
+}
+
+impl Default for DisplayTransform {
+	fn default() -> Self {
+		Self { offset: Vec3::ZERO, rotation: Vec3::ZERO, scale: 1. }
+	}
+}
+
+impl DisplayTransform {
+	pub fn matrix(&self) -> Mat4 {
+		let [yaw, pitch, roll] = self.rotation.to_array().map(f32::to_radians);
+		Mat4::from_scale_rotation_translation(Vec3::splat(self.scale),
+			Quat::from_euler(EulerRot::ZYX, yaw, pitch, roll), self.offset)
+	}
+	pub fn is_identity(&self) -> bool {
+		*self == Self::default()
+	}
+}
+
+// A model drawn at one of the tags of the main model, or on its own if the
+// tag is empty
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AttachmentSettings {
+	pub path: PathBuf,
+	pub tag: String,
+	#[serde(default)]
+	pub texture: Option<PathBuf>,
+	#[serde(default)]
+	pub hidden: bool,
+	#[serde(default)]
+	pub transform: DisplayTransform,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraSettings {
+	pub longitude: f32,
+	pub latitude: f32,
+	pub distance: f32,
+	pub fov: f32,
+	#[serde(default)]
+	pub orthographic: bool,
+	#[serde(default)]
+	pub target: Vec3,
+}
+
+impl From<&OrbitCamera> for CameraSettings {
+	fn from(camera: &OrbitCamera) -> Self {
+		Self {
+			longitude: camera.longtude,
+			latitude: camera.latitude,
+			distance: camera.distance,
+			fov: camera.fov,
+			orthographic: camera.orthographic,
+			target: camera.target,
+		}
+	}
+}
+
+impl CameraSettings {
+	// The aspect ratio and clipping planes are left alone
+	pub fn apply(&self, camera: &mut OrbitCamera) {
+		camera.longtude = self.longitude;
+		camera.latitude = self.latitude;
+		camera.distance = self.distance;
+		camera.fov = self.fov;
+		camera.orthographic = self.orthographic;
+		camera.target = self.target;
+	}
+}
+
+// What the camera is looking at, as a short string which can be pasted into
+// another copy of the viewer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewState {
+	pub camera: CameraSettings,
+	pub frame: f32,
+	pub view_mode: u32,
+}
+
+const VIEW_STATE_PREFIX: &str = "md3view:";
+
+impl fmt::Display for ViewState {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let CameraSettings { longitude, latitude, distance, fov, orthographic, target } = self.camera;
+		write!(f, "{}{},{},{},{},{},{},{},{},{},{}", VIEW_STATE_PREFIX, longitude, latitude, distance, fov,
+			orthographic as u8, target.x, target.y, target.z, self.frame, self.view_mode)
+	}
+}
+
+impl FromStr for ViewState {
+	type Err = Error;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let fields = s.trim().strip_prefix(VIEW_STATE_PREFIX)
+			.ok_or_else(|| Error::msg("This isn't a copied view"))?;
+		let values = fields.split(',').map(str::parse::<f32>).collect::<Result<Vec<f32>, _>>()?;
+		match values[..] {
+			[longitude, latitude, distance, fov, orthographic, x, y, z, frame, view_mode] => Ok(Self {
+				camera: CameraSettings {
+					longitude, latitude, distance, fov,
+					orthographic: orthographic != 0.,
+					target: Vec3::new(x, y, z),
+				},
+				frame,
+				view_mode: view_mode as u32,
+			}),
+			_ => Err(Error::msg(format!("A copied view has 10 numbers, not {}", values.len()))),
+		}
+	}
+}
+
+// The entity light
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LightSettings {
+	pub enabled: bool,
+	pub ambient: [f32; 3],
+	pub directed: [f32; 3],
+	pub yaw: f32,
+	pub pitch: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SurfaceSettings {
+	// Index into MD3Surface.shaders used for texturing
+	#[serde(default)]
+	pub shader: usize,
+	// Image used instead of the shader's texture
+	#[serde(default)]
+	pub texture: Option<PathBuf>,
+	// tcGen environment
+	#[serde(default)]
+	pub envmap: bool,
+	#[serde(default)]
+	pub normal_map: Option<PathBuf>,
+	#[serde(default)]
+	pub specular_map: Option<PathBuf>,
+	#[serde(default)]
+	pub hidden: bool,
+	#[serde(default)]
+	pub blend: BlendMode,
+	// Backface culling is off
+	#[serde(default)]
+	pub two_sided: bool,
+}
+
+impl Project {
+	pub fn read(path: impl AsRef<Path>) -> Result<Project, Error> {
+		let reader = BufReader::new(File::open(path.as_ref())?);
+		let mut project: Project = serde_json::from_reader(reader)?;
+		if let Some(dir) = path.as_ref().parent() {
+			project.map_paths(|p| dir.join(p));
+		}
+		Ok(project)
+	}
+	// Files next to the project, or in folders next to it, are saved
+	// relative to it, so the project can be shared along with them
+	pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+		let mut project = self.clone();
+		if let Some(dir) = path.as_ref().parent().and_then(|d| d.canonicalize().ok()) {
+			project.map_paths(|p| match p.canonicalize().ok().and_then(|c| c.strip_prefix(&dir).ok().map(Path::to_path_buf)) {
+				Some(relative) => relative,
+				None => p.to_path_buf(),
+			});
+		}
+		let writer = BufWriter::new(File::create(path)?);
+		serde_json::to_writer_pretty(writer, &project)?;
+		Ok(())
+	}
+	fn map_paths(&mut self, map: impl Fn(&Path) -> PathBuf) {
+		let paths = self.model.iter_mut()
+			.chain(self.skybox.iter_mut())
+			.chain(self.surfaces.iter_mut().flat_map(|s| s.texture.iter_mut()
+				.chain(s.normal_map.iter_mut())
+				.chain(s.specular_map.iter_mut())))
+			.chain(self.attachments.iter_mut().flat_map(|a| std::iter::once(&mut a.path)
+				.chain(a.texture.iter_mut())));
+		paths.for_each(|p| *p = map(p));
+	}
+	// The textures which will be needed once the model is loaded
+	pub fn textures(&self) -> impl Iterator<Item = &PathBuf> {
+		self.surfaces.iter()
+			.flat_map(|s| [&s.texture, &s.normal_map, &s.specular_map])
+			.flatten()
+	}
+}
+
+// The surfaces of a model which was just loaded: as they were saved in the
+// project, or with the shaders and textures remembered for the model when
+// it's opened on its own. The settings are only read, so opening a project
+// doesn't change what's remembered.
+pub fn restored_surfaces(loaded: &[SurfaceSettings], remembered: Option<&ModelSettings>, project: Option<&Project>) -> Vec<SurfaceSettings> {
+	loaded.iter().enumerate().map(|(surface, loaded)| match project {
+		Some(project) => project.surfaces.get(surface).cloned().unwrap_or_else(|| loaded.clone()),
+		None => SurfaceSettings {
+			shader: remembered.and_then(|m| m.shaders.get(&surface)).copied().unwrap_or(loaded.shader),
+			texture: remembered.and_then(|m| m.textures.get(&surface)).cloned().or_else(|| loaded.texture.clone()),
+			..loaded.clone()
+		},
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn relative_paths() {
+		let dir = std::env::temp_dir().join("rustmd3view_project_test");
+		std::fs::create_dir_all(dir.join("models")).unwrap();
+		let model = dir.join("models").join("head.md3");
+		File::create(&model).unwrap();
+		let project = Project {
+			model: Some(model.clone()),
+			skybox: Some(PathBuf::from("/nonexistent/sky.png")),
+			..Default::default()
+		};
+		let path = dir.join("scene.json");
+		project.write(&path).unwrap();
+		let json = std::fs::read_to_string(&path).unwrap();
+		assert!(json.contains("\"models/head.md3\""));
+		let read = Project::read(&path).unwrap();
+		assert_eq!(read.model.unwrap().canonicalize().unwrap(), model.canonicalize().unwrap());
+		assert_eq!(read.skybox, Some(PathBuf::from("/nonexistent/sky.png")));
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn opening_leaves_settings_unchanged() {
+		use crate::settings::Settings;
+		let model = Path::new("models/box.md3");
+		let mut settings = Settings::default();
+		settings.model_mut(model).textures.insert(0, PathBuf::from("box_red.png"));
+		settings.model_mut(model).shaders.insert(1, 2);
+		let before = serde_json::to_string(&settings).unwrap();
+		let project = Project {
+			model: Some(model.to_path_buf()),
+			surfaces: vec![
+				SurfaceSettings { texture: Some(PathBuf::from("box_blue.png")), ..Default::default() },
+				SurfaceSettings { hidden: true, ..Default::default() },
+			],
+			..Default::default()
+		};
+		let loaded = vec![SurfaceSettings::default(); 2];
+		let surfaces = restored_surfaces(&loaded, settings.model(model), Some(&project));
+		assert_eq!(surfaces[0].texture, Some(PathBuf::from("box_blue.png")));
+		assert_eq!((surfaces[1].shader, surfaces[1].hidden), (0, true));
+		assert_eq!(serde_json::to_string(&settings).unwrap(), before);
+		// Opened on its own, the model gets what was remembered
+		let surfaces = restored_surfaces(&loaded, settings.model(model), None);
+		assert_eq!(surfaces[0].texture, Some(PathBuf::from("box_red.png")));
+		assert_eq!(surfaces[1].shader, 2);
+	}
+
+	#[test]
+	fn view_state() {
+		let view = ViewState {
+			camera: CameraSettings {
+				longitude: 0.1, latitude: -0.25, distance: 96., fov: 1.2,
+				orthographic: true, target: Vec3::new(0., 4.5, -12.),
+			},
+			frame: 3.5,
+			view_mode: 2,
+		};
+		let text = view.to_string();
+		assert_eq!(text, "md3view:0.1,-0.25,96,1.2,1,0,4.5,-12,3.5,2");
+		assert_eq!(text.parse::<ViewState>().unwrap(), view);
+		assert!("md3view:1,2,3".parse::<ViewState>().is_err());
+		assert!("0.1,-0.25,96,1.2,1,0,4.5,-12,3.5,2".parse::<ViewState>().is_err());
+	}
+}
@@ -1,9 +1,11 @@
 use anyhow::Error as AError;
 use glam::{Vec2, Vec3, Mat4};
-use crate::md3::MD3Surface;
+use rustmd3::md3::MD3Surface;
 use crate::res::{Surface, SurfaceType};
-use glow::{Context, HasContext, NativeUniformLocation};
+use glow::{Context, HasContext, PixelPackData, UniformLocation};
+use image::{RgbaImage, imageops};
 use std::{
+	cell::{Cell, RefCell},
 	mem,
 	ops::{Deref, DerefMut},
 	rc::Rc,
@@ -11,11 +13,10 @@ use std::{
 	marker::PhantomData,
 };
 use bytemuck::{Pod, Zeroable};
-use crate::err_util::GLError;
+use crate::err_util::{self, GLError};
 use once_cell::race::OnceBox;
+use gl_macros::model_data;
 
-// #[macro_use]
-// mod macros;
 pub trait InterleavedVertexAttribute {
 	unsafe fn setup_vertex_attrs(glc: &Context);
 	fn stride() -> i32 where Self : Sized {
@@ -30,287 +31,151 @@ pub trait ShaderUniformLocations : Default {
 pub trait ShaderUniforms<L> where L: ShaderUniformLocations {
 	fn set(&self, glc: &Context, locations: &L) -> ();
 }
-// Brainstorming
-/* 
-// Input
-model_data!(MD3 {
+model_data!(#[derive(Debug, Clone)] MD3 {
 	attr index: u32,
 	attr uv: Vec2,
-	mut uniform gzdoom: bool,
-	uniform anim: Rc<Texture>,
-	mut uniform eye: Mat4,
-	mut uniform frame: f32,
-	mut uniform mode: u32,
+	// glam's Vec4 is 16-byte aligned, which would add padding
+	attr tangent: [f32; 4],
+	uniform gzdoom: bool,
+	#[buffer] uniform anim: Rc<Texture>,
+	uniform eye: Mat4,
+	// The Y axis flip, and the display transform
+	uniform model: Mat4,
+	uniform frame: f32,
+	uniform mode: u32,
 	uniform tex: Rc<Texture>,
-})
- */
-/* 
-// Output
+	uniform numVerts: i32,
+	uniform envmap: bool,
+	uniform viewOrigin: Vec3,
+	#[cube] uniform sky: Option<Rc<Texture>>,
+	uniform skyReflection: bool,
+	uniform normalMap: Option<Rc<Texture>>,
+	uniform specularMap: Option<Rc<Texture>>,
+	uniform alphaThreshold: f32,
+	uniform entityLight: bool,
+	uniform ambientLight: Vec3,
+	uniform directedLight: Vec3,
+	uniform lightDirection: Vec3,
+	// How far each vertex is from the same vertex in another model
+	#[buffer] uniform deviation: Option<Rc<Texture>>,
+	uniform maxDeviation: f32,
+	// Angle between the stored and calculated normal of each vertex
+	#[buffer] uniform normalError: Option<Rc<Texture>>,
+	uniform normalThreshold: f32,
+	// Used instead of tex in the UV checker view mode
+	uniform checker: Option<Rc<Texture>>,
+	// Push the vertices out along their normals, for the silhouette outline
+	uniform outline: f32,
+	// Drawn in the opaque pass, so the alpha of the texture isn't used
+	uniform opaque: bool,
+	// Texels of tex which aren't affected by lighting
+	uniform fullbright: Option<Rc<Texture>>,
+});
+
+model_data!(#[derive(Debug, Clone, Default)] Res {
+	attr position: Vec3,
+	attr colour: Vec3,
+	attr normal: Vec3,
+	uniform eye: Mat4,
+	uniform shaded: bool,
+});
+
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Zeroable, Pod, Default)]
-pub struct MD3Vertex {
-	index: u32,
-	uv: Vec2,
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VertexSprite {
+	pub position: Vec2,
+	pub size: Vec2,
 }
 
-impl InterleavedVertexAttribute for MD3Vertex {
+// By hand, like the model_data! vertices, so there's no unused padding check
+const _: () = assert!(mem::size_of::<VertexSprite>() == mem::size_of::<Vec2>() * 2);
+unsafe impl Zeroable for VertexSprite {}
+unsafe impl Pod for VertexSprite {}
+
+impl InterleavedVertexAttribute for VertexSprite {
 	unsafe fn setup_vertex_attrs(glc: &Context) {
 		let mut attrib_index = 0;
 		let mut offset = 0;
 		let stride = Self::stride();
 
-		glc.vertex_attrib_pointer_i32(attrib_index, 1, glow::UNSIGNED_INT,
-			stride, offset);
+		glc.vertex_attrib_pointer_f32(attrib_index, 2, glow::FLOAT, false, stride, offset);
 		glc.enable_vertex_attrib_array(attrib_index);
-		offset += mem::size_of::<u32>() as i32;
+		offset += mem::size_of::<Vec2>() as i32;
 		attrib_index += 1;
 
-		glc.vertex_attrib_pointer_f32(attrib_index, 2, glow::FLOAT, false,
-			stride, offset);
+		glc.vertex_attrib_pointer_f32(attrib_index, 2, glow::FLOAT, false, stride, offset);
 		glc.enable_vertex_attrib_array(attrib_index);
 		// offset += mem::size_of::<Vec2>() as i32;
 		// attrib_index += 1;
 	}
 }
 
-#[derive(Debug, Clone)]
-pub struct MD3Uniforms {
-	pub gzdoom: bool,
-	pub anim: Rc<Texture>,
-	pub eye: Mat4,
-	pub frame: f32,
-	pub mode: u32,
-	pub tex: Rc<Texture>,
-}
-
-#[derive(Debug, Clone, Default)]
-pub struct MD3UniformLocations {
-	gzdoom: Option<NativeUniformLocation>,
-	anim: Option<NativeUniformLocation>,
-	eye: Option<NativeUniformLocation>,
-	frame: Option<NativeUniformLocation>,
-	mode: Option<NativeUniformLocation>,
-	tex: Option<NativeUniformLocation>,
-}
-
-impl ShaderUniformLocations for MD3UniformLocations {
-	fn setup(&mut self, glc: &Context, program: <Context as HasContext>::Program) {
-		unsafe {
-			self.gzdoom = glc.get_uniform_location(program, "gzdoom");
-			self.anim = glc.get_uniform_location(program, "anim");
-			self.eye = glc.get_uniform_location(program, "eye");
-			self.frame = glc.get_uniform_location(program, "frame");
-			self.mode = glc.get_uniform_location(program, "mode");
-			self.tex = glc.get_uniform_location(program, "tex");
-		}
-	}
-}
-
-impl ShaderUniforms<MD3UniformLocations> for MD3Uniforms {
-	fn set(&self, glc: &Context, locations: &MD3UniformLocations) -> () {
-		let mut texture = TextureUnit::default();
-		unsafe {
-			glc.uniform_1_u32(locations.gzdoom.as_ref(), self.gzdoom as u32);
-
-			glc.active_texture(texture.slot());
-			glc.bind_texture(glow::TEXTURE_2D, Some(self.anim.tex()));
-			glc.uniform_1_i32(locations.anim.as_ref(), texture.uniform());
-
-			glc.uniform_matrix_4_f32_slice(locations.eye.as_ref(), false, self.eye.as_ref());
-
-			glc.uniform_1_f32(locations.frame.as_ref(), self.frame);
-
-			glc.uniform_1_u32(locations.mode.as_ref(), self.mode);
-
-			texture.next();
-			glc.active_texture(texture.slot());
-			glc.bind_texture(glow::TEXTURE_2D, Some(self.tex.tex()));
-			glc.uniform_1_i32(locations.tex.as_ref(), texture.uniform());
-		}
-	}
-}
- */
-
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Zeroable, Pod, Default)]
-pub struct VertexMD3 {
-	index: u32,
-	uv: Vec2,
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VertexScreen {
+	pub position: Vec2,
 }
 
-impl InterleavedVertexAttribute for VertexMD3 {
+const _: () = assert!(mem::size_of::<VertexScreen>() == mem::size_of::<Vec2>());
+unsafe impl Zeroable for VertexScreen {}
+unsafe impl Pod for VertexScreen {}
+
+impl InterleavedVertexAttribute for VertexScreen {
 	unsafe fn setup_vertex_attrs(glc: &Context) {
-		let mut attrib_index = 0;
-		let mut offset = 0;
+		let attrib_index = 0;
+		let offset = 0;
 		let stride = Self::stride();
 
-		glc.vertex_attrib_pointer_i32(attrib_index, 1, glow::UNSIGNED_INT,
-			stride, offset);
-		glc.enable_vertex_attrib_array(attrib_index);
-		offset += mem::size_of::<u32>() as i32;
-		attrib_index += 1;
-
-		glc.vertex_attrib_pointer_f32(attrib_index, 2, glow::FLOAT, false,
-			stride, offset);
+		glc.vertex_attrib_pointer_f32(attrib_index, 2, glow::FLOAT, false, stride, offset);
 		glc.enable_vertex_attrib_array(attrib_index);
-		// offset += mem::size_of::<Vec2>() as i32;
-		// attrib_index += 1;
 	}
 }
 
+// A triangle which covers the whole screen
+pub const SCREEN_TRIANGLE_V: [VertexScreen; 3] = [
+	VertexScreen { position: Vec2::new(-1., -1.) },
+	VertexScreen { position: Vec2::new(3., -1.) },
+	VertexScreen { position: Vec2::new(-1., 3.) },
+];
+pub const SCREEN_TRIANGLE_I: [u8; 3] = [0, 1, 2];
+
 // TODO: Macro-ize!
 #[allow(non_snake_case)]
 #[derive(Debug, Clone)]
-pub struct UniformsMD3 {
-	pub gzdoom: bool,
-	pub anim: Rc<Texture>,
-	pub eye: Mat4,
-	pub frame: f32,
-	pub mode: u32,
-	pub tex: Rc<Texture>,
-	pub rowsPerFrame: i32,
+pub struct UniformsSky {
+	pub invEye: Mat4,
+	pub sky: Rc<Texture>,
 }
 
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, Default)]
-pub struct UniformsMD3Locations {
-	gzdoom: Option<NativeUniformLocation>,
-	anim: Option<NativeUniformLocation>,
-	eye: Option<NativeUniformLocation>,
-	frame: Option<NativeUniformLocation>,
-	mode: Option<NativeUniformLocation>,
-	tex: Option<NativeUniformLocation>,
-	rowsPerFrame: Option<NativeUniformLocation>,
+pub struct UniformsSkyLocations {
+	invEye: Option<UniformLocation>,
+	sky: Option<UniformLocation>,
 }
 
-impl ShaderUniformLocations for UniformsMD3Locations {
+impl ShaderUniformLocations for UniformsSkyLocations {
 	fn setup(&mut self, glc: &Context, program: <Context as HasContext>::Program) {
 		unsafe {
-			self.gzdoom = glc.get_uniform_location(program, "gzdoom");
-			self.anim = glc.get_uniform_location(program, "anim");
-			self.eye = glc.get_uniform_location(program, "eye");
-			self.frame = glc.get_uniform_location(program, "frame");
-			self.mode = glc.get_uniform_location(program, "mode");
-			self.tex = glc.get_uniform_location(program, "tex");
-			self.rowsPerFrame = glc.get_uniform_location(program, "rowsPerFrame");
+			self.invEye = glc.get_uniform_location(program, "invEye");
+			self.sky = glc.get_uniform_location(program, "sky");
 		}
 	}
 }
 
-impl ShaderUniforms<UniformsMD3Locations> for UniformsMD3 {
-	fn set(&self, glc: &Context, locations: &UniformsMD3Locations) -> () {
-		let mut texture = TextureUnit::default();
+impl ShaderUniforms<UniformsSkyLocations> for UniformsSky {
+	fn set(&self, glc: &Context, locations: &UniformsSkyLocations) {
+		let texture = TextureUnit::default();
 		unsafe {
-			glc.uniform_1_u32(locations.gzdoom.as_ref(), self.gzdoom as u32);
+			glc.uniform_matrix_4_f32_slice(locations.invEye.as_ref(), false, self.invEye.as_ref());
 
 			glc.active_texture(texture.slot());
-			glc.bind_texture(glow::TEXTURE_2D, Some(self.anim.tex()));
-			glc.uniform_1_i32(locations.anim.as_ref(), texture.uniform());
-
-			glc.uniform_matrix_4_f32_slice(locations.eye.as_ref(), false, self.eye.as_ref());
-
-			glc.uniform_1_f32(locations.frame.as_ref(), self.frame);
-
-			glc.uniform_1_u32(locations.mode.as_ref(), self.mode);
-
-			texture.next();
-			glc.active_texture(texture.slot());
-			glc.bind_texture(glow::TEXTURE_2D, Some(self.tex.tex()));
-			glc.uniform_1_i32(locations.tex.as_ref(), texture.uniform());
-
-			glc.uniform_1_i32(locations.rowsPerFrame.as_ref(), self.rowsPerFrame);
+			glc.bind_texture(glow::TEXTURE_CUBE_MAP, Some(self.sky.tex()));
+			glc.uniform_1_i32(locations.sky.as_ref(), texture.uniform());
 		}
 	}
 }
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy, Zeroable, Pod, Default)]
-pub struct VertexRes {
-	pub position: Vec3,
-	pub colour: Vec3,
-	pub normal: Vec3,
-}
-
-impl InterleavedVertexAttribute for VertexRes {
-	unsafe fn setup_vertex_attrs(glc: &Context) {
-		let mut attrib_index = 0;
-		let mut offset = 0;
-		let stride = Self::stride();
-
-		glc.vertex_attrib_pointer_f32(attrib_index, 3, glow::FLOAT, false, stride, offset);
-		glc.enable_vertex_attrib_array(attrib_index);
-		offset += mem::size_of::<Vec3>() as i32;
-		attrib_index += 1;
-
-		glc.vertex_attrib_pointer_f32(attrib_index, 3, glow::FLOAT, false, stride, offset);
-		glc.enable_vertex_attrib_array(attrib_index);
-		offset += mem::size_of::<Vec3>() as i32;
-		attrib_index += 1;
-
-		glc.vertex_attrib_pointer_f32(attrib_index, 3, glow::FLOAT, false, stride, offset);
-		glc.enable_vertex_attrib_array(attrib_index);
-		// offset += mem::size_of::<Vec3>() as i32;
-		// attrib_index += 1;
-	}
-}
-
-// TODO: Macro-ize!
-#[derive(Debug, Clone, Default)]
-pub struct UniformsRes {
-	pub eye: Mat4,
-	pub shaded: bool,
-}
-
-#[derive(Debug, Clone, Default)]
-pub struct UniformsResLocations {
-	eye: Option<NativeUniformLocation>,
-	shaded: Option<NativeUniformLocation>,
-}
-
-impl ShaderUniformLocations for UniformsResLocations {
-	fn setup(&mut self, glc: &Context, program: <Context as HasContext>::Program) {
-		unsafe {
-			self.eye = glc.get_uniform_location(program, "eye");
-			self.shaded = glc.get_uniform_location(program, "shaded");
-		}
-	}
-}
-
-impl ShaderUniforms<UniformsResLocations> for UniformsRes {
-	fn set(&self, glc: &Context, locations: &UniformsResLocations) -> () {
-		let mut _texture = TextureUnit::default();
-		unsafe {
-			glc.uniform_matrix_4_f32_slice(locations.eye.as_ref(), false, self.eye.as_ref());
-			glc.uniform_1_u32(locations.shaded.as_ref(), self.shaded as u32);
-		}
-	}
-}
-
-#[repr(C)]
-#[derive(Debug, Clone, Copy, Zeroable, Pod, Default)]
-pub struct VertexSprite {
-	pub position: Vec2,
-	pub size: Vec2,
-}
-
-impl InterleavedVertexAttribute for VertexSprite {
-	unsafe fn setup_vertex_attrs(glc: &Context) {
-		let mut attrib_index = 0;
-		let mut offset = 0;
-		let stride = Self::stride();
-
-		glc.vertex_attrib_pointer_f32(attrib_index, 2, glow::FLOAT, false, stride, offset);
-		glc.enable_vertex_attrib_array(attrib_index);
-		offset += mem::size_of::<Vec2>() as i32;
-		attrib_index += 1;
-
-		glc.vertex_attrib_pointer_f32(attrib_index, 2, glow::FLOAT, false, stride, offset);
-		glc.enable_vertex_attrib_array(attrib_index);
-		// offset += mem::size_of::<Vec2>() as i32;
-		// attrib_index += 1;
-	}
-}
-
 #[derive(Debug)]
 pub struct VertexBuffer {
 	glc: Arc<Context>,
@@ -343,8 +208,14 @@ impl VertexBuffer {
 		}
 	}
 	pub fn from_surface(glc: Arc<Context>, surf: &MD3Surface) -> Self {
-		let buf: Vec<VertexMD3> = surf.texcoords.iter().enumerate()
-			.map(|(index, uv)| VertexMD3 {index: index as u32, uv: uv.0})
+		// Tangents are calculated for the first frame, and re-orthogonalized
+		// in the vertex shader for the other frames.
+		let tangents = surf.tangents(0);
+		let buf: Vec<VertexMD3> = surf.texcoords.iter().zip(tangents)
+			.enumerate()
+			.map(|(index, (uv, tangent))| VertexMD3 {
+				index: index as u32, uv: uv.0, tangent: tangent.to_array()
+			})
 			.collect();
 		VertexBuffer::new(glc, buf.into_boxed_slice())
 	}
@@ -353,7 +224,7 @@ impl VertexBuffer {
 impl Drop for VertexBuffer {
 	fn drop(&mut self) {
 		#[cfg(feature = "log_drop_gl_resources")]
-		println!("Drop VertexBuffer");
+		log::debug!("Drop VertexBuffer");
 		let glc = &self.glc;
 		unsafe {
 			glc.delete_vertex_array(self.vao);
@@ -405,7 +276,7 @@ impl IndexBuffer<u32> {
 impl<I> Drop for IndexBuffer<I> where I : IndexInteger + Pod {
 	fn drop(&mut self) {
 		#[cfg(feature = "log_drop_gl_resources")]
-		println!("Drop IndexBuffer");
+		log::debug!("Drop IndexBuffer");
 		let glc = &self.glc;
 		unsafe { glc.delete_buffer(self.ebo); }
 	}
@@ -415,49 +286,60 @@ impl<I> Drop for IndexBuffer<I> where I : IndexInteger + Pod {
 pub struct Texture {
 	glc: Arc<Context>,
 	tex: <Context as HasContext>::Texture,
+	// The data of buffer textures
+	buffer: Option<<Context as HasContext>::Buffer>,
 }
 
 impl Drop for Texture {
 	fn drop(&mut self) {
 		#[cfg(feature = "log_drop_gl_resources")]
-		println!("Drop Texture");
+		log::debug!("Drop Texture");
 		let glc = &self.glc;
 		unsafe {
 			glc.delete_texture(self.tex);
+			if let Some(buffer) = self.buffer {
+				glc.delete_buffer(buffer);
+			}
 		}
 	}
 }
 
+// Internal format, format, and data type for a surface
+fn surface_formats(texture_type: SurfaceType) -> (i32, u32, u32) {
+	// NOTE: 16-bit images are untested!
+	let tex_iformat: i32 = match texture_type {
+		SurfaceType::U8RGBA => glow::RGBA8,
+		SurfaceType::U8RGB => glow::RGB8,
+		SurfaceType::U16RGB => glow::RGB32F,
+		SurfaceType::U16RGBA => glow::RGBA32F,
+		SurfaceType::F32RGB => glow::RGB32F,
+		SurfaceType::F32RGBA => glow::RGBA32F,
+	}.try_into().unwrap();
+	let tex_format = match texture_type {
+		SurfaceType::U8RGBA => glow::RGBA,
+		SurfaceType::U8RGB => glow::RGB,
+		SurfaceType::U16RGB => glow::RGB16UI,
+		SurfaceType::U16RGBA => glow::RGBA16UI,
+		SurfaceType::F32RGB => glow::RGB32F,
+		SurfaceType::F32RGBA => glow::RGBA32F,
+	};
+	let data_type = match texture_type {
+		SurfaceType::U8RGBA => glow::UNSIGNED_BYTE,
+		SurfaceType::U8RGB => glow::UNSIGNED_BYTE,
+		SurfaceType::U16RGB => glow::UNSIGNED_SHORT,
+		SurfaceType::U16RGBA => glow::UNSIGNED_SHORT,
+		SurfaceType::F32RGB => glow::FLOAT,
+		SurfaceType::F32RGBA => glow::FLOAT,
+	};
+	(tex_iformat, tex_format, data_type)
+}
+
 impl Texture {
 	pub fn try_from_surface(glc: Arc<Context>, tex: &Surface) -> Result<Self, AError> {
 		unsafe {
 			let texture = glc.create_texture().map_err(AError::msg)?;
 			glc.bind_texture(glow::TEXTURE_2D, Some(texture));
-			// NOTE: 16-bit images are untested!
-			let tex_iformat: i32 = match tex.texture_type {
-				SurfaceType::U8RGBA => glow::RGBA32F,
-				SurfaceType::U8RGB => glow::RGB32F,
-				SurfaceType::U16RGB => glow::RGB32F,
-				SurfaceType::U16RGBA => glow::RGBA32F,
-				SurfaceType::F32RGB => glow::RGB32F,
-				SurfaceType::F32RGBA => glow::RGBA32F,
-			}.try_into().unwrap();
-			let tex_format = match tex.texture_type {
-				SurfaceType::U8RGBA => glow::RGBA,
-				SurfaceType::U8RGB => glow::RGB,
-				SurfaceType::U16RGB => glow::RGB16UI,
-				SurfaceType::U16RGBA => glow::RGBA16UI,
-				SurfaceType::F32RGB => glow::RGB32F,
-				SurfaceType::F32RGBA => glow::RGBA32F,
-			};
-			let data_type = match tex.texture_type {
-				SurfaceType::U8RGBA => glow::UNSIGNED_BYTE,
-				SurfaceType::U8RGB => glow::UNSIGNED_BYTE,
-				SurfaceType::U16RGB => glow::UNSIGNED_SHORT,
-				SurfaceType::U16RGBA => glow::UNSIGNED_SHORT,
-				SurfaceType::F32RGB => glow::FLOAT,
-				SurfaceType::F32RGBA => glow::FLOAT,
-			};
+			let (tex_iformat, tex_format, data_type) = surface_formats(tex.texture_type);
 			let min_filter = glow::LINEAR as i32;
 			let mag_filter = glow::LINEAR as i32;
 			glc.tex_image_2d(glow::TEXTURE_2D, 0, tex_iformat,
@@ -472,85 +354,244 @@ impl Texture {
 			Ok(Texture{
 				tex: texture,
 				glc,
+				buffer: None,
 			})
 		}
 	}
-	pub fn try_from_md3(glc: Arc<Context>, surf: &MD3Surface) -> Result<(Self, u32), AError> {
-		// Animations may need some additional processing
-		enum UploadError {
-			GLError(GLError),
-			Message(String),
-			TooBig,
+	// Replace the image data of an existing texture, e.g. when the file it was
+	// loaded from has changed.
+	pub fn update_from_surface(&self, tex: &Surface) -> Result<(), AError> {
+		let glc = &self.glc;
+		unsafe {
+			glc.bind_texture(glow::TEXTURE_2D, Some(self.tex));
+			let (tex_iformat, tex_format, data_type) = surface_formats(tex.texture_type);
+			glc.tex_image_2d(glow::TEXTURE_2D, 0, tex_iformat,
+				tex.width as i32, tex.height as i32, 0, tex_format,
+				data_type, Some(&tex.data));
+			let result = GLError::get(glc);
+			glc.bind_texture(glow::TEXTURE_2D, None);
+			Ok(result?)
 		}
-		fn try_upload(glc: &Context, width: i32, height: i32, data: &[u8]) -> Result<<Context as HasContext>::Texture, UploadError> {
-			let internal_format = glow::RGBA32I as i32;
-			let tex_format = glow::RGBA_INTEGER;
-			let data_type = glow::INT;
-			let target = glow::TEXTURE_2D;
-			unsafe {
-				let texture = glc.create_texture().map_err(UploadError::Message)?;
-				glc.bind_texture(target, Some(texture));
-				glc.tex_image_2d(target, 0, internal_format, width, height, 0, tex_format, data_type, Some(data));
-				match GLError::get(&glc) {
-					Ok(_) => Ok(texture),
-					Err(err) => {
-						glc.delete_texture(texture);
-						match err {
-							GLError::InvalidValue => Err(UploadError::TooBig),
-							e => Err(UploadError::GLError(e)),
-						}
-					},
-				}
-			}
+	}
+	// Use the nearest texel instead of blending between them, like software
+	// renderers do
+	pub fn set_nearest(&self, nearest: bool) {
+		let filter = if nearest { glow::NEAREST } else { glow::LINEAR } as i32;
+		let glc = &self.glc;
+		unsafe {
+			glc.bind_texture(glow::TEXTURE_2D, Some(self.tex));
+			glc.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, filter);
+			glc.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, filter);
+			glc.bind_texture(glow::TEXTURE_2D, None);
 		}
-		let mut width = surf.num_verts as i32;
-		let mut two_power = (1..MAX_TEXTURE_POT.get().copied().unwrap()).rev().filter(|&i| {
-			2i32.pow(i) < width
-		}).next().unwrap_or(0);
-		let mut rows_per_frame;
-		let tex_handle = loop {
-			let an = surf.make_animation(Some(width as usize));
-			rows_per_frame = an.rows_per_frame;
-			let height = (an.rows_per_frame * an.frames) as i32;
-			match try_upload(&glc, width, height, &an.data) {
-				Ok(tex) => {
-					break Ok(Texture {
-						glc: Arc::clone(&glc),
-						tex,
-					});
-				},
+	}
+	// A value for each vertex of each frame of a surface, laid out like the
+	// animation texture
+	pub fn try_from_vertex_values(glc: Arc<Context>, values: &[f32]) -> Result<Self, AError> {
+		let data: Vec<u8> = values.iter().copied().flat_map(f32::to_ne_bytes).collect();
+		Self::try_from_buffer(glc, (glow::R32F, glow::RED, glow::FLOAT), values.len(), &data)
+	}
+	// A 1D texture which shaders read with texelFetch, using a buffer, which
+	// can be much bigger than a 2D texture is wide. The format is the internal
+	// format, and the format and type of the data.
+	fn try_from_buffer(glc: Arc<Context>, format: (u32, u32, u32), texels: usize, data: &[u8]) -> Result<Self, AError> {
+		if !buffer_textures() {
+			return Self::try_from_rows(glc, format, texels, data);
+		}
+		let internal_format = format.0;
+		let max_texels = MAX_TEXTURE_BUFFER_SIZE.get().copied().unwrap_or(65536);
+		if texels > max_texels {
+			return Err(AError::msg(format!(
+				"Too much vertex data to upload to the GPU! ({} vertices, the maximum is {})",
+				texels, max_texels)));
+		}
+		let tex_buffer = TEX_BUFFER.get()
+			.ok_or_else(|| AError::msg("Buffer textures are not supported"))?;
+		unsafe {
+			let buffer = glc.create_buffer().map_err(AError::msg)?;
+			glc.bind_buffer(glow::TEXTURE_BUFFER, Some(buffer));
+			// Empty buffers can't be used
+			match data.is_empty() {
+				true => glc.buffer_data_size(glow::TEXTURE_BUFFER, 16, glow::STATIC_DRAW),
+				false => glc.buffer_data_u8_slice(glow::TEXTURE_BUFFER, data, glow::STATIC_DRAW),
+			}
+			// glow doesn't give out buffer names, but OpenGL does
+			let buffer_name = glc.get_parameter_i32(glow::TEXTURE_BUFFER) as u32;
+			glc.bind_buffer(glow::TEXTURE_BUFFER, None);
+			let texture = match glc.create_texture() {
+				Ok(texture) => texture,
 				Err(e) => {
-					match e {
-						UploadError::GLError(e) => break Err(AError::from(e)),
-						UploadError::Message(m) => break Err(AError::msg(m)),
-						UploadError::TooBig => {
-							if two_power > 0 {
-								width = 2i32.pow(two_power);
-								two_power -= 1;
-							} else {
-								break Err(AError::msg("Animation is too big to upload to the GPU!"));
-							}
-						},
-					}
+					glc.delete_buffer(buffer);
+					return Err(AError::msg(e));
 				},
-			}
-		}?;
-		let wrapping = glow::REPEAT as i32;
-		let filter = glow::NEAREST as i32;
-		let target = glow::TEXTURE_2D;
+			};
+			glc.bind_texture(glow::TEXTURE_BUFFER, Some(texture));
+			tex_buffer(glow::TEXTURE_BUFFER, internal_format, buffer_name);
+			let result = GLError::get(&glc);
+			glc.bind_texture(glow::TEXTURE_BUFFER, None);
+			let texture = Texture { tex: texture, glc, buffer: Some(buffer) };
+			result?;
+			Ok(texture)
+		}
+	}
+	// Faces are in OpenGL order: +X, -X, +Y, -Y, +Z, -Z
+	pub fn try_from_cube_faces(glc: Arc<Context>, faces: &[Surface; 6]) -> Result<Self, AError> {
+		let target = glow::TEXTURE_CUBE_MAP;
 		unsafe {
+			let texture = glc.create_texture().map_err(AError::msg)?;
+			glc.bind_texture(target, Some(texture));
+			for (face, tex) in faces.iter().enumerate() {
+				let (tex_iformat, tex_format, data_type) = surface_formats(tex.texture_type);
+				glc.tex_image_2d(glow::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32, 0,
+					tex_iformat, tex.width as i32, tex.height as i32, 0,
+					tex_format, data_type, Some(&tex.data));
+				if let Err(e) = GLError::get(&glc) {
+					glc.bind_texture(target, None);
+					glc.delete_texture(texture);
+					return Err(AError::from(e));
+				}
+			}
+			let wrapping = glow::CLAMP_TO_EDGE as i32;
+			let filter = glow::LINEAR as i32;
 			glc.tex_parameter_i32(target, glow::TEXTURE_WRAP_S, wrapping);
 			glc.tex_parameter_i32(target, glow::TEXTURE_WRAP_T, wrapping);
+			glc.tex_parameter_i32(target, glow::TEXTURE_WRAP_R, wrapping);
 			glc.tex_parameter_i32(target, glow::TEXTURE_MIN_FILTER, filter);
 			glc.tex_parameter_i32(target, glow::TEXTURE_MAG_FILTER, filter);
+			glc.bind_texture(target, None);
+			Ok(Texture{
+				tex: texture,
+				glc,
+				buffer: None,
+			})
+		}
+	}
+	// The positions and normals of every vertex in every frame, one after
+	// the other, in an RGBA16I buffer texture
+	// The same, as a 2D texture, filled row by row, for OpenGL ES 3.0
+	fn try_from_rows(glc: Arc<Context>, (internal_format, format, data_type): (u32, u32, u32), texels: usize, data: &[u8]) -> Result<Self, AError> {
+		let max_size = unsafe { glc.get_parameter_i32(glow::MAX_TEXTURE_SIZE) }.max(1) as usize;
+		let width = texels.clamp(1, max_size);
+		let height = texels.div_ceil(width).max(1);
+		if height > max_size {
+			return Err(AError::msg(format!(
+				"Too much vertex data to upload to the GPU! ({} vertices, the maximum is {})",
+				texels, max_size * max_size)));
 		}
-		Ok((tex_handle, rows_per_frame))
+		// The last row is filled up with zeroes. Empty textures are still
+		// big enough for one texel of any format.
+		let texel_size = data.len().checked_div(texels).unwrap_or(0);
+		let mut data = data.to_vec();
+		data.resize((width * height * texel_size).max(16), 0);
+		unsafe {
+			let texture = glc.create_texture().map_err(AError::msg)?;
+			glc.bind_texture(glow::TEXTURE_2D, Some(texture));
+			glc.tex_image_2d(glow::TEXTURE_2D, 0, internal_format as i32,
+				width as i32, height as i32, 0, format, data_type, Some(&data));
+			let result = GLError::get(&glc);
+			// Integer textures can't be filtered
+			glc.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::NEAREST as i32);
+			glc.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::NEAREST as i32);
+			glc.bind_texture(glow::TEXTURE_2D, None);
+			let texture = Texture { tex: texture, glc, buffer: None };
+			result?;
+			Ok(texture)
+		}
+	}
+	pub fn try_from_md3(glc: Arc<Context>, surf: &MD3Surface) -> Result<Self, AError> {
+		let animation = surf.make_animation(Some(surf.num_verts.max(1)));
+		Self::try_from_buffer(glc, (glow::RGBA16I, glow::RGBA_INTEGER, glow::SHORT),
+			surf.num_verts * surf.num_frames, &animation.data)
 	}
 	pub fn tex(&self) -> <Context as HasContext>::Texture {
 		self.tex
 	}
 }
 
+// An offscreen render target with colour and depth attachments
+#[derive(Debug)]
+pub struct Framebuffer {
+	glc: Arc<Context>,
+	fbo: <Context as HasContext>::Framebuffer,
+	colour: <Context as HasContext>::Renderbuffer,
+	depth: <Context as HasContext>::Renderbuffer,
+	pub width: u32,
+	pub height: u32,
+	// Viewport to restore when unbinding
+	old_viewport: [i32; 4],
+}
+
+impl Drop for Framebuffer {
+	fn drop(&mut self) {
+		#[cfg(feature = "log_drop_gl_resources")]
+		log::debug!("Drop Framebuffer");
+		let glc = &self.glc;
+		unsafe {
+			glc.delete_framebuffer(self.fbo);
+			glc.delete_renderbuffer(self.colour);
+			glc.delete_renderbuffer(self.depth);
+		}
+	}
+}
+
+impl Framebuffer {
+	pub fn new(glc: Arc<Context>, width: u32, height: u32) -> Result<Self, AError> {
+		unsafe {
+			let fbo = glc.create_framebuffer().map_err(AError::msg)?;
+			let colour = glc.create_renderbuffer().map_err(AError::msg)?;
+			let depth = glc.create_renderbuffer().map_err(AError::msg)?;
+			glc.bind_renderbuffer(glow::RENDERBUFFER, Some(colour));
+			glc.renderbuffer_storage(glow::RENDERBUFFER, glow::RGBA8, width as i32, height as i32);
+			glc.bind_renderbuffer(glow::RENDERBUFFER, Some(depth));
+			glc.renderbuffer_storage(glow::RENDERBUFFER, glow::DEPTH_COMPONENT24, width as i32, height as i32);
+			glc.bind_renderbuffer(glow::RENDERBUFFER, None);
+			glc.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+			glc.framebuffer_renderbuffer(glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0, glow::RENDERBUFFER, Some(colour));
+			glc.framebuffer_renderbuffer(glow::FRAMEBUFFER, glow::DEPTH_ATTACHMENT, glow::RENDERBUFFER, Some(depth));
+			let status = glc.check_framebuffer_status(glow::FRAMEBUFFER);
+			glc.bind_framebuffer(glow::FRAMEBUFFER, None);
+			let framebuffer = Framebuffer {
+				glc: Arc::clone(&glc),
+				fbo, colour, depth,
+				width, height,
+				old_viewport: [0; 4],
+			};
+			GLError::get(&glc)?;
+			if status != glow::FRAMEBUFFER_COMPLETE {
+				return Err(AError::msg(format!("Framebuffer is incomplete: {:#x}", status)));
+			}
+			Ok(framebuffer)
+		}
+	}
+	pub fn bind(&mut self) {
+		let glc = &self.glc;
+		unsafe {
+			glc.get_parameter_i32_slice(glow::VIEWPORT, &mut self.old_viewport);
+			glc.bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbo));
+			glc.viewport(0, 0, self.width as i32, self.height as i32);
+		}
+	}
+	pub fn unbind(&self) {
+		let glc = &self.glc;
+		let [x, y, width, height] = self.old_viewport;
+		unsafe {
+			glc.bind_framebuffer(glow::FRAMEBUFFER, None);
+			glc.viewport(x, y, width, height);
+		}
+	}
+	// Read the colour attachment. The framebuffer must be bound.
+	pub fn read_image(&self) -> RgbaImage {
+		let mut image = RgbaImage::new(self.width, self.height);
+		unsafe {
+			self.glc.read_pixels(0, 0, self.width as i32, self.height as i32,
+				glow::RGBA, glow::UNSIGNED_BYTE, PixelPackData::Slice(&mut image));
+		}
+		// OpenGL images are upside down
+		imageops::flip_vertical_in_place(&mut image);
+		image
+	}
+}
+
 pub enum ShaderStage {
 	Vertex,
 	Fragment,
@@ -571,9 +612,10 @@ impl From<ShaderStage> for u32 {
 pub struct ShaderProgram<L>
 where L: ShaderUniformLocations + Default {
 	glc: Arc<Context>,
-	prog: <Context as HasContext>::Program,
+	// Both can be replaced when the shaders are edited
+	prog: Cell<<Context as HasContext>::Program>,
 	// Make sure uniform structs match
-	locations: L,
+	locations: RefCell<L>,
 }
 
 impl<L> ShaderProgram<L>
@@ -581,20 +623,26 @@ where L: ShaderUniformLocations + Default {
 	pub fn activate(&self) -> Result<(), AError> {
 		let glc = &self.glc;
 		unsafe {
-			glc.use_program(Some(self.prog));
+			glc.use_program(Some(self.prog.get()));
 		}
 		Ok(())
 	}
+	// Use another program in place of this one, for everything sharing it.
+	// The old program is deleted.
+	pub fn replace(&self, other: ShaderProgram<L>) {
+		self.prog.swap(&other.prog);
+		self.locations.swap(&other.locations);
+	}
 }
 
 impl<L> Drop for ShaderProgram<L>
 where L: ShaderUniformLocations + Default {
 	fn drop(&mut self) {
 		#[cfg(feature = "log_drop_gl_resources")]
-		println!("Drop ShaderProgram");
+		log::debug!("Drop ShaderProgram");
 		let glc = &self.glc;
 		unsafe {
-			glc.delete_program(self.prog);
+			glc.delete_program(self.prog.get());
 		}
 	}
 }
@@ -628,7 +676,7 @@ where L: ShaderUniformLocations + Default {
 		for shader in self.shaders {
 			unsafe {
 				let gl_shader = glc.create_shader(shader.stage.into()).map_err(AError::msg)?;
-				glc.shader_source(gl_shader, shader.source);
+				glc.shader_source(gl_shader, &shader_source(&glc, shader.source));
 				glc.compile_shader(gl_shader);
 				if !glc.get_shader_compile_status(gl_shader) {
 					let e = Err(glc.get_shader_info_log(gl_shader));
@@ -666,14 +714,54 @@ where L: ShaderUniformLocations + Default {
 		};
 		Ok(ShaderProgram {
 			glc,
-			prog,
-			locations,
+			prog: Cell::new(prog),
+			locations: RefCell::new(locations),
 		})
 	}
 }
 
+// The shaders are written for OpenGL 3.3, so the #version line is changed
+// for OpenGL ES, and the things it doesn't have are left out
+fn shader_source(glc: &Context, source: &str) -> String {
+	let body = match source.starts_with("#version") {
+		true => source.split_once('\n').map_or("", |(_, body)| body),
+		false => source,
+	};
+	let mut header = match (glc.version().is_embedded, buffer_textures()) {
+		(false, _) => String::from("#version 330 core\n"),
+		(true, false) => String::from("#version 300 es\n"),
+		(true, true) => String::from("#version 320 es\nprecision highp isamplerBuffer;\nprecision highp samplerBuffer;\n"),
+	};
+	if glc.version().is_embedded {
+		header.push_str("precision highp float;\nprecision highp int;\n");
+		header.push_str("precision highp sampler2D;\nprecision highp isampler2D;\nprecision highp samplerCube;\n");
+	}
+	if buffer_textures() {
+		header.push_str("#define BUFFER_TEXTURES\n");
+	}
+	header + body
+}
+
 pub static MAX_TEXTURE_UNITS: OnceBox<u8> = OnceBox::new();
-pub static MAX_TEXTURE_POT: OnceBox<u32> = OnceBox::new();
+// Whether there are buffer textures, which OpenGL ES 3.0 doesn't have
+pub static BUFFER_TEXTURES: OnceBox<bool> = OnceBox::new();
+// glTexBuffer, which glow doesn't have, so it's loaded with the window
+pub type TexBufferFn = unsafe extern "system" fn(target: u32, internal_format: u32, buffer: u32);
+pub static TEX_BUFFER: OnceBox<TexBufferFn> = OnceBox::new();
+// In texels
+pub static MAX_TEXTURE_BUFFER_SIZE: OnceBox<usize> = OnceBox::new();
+
+pub fn buffer_textures() -> bool {
+	BUFFER_TEXTURES.get().copied().unwrap_or(false)
+}
+
+// Where textures made by try_from_buffer are bound
+pub fn buffer_texture_target() -> u32 {
+	match buffer_textures() {
+		true => glow::TEXTURE_BUFFER,
+		false => glow::TEXTURE_2D,
+	}
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct TextureUnit(pub u8);
@@ -773,14 +861,26 @@ impl<I, U, L> BasicModel<I, U, L> where
 {
 	pub fn render<F>(&mut self, glc: &Context, modify_uniforms: F) -> Result<(), AError>
 	where F: Fn(&mut U) -> () {
+		self.draw(glc, glow::TRIANGLES, modify_uniforms)
+	}
+	// Same as render, but the indices are pairs of line endpoints
+	pub fn render_lines<F>(&mut self, glc: &Context, modify_uniforms: F) -> Result<(), AError>
+	where F: Fn(&mut U) {
+		self.draw(glc, glow::LINES, modify_uniforms)
+	}
+	fn draw<F>(&mut self, glc: &Context, mode: u32, modify_uniforms: F) -> Result<(), AError>
+	where F: Fn(&mut U) {
 		self.shader.activate()?;
 		modify_uniforms(&mut self.uniforms);
-		self.uniforms.set(glc, &self.shader.locations);
+		self.uniforms.set(glc, &self.shader.locations.borrow());
 		unsafe {
 			glc.bind_vertex_array(Some(self.vertex.vao));
 			glc.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.index.ebo));
-			glc.draw_elements(glow::TRIANGLES, self.index.size, I::GL_TYPE, 0);
-			GLError::get(glc)?;
+			glc.draw_elements(mode, self.index.size, I::GL_TYPE, 0);
+			// Waiting for the error after every draw call is slow
+			if !err_util::debug_output() {
+				GLError::get(glc)?;
+			}
 		}
 		Ok(())
 	}
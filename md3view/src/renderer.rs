@@ -0,0 +1,236 @@
+use glam::{Vec2, Vec3, Mat4};
+use glow::{Context, HasContext};
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+use crate::eye::{Camera, LookAtCamera, OrbitCamera};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderPass {
+	Sky,
+	Opaque,
+	Transparent,
+	TagGizmos,
+	WorldAxes,
+	// Debugging aids, like the camera frustum
+	Overlays,
+}
+
+impl RenderPass {
+	// Everything, in the order it's drawn
+	pub const ALL: [RenderPass; 6] = [
+		RenderPass::Sky,
+		RenderPass::Opaque,
+		RenderPass::Transparent,
+		RenderPass::TagGizmos,
+		RenderPass::WorldAxes,
+		RenderPass::Overlays,
+	];
+	// Just the scene, for rendering to images
+	pub const SCENE: [RenderPass; 3] = [
+		RenderPass::Sky,
+		RenderPass::Opaque,
+		RenderPass::Transparent,
+	];
+
+	fn state(self) -> PassState {
+		match self {
+			RenderPass::Sky => PassState {
+				depth_func: glow::ALWAYS,
+				depth_write: false,
+				cull_face: None,
+				blend: None,
+			},
+			RenderPass::Opaque | RenderPass::TagGizmos => PassState::default(),
+			RenderPass::Transparent => PassState {
+				depth_write: false,
+				blend: Some((glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA)),
+				..Default::default()
+			},
+			// Always on top of the model
+			RenderPass::WorldAxes => PassState {
+				depth_func: glow::ALWAYS,
+				depth_write: false,
+				..Default::default()
+			},
+			RenderPass::Overlays => PassState {
+				depth_write: false,
+				..Default::default()
+			},
+		}
+	}
+	// Set up the GL state for this pass
+	pub fn begin(self, glc: &Context) {
+		let state = self.state();
+		unsafe {
+			glc.enable(glow::DEPTH_TEST);
+			glc.depth_func(state.depth_func);
+			glc.depth_mask(state.depth_write);
+			match state.cull_face {
+				Some(face) => {
+					glc.enable(glow::CULL_FACE);
+					glc.cull_face(face);
+				},
+				None => glc.disable(glow::CULL_FACE),
+			}
+			match state.blend {
+				Some((src, dst)) => {
+					glc.enable(glow::BLEND);
+					glc.blend_func(src, dst);
+				},
+				None => glc.disable(glow::BLEND),
+			}
+		}
+	}
+}
+
+// How a surface is combined with what's behind it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum BlendMode {
+	#[default]
+	Opaque,
+	// Opaque, but fragments with less alpha than the threshold are discarded
+	AlphaTest(f32),
+	AlphaBlend,
+	Additive,
+}
+
+impl BlendMode {
+	// Drawn in the transparent pass, after everything else in the scene
+	pub fn transparent(self) -> bool {
+		matches!(self, BlendMode::AlphaBlend | BlendMode::Additive)
+	}
+	pub fn name(self) -> &'static str {
+		match self {
+			BlendMode::Opaque => "Opaque",
+			BlendMode::AlphaTest(_) => "Alpha test",
+			BlendMode::AlphaBlend => "Alpha blend",
+			BlendMode::Additive => "Additive",
+		}
+	}
+	pub fn alpha_threshold(self) -> f32 {
+		match self {
+			BlendMode::AlphaTest(threshold) => threshold,
+			_ => 0.,
+		}
+	}
+	// Set the blend function for a surface in the transparent pass. The
+	// alpha is blended so that images with a transparent background can be
+	// put on top of something else.
+	pub fn apply(self, glc: &Context) {
+		let (src, dst) = match self {
+			BlendMode::Additive => (glow::ONE, glow::ONE),
+			_ => (glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA),
+		};
+		unsafe {
+			glc.blend_func_separate(src, dst, glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+		}
+	}
+}
+
+// Turn backface culling off for a two-sided surface, or back on again
+pub fn set_two_sided(glc: &Context, two_sided: bool) {
+	unsafe {
+		if two_sided {
+			glc.disable(glow::CULL_FACE);
+		} else {
+			glc.enable(glow::CULL_FACE);
+		}
+	}
+}
+
+// Cull the front faces instead of the back faces, for drawing outlines
+pub fn set_cull_front(glc: &Context, front: bool) {
+	unsafe {
+		glc.enable(glow::CULL_FACE);
+		glc.cull_face(if front { glow::FRONT } else { glow::BACK });
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PassState {
+	depth_func: u32,
+	depth_write: bool,
+	cull_face: Option<u32>,
+	// Source and destination factors
+	blend: Option<(u32, u32)>,
+}
+
+impl Default for PassState {
+	fn default() -> Self {
+		Self {
+			depth_func: glow::LESS,
+			depth_write: true,
+			cull_face: Some(glow::BACK),
+			blend: None,
+		}
+	}
+}
+
+// Where the scene is being looked at from. Everything is in world space.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+	pub view_projection: Mat4,
+	// View-projection matrix without translation, for the skybox
+	pub rotation_projection: Mat4,
+	pub position: Vec3,
+	pub forward: Vec3,
+	pub fov: f32,
+	pub aspect: f32,
+	// Logical pixels
+	pub size: Vec2,
+}
+
+impl View {
+	pub fn orbit(camera: &OrbitCamera, size: Vec2) -> Self {
+		Self {
+			view_projection: camera.view_projection(),
+			rotation_projection: camera.rotation_projection(),
+			position: camera.position(),
+			forward: camera.forward(),
+			fov: camera.fov,
+			aspect: camera.aspect,
+			size,
+		}
+	}
+	pub fn look_at(camera: &LookAtCamera, size: Vec2) -> Self {
+		Self {
+			view_projection: camera.view_projection(),
+			rotation_projection: camera.rotation_projection(),
+			position: camera.position,
+			forward: (camera.target - camera.position).normalize_or_zero(),
+			fov: camera.fov,
+			aspect: camera.aspect,
+			size,
+		}
+	}
+}
+
+pub trait Scene {
+	fn draw(&mut self, glc: &Arc<Context>, pass: RenderPass, view: &View);
+}
+
+// Clears the screen, and draws a scene one pass at a time
+pub struct Renderer {
+	glc: Arc<Context>,
+	passes: Vec<RenderPass>,
+}
+
+impl Renderer {
+	pub fn new(glc: Arc<Context>, passes: &[RenderPass]) -> Self {
+		Self { glc, passes: passes.to_vec() }
+	}
+	pub fn clear(&self) {
+		unsafe {
+			// Clearing the depth buffer does nothing if depth writes are off
+			self.glc.depth_mask(true);
+			self.glc.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+		}
+	}
+	pub fn render(&self, scene: &mut impl Scene, view: &View) {
+		self.clear();
+		self.passes.iter().for_each(|&pass| {
+			pass.begin(&self.glc);
+			scene.draw(&self.glc, pass, view);
+		});
+	}
+}
@@ -2,14 +2,15 @@ use anyhow::Error;
 use std::{
 	borrow::Cow,
 	env,
-	path::Path,
+	path::{Path, PathBuf},
 	fs::{self, File},
-	io::BufReader,
+	io::{BufRead, BufReader, Cursor, Seek},
 	ops::Deref,
 };
+use crate::palette::{self, IndexedImage, Palette};
 use crate::render::VertexRes;
-use glam::Vec3;
-use image::{io::Reader, ImageBuffer, Pixel, DynamicImage::*};
+use glam::{Vec2, Vec3};
+use image::{io::Reader, ImageBuffer, Pixel, RgbaImage, DynamicImage::*, imageops};
 use bytemuck::Pod;
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -46,9 +47,25 @@ pub struct Surface {
 
 impl Surface {
 	pub fn read_image(path: impl AsRef<Path>) -> Result<Surface, Error> {
-		use SurfaceType::*;
+		let path = path.as_ref();
+		if palette::is_palettized(path) {
+			let image = palette::read(path)?;
+			let palette = match image.palette.clone() {
+				Some(palette) => palette,
+				None => palette::find(path)?,
+			};
+			return Ok(Surface::from_indexed(&image, &palette));
+		}
 		let file_reader = BufReader::new(File::open(path)?);
-		let image = Reader::new(file_reader)
+		Surface::decode(Reader::new(file_reader))
+	}
+	// An image file which has already been read
+	pub fn from_image_bytes(bytes: &[u8]) -> Result<Surface, Error> {
+		Surface::decode(Reader::new(Cursor::new(bytes)))
+	}
+	fn decode(reader: Reader<impl BufRead + Seek>) -> Result<Surface, Error> {
+		use SurfaceType::*;
+		let image = reader
 			.with_guessed_format()?
 			.decode()?;
 		fn to_surface<P: Pixel, T>(buf: ImageBuffer<P, T>, fmt: SurfaceType) -> Surface
@@ -78,37 +95,272 @@ impl Surface {
 			_ => todo!(),
 		}
 	}
+	pub fn from_indexed(image: &IndexedImage, palette: &Palette) -> Surface {
+		let data: Vec<u8> = image.pixels.iter().flat_map(|pixel| match pixel {
+			Some(index) => {
+				let [r, g, b] = palette.0[*index as usize];
+				[r, g, b, 255]
+			},
+			None => [0; 4],
+		}).collect();
+		Surface {
+			width: image.width,
+			height: image.height,
+			texture_type: SurfaceType::U8RGBA,
+			data: data.into_boxed_slice(),
+		}
+	}
+	// White where the texels of an 8-bit texture are fullbright, or None if
+	// there aren't any
+	pub fn read_fullbright_mask(path: &Path) -> Result<Option<Surface>, Error> {
+		let image = palette::read(path)?;
+		if !image.has_fullbright() {
+			return Ok(None);
+		}
+		let data: Vec<u8> = image.pixels.iter()
+			.flat_map(|pixel| match pixel {
+				Some(index) if palette::FULLBRIGHT.contains(index) => [255; 4],
+				_ => [0, 0, 0, 255],
+			}).collect();
+		Ok(Some(Surface {
+			width: image.width,
+			height: image.height,
+			texture_type: SurfaceType::U8RGBA,
+			data: data.into_boxed_slice(),
+		}))
+	}
+	// The nearest texel to a texture coordinate, which wraps around like it
+	// does in the engine. Only 8-bit images can be read this way.
+	pub fn texel(&self, uv: Vec2) -> Option<[u8; 4]> {
+		let channels = match self.texture_type {
+			SurfaceType::U8RGBA => 4,
+			SurfaceType::U8RGB => 3,
+			_ => return None,
+		};
+		if self.width == 0 || self.height == 0 { return None; }
+		let x = ((uv.x.rem_euclid(1.) * self.width as f32) as u32).min(self.width - 1);
+		let y = ((uv.y.rem_euclid(1.) * self.height as f32) as u32).min(self.height - 1);
+		let start = (y * self.width + x) as usize * channels;
+		let texel = self.data.get(start..start + channels)?;
+		Some([texel[0], texel[1], texel[2], texel.get(3).copied().unwrap_or(255)])
+	}
+	pub fn from_rgba(image: RgbaImage) -> Surface {
+		let (width, height) = image.dimensions();
+		Surface {
+			width, height,
+			texture_type: SurfaceType::U8RGBA,
+			data: image.into_raw().into_boxed_slice(),
+		}
+	}
+	// Read the six faces of a skybox, in OpenGL cube map order. The path can
+	// be one of six Quake 3 sky images (foo_rt.tga, foo_bk.tga, etc.), a
+	// horizontal cross (4:3), or an equirectangular panorama (2:1).
+	pub fn read_skybox(path: impl AsRef<Path>) -> Result<[Surface; 6], Error> {
+		let path = path.as_ref();
+		let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+		let side = Q3_SKY_SUFFIXES.iter().find(|suf| stem.ends_with(*suf));
+		if let Some(side) = side {
+			let base = &stem[..stem.len() - side.len()];
+			let read_side = |suffix: &str| -> Result<RgbaImage, Error> {
+				let mut fname = format!("{}{}", base, suffix);
+				if let Some(ext) = path.extension() {
+					fname.push('.');
+					fname.push_str(&ext.to_string_lossy());
+				}
+				Ok(image::open(path.with_file_name(fname))?.into_rgba8())
+			};
+			// See the comment above Q3_SKY_SUFFIXES
+			let rt = read_side("_rt")?;
+			let bk = read_side("_bk")?;
+			let lf = read_side("_lf")?;
+			let ft = read_side("_ft")?;
+			let up = imageops::rotate270(&read_side("_up")?);
+			let dn = imageops::rotate90(&read_side("_dn")?);
+			return Ok([rt, bk, up, dn, lf, ft].map(Surface::from_rgba));
+		}
+		let image = image::open(path)?.into_rgba8();
+		let (width, height) = image.dimensions();
+		if width == height * 2 {
+			Ok(equirect_to_cube(&image))
+		} else if width * 3 == height * 4 {
+			// +Y
+			// -X +Z +X -Z
+			// -Y
+			let size = width / 4;
+			let tile = |x: u32, y: u32| {
+				Surface::from_rgba(imageops::crop_imm(&image, x * size, y * size, size, size).to_image())
+			};
+			Ok([tile(2, 1), tile(0, 1), tile(1, 0), tile(1, 2), tile(1, 1), tile(3, 1)])
+		} else {
+			Err(Error::msg(format!("{} is not a Quake 3 sky image, a horizontal cross, or an equirectangular panorama", path.display())))
+		}
+	}
+	// A checkerboard with `cells` squares along each side, each labelled with
+	// its column and row, for checking UV seams and texel density. The colours
+	// go from blue to red along U and get lighter along V, so flipped and
+	// mirrored UVs stand out.
+	pub fn checkerboard(cells: u32) -> Surface {
+		let cells = cells.clamp(1, CHECKER_MAX_CELLS);
+		let size = cells * CHECKER_CELL_SIZE;
+		let image = RgbaImage::from_fn(size, size, |x, y| {
+			let (column, row) = (x / CHECKER_CELL_SIZE, y / CHECKER_CELL_SIZE);
+			let (cx, cy) = (x % CHECKER_CELL_SIZE, y % CHECKER_CELL_SIZE);
+			// Only the top of the cell has the label in it
+			if cy < CHECKER_FONT_SCALE * 7 && label_pixel(&format!("{},{}", column, row), cx, cy) {
+				return image::Rgba([0, 0, 0, 255]);
+			}
+			let u = (column as f32 + 0.5) / cells as f32;
+			let v = (row as f32 + 0.5) / cells as f32;
+			let shade = if (column + row) % 2 == 0 { 1. } else { 0.6 };
+			let channel = |c: f32| ((c * (0.5 + v * 0.5) * shade).clamp(0., 1.) * 255.) as u8;
+			image::Rgba([channel(u), channel(0.5), channel(1. - u), 255])
+		});
+		Surface::from_rgba(image)
+	}
+}
+
+pub const CHECKER_MAX_CELLS: u32 = 32;
+const CHECKER_CELL_SIZE: u32 = 64;
+// Each pixel of the 3x5 digits is drawn as a square this big
+const CHECKER_FONT_SCALE: u32 = 2;
+
+// 3x5 pixel digits and a comma, one row per 3 bits, top row first
+const CHECKER_FONT: [(char, [u8; 5]); 11] = [
+	('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+	('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+	('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+	('3', [0b111, 0b001, 0b011, 0b001, 0b111]),
+	('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+	('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+	('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+	('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+	('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+	('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+	(',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+];
+
+// Whether a pixel of a checkerboard cell is part of its label, which is
+// drawn near the top left corner
+fn label_pixel(label: &str, x: u32, y: u32) -> bool {
+	let margin = CHECKER_FONT_SCALE * 2;
+	let (x, y) = match (x.checked_sub(margin), y.checked_sub(margin)) {
+		(Some(x), Some(y)) => (x / CHECKER_FONT_SCALE, y / CHECKER_FONT_SCALE),
+		_ => return false,
+	};
+	// 3 pixels wide with a gap of 1
+	let (index, column) = (x as usize / 4, x % 4);
+	let glyph = label.chars().nth(index)
+		.and_then(|c| CHECKER_FONT.iter().find(|(g, _)| *g == c));
+	match glyph {
+		Some((_, rows)) if column < 3 && y < 5 => rows[y as usize] & (0b100 >> column) != 0,
+		_ => false,
+	}
+}
+
+// Quake sky to OpenGL cube map:
+// Quake 3 sky images are named after the sides they are on, and the cube map
+// is sampled using the Quake direction with Y and Z swapped. The sides of the
+// sky end up on the cube map faces like so:
+// rt (+X) -> +X, bk (-X) -> -X, lf (+Y) -> +Z, ft (-Y) -> -Z
+// up (+Z) -> +Y (rotated 90 degrees counter-clockwise)
+// dn (-Z) -> -Y (rotated 90 degrees clockwise)
+const Q3_SKY_SUFFIXES: [&str; 6] = ["_rt", "_bk", "_lf", "_ft", "_up", "_dn"];
+
+fn equirect_to_cube(image: &RgbaImage) -> [Surface; 6] {
+	use std::f32::consts::{PI, TAU};
+	let (width, height) = image.dimensions();
+	let size = (width / 4).max(1);
+	let face = |direction: &dyn Fn(f32, f32) -> Vec3| {
+		let face = RgbaImage::from_fn(size, size, |x, y| {
+			// Cube map face coordinates, from -1 to 1
+			let s = (x as f32 + 0.5) / size as f32 * 2. - 1.;
+			let t = (y as f32 + 0.5) / size as f32 * 2. - 1.;
+			// OpenGL direction to Quake direction
+			let dir = direction(s, t).normalize();
+			let dir = Vec3::new(dir.x, dir.z, dir.y);
+			let longitude = dir.y.atan2(dir.x);
+			let latitude = dir.z.asin();
+			let u = (0.5 - longitude / TAU) * width as f32;
+			let v = (0.5 - latitude / PI) * height as f32;
+			*image.get_pixel((u as u32).min(width - 1), (v as u32).min(height - 1))
+		});
+		Surface::from_rgba(face)
+	};
+	// See the OpenGL specification, section 8.13, "Cube Map Texture Selection"
+	[
+		face(&|s, t| Vec3::new(1., -t, -s)),
+		face(&|s, t| Vec3::new(-1., -t, s)),
+		face(&|s, t| Vec3::new(s, 1., t)),
+		face(&|s, t| Vec3::new(s, -1., -t)),
+		face(&|s, t| Vec3::new(s, -t, 1.)),
+		face(&|s, t| Vec3::new(-s, -t, -1.)),
+	]
 }
 
 pub struct AppResources {
+	// The assets folder, if there is one
+	pub path: Option<PathBuf>,
 	pub null_surface: Surface,
 	pub md3_pixel_shader: String,
 	pub md3_vertex_shader: String,
 	pub res_pixel_shader: String,
 	pub res_vertex_shader: String,
+	pub sky_pixel_shader: String,
+	pub sky_vertex_shader: String,
 }
 
+// Copies of the assets built into the executable, so that it can be run
+// from anywhere
+const EMBEDDED_ASSETS: [(&str, &[u8]); 7] = [
+	("null.png", include_bytes!("../assets/null.png")),
+	("md3.vert", include_bytes!("../assets/md3.vert")),
+	("md3.frag", include_bytes!("../assets/md3.frag")),
+	("res.vert", include_bytes!("../assets/res.vert")),
+	("res.frag", include_bytes!("../assets/res.frag")),
+	("sky.vert", include_bytes!("../assets/sky.vert")),
+	("sky.frag", include_bytes!("../assets/sky.frag")),
+];
+
 impl AppResources {
+	// Files in the assets folder are used instead of the built in ones, so
+	// that shaders can be changed without rebuilding. The folder is the given
+	// path, or "assets" in the current directory or next to the executable.
 	pub fn try_load(path: Option<impl AsRef<Path>>) -> Result<Box<AppResources>, Error> {
-		let path = match path {
-			Some(ref p) => Cow::from(p.as_ref()),
-			None => {
-				let mut pwd = env::current_dir()?;
-				pwd.push("assets");
-				Cow::from(pwd)
-			},
+		let path: Option<PathBuf> = match path {
+			Some(p) => Some(p.as_ref().to_path_buf()),
+			None => [env::current_dir().ok(), env::current_exe().ok()
+				.and_then(|exe| exe.parent().map(Path::to_path_buf))].into_iter()
+				.flatten()
+				.map(|directory| directory.join("assets"))
+				.find(|directory| directory.is_dir()),
+		};
+		let read = |name: &str| -> Result<Cow<'static, [u8]>, Error> {
+			match path.as_ref().map(|p| p.join(name)).filter(|file| file.is_file()) {
+				Some(file) => Ok(Cow::from(fs::read(file)?)),
+				None => EMBEDDED_ASSETS.iter().find(|(n, _)| *n == name)
+					.map(|(_, bytes)| Cow::from(*bytes))
+					.ok_or_else(|| Error::msg(format!("No asset called {}", name))),
+			}
+		};
+		let read_string = |name: &str| -> Result<String, Error> {
+			Ok(String::from_utf8(read(name)?.into_owned())?)
 		};
-		let null_texture = Surface::read_image(path.join("null.png"))?;
-		let md3_vertex_shader = fs::read_to_string(path.join("md3.vert"))?;
-		let md3_pixel_shader = fs::read_to_string(path.join("md3.frag"))?;
-		let res_vertex_shader = fs::read_to_string(path.join("res.vert"))?;
-		let res_pixel_shader = fs::read_to_string(path.join("res.frag"))?;
+		let null_texture = Surface::from_image_bytes(&read("null.png")?)?;
+		let md3_vertex_shader = read_string("md3.vert")?;
+		let md3_pixel_shader = read_string("md3.frag")?;
+		let res_vertex_shader = read_string("res.vert")?;
+		let res_pixel_shader = read_string("res.frag")?;
+		let sky_vertex_shader = read_string("sky.vert")?;
+		let sky_pixel_shader = read_string("sky.frag")?;
 		Ok(Box::new(AppResources {
+			path,
 			null_surface: null_texture,
 			md3_pixel_shader,
 			md3_vertex_shader,
 			res_pixel_shader,
 			res_vertex_shader,
+			sky_pixel_shader,
+			sky_vertex_shader,
 		}))
 	}
 }
@@ -206,3 +458,67 @@ VertexRes { position: Vec3::new(30.0, -1.0, 1.0), colour: Vec3::new(1.0, 0.0, 0.
 VertexRes { position: Vec3::new(30.0, 1.0, 1.0), colour: Vec3::new(1.0, 0.0, 0.0), normal: Vec3::new(0.0, 0.0, 1.0) }
 ];
 pub const TAGAXES_I: [u8; 90] = [0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7, 8, 9, 10, 8, 10, 11, 12, 13, 14, 12, 14, 15, 16, 17, 18, 16, 18, 19, 20, 21, 22, 20, 22, 23, 24, 25, 26, 24, 26, 27, 28, 29, 30, 28, 30, 31, 32, 33, 34, 32, 34, 35, 36, 37, 38, 36, 38, 39, 40, 41, 42, 40, 42, 43, 44, 45, 46, 44, 46, 47, 48, 49, 50, 48, 50, 51, 52, 53, 54, 52, 54, 55, 56, 57, 58, 56, 58, 59];
+
+// Corners of the clip space volume, for drawing camera frustums as lines.
+// The near plane is yellow, and the far plane is red.
+pub const FRUSTUM_V: [VertexRes; 8] = [
+VertexRes { position: Vec3::new(-1.0, -1.0, 0.0), colour: Vec3::new(1.0, 1.0, 0.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(1.0, -1.0, 0.0), colour: Vec3::new(1.0, 1.0, 0.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(1.0, 1.0, 0.0), colour: Vec3::new(1.0, 1.0, 0.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(-1.0, 1.0, 0.0), colour: Vec3::new(1.0, 1.0, 0.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(-1.0, -1.0, 1.0), colour: Vec3::new(1.0, 0.0, 0.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(1.0, -1.0, 1.0), colour: Vec3::new(1.0, 0.0, 0.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(1.0, 1.0, 1.0), colour: Vec3::new(1.0, 0.0, 0.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(-1.0, 1.0, 1.0), colour: Vec3::new(1.0, 0.0, 0.0), normal: Vec3::ZERO },
+];
+// A unit cube, for drawing bounding boxes as lines
+pub const BOUNDS_V: [VertexRes; 8] = [
+VertexRes { position: Vec3::new(0.0, 0.0, 0.0), colour: Vec3::new(1.0, 1.0, 1.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(1.0, 0.0, 0.0), colour: Vec3::new(1.0, 1.0, 1.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(1.0, 1.0, 0.0), colour: Vec3::new(1.0, 1.0, 1.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(0.0, 1.0, 0.0), colour: Vec3::new(1.0, 1.0, 1.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(0.0, 0.0, 1.0), colour: Vec3::new(1.0, 1.0, 1.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(1.0, 0.0, 1.0), colour: Vec3::new(1.0, 1.0, 1.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(1.0, 1.0, 1.0), colour: Vec3::new(1.0, 1.0, 1.0), normal: Vec3::ZERO },
+VertexRes { position: Vec3::new(0.0, 1.0, 1.0), colour: Vec3::new(1.0, 1.0, 1.0), normal: Vec3::ZERO },
+];
+// Edges of FRUSTUM_V and BOUNDS_V
+pub const CUBE_LINES_I: [u8; 24] = [0, 1, 1, 2, 2, 3, 3, 0, 4, 5, 5, 6, 6, 7, 7, 4, 0, 4, 1, 5, 2, 6, 3, 7];
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn embedded_assets() {
+		// Everything is built in when the assets folder doesn't exist
+		let res = AppResources::try_load(Some("does/not/exist")).unwrap();
+		assert!(res.null_surface.width > 0 && res.null_surface.height > 0);
+		assert!(res.md3_vertex_shader.starts_with("#version"));
+	}
+
+	#[test]
+	fn checkerboard() {
+		let checker = Surface::checkerboard(4);
+		assert_eq!((checker.width, checker.height), (256, 256));
+		assert_eq!(checker.data.len(), 256 * 256 * 4);
+		assert_eq!(Surface::checkerboard(1000).width, CHECKER_MAX_CELLS * CHECKER_CELL_SIZE);
+		// The top of the "1" in the label of the second column
+		assert!(label_pixel("1,0", 6, 4));
+		assert!(!label_pixel("1,0", 4, 4));
+		assert!(!label_pixel("1,0", 40, 40));
+	}
+
+	#[test]
+	fn texels() {
+		let surface = Surface {
+			width: 2, height: 1,
+			texture_type: SurfaceType::U8RGB,
+			data: Box::new([255, 0, 0, 0, 0, 255]),
+		};
+		assert_eq!(surface.texel(Vec2::new(0.25, 0.5)), Some([255, 0, 0, 255]));
+		assert_eq!(surface.texel(Vec2::new(0.75, 0.)), Some([0, 0, 255, 255]));
+		// Wrapped around
+		assert_eq!(surface.texel(Vec2::new(-0.25, 3.)), Some([0, 0, 255, 255]));
+	}
+}
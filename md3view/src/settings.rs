@@ -0,0 +1,227 @@
+use anyhow::Error;
+use crate::backup::BackupSettings;
+use crate::keymap::Keymap;
+use crate::units::{NumberFormat, UnitSystem};
+use rustmd3::limits::EngineProfile;
+use serde::{Serialize, Deserialize};
+use std::{
+	collections::{BTreeMap, HashMap},
+	env,
+	path::{Path, PathBuf},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+	fs::File,
+	io::{BufReader, BufWriter},
+};
+
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "rustmd3view.settings";
+
+// Settings which are kept between sessions, in the user's config directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+	#[serde(default)]
+	pub models: HashMap<PathBuf, ModelSettings>,
+	#[serde(default)]
+	pub units: UnitSystem,
+	// None to use the system locale
+	#[serde(default)]
+	pub number_format: Option<NumberFormat>,
+	// Multiplied by the monitor's scale factor. None to use the scale factor
+	// as it is.
+	#[serde(default)]
+	pub ui_scale: Option<f32>,
+	#[serde(default)]
+	pub sensitivity: Sensitivity,
+	// Drag the camera with the pointer's movement, which is affected by the
+	// system's acceleration settings, rather than raw mouse movement
+	#[serde(default)]
+	pub pointer_acceleration: bool,
+	// Backups of models which are saved over
+	#[serde(default)]
+	pub backups: BackupSettings,
+	#[serde(default)]
+	pub texture_budget: TextureBudget,
+	#[serde(default)]
+	pub frame_rate: FrameRate,
+	// Unfiltered skins, like software renderers and GZDoom with the texture
+	// filter off
+	#[serde(default)]
+	pub nearest_filtering: bool,
+	// For PCX, WAL and LMP skins without their own palette. None to look for
+	// the game's palette near each skin.
+	#[serde(default)]
+	pub palette: Option<PathBuf>,
+	// Load what can be read from broken models, instead of refusing them
+	#[serde(default)]
+	pub tolerant_parsing: bool,
+	#[serde(default)]
+	pub keymap: Keymap,
+	// Leave the background of screenshots and rendered images transparent
+	#[serde(default)]
+	pub transparent_screenshots: bool,
+	// The engine whose limits models are checked against
+	#[serde(default)]
+	pub engine_profile: EngineProfile,
+	// The frame, frame rate, triangle count and camera angles, over the view
+	#[serde(default)]
+	pub show_hud: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameRate {
+	// Only used when the window is created
+	pub vsync: bool,
+	// Frames per second, or None for no limit
+	pub limit: Option<u32>,
+	// Draw a graph of recent frame times over the view
+	pub show_frame_times: bool,
+}
+
+impl Default for FrameRate {
+	fn default() -> Self {
+		Self { vsync: true, limit: None, show_frame_times: false }
+	}
+}
+
+// How much GPU memory cached textures which aren't being used can take up
+// before the least recently used ones are thrown away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureBudget {
+	pub megabytes: u32,
+}
+
+impl Default for TextureBudget {
+	fn default() -> Self {
+		Self { megabytes: 512 }
+	}
+}
+
+impl TextureBudget {
+	pub fn bytes(&self) -> usize {
+		self.megabytes as usize * 1024 * 1024
+	}
+}
+
+// How fast the camera moves
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sensitivity {
+	// Multiplies the speed of mouse dragging
+	pub mouse: f32,
+	// Fraction of the camera's distance to zoom per wheel notch or key press
+	pub zoom: f32,
+	// Degrees to orbit per key press
+	pub orbit: f32,
+}
+
+impl Default for Sensitivity {
+	fn default() -> Self {
+		Self {
+			mouse: 1.,
+			zoom: 0.1,
+			orbit: 5.,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelSettings {
+	// Surface index, and the image used instead of its shader
+	#[serde(default)]
+	pub textures: BTreeMap<usize, PathBuf>,
+	// Surface index, and the shader used for its texture if it isn't the
+	// first one
+	#[serde(default)]
+	pub shaders: BTreeMap<usize, usize>,
+	// Fragment shader used instead of md3.frag, with the same uniforms
+	#[serde(default)]
+	pub fragment_shader: Option<PathBuf>,
+}
+
+impl Settings {
+	pub fn path() -> Option<PathBuf> {
+		let config_dir = if cfg!(windows) {
+			env::var_os("APPDATA").map(PathBuf::from)
+		} else {
+			env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+				.or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".config")))
+		};
+		config_dir.map(|d| d.join("rustmd3view").join("settings.json"))
+	}
+	// Missing or broken settings files are treated as empty
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn load() -> Settings {
+		Settings::path().and_then(|path| Settings::read(path).ok()).unwrap_or_default()
+	}
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn save(&self) -> Result<(), Error> {
+		let path = Settings::path()
+			.ok_or_else(|| Error::msg("Could not find the config directory"))?;
+		if let Some(directory) = path.parent() {
+			std::fs::create_dir_all(directory)?;
+		}
+		self.write(path)
+	}
+	// Browsers keep them in the site's local storage instead
+	#[cfg(target_arch = "wasm32")]
+	pub fn load() -> Settings {
+		crate::web::local_storage()
+			.and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten())
+			.and_then(|json| serde_json::from_str(&json).ok())
+			.unwrap_or_default()
+	}
+	#[cfg(target_arch = "wasm32")]
+	pub fn save(&self) -> Result<(), Error> {
+		let storage = crate::web::local_storage()
+			.ok_or_else(|| Error::msg("Local storage is unavailable"))?;
+		storage.set_item(STORAGE_KEY, &serde_json::to_string(self)?).map_err(crate::web::js_error)
+	}
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn read(path: impl AsRef<Path>) -> Result<Settings, Error> {
+		let reader = BufReader::new(File::open(path)?);
+		Ok(serde_json::from_reader(reader)?)
+	}
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+		let writer = BufWriter::new(File::create(path)?);
+		serde_json::to_writer_pretty(writer, self)?;
+		Ok(())
+	}
+	pub fn number_format(&self) -> NumberFormat {
+		self.number_format.unwrap_or_else(NumberFormat::system)
+	}
+	pub fn length(&self, units: f32) -> String {
+		self.number_format().length(units, self.units)
+	}
+	// Models are identified by their absolute path
+	pub fn model(&self, path: &Path) -> Option<&ModelSettings> {
+		self.models.get(&model_key(path))
+	}
+	pub fn model_mut(&mut self, path: &Path) -> &mut ModelSettings {
+		self.models.entry(model_key(path)).or_default()
+	}
+}
+
+fn model_key(path: &Path) -> PathBuf {
+	path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn texture_overrides_round_trip() {
+		let mut settings = Settings::default();
+		settings.model_mut(Path::new("models/box.md3")).textures
+			.insert(1, PathBuf::from("box_red.png"));
+		let json = serde_json::to_string(&settings).unwrap();
+		let settings: Settings = serde_json::from_str(&json).unwrap();
+		let model = settings.model(Path::new("models/box.md3")).unwrap();
+		assert_eq!(model.textures.get(&1), Some(&PathBuf::from("box_red.png")));
+	}
+}
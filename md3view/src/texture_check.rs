@@ -0,0 +1,110 @@
+// Checking that skins are a size the engine can use as they are, and the shape
+// the UVs were made for
+use anyhow::Error;
+use crate::backup::{self, BackupSettings};
+use crate::palette;
+use glam::Vec2;
+use image::imageops::FilterType;
+use rustmd3::limits::EngineLimits;
+use std::{fmt, path::Path};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureWarning {
+	NotPowerOfTwo(u32, u32),
+	TooBig { width: u32, height: u32, max: u32 },
+	// Expected is the width divided by the height that the UVs were made for
+	Stretched { width: u32, height: u32, expected: f32 },
+}
+
+impl fmt::Display for TextureWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			TextureWarning::NotPowerOfTwo(width, height) => write!(f,
+				"The texture is {}×{}, which isn't a power of two, so the engine will resample it and make it blurry.",
+				width, height),
+			TextureWarning::TooBig { width, height, max } => write!(f,
+				"The texture is {}×{}, but the engine can't load textures bigger than {}×{}.",
+				width, height, max, max),
+			TextureWarning::Stretched { width, height, expected } => write!(f,
+				"The texture is {}×{}, but the UVs were made for a texture about {:.1} times as wide as it is tall, so it will look stretched.",
+				width, height, expected),
+		}
+	}
+}
+
+// How much wider or taller the texels can be than they should be before it's
+// worth a warning
+const MAX_STRETCH: f32 = 1.5;
+
+// uv_span is how far across the model the width and height of the texture go
+pub fn check(width: u32, height: u32, uv_span: Option<Vec2>, limits: &EngineLimits) -> Vec<TextureWarning> {
+	let mut warnings = vec![];
+	if limits.power_of_two && !(width.is_power_of_two() && height.is_power_of_two()) {
+		warnings.push(TextureWarning::NotPowerOfTwo(width, height));
+	}
+	if let Some(max) = limits.texture_size.filter(|&max| width.max(height) > max) {
+		warnings.push(TextureWarning::TooBig { width, height, max });
+	}
+	if let Some(span) = uv_span.filter(|span| span.min_element() > 0.) {
+		let expected = span.x / span.y;
+		let stretch = (width as f32 / height as f32) / expected;
+		if stretch.max(1. / stretch) > MAX_STRETCH {
+			warnings.push(TextureWarning::Stretched { width, height, expected });
+		}
+	}
+	warnings
+}
+
+// The biggest size, no bigger than the given one, which the engine can use
+// without resampling it
+pub fn fitted_size(width: u32, height: u32, limits: &EngineLimits) -> (u32, u32) {
+	let fit = |size: u32| {
+		let size = limits.texture_size.map_or(size, |max| size.min(max));
+		match limits.power_of_two {
+			true => 1 << (31 - size.max(1).leading_zeros()),
+			false => size.max(1),
+		}
+	};
+	(fit(width), fit(height))
+}
+
+pub fn image_size(path: &Path) -> Result<(u32, u32), Error> {
+	if palette::is_palettized(path) {
+		let image = palette::read(path)?;
+		return Ok((image.width, image.height));
+	}
+	Ok(image::image_dimensions(path)?)
+}
+
+// Scale the image and save it over itself, after backing it up
+pub fn resize(path: &Path, width: u32, height: u32, backups: &BackupSettings) -> Result<(), Error> {
+	if palette::is_palettized(path) {
+		return Err(Error::msg("8-bit textures can't be resized here, since they have to keep their palette"));
+	}
+	let image = image::open(path)?;
+	backup::backup(path, backups)?;
+	image.resize_exact(width, height, FilterType::Lanczos3).save(path)?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rustmd3::limits::EngineProfile;
+
+	#[test]
+	fn texture_warnings() {
+		let quake3 = EngineProfile::Quake3.limits();
+		assert_eq!(check(256, 256, Some(Vec2::new(10., 10.)), &quake3), vec![]);
+		assert_eq!(check(4000, 256, None, &quake3), vec![
+			TextureWarning::NotPowerOfTwo(4000, 256),
+			TextureWarning::TooBig { width: 4000, height: 256, max: 2048 },
+		]);
+		// A square texture on UVs made for a wide one
+		assert!(matches!(check(256, 256, Some(Vec2::new(40., 10.)), &quake3).as_slice(),
+			[TextureWarning::Stretched { expected, .. }] if *expected == 4.));
+		assert_eq!(check(300, 200, None, &EngineProfile::GZDoom.limits()), vec![]);
+		assert_eq!(fitted_size(4000, 300, &quake3), (2048, 256));
+		assert_eq!(fitted_size(4000, 300, &EngineProfile::GZDoom.limits()), (4000, 300));
+	}
+}
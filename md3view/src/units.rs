@@ -0,0 +1,116 @@
+use serde::{Serialize, Deserialize};
+use std::env;
+
+// MD3 models are usually made with the Quake convention of 1 unit = 1 inch
+const METRES_PER_UNIT: f32 = 0.0254;
+const UNITS_PER_FOOT: f32 = 12.;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnitSystem {
+	#[default]
+	Units,
+	Metric,
+	Imperial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NumberFormat {
+	pub decimal: char,
+	// Put between each group of 3 digits in the integer part
+	pub grouping: Option<char>,
+}
+
+impl NumberFormat {
+	pub const ENGLISH: NumberFormat = NumberFormat { decimal: '.', grouping: Some(',') };
+	pub const GERMAN: NumberFormat = NumberFormat { decimal: ',', grouping: Some('.') };
+	pub const FRENCH: NumberFormat = NumberFormat { decimal: ',', grouping: Some(' ') };
+	pub const PLAIN: NumberFormat = NumberFormat { decimal: '.', grouping: None };
+
+	// Guess the number format from the locale environment variables
+	pub fn system() -> NumberFormat {
+		let locale = ["LC_ALL", "LC_NUMERIC", "LANG"].iter()
+			.filter_map(|var| env::var(var).ok())
+			.find(|locale| !locale.is_empty());
+		match locale {
+			Some(locale) => NumberFormat::from_locale(&locale),
+			None => NumberFormat::ENGLISH,
+		}
+	}
+	pub fn from_locale(locale: &str) -> NumberFormat {
+		let language = locale.split(['_', '-', '.', '@']).next().unwrap_or_default();
+		match language {
+			"C" | "POSIX" => NumberFormat::PLAIN,
+			"de" | "nl" | "it" | "es" | "pt" | "da" | "tr" | "id" => NumberFormat::GERMAN,
+			"fr" | "ru" | "pl" | "sv" | "fi" | "nb" | "nn" | "cs" | "sk" | "uk" | "hu" =>
+				NumberFormat::FRENCH,
+			_ => NumberFormat::ENGLISH,
+		}
+	}
+	pub fn format(&self, value: f32, decimals: usize) -> String {
+		let plain = format!("{:.*}", decimals, value.abs());
+		let (integer, fraction) = match plain.split_once('.') {
+			Some((i, f)) => (i, Some(f)),
+			None => (plain.as_str(), None),
+		};
+		let mut formatted = String::with_capacity(plain.len() + integer.len() / 3 + 1);
+		// Don't show -0
+		if value.is_sign_negative() && plain.bytes().any(|b| b.is_ascii_digit() && b != b'0') {
+			formatted.push('-');
+		}
+		integer.chars().enumerate().for_each(|(i, c)| {
+			if let Some(sep) = self.grouping.filter(|_| i > 0 && (integer.len() - i) % 3 == 0) {
+				formatted.push(sep);
+			}
+			formatted.push(c);
+		});
+		if let Some(fraction) = fraction {
+			formatted.push(self.decimal);
+			formatted.push_str(fraction);
+		}
+		formatted
+	}
+	pub fn length(&self, units: f32, system: UnitSystem) -> String {
+		match system {
+			UnitSystem::Units => format!("{} u", self.format(units, 2)),
+			UnitSystem::Metric => {
+				let metres = units * METRES_PER_UNIT;
+				if metres.abs() >= 1. {
+					format!("{} m", self.format(metres, 2))
+				} else {
+					format!("{} cm", self.format(metres * 100., 1))
+				}
+			},
+			UnitSystem::Imperial => if units.abs() >= UNITS_PER_FOOT {
+				format!("{} ft", self.format(units / UNITS_PER_FOOT, 2))
+			} else {
+				format!("{} in", self.format(units, 1))
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn locale_formats() {
+		assert_eq!(NumberFormat::from_locale("de_DE.UTF-8"), NumberFormat::GERMAN);
+		assert_eq!(NumberFormat::from_locale("en_GB.UTF-8"), NumberFormat::ENGLISH);
+		assert_eq!(NumberFormat::from_locale("C"), NumberFormat::PLAIN);
+		assert_eq!(NumberFormat::ENGLISH.format(1234567.5, 1), "1,234,567.5");
+		assert_eq!(NumberFormat::GERMAN.format(-1234.5, 1), "-1.234,5");
+		assert_eq!(NumberFormat::FRENCH.format(123., 0), "123");
+		assert_eq!(NumberFormat::PLAIN.format(-0.001, 2), "0.00");
+	}
+
+	#[test]
+	fn lengths() {
+		let fmt = NumberFormat::ENGLISH;
+		assert_eq!(fmt.length(64., UnitSystem::Units), "64.00 u");
+		assert_eq!(fmt.length(64., UnitSystem::Metric), "1.63 m");
+		assert_eq!(fmt.length(10., UnitSystem::Metric), "25.4 cm");
+		assert_eq!(fmt.length(72., UnitSystem::Imperial), "6.00 ft");
+		assert_eq!(fmt.length(6., UnitSystem::Imperial), "6.0 in");
+	}
+}
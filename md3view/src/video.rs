@@ -0,0 +1,408 @@
+// Encoding rendered frames into a video. WebM videos are encoded as VP9 here,
+// and MP4 videos are encoded by ffmpeg, which has to be installed, in builds
+// with the ffmpeg feature. The frames are rendered on the main thread, since
+// that's where OpenGL is, and encoded on another one, so the window keeps
+// responding.
+use crate::webm::WebmWriter;
+use anyhow::Error;
+use image::RgbaImage;
+use oxideav_core::{registry::Encoder, CodecId, CodecParameters, Frame, PixelFormat, VideoFrame, VideoPlane};
+use oxideav_vp9::Vp9Encoder;
+use std::{
+	fs::File,
+	io::BufWriter,
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::{self, Receiver, SyncSender},
+		Arc,
+	},
+	thread::JoinHandle,
+};
+#[cfg(feature = "ffmpeg")]
+use std::{
+	io::{Read, Write},
+	process::{Child, ChildStdin, Command, Stdio},
+};
+
+// How many rendered frames can wait to be encoded
+const QUEUE_LENGTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+	// VP9, which can keep the transparency
+	WebM,
+	// H.264
+	Mp4,
+}
+
+impl VideoFormat {
+	pub fn from_path(path: &Path) -> Option<Self> {
+		match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+			"webm" => Some(VideoFormat::WebM),
+			"mp4" => Some(VideoFormat::Mp4),
+			_ => None,
+		}
+	}
+}
+
+// Checks which are the same for every format
+fn check_settings(path: &Path, width: u32, height: u32) -> Result<VideoFormat, Error> {
+	let format = VideoFormat::from_path(path)
+		.ok_or_else(|| Error::msg("Videos can be saved as .webm or .mp4"))?;
+	// YUV 4:2:0 needs an even width and height
+	if (width | height) & 1 != 0 {
+		return Err(Error::msg("The width and height of a video have to be even"));
+	}
+	Ok(format)
+}
+
+pub enum VideoEncoder {
+	WebM(Box<WebmEncoder>),
+	#[cfg(feature = "ffmpeg")]
+	Ffmpeg(FfmpegEncoder),
+}
+
+impl VideoEncoder {
+	// `quantizer` is the quality of WebM videos, from 0, which is lossless, to
+	// 255, which makes the smallest videos
+	pub fn start(path: &Path, width: u32, height: u32, fps: u32, transparent: bool, quantizer: u8) -> Result<Self, Error> {
+		match check_settings(path, width, height)? {
+			VideoFormat::WebM => WebmEncoder::start(path, width, height, fps, transparent, quantizer).map(|encoder| VideoEncoder::WebM(Box::new(encoder))),
+			#[cfg(feature = "ffmpeg")]
+			VideoFormat::Mp4 => FfmpegEncoder::start(path, width, height, fps, transparent).map(VideoEncoder::Ffmpeg),
+			#[cfg(not(feature = "ffmpeg"))]
+			VideoFormat::Mp4 => Err(Error::msg("MP4 videos can only be saved by builds with the ffmpeg feature. Save it as WebM instead.")),
+		}
+	}
+	fn write_frame(&mut self, image: &RgbaImage) -> Result<(), Error> {
+		match self {
+			VideoEncoder::WebM(encoder) => encoder.write_frame(image),
+			#[cfg(feature = "ffmpeg")]
+			VideoEncoder::Ffmpeg(encoder) => encoder.write_frame(image),
+		}
+	}
+	fn finish(self) -> Result<(), Error> {
+		match self {
+			VideoEncoder::WebM(encoder) => encoder.finish(),
+			#[cfg(feature = "ffmpeg")]
+			VideoEncoder::Ffmpeg(encoder) => encoder.finish(),
+		}
+	}
+	// Stop, and delete the unfinished video
+	fn abort(self) {
+		let path = match self {
+			VideoEncoder::WebM(encoder) => encoder.path,
+			#[cfg(feature = "ffmpeg")]
+			VideoEncoder::Ffmpeg(mut encoder) => {
+				encoder.ffmpeg.kill().ok();
+				encoder.ffmpeg.wait().ok();
+				encoder.path
+			},
+		};
+		std::fs::remove_file(path).ok();
+	}
+}
+
+// BT.601, which is what players expect when the video doesn't say
+fn yuv(rgb: [u32; 3]) -> [u8; 3] {
+	let [r, g, b] = rgb.map(|c| c as i32);
+	[
+		((66 * r + 129 * g + 25 * b + 128) >> 8) + 16,
+		((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128,
+		((112 * r - 94 * g - 18 * b + 128) >> 8) + 128,
+	].map(|c| c as u8)
+}
+
+// The image as YUV 4:2:0, with each chroma sample from 4 pixels
+fn yuv420(image: &RgbaImage) -> [Vec<u8>; 3] {
+	let (width, height) = image.dimensions();
+	let luma = image.pixels().map(|p| yuv([p[0], p[1], p[2]].map(u32::from))[0]).collect();
+	let (u, v) = (0..height / 2).flat_map(|y| (0..width / 2).map(move |x| (x * 2, y * 2)))
+		.map(|(x, y)| {
+			let mut sum = [0u32; 3];
+			[(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)].iter()
+				.for_each(|&(x, y)| (0..3).for_each(|c| sum[c] += image.get_pixel(x, y)[c] as u32));
+			let [_, u, v] = yuv(sum.map(|c| (c + 2) / 4));
+			(u, v)
+		}).unzip();
+	[luma, u, v]
+}
+
+// Transparent videos have a second stream, with the alpha as brightness
+fn alpha420(image: &RgbaImage) -> [Vec<u8>; 3] {
+	let chroma = vec![128; (image.width() / 2 * image.height() / 2) as usize];
+	[image.pixels().map(|p| p[3]).collect(), chroma.clone(), chroma]
+}
+
+fn vp9_encoder(width: u32, height: u32, quantizer: u8) -> Result<Vp9Encoder, Error> {
+	let mut parameters = CodecParameters::video(CodecId::new("vp9"));
+	parameters.width = Some(width);
+	parameters.height = Some(height);
+	parameters.pixel_format = Some(PixelFormat::Yuv420P);
+	match quantizer {
+		0 => parameters.options.insert("lossless", "true"),
+		q => parameters.options.insert("q", q.to_string()),
+	}
+	Vp9Encoder::new(&parameters).map_err(Error::from)
+}
+
+fn encode_vp9(encoder: &mut Vp9Encoder, [y, u, v]: [Vec<u8>; 3], width: u32, index: u64) -> Result<Vec<u8>, Error> {
+	let stride = width as usize;
+	let frame = VideoFrame {
+		pts: Some(index as i64),
+		planes: vec![
+			VideoPlane { stride, data: y },
+			VideoPlane { stride: stride / 2, data: u },
+			VideoPlane { stride: stride / 2, data: v },
+		],
+	};
+	encoder.send_frame(&Frame::Video(frame))?;
+	Ok(encoder.receive_packet()?.data)
+}
+
+pub struct WebmEncoder {
+	path: PathBuf,
+	writer: WebmWriter<BufWriter<File>>,
+	colour: Vp9Encoder,
+	alpha: Option<Vp9Encoder>,
+	size: (u32, u32),
+	frames: u64,
+}
+
+impl WebmEncoder {
+	fn start(path: &Path, width: u32, height: u32, fps: u32, transparent: bool, quantizer: u8) -> Result<Self, Error> {
+		let colour = vp9_encoder(width, height, quantizer)?;
+		let alpha = transparent.then(|| vp9_encoder(width, height, quantizer)).transpose()?;
+		let file = BufWriter::new(File::create(path)?);
+		let writer = WebmWriter::new(file, width, height, fps, transparent)?;
+		Ok(Self { path: path.to_path_buf(), writer, colour, alpha, size: (width, height), frames: 0 })
+	}
+	fn write_frame(&mut self, image: &RgbaImage) -> Result<(), Error> {
+		if image.dimensions() != self.size {
+			return Err(Error::msg("The frame is not the same size as the video"));
+		}
+		let frame = encode_vp9(&mut self.colour, yuv420(image), self.size.0, self.frames)?;
+		let alpha = match self.alpha.as_mut() {
+			Some(encoder) => Some(encode_vp9(encoder, alpha420(image), self.size.0, self.frames)?),
+			None => None,
+		};
+		// Only the first frame is a keyframe
+		self.writer.write_frame(&frame, alpha.as_deref(), self.frames == 0)?;
+		self.frames += 1;
+		Ok(())
+	}
+	fn finish(self) -> Result<(), Error> {
+		self.writer.finish()?;
+		Ok(())
+	}
+}
+
+#[cfg(feature = "ffmpeg")]
+fn ffmpeg_args(path: &Path, width: u32, height: u32, fps: u32, transparent: bool) -> Result<Vec<String>, Error> {
+	if transparent {
+		return Err(Error::msg("MP4 videos can't have a transparent background. Use WebM instead."));
+	}
+	let mut args: Vec<String> = [
+		"-hide_banner", "-loglevel", "error", "-y",
+		"-f", "rawvideo", "-pix_fmt", "rgba",
+	].iter().map(|a| a.to_string()).collect();
+	args.extend([
+		String::from("-s"), format!("{}x{}", width, height),
+		String::from("-r"), fps.to_string(),
+		String::from("-i"), String::from("-"),
+	]);
+	args.extend(["-c:v", "libx264", "-pix_fmt", "yuv420p", "-crf", "18"].iter().map(|a| a.to_string()));
+	args.push(path.to_string_lossy().into_owned());
+	Ok(args)
+}
+
+// MP4 videos, which are encoded by ffmpeg from frames piped to it
+// uncompressed
+#[cfg(feature = "ffmpeg")]
+pub struct FfmpegEncoder {
+	path: PathBuf,
+	ffmpeg: Child,
+	input: ChildStdin,
+	size: (u32, u32),
+}
+
+#[cfg(feature = "ffmpeg")]
+impl FfmpegEncoder {
+	fn start(path: &Path, width: u32, height: u32, fps: u32, transparent: bool) -> Result<Self, Error> {
+		let args = ffmpeg_args(path, width, height, fps, transparent)?;
+		let mut ffmpeg = Command::new("ffmpeg").args(&args)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::null())
+			.stderr(Stdio::piped())
+			.spawn()
+			.map_err(|e| Error::msg(format!("Could not run ffmpeg, which is needed to save MP4 videos: {}", e)))?;
+		let input = ffmpeg.stdin.take().ok_or_else(|| Error::msg("Could not write to ffmpeg"))?;
+		Ok(Self { path: path.to_path_buf(), ffmpeg, input, size: (width, height) })
+	}
+	fn write_frame(&mut self, image: &RgbaImage) -> Result<(), Error> {
+		if image.dimensions() != self.size {
+			return Err(Error::msg("The frame is not the same size as the video"));
+		}
+		self.input.write_all(image.as_raw()).map_err(Error::from)
+	}
+	// Wait for ffmpeg to finish writing the file
+	fn finish(self) -> Result<(), Error> {
+		let Self { mut ffmpeg, input, .. } = self;
+		drop(input);
+		let status = ffmpeg.wait()?;
+		if status.success() {
+			return Ok(());
+		}
+		let mut message = String::new();
+		if let Some(mut stderr) = ffmpeg.stderr.take() {
+			stderr.read_to_string(&mut message)?;
+		}
+		Err(Error::msg(format!("ffmpeg failed ({}):\n{}", status, message.trim())))
+	}
+}
+
+// A video being encoded on another thread
+pub struct Encoding {
+	frames: Option<SyncSender<RgbaImage>>,
+	encoded: Receiver<()>,
+	thread: Option<JoinHandle<Result<(), Error>>>,
+	cancelled: Arc<AtomicBool>,
+	pub sent: usize,
+	pub done: usize,
+}
+
+impl Encoding {
+	pub fn start(mut encoder: VideoEncoder) -> Self {
+		let (sender, frames) = mpsc::sync_channel::<RgbaImage>(QUEUE_LENGTH);
+		let (encoded_sender, encoded) = mpsc::channel();
+		let cancelled = Arc::new(AtomicBool::new(false));
+		let thread_cancelled = Arc::clone(&cancelled);
+		let thread = std::thread::spawn(move || {
+			// Until every frame has been sent
+			for image in frames {
+				if thread_cancelled.load(Ordering::Relaxed) {
+					break;
+				}
+				if let Err(e) = encoder.write_frame(&image) {
+					encoder.abort();
+					return Err(e);
+				}
+				encoded_sender.send(()).ok();
+			}
+			if thread_cancelled.load(Ordering::Relaxed) {
+				encoder.abort();
+				return Ok(());
+			}
+			encoder.finish()
+		});
+		Self { frames: Some(sender), encoded, thread: Some(thread), cancelled, sent: 0, done: 0 }
+	}
+	// Whether another frame can be sent without waiting for the encoder
+	pub fn ready(&self) -> bool {
+		self.frames.is_some() && self.sent - self.done < QUEUE_LENGTH
+	}
+	pub fn send(&mut self, image: RgbaImage) {
+		// The thread has stopped if it failed, which `poll` reports
+		if let Some(Ok(())) = self.frames.as_ref().map(|frames| frames.send(image)) {
+			self.sent += 1;
+		}
+	}
+	// Every frame has been sent
+	pub fn finish(&mut self) {
+		self.frames = None;
+	}
+	// Stop encoding, and delete the video
+	pub fn cancel(mut self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+		self.frames = None;
+	}
+	// Counts the encoded frames, and once the thread has stopped, whether
+	// the video was saved
+	pub fn poll(&mut self) -> Option<Result<(), Error>> {
+		let finished = self.thread.as_ref()?.is_finished();
+		// Counted after checking, so none are missed if it has just finished
+		self.done += self.encoded.try_iter().count();
+		if !finished {
+			return None;
+		}
+		let thread = self.thread.take()?;
+		Some(thread.join().unwrap_or_else(|_| Err(Error::msg("The video encoder crashed"))))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use oxideav_core::registry::Decoder;
+	use oxideav_vp9::Vp9Decoder;
+
+	#[test]
+	fn formats() {
+		assert_eq!(VideoFormat::from_path(Path::new("turntable.webm")), Some(VideoFormat::WebM));
+		assert_eq!(VideoFormat::from_path(Path::new("a.MP4")), Some(VideoFormat::Mp4));
+		assert_eq!(VideoFormat::from_path(Path::new("a.gif")), None);
+		assert!(check_settings(Path::new("a.gif"), 640, 480).is_err());
+		assert!(check_settings(Path::new("a.webm"), 641, 480).is_err());
+	}
+
+	#[cfg(feature = "ffmpeg")]
+	#[test]
+	fn arguments() {
+		let path = Path::new("turntable.mp4");
+		let args = ffmpeg_args(path, 640, 480, 30, false).unwrap();
+		assert!(args.windows(2).any(|a| a == ["-s", "640x480"]));
+		assert_eq!(args.last().map(String::as_str), Some("turntable.mp4"));
+		assert!(ffmpeg_args(path, 640, 480, 30, true).is_err());
+	}
+
+	#[test]
+	fn colours() {
+		assert_eq!(yuv([0, 0, 0]), [16, 128, 128]);
+		assert_eq!(yuv([255, 255, 255]), [235, 128, 128]);
+		let image = RgbaImage::from_fn(4, 2, |x, _| image::Rgba([255 * (x / 2) as u8, 0, 0, 255]));
+		let [y, u, v] = yuv420(&image);
+		assert_eq!(y, [16, 16, 82, 82, 16, 16, 82, 82]);
+		assert_eq!((u, v), (vec![128, 90], vec![128, 240]));
+	}
+
+	#[test]
+	fn vp9_round_trip() {
+		let image = RgbaImage::from_fn(32, 16, |x, y| image::Rgba([x as u8 * 8, y as u8 * 16, 128, 255]));
+		let planes = yuv420(&image);
+		let mut encoder = vp9_encoder(32, 16, 0).unwrap();
+		let frame = encode_vp9(&mut encoder, planes.clone(), 32, 0).unwrap();
+		let mut decoder = Vp9Decoder::new();
+		decoder.send_packet(&oxideav_core::Packet::new(0, oxideav_core::TimeBase::MILLIS, frame)).unwrap();
+		let Ok(Frame::Video(decoded)) = decoder.receive_frame() else { panic!("No frame was decoded") };
+		// Lossless
+		let luma = &decoded.image_planes()[0];
+		let rows: Vec<u8> = (0..16).flat_map(|row| luma.data[row * luma.stride..][..32].to_vec()).collect();
+		assert_eq!(rows, planes[0]);
+	}
+
+	#[test]
+	fn transparent_webm() {
+		let path = std::env::temp_dir().join(format!("rustmd3view-video-{}.webm", std::process::id()));
+		let encoder = VideoEncoder::start(&path, 16, 16, 10, true, 200).unwrap();
+		let mut encoding = Encoding::start(encoder);
+		for frame in 0..3u8 {
+			while !encoding.ready() {
+				assert!(encoding.poll().is_none());
+			}
+			encoding.send(RgbaImage::from_pixel(16, 16, image::Rgba([frame * 80, 0, 0, 128])));
+		}
+		encoding.finish();
+		let result = loop {
+			if let Some(result) = encoding.poll() {
+				break result;
+			}
+		};
+		assert!(result.is_ok());
+		assert_eq!(encoding.done, 3);
+		let data = std::fs::read(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+		// Each frame has its alpha frame
+		assert_eq!(data.windows(2).filter(|w| w == &[0x75, 0xA1]).count(), 3);
+	}
+}
@@ -0,0 +1,152 @@
+// The browser side of the viewer, for embedding it in a web page. There's no
+// file system, so models are opened with the browser's file picker, or
+// fetched from the site, like viewer.html?model=models/players/sarge/upper.md3
+//
+// Build it with
+// cargo build -p rustmd3view --target wasm32-unknown-unknown --no-default-features
+// and run wasm-bindgen on the result. The page can have a canvas with the id
+// "rustmd3view" for the viewer to draw to, or one is added to the body.
+use anyhow::Error;
+use js_sys::Uint8Array;
+use std::{
+	cell::RefCell,
+	collections::HashMap,
+	path::{Path, PathBuf},
+	rc::Rc,
+};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{HtmlInputElement, Response, Storage};
+
+pub const CANVAS_ID: &str = "rustmd3view";
+
+thread_local! {
+	// Files the user picked, which are read from here instead of being
+	// fetched. Textures are looked up by their file name too, since the paths
+	// in the model are relative to the game's folder.
+	static PICKED: RefCell<HashMap<PathBuf, Vec<u8>>> = RefCell::new(HashMap::new());
+}
+
+pub fn js_error(value: JsValue) -> Error {
+	Error::msg(value.as_string().unwrap_or_else(|| format!("{:?}", value)))
+}
+
+// Picking a model, and optionally its textures, with the browser's file
+// picker. It works like the file dialog it replaces.
+pub struct FilePicker {
+	input: HtmlInputElement,
+	// The model, once its files have been read
+	picked: Rc<RefCell<Option<PathBuf>>>,
+	selected: Option<PathBuf>,
+	_on_change: Closure<dyn FnMut()>,
+}
+
+impl FilePicker {
+	pub fn new(accept: &str) -> Self {
+		let input: HtmlInputElement = web_sys::window()
+			.and_then(|window| window.document())
+			.and_then(|document| document.create_element("input").ok())
+			.and_then(|element| element.dyn_into().ok())
+			.expect("Could not make a file input");
+		input.set_type("file");
+		input.set_multiple(true);
+		input.set_accept(accept);
+		let picked = Rc::new(RefCell::new(None));
+		let on_change = {
+			let (input, picked) = (input.clone(), Rc::clone(&picked));
+			Closure::<dyn FnMut()>::new(move || {
+				let files = input.files();
+				let files: Vec<web_sys::File> = (0..files.as_ref().map_or(0, |f| f.length()))
+					.filter_map(|index| files.as_ref()?.get(index))
+					.collect();
+				wasm_bindgen_futures::spawn_local(read_picked(files, Rc::clone(&picked)));
+			})
+		};
+		input.set_onchange(Some(on_change.as_ref().unchecked_ref()));
+		Self { input, picked, selected: None, _on_change: on_change }
+	}
+	pub fn open(&mut self) {
+		// So picking the same file again still counts as a change
+		self.input.set_value("");
+		self.input.click();
+	}
+	pub fn show(&mut self, _ctx: &egui::Context) -> &Self {
+		self.selected = self.picked.borrow_mut().take();
+		self
+	}
+	pub fn selected(&self) -> bool {
+		self.selected.is_some()
+	}
+	pub fn path(&self) -> Option<PathBuf> {
+		self.selected.clone()
+	}
+	// The browser's dialog isn't drawn over the viewer
+	pub fn visible(&self) -> bool {
+		false
+	}
+}
+
+async fn read_picked(files: Vec<web_sys::File>, picked: Rc<RefCell<Option<PathBuf>>>) {
+	let mut model = None;
+	for file in files {
+		let path = PathBuf::from(file.name());
+		match read_file(&file).await {
+			Ok(bytes) => PICKED.with(|files| files.borrow_mut().insert(path.clone(), bytes)),
+			Err(e) => {
+				log::error!("Could not read {}: {}", path.display(), e);
+				continue;
+			},
+		};
+		if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("md3")) {
+			model.get_or_insert(path);
+		}
+	}
+	match model {
+		Some(model) => *picked.borrow_mut() = Some(model),
+		None => log::error!("No model was picked"),
+	}
+}
+
+async fn read_file(file: &web_sys::File) -> Result<Vec<u8>, Error> {
+	let buffer = JsFuture::from(file.array_buffer()).await.map_err(js_error)?;
+	Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+pub async fn fetch(url: &str) -> Result<Vec<u8>, Error> {
+	let window = web_sys::window().ok_or_else(|| Error::msg("There is no window"))?;
+	let response: Response = JsFuture::from(window.fetch_with_str(url)).await
+		.and_then(|response| response.dyn_into())
+		.map_err(js_error)?;
+	if !response.ok() {
+		return Err(Error::msg(format!("{} {}", response.status(), response.status_text())));
+	}
+	let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?).await.map_err(js_error)?;
+	Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+// A picked file, or one fetched from the site, relative to the page
+pub async fn read(path: &Path) -> Result<Vec<u8>, Error> {
+	let picked = PICKED.with(|files| {
+		let files = files.borrow();
+		files.get(path)
+			.or_else(|| files.get(Path::new(path.file_name()?)))
+			.cloned()
+	});
+	if let Some(bytes) = picked {
+		return Ok(bytes);
+	}
+	let url = path.to_string_lossy().replace('\\', "/");
+	fetch(&url).await.map_err(|e| Error::msg(format!("Could not fetch {}: {}", url, e)))
+}
+
+// The model given in the page's address, if there is one
+pub fn model_url() -> Option<PathBuf> {
+	let search = web_sys::window()?.location().search().ok()?;
+	let parameters = web_sys::UrlSearchParams::new_with_str(&search).ok()?;
+	parameters.get("model").filter(|model| !model.is_empty()).map(PathBuf::from)
+}
+
+// Where the settings are kept
+pub fn local_storage() -> Option<Storage> {
+	web_sys::window()?.local_storage().ok()?
+}
@@ -0,0 +1,252 @@
+// Writing VP9 frames into a WebM file, which is a subset of Matroska. Only
+// what's needed for one video track is written: the header, the track, and
+// the frames grouped into clusters. The segment's size and the duration are
+// filled in once every frame has been written.
+use std::io::{self, Seek, SeekFrom, Write};
+
+const EBML: u32 = 0x1A45DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x18538067;
+const INFO: u32 = 0x1549A966;
+const TIMESTAMP_SCALE: u32 = 0x2AD7B1;
+const DURATION: u32 = 0x4489;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const FLAG_LACING: u32 = 0x9C;
+const DEFAULT_DURATION: u32 = 0x23E383;
+const CODEC_ID: u32 = 0x86;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const ALPHA_MODE: u32 = 0x53C0;
+const CLUSTER: u32 = 0x1F43B675;
+const TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+const BLOCK_GROUP: u32 = 0xA0;
+const BLOCK: u32 = 0xA1;
+const REFERENCE_BLOCK: u32 = 0xFB;
+const BLOCK_ADDITIONS: u32 = 0x75A1;
+const BLOCK_MORE: u32 = 0xA6;
+const BLOCK_ADD_ID: u32 = 0xEE;
+const BLOCK_ADDITIONAL: u32 = 0xA5;
+
+// Timestamps are in milliseconds
+const MS: u64 = 1_000_000;
+// Block timestamps are 16 bit offsets from the cluster's, so a new cluster is
+// started long before they'd overflow
+const CLUSTER_MS: u64 = 5000;
+// An 8 byte size which is filled in later
+const UNKNOWN_SIZE: [u8; 8] = [0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+fn id(out: &mut Vec<u8>, id: u32) {
+	let bytes = id.to_be_bytes();
+	let skip = bytes.iter().take_while(|&&b| b == 0).count();
+	out.extend_from_slice(&bytes[skip..]);
+}
+
+// Sizes are variable length, with the length marked by the first set bit
+fn size(out: &mut Vec<u8>, size: u64) {
+	let length = (1..=8u32).find(|&length| size < (1 << (7 * length)) - 1).unwrap_or(8);
+	let marked = size | 1 << (7 * length);
+	out.extend_from_slice(&marked.to_be_bytes()[8 - length as usize..]);
+}
+
+fn element(out: &mut Vec<u8>, element: u32, data: &[u8]) {
+	id(out, element);
+	size(out, data.len() as u64);
+	out.extend_from_slice(data);
+}
+
+fn uint(out: &mut Vec<u8>, element: u32, value: u64) {
+	let bytes = value.to_be_bytes();
+	let skip = bytes.iter().take_while(|&&b| b == 0).count().min(7);
+	self::element(out, element, &bytes[skip..]);
+}
+
+fn master(out: &mut Vec<u8>, element: u32, children: impl FnOnce(&mut Vec<u8>)) {
+	let mut data = vec![];
+	children(&mut data);
+	self::element(out, element, &data);
+}
+
+// A block's track number, its time from the start of the cluster, and flags
+fn block_header(out: &mut Vec<u8>, time: i16, flags: u8) {
+	size(out, 1);
+	out.extend_from_slice(&time.to_be_bytes());
+	out.push(flags);
+}
+
+pub struct WebmWriter<W: Write + Seek> {
+	out: W,
+	frame_ms: f64,
+	// Where the segment's data starts, and where its duration is
+	segment_start: u64,
+	duration_at: u64,
+	cluster: Vec<u8>,
+	cluster_ms: u64,
+	frames: u64,
+}
+
+impl<W: Write + Seek> WebmWriter<W> {
+	// With `alpha`, each frame has a second VP9 frame of its transparency
+	pub fn new(mut out: W, width: u32, height: u32, fps: u32, alpha: bool) -> io::Result<Self> {
+		let mut header = vec![];
+		master(&mut header, EBML, |e| {
+			uint(e, EBML_VERSION, 1);
+			uint(e, EBML_READ_VERSION, 1);
+			uint(e, EBML_MAX_ID_LENGTH, 4);
+			uint(e, EBML_MAX_SIZE_LENGTH, 8);
+			element(e, DOC_TYPE, b"webm");
+			uint(e, DOC_TYPE_VERSION, 4);
+			uint(e, DOC_TYPE_READ_VERSION, 2);
+		});
+		id(&mut header, SEGMENT);
+		header.extend_from_slice(&UNKNOWN_SIZE);
+		let segment_start = header.len() as u64;
+		let app = concat!("rustmd3view ", env!("CARGO_PKG_VERSION"));
+		master(&mut header, INFO, |e| {
+			uint(e, TIMESTAMP_SCALE, MS);
+			element(e, MUXING_APP, app.as_bytes());
+			element(e, WRITING_APP, app.as_bytes());
+			// Last, so it's easy to find
+			element(e, DURATION, &0f64.to_be_bytes());
+		});
+		let duration_at = header.len() as u64 - 8;
+		master(&mut header, TRACKS, |e| master(e, TRACK_ENTRY, |e| {
+			uint(e, TRACK_NUMBER, 1);
+			uint(e, TRACK_UID, 1);
+			uint(e, TRACK_TYPE, 1);
+			uint(e, FLAG_LACING, 0);
+			uint(e, DEFAULT_DURATION, 1_000_000_000 / fps as u64);
+			element(e, CODEC_ID, b"V_VP9");
+			master(e, VIDEO, |e| {
+				uint(e, PIXEL_WIDTH, width as u64);
+				uint(e, PIXEL_HEIGHT, height as u64);
+				if alpha {
+					uint(e, ALPHA_MODE, 1);
+				}
+			});
+		}));
+		out.write_all(&header)?;
+		Ok(Self {
+			out,
+			frame_ms: 1000. / fps as f64,
+			segment_start,
+			duration_at,
+			cluster: vec![],
+			cluster_ms: 0,
+			frames: 0,
+		})
+	}
+	fn write_cluster(&mut self) -> io::Result<()> {
+		if self.cluster.is_empty() {
+			return Ok(());
+		}
+		let mut cluster = vec![];
+		master(&mut cluster, CLUSTER, |e| {
+			uint(e, TIMESTAMP, self.cluster_ms);
+			e.append(&mut self.cluster);
+		});
+		self.out.write_all(&cluster)
+	}
+	// The next frame, and the alpha frame for it if the video has one
+	pub fn write_frame(&mut self, frame: &[u8], alpha: Option<&[u8]>, keyframe: bool) -> io::Result<()> {
+		let step = self.frame_ms;
+		let frame_ms = |frame: u64| (frame as f64 * step).round() as u64;
+		let ms = frame_ms(self.frames);
+		if ms - self.cluster_ms >= CLUSTER_MS {
+			self.write_cluster()?;
+		}
+		if self.cluster.is_empty() {
+			self.cluster_ms = ms;
+		}
+		let time = (ms - self.cluster_ms) as i16;
+		let mut block = vec![];
+		match alpha {
+			None => {
+				block_header(&mut block, time, if keyframe { 0x80 } else { 0 });
+				block.extend_from_slice(frame);
+				element(&mut self.cluster, SIMPLE_BLOCK, &block);
+			},
+			// Alpha is only allowed in a block group
+			Some(alpha) => {
+				block_header(&mut block, time, 0);
+				block.extend_from_slice(frame);
+				let previous = frame_ms(self.frames.saturating_sub(1)) as i64 - ms as i64;
+				master(&mut self.cluster, BLOCK_GROUP, |e| {
+					element(e, BLOCK, &block);
+					// Frames other than keyframes refer to the one before
+					if !keyframe {
+						element(e, REFERENCE_BLOCK, &(previous as i16).to_be_bytes());
+					}
+					master(e, BLOCK_ADDITIONS, |e| master(e, BLOCK_MORE, |e| {
+						uint(e, BLOCK_ADD_ID, 1);
+						element(e, BLOCK_ADDITIONAL, alpha);
+					}));
+				});
+			},
+		}
+		self.frames += 1;
+		Ok(())
+	}
+	// Write the last cluster, and fill in the sizes
+	pub fn finish(mut self) -> io::Result<W> {
+		self.write_cluster()?;
+		let end = self.out.stream_position()?;
+		let duration = self.frames as f64 * self.frame_ms;
+		self.out.seek(SeekFrom::Start(self.duration_at))?;
+		self.out.write_all(&duration.to_be_bytes())?;
+		self.out.seek(SeekFrom::Start(self.segment_start - 8))?;
+		self.out.write_all(&((end - self.segment_start) | 1 << 56).to_be_bytes())?;
+		self.out.seek(SeekFrom::Start(end))?;
+		self.out.flush()?;
+		Ok(self.out)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+
+	#[test]
+	fn sizes() {
+		let encoded = |value| {
+			let mut out = vec![];
+			size(&mut out, value);
+			out
+		};
+		assert_eq!(encoded(0), [0x80]);
+		assert_eq!(encoded(126), [0xFE]);
+		// 127 on its own means the size is unknown
+		assert_eq!(encoded(127), [0x40, 0x7F]);
+		assert_eq!(encoded(1000), [0x43, 0xE8]);
+	}
+
+	#[test]
+	fn file() {
+		let mut writer = WebmWriter::new(Cursor::new(vec![]), 16, 8, 25, false).unwrap();
+		writer.write_frame(b"key", None, true).unwrap();
+		writer.write_frame(b"next", None, false).unwrap();
+		let data = writer.finish().unwrap().into_inner();
+		assert_eq!(&data[..4], &EBML.to_be_bytes());
+		let segment = data.windows(4).position(|w| w == SEGMENT.to_be_bytes()).unwrap();
+		let segment_size = u64::from_be_bytes(data[segment + 4..segment + 12].try_into().unwrap());
+		assert_eq!(segment_size, (data.len() - segment - 12) as u64 | 1 << 56);
+		let duration = data.windows(3).position(|w| w == [0x44, 0x89, 0x88]).unwrap() + 3;
+		assert_eq!(f64::from_be_bytes(data[duration..duration + 8].try_into().unwrap()), 80.);
+		// The second frame is 40ms after the first, and isn't a keyframe
+		assert!(data.ends_with(&[0xA3, 0x88, 0x81, 0, 40, 0, b'n', b'e', b'x', b't']));
+	}
+}
@@ -1,23 +1,41 @@
-use glow::{Context as GLContext};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::err_util::{self, DebugMessageCallbackFn};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::render::{TexBufferFn, BUFFER_TEXTURES, TEX_BUFFER};
+use glow::{Context as GLContext, HasContext};
+#[cfg(not(target_arch = "wasm32"))]
 use glutin::{
-	event_loop::EventLoop,
-	window::{Window, WindowBuilder},
 	ContextBuilder,
 	ContextWrapper,
 	PossiblyCurrent,
 	GlProfile,
 	GlRequest,
-	Api
 };
+#[cfg(not(target_arch = "wasm32"))]
+use winit::window::Window;
+use winit::{event_loop::EventLoop, window::WindowBuilder};
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use egui_glow::EguiGlow;
+#[cfg(target_arch = "wasm32")]
+pub use web::{EguiGlow, WindowContext};
+
+#[cfg(not(target_arch = "wasm32"))]
 type WindowContext = ContextWrapper<PossiblyCurrent, Window>;
 
-pub fn create_window<T>(el: &EventLoop<T>, title: Option<&str>) -> (WindowContext, GLContext) {
-	let wb = WindowBuilder::new().with_title(title.unwrap_or("A fantastic window!"));
+// The window is hidden when it's only needed for rendering off screen
+#[cfg(not(target_arch = "wasm32"))]
+pub fn create_window<T>(el: &EventLoop<T>, title: Option<&str>, vsync: bool, visible: bool) -> (WindowContext, GLContext) {
+	let wb = WindowBuilder::new()
+		.with_title(title.unwrap_or("A fantastic window!"))
+		.with_visible(visible);
 
 	let wc = ContextBuilder::new()
 		.with_gl_profile(GlProfile::Core)
-		.with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+		// OpenGL ES for ARM boards and the like
+		.with_gl(GlRequest::GlThenGles { opengl_version: (3, 3), opengles_version: (3, 0) })
+		.with_vsync(vsync)
+		.with_gl_debug_flag(cfg!(debug_assertions))
 		.build_windowed(wb, &el).unwrap();
 
 	let wc = unsafe { wc.make_current().unwrap() };
@@ -26,6 +44,171 @@ pub fn create_window<T>(el: &EventLoop<T>, title: Option<&str>) -> (WindowContex
 		GLContext::from_loader_function(
 			|name| wc.get_proc_address(name))
 	};
+	let tex_buffer = wc.get_proc_address("glTexBuffer");
+	let version = glc.version();
+	let buffer_textures = !version.is_embedded || (version.major, version.minor) >= (3, 2);
+	if buffer_textures && !tex_buffer.is_null() {
+		let tex_buffer: TexBufferFn = unsafe { std::mem::transmute(tex_buffer) };
+		TEX_BUFFER.set(Box::new(tex_buffer)).ok();
+		BUFFER_TEXTURES.set(Box::new(true)).ok();
+	}
+	// Have the driver tell us about errors, rather than asking for them after
+	// everything
+	let debug_message_callback = ["glDebugMessageCallback", "glDebugMessageCallbackKHR"].into_iter()
+		.map(|name| wc.get_proc_address(name))
+		.find(|f| !f.is_null());
+	if let (true, Some(debug_message_callback)) = (glc.supports_debug(), debug_message_callback) {
+		unsafe {
+			let debug_message_callback: DebugMessageCallbackFn = std::mem::transmute(debug_message_callback);
+			debug_message_callback(Some(err_util::debug_callback), std::ptr::null());
+			glc.enable(glow::DEBUG_OUTPUT);
+			// So the messages come in while the call that caused them is on the
+			// stack, for debugging
+			if cfg!(debug_assertions) {
+				glc.enable(glow::DEBUG_OUTPUT_SYNCHRONOUS);
+			}
+			glc.debug_message_control(glow::DONT_CARE, glow::DONT_CARE,
+				glow::DEBUG_SEVERITY_NOTIFICATION, &[], false);
+		}
+		err_util::set_debug_output(true);
+	}
 
 	(wc, glc)
 }
+
+// In a browser, the window is a canvas on the page, which is drawn to with
+// WebGL 2
+#[cfg(target_arch = "wasm32")]
+pub fn create_window<T>(el: &EventLoop<T>, title: Option<&str>, _vsync: bool, _visible: bool) -> (WindowContext, GLContext) {
+	use wasm_bindgen::JsCast;
+	use web_sys::{HtmlCanvasElement, WebGl2RenderingContext};
+	use winit::platform::web::{WindowBuilderExtWebSys, WindowExtWebSys};
+
+	let document = web_sys::window().and_then(|w| w.document()).expect("There is no document");
+	let canvas = document.get_element_by_id(crate::web::CANVAS_ID)
+		.and_then(|element| element.dyn_into::<HtmlCanvasElement>().ok());
+	let wb = WindowBuilder::new()
+		.with_title(title.unwrap_or("A fantastic window!"))
+		.with_canvas(canvas);
+	let window = wb.build(el).unwrap();
+	let canvas = window.canvas();
+	if canvas.parent_element().is_none() {
+		document.body().expect("The page has no body").append_child(&canvas).unwrap();
+	}
+
+	let glc = canvas.get_context("webgl2").ok().flatten()
+		.and_then(|context| context.dyn_into::<WebGl2RenderingContext>().ok())
+		.map(GLContext::from_webgl2_context)
+		.expect("WebGL 2 is unavailable");
+	log::info!("WebGL version: {}", unsafe { glc.get_parameter_string(glow::VERSION) });
+
+	(WindowContext::new(window), glc)
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+	use glow::Context as GLContext;
+	use std::{rc::Rc, sync::Arc, time::Duration};
+	use wasm_bindgen::{closure::Closure, JsCast};
+	use winit::{
+		dpi::{LogicalSize, PhysicalSize},
+		event::WindowEvent,
+		event_loop::EventLoopWindowTarget,
+		platform::web::WindowExtWebSys,
+		window::Window,
+	};
+
+	// Stands in for the desktop's window and OpenGL context. The browser
+	// shows what was drawn by itself, so there are no buffers to swap.
+	pub struct WindowContext(Rc<Window>);
+
+	impl WindowContext {
+		pub fn new(window: Window) -> Self {
+			let window = Rc::new(window);
+			fit_to_page(&window);
+			// Follow the page's layout when the browser window is resized
+			let on_resize = {
+				let window = Rc::clone(&window);
+				Closure::<dyn FnMut()>::new(move || fit_to_page(&window))
+			};
+			if let Some(browser) = web_sys::window() {
+				browser.add_event_listener_with_callback("resize", on_resize.as_ref().unchecked_ref()).ok();
+			}
+			// It's needed for as long as the page is open
+			on_resize.forget();
+			Self(window)
+		}
+		pub fn window(&self) -> &Window {
+			&self.0
+		}
+		// The canvas was already resized
+		pub fn resize(&self, _size: PhysicalSize<u32>) {}
+		pub fn swap_buffers(&self) -> Result<(), std::convert::Infallible> {
+			Ok(())
+		}
+	}
+
+	// The canvas fills the element it's in, or the browser window if the
+	// element doesn't have a size of its own, like an empty body
+	fn fit_to_page(window: &Window) {
+		let parent = window.canvas().parent_element()
+			.map(|parent| (parent.client_width(), parent.client_height()))
+			.filter(|&(width, height)| width > 0 && height > 0);
+		let page = web_sys::window().and_then(|browser| Some((
+			browser.inner_width().ok()?.as_f64()? as i32,
+			browser.inner_height().ok()?.as_f64()? as i32)));
+		if let Some((width, height)) = parent.or(page) {
+			window.set_inner_size(LogicalSize::new(width, height));
+		}
+	}
+
+	// egui_glow only has this for desktops, so this is the same thing for
+	// browsers
+	pub struct EguiGlow {
+		pub egui_ctx: egui::Context,
+		pub egui_winit: egui_winit::State,
+		pub painter: egui_glow::Painter,
+		shapes: Vec<egui::epaint::ClippedShape>,
+		textures_delta: egui::TexturesDelta,
+	}
+
+	impl EguiGlow {
+		pub fn new<T>(el: &EventLoopWindowTarget<T>, glc: Arc<GLContext>) -> Self {
+			// Without a size, egui_glow doesn't draw to a framebuffer of its
+			// own to correct the colours, and does it in its shader instead
+			let painter = egui_glow::Painter::new(glc, None, "").unwrap();
+			Self {
+				egui_ctx: Default::default(),
+				egui_winit: egui_winit::State::new(el),
+				painter,
+				shapes: vec![],
+				textures_delta: Default::default(),
+			}
+		}
+		pub fn on_event(&mut self, event: &WindowEvent<'_>) -> bool {
+			self.egui_winit.on_event(&self.egui_ctx, event)
+		}
+		pub fn run(&mut self, window: &Window, run_ui: impl FnMut(&egui::Context)) -> Duration {
+			let raw_input = self.egui_winit.take_egui_input(window);
+			let egui::FullOutput { platform_output, repaint_after, textures_delta, shapes } =
+				self.egui_ctx.run(raw_input, run_ui);
+			self.egui_winit.handle_platform_output(window, &self.egui_ctx, platform_output);
+			self.shapes = shapes;
+			self.textures_delta.append(textures_delta);
+			repaint_after
+		}
+		pub fn paint(&mut self, window: &Window) {
+			let shapes = std::mem::take(&mut self.shapes);
+			let mut textures_delta = std::mem::take(&mut self.textures_delta);
+			for (id, image_delta) in textures_delta.set {
+				self.painter.set_texture(id, &image_delta);
+			}
+			let clipped_primitives = self.egui_ctx.tessellate(shapes);
+			let dimensions: [u32; 2] = window.inner_size().into();
+			self.painter.paint_primitives(dimensions, self.egui_ctx.pixels_per_point(), &clipped_primitives);
+			for id in textures_delta.free.drain(..) {
+				self.painter.free_texture(id);
+			}
+		}
+	}
+}
@@ -0,0 +1,121 @@
+// Comparing the vertex positions of two models with the same topology, like
+// a model and the same model after going through an exporter
+use crate::md3::MD3Model;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TopologyError {
+	#[error("The models have {0} and {1} surfaces")]
+	SurfaceCount(usize, usize),
+	#[error("The models have {0} and {1} frames")]
+	FrameCount(usize, usize),
+	#[error("Surface {surface} has {a} and {b} vertices")]
+	VertexCount { surface: usize, a: usize, b: usize },
+	#[error("Surface {0} has different triangles")]
+	Triangles(usize),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameDeviation {
+	pub max: f32,
+	pub mean: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshDiff {
+	// Distance between each vertex of each surface in the two models, in the
+	// same order as MD3Surface.vertices
+	pub surfaces: Vec<Vec<f32>>,
+	pub frames: Vec<FrameDeviation>,
+}
+
+impl MeshDiff {
+	pub fn max(&self) -> f32 {
+		self.frames.iter().map(|f| f.max).fold(0., f32::max)
+	}
+	pub fn mean(&self) -> f32 {
+		match self.frames.len() {
+			0 => 0.,
+			frames => self.frames.iter().map(|f| f.mean).sum::<f32>() / frames as f32,
+		}
+	}
+}
+
+impl fmt::Display for MeshDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{:>6} {:>12} {:>12}", "Frame", "Max", "Mean")?;
+		self.frames.iter().enumerate().try_for_each(|(frame, deviation)| {
+			writeln!(f, "{:>6} {:>12.6} {:>12.6}", frame, deviation.max, deviation.mean)
+		})?;
+		write!(f, "{:>6} {:>12.6} {:>12.6}", "All", self.max(), self.mean())
+	}
+}
+
+pub fn compare(a: &MD3Model, b: &MD3Model) -> Result<MeshDiff, TopologyError> {
+	if a.surfaces.len() != b.surfaces.len() {
+		return Err(TopologyError::SurfaceCount(a.surfaces.len(), b.surfaces.len()));
+	}
+	let num_frames = a.frames.len();
+	if num_frames != b.frames.len() {
+		return Err(TopologyError::FrameCount(num_frames, b.frames.len()));
+	}
+	a.surfaces.iter().zip(b.surfaces.iter()).enumerate().try_for_each(|(surface, (sa, sb))| {
+		if sa.num_verts != sb.num_verts {
+			return Err(TopologyError::VertexCount { surface, a: sa.num_verts, b: sb.num_verts });
+		}
+		let same_triangles = sa.triangles.len() == sb.triangles.len() &&
+			sa.triangles.iter().zip(sb.triangles.iter()).all(|(ta, tb)| ta.0 == tb.0);
+		if !same_triangles {
+			return Err(TopologyError::Triangles(surface));
+		}
+		// The vertices of a surface are the same for every frame
+		if sa.num_frames != num_frames || sb.num_frames != num_frames {
+			return Err(TopologyError::FrameCount(sa.num_frames, sb.num_frames));
+		}
+		Ok(())
+	})?;
+	let surfaces: Vec<Vec<f32>> = a.surfaces.iter().zip(b.surfaces.iter())
+		.map(|(sa, sb)| sa.vertices.iter().zip(sb.vertices.iter())
+			.map(|(va, vb)| va.position().distance(vb.position()))
+			.collect())
+		.collect();
+	let frames = (0..num_frames).map(|frame| {
+		let (max, sum, count) = a.surfaces.iter().zip(surfaces.iter())
+			.flat_map(|(surface, deviation)| {
+				let start = frame * surface.num_verts;
+				deviation[start..start + surface.num_verts].iter()
+			})
+			.fold((0f32, 0f32, 0usize), |(max, sum, count), &d| (max.max(d), sum + d, count + 1));
+		let mean = if count > 0 { sum / count as f32 } else { 0. };
+		FrameDeviation { max, mean }
+	}).collect();
+	Ok(MeshDiff { surfaces, frames })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::md3::tests::{test_model, test_surface};
+	use crate::md3::{MD3FrameVertex, MD3Surface, MD3Triangle};
+
+	fn model(vertices: Vec<MD3FrameVertex>) -> MD3Model {
+		test_model(vec![MD3Surface { triangles: vec![MD3Triangle([0, 1, 1])], ..test_surface("", 2, vertices) }])
+	}
+
+	#[test]
+	fn deviation() {
+		let vertex = |x| MD3FrameVertex { x, y: 0, z: 0, n: 0 };
+		let a = model(vec![vertex(0), vertex(64), vertex(0), vertex(0)]);
+		let b = model(vec![vertex(0), vertex(64), vertex(128), vertex(64)]);
+		let diff = compare(&a, &b).unwrap();
+		assert_eq!(diff.surfaces, vec![vec![0., 0., 2., 1.]]);
+		assert_eq!(diff.frames, vec![
+			FrameDeviation { max: 0., mean: 0. },
+			FrameDeviation { max: 2., mean: 1.5 },
+		]);
+		assert_eq!(diff.max(), 2.);
+		let c = model(vec![vertex(0), vertex(64)]);
+		assert_eq!(compare(&a, &c), Err(TopologyError::FrameCount(2, 1)));
+	}
+}
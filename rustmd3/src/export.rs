@@ -0,0 +1,661 @@
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use anyhow::Error;
+use crate::md3::{MD3Model, MD3Surface};
+use crate::str_util::StringFromBytes;
+use glam::{Mat3, Vec2, Vec3};
+use image::{Rgba, RgbaImage, imageops::{self, FilterType}};
+use serde::Serialize;
+use std::{borrow::Cow, collections::HashMap, io::Write, ops::RangeInclusive, path::Path};
+
+pub const UV_LINE_COLOUR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+// Rasterize the UV wireframe of the given surfaces, optionally over a
+// background image (usually the current skin), which is stretched to fit.
+pub fn uv_layout(surfaces: &[&MD3Surface], size: u32, background: Option<&RgbaImage>) -> RgbaImage {
+	let mut image = match background {
+		Some(bg) => imageops::resize(bg, size, size, FilterType::Triangle),
+		None => RgbaImage::from_pixel(size, size, Rgba([0, 0, 0, 0])),
+	};
+	let scale = size as f32;
+	surfaces.iter().for_each(|surf| {
+		surf.triangles.iter().for_each(|tri| {
+			let corners = tri.0.map(|vi| {
+				surf.texcoords.get(vi as usize).map(|uv| uv.0 * scale)
+					.unwrap_or_default()
+			});
+			(0..3).for_each(|i| {
+				let a = corners[i];
+				let b = corners[(i + 1) % 3];
+				draw_line(&mut image, (a.x, a.y), (b.x, b.y), UV_LINE_COLOUR);
+			});
+		});
+	});
+	image
+}
+
+// Bresenham's line algorithm. Pixels outside of the image are skipped.
+fn draw_line(image: &mut RgbaImage, a: (f32, f32), b: (f32, f32), colour: Rgba<u8>) {
+	let (mut x0, mut y0) = (a.0.floor() as i64, a.1.floor() as i64);
+	let (x1, y1) = (b.0.floor() as i64, b.1.floor() as i64);
+	let dx = (x1 - x0).abs();
+	let dy = -(y1 - y0).abs();
+	let sx = if x0 < x1 { 1 } else { -1 };
+	let sy = if y0 < y1 { 1 } else { -1 };
+	let mut err = dx + dy;
+	let (width, height) = (image.width() as i64, image.height() as i64);
+	loop {
+		if x0 >= 0 && y0 >= 0 && x0 < width && y0 < height {
+			image.put_pixel(x0 as u32, y0 as u32, colour);
+		}
+		if x0 == x1 && y0 == y1 { break; }
+		let e2 = 2 * err;
+		if e2 >= dy { err += dy; x0 += sx; }
+		if e2 <= dx { err += dx; y0 += sy; }
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VertexDataFormat {
+	#[default]
+	Csv,
+	Json,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct VertexRecord {
+	frame: usize,
+	vertex: usize,
+	x: f32,
+	y: f32,
+	z: f32,
+	nx: f32,
+	ny: f32,
+	nz: f32,
+	// Encoded normal
+	n: u16,
+}
+
+// Write the positions and decoded normals of the vertices of a surface for
+// the given frames.
+pub fn vertex_data(surf: &MD3Surface, frames: RangeInclusive<usize>, gzdoom: bool, format: VertexDataFormat, writer: &mut impl Write) -> Result<(), Error> {
+	let last_frame = surf.num_frames.saturating_sub(1);
+	let frames = *frames.start().min(&last_frame)..=*frames.end().min(&last_frame);
+	let records = frames.flat_map(|frame| {
+		// Frames which aren't in the file, like in surfaces without any
+		// frames, are left out
+		let start = frame * surf.num_verts;
+		surf.vertices.get(start..start + surf.num_verts).unwrap_or_default().iter().enumerate()
+			.map(move |(vertex, vert)| {
+				let pos = vert.position();
+				let normal = vert.normal(gzdoom);
+				VertexRecord {
+					frame, vertex,
+					x: pos.x, y: pos.y, z: pos.z,
+					nx: normal.x, ny: normal.y, nz: normal.z,
+					n: vert.n,
+				}
+			})
+	});
+	match format {
+		VertexDataFormat::Csv => {
+			writeln!(writer, "frame,vertex,x,y,z,nx,ny,nz,n")?;
+			for r in records {
+				writeln!(writer, "{},{},{},{},{},{},{},{},{}",
+					r.frame, r.vertex, r.x, r.y, r.z, r.nx, r.ny, r.nz, r.n)?;
+			}
+		},
+		VertexDataFormat::Json => {
+			let records: Vec<VertexRecord> = records.collect();
+			serde_json::to_writer_pretty(writer, &records)?;
+		},
+	}
+	Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FrameBoundsRecord<'a> {
+	frame: usize,
+	name: Cow<'a, str>,
+	min: Vec3,
+	max: Vec3,
+	origin: Vec3,
+	radius: f32,
+}
+
+fn frame_bounds_records(model: &MD3Model) -> Vec<FrameBoundsRecord<'_>> {
+	model.frames.iter().enumerate()
+		.map(|(frame, f)| FrameBoundsRecord {
+			frame,
+			name: String::from_utf8_stop(&f.name),
+			min: f.min,
+			max: f.max,
+			origin: f.origin,
+			radius: f.radius,
+		}).collect()
+}
+
+// Write the bounding box and radius of every frame of the model as JSON
+pub fn frame_bounds(model: &MD3Model, writer: &mut impl Write) -> Result<(), Error> {
+	serde_json::to_writer_pretty(writer, &frame_bounds_records(model))?;
+	Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ModelRecord<'a> {
+	name: Cow<'a, str>,
+	version: i32,
+	num_frames: usize,
+	num_tags: usize,
+	num_surfaces: usize,
+	frames: Vec<FrameBoundsRecord<'a>>,
+	// The tags in each frame
+	tags: Vec<Vec<TagRecord<'a>>>,
+	surfaces: Vec<SurfaceRecord<'a>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TagRecord<'a> {
+	name: Cow<'a, str>,
+	origin: Vec3,
+	axes: Mat3,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ShaderRecord<'a> {
+	name: Cow<'a, str>,
+	index: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SurfaceRecord<'a> {
+	name: Cow<'a, str>,
+	num_verts: usize,
+	num_frames: usize,
+	num_triangles: usize,
+	shaders: Vec<ShaderRecord<'a>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	triangles: Option<Vec<[u32; 3]>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	texcoords: Option<Vec<Vec2>>,
+	// Encoded positions and normals, num_verts for each frame
+	#[serde(skip_serializing_if = "Option::is_none")]
+	vertices: Option<Vec<[i32; 4]>>,
+}
+
+// Write everything about the model except its geometry as JSON, for other
+// tools. The triangles, texture coordinates and vertices are included if
+// `full` is true.
+pub fn model_structure(model: &MD3Model, full: bool, writer: &mut impl Write) -> Result<(), Error> {
+	let tags = match model.num_tags {
+		0 => vec![],
+		num_tags => model.tags.chunks(num_tags).map(|frame| frame.iter().map(|tag| TagRecord {
+			name: String::from_utf8_stop(&tag.name),
+			origin: tag.origin,
+			axes: tag.axes,
+		}).collect()).collect(),
+	};
+	let surfaces = model.surfaces.iter().map(|surf| SurfaceRecord {
+		name: String::from_utf8_stop(&surf.name),
+		num_verts: surf.num_verts,
+		num_frames: surf.num_frames,
+		num_triangles: surf.triangles.len(),
+		shaders: surf.shaders.iter().map(|shader| ShaderRecord {
+			name: String::from_utf8_stop(&shader.name),
+			index: shader.index,
+		}).collect(),
+		triangles: full.then(|| surf.triangles.iter().map(|t| t.0).collect()),
+		texcoords: full.then(|| surf.texcoords.iter().map(|t| t.0).collect()),
+		vertices: full.then(|| surf.vertices.iter()
+			.map(|v| [v.x as i32, v.y as i32, v.z as i32, v.n as i32]).collect()),
+	}).collect();
+	let record = ModelRecord {
+		name: String::from_utf8_stop(&model.name),
+		version: model.version,
+		num_frames: model.frames.len(),
+		num_tags: model.num_tags,
+		num_surfaces: model.surfaces.len(),
+		frames: frame_bounds_records(model),
+		tags,
+		surfaces,
+	};
+	serde_json::to_writer_pretty(writer, &record)?;
+	Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MeshFormat {
+	// Binary STL
+	#[default]
+	Stl,
+	// Binary little-endian PLY
+	Ply,
+}
+
+impl MeshFormat {
+	pub fn from_path(path: &Path) -> Option<Self> {
+		match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+			"stl" => Some(MeshFormat::Stl),
+			"ply" => Some(MeshFormat::Ply),
+			_ => None,
+		}
+	}
+	pub fn extension(self) -> &'static str {
+		match self {
+			MeshFormat::Stl => "stl",
+			MeshFormat::Ply => "ply",
+		}
+	}
+}
+
+// All of the surfaces of a frame as one mesh, with the vertices in the same
+// place welded together, so slicers see a closed mesh where the model is one.
+// The triangles are wound anticlockwise, and triangles which have no area
+// after welding are left out.
+pub fn welded_mesh(model: &MD3Model, frame: usize) -> (Vec<Vec3>, Vec<[u32; 3]>) {
+	let mut positions = vec![];
+	let mut welded: HashMap<[i16; 3], u32> = HashMap::new();
+	let mut triangles = vec![];
+	model.surfaces.iter().for_each(|surf| {
+		let num_verts = surf.num_verts;
+		let verts = match surf.vertices.get(frame * num_verts..(frame + 1) * num_verts) {
+			Some(v) => v,
+			None => return,
+		};
+		let indices: Vec<u32> = verts.iter().map(|v| *welded.entry([v.x, v.y, v.z]).or_insert_with(|| {
+			positions.push(v.position());
+			positions.len() as u32 - 1
+		})).collect();
+		triangles.extend(surf.triangles.iter().filter_map(|tri| {
+			let [a, b, c] = tri.0.map(|i| indices.get(i as usize).copied());
+			// MD3 triangles are wound clockwise
+			let triangle = [a?, c?, b?];
+			(triangle[0] != triangle[1] && triangle[1] != triangle[2] && triangle[2] != triangle[0])
+				.then_some(triangle)
+		}));
+	});
+	(positions, triangles)
+}
+
+// Write a frame of the model as a mesh for 3D printing, with `scale`
+// millimetres per unit
+pub fn print_mesh(model: &MD3Model, frame: usize, scale: f32, format: MeshFormat, writer: &mut impl Write) -> Result<(), Error> {
+	let (positions, triangles) = welded_mesh(model, frame);
+	let positions: Vec<Vec3> = positions.into_iter().map(|p| p * scale).collect();
+	let name = String::from_utf8_stop(&model.name);
+	match format {
+		MeshFormat::Stl => {
+			let mut header = [0u8; 80];
+			let title = format!("{} frame {}", name, frame);
+			let len = title.len().min(header.len());
+			header[..len].copy_from_slice(&title.as_bytes()[..len]);
+			writer.write_all(&header)?;
+			writer.write_all(&(triangles.len() as u32).to_le_bytes())?;
+			for triangle in triangles.iter() {
+				let [a, b, c] = triangle.map(|i| positions[i as usize]);
+				let normal = (b - a).cross(c - a).normalize_or_zero();
+				for v in [normal, a, b, c] {
+					v.to_array().iter().try_for_each(|n| writer.write_all(&n.to_le_bytes()))?;
+				}
+				// Attribute byte count
+				writer.write_all(&[0, 0])?;
+			}
+		},
+		MeshFormat::Ply => {
+			write!(writer, concat!(
+				"ply\n",
+				"format binary_little_endian 1.0\n",
+				"comment {} frame {}, in millimetres\n",
+				"element vertex {}\n",
+				"property float x\n",
+				"property float y\n",
+				"property float z\n",
+				"element face {}\n",
+				"property list uchar int vertex_indices\n",
+				"end_header\n"),
+				name.replace('\n', " "), frame, positions.len(), triangles.len())?;
+			for position in positions.iter() {
+				position.to_array().iter().try_for_each(|n| writer.write_all(&n.to_le_bytes()))?;
+			}
+			for triangle in triangles.iter() {
+				writer.write_all(&[3])?;
+				triangle.iter().try_for_each(|&i| writer.write_all(&(i as i32).to_le_bytes()))?;
+			}
+		},
+	}
+	Ok(())
+}
+
+pub const CONTACT_SHEET_BACKGROUND: Rgba<u8> = Rgba([32, 32, 32, 255]);
+pub const CONTACT_SHEET_TEXT: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const LABEL_SIZE: f32 = 14.;
+const LABEL_HEIGHT: u32 = 20;
+
+// Arrange the previews in a grid, with the name of each one below it. The
+// previews should all be the same size.
+pub fn contact_sheet(previews: &[(String, RgbaImage)], font: &FontRef) -> RgbaImage {
+	let (tile_width, tile_height) = previews.first()
+		.map(|(_, image)| image.dimensions()).unwrap_or((0, 0));
+	let columns = (previews.len() as f32).sqrt().ceil().max(1.) as u32;
+	let rows = (previews.len() as u32).div_ceil(columns);
+	let cell_height = tile_height + LABEL_HEIGHT;
+	let mut sheet = RgbaImage::from_pixel(columns * tile_width, rows * cell_height,
+		CONTACT_SHEET_BACKGROUND);
+	previews.iter().enumerate().for_each(|(index, (name, image))| {
+		let x = index as u32 % columns * tile_width;
+		let y = index as u32 / columns * cell_height;
+		imageops::overlay(&mut sheet, image, x as i64, y as i64);
+		draw_text(&mut sheet, font, name, (x as f32 + 2., (y + tile_height) as f32 + 2.),
+			tile_width as f32 - 4., CONTACT_SHEET_TEXT);
+	});
+	sheet
+}
+
+// Doom's sprite frame letters, with the extra ones ZDoom allows
+const SPRITE_FRAMES: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]";
+// In the order the rotations go around the thing. 16 rotations put GZDoom's
+// extra ones between Doom's.
+const SPRITE_ROTATIONS_8: &[u8] = b"12345678";
+const SPRITE_ROTATIONS_16: &[u8] = b"192A3B4C5D6E7F8G";
+
+// The lump name of a sprite, like TROOA1. None if there are too many frames,
+// or the number of rotations isn't 8 or 16.
+pub fn sprite_name(prefix: &str, frame: usize, rotation: usize, rotations: usize) -> Option<String> {
+	let names = match rotations {
+		8 => SPRITE_ROTATIONS_8,
+		16 => SPRITE_ROTATIONS_16,
+		_ => return None,
+	};
+	Some(format!("{}{}{}", prefix, *SPRITE_FRAMES.get(frame)? as char, *names.get(rotation)? as char))
+}
+
+pub const MAX_SPRITE_FRAMES: usize = SPRITE_FRAMES.len();
+
+// A view of the model, cut down to what was drawn. The offset is where the
+// model's origin is from the top left of the image, like Doom's sprite
+// offsets.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+	pub name: String,
+	pub image: RgbaImage,
+	pub offset: (i32, i32),
+}
+
+impl Sprite {
+	// Cut off the transparent edges of a rendered image. origin is where the
+	// model's origin is in it.
+	pub fn trimmed(name: String, image: &RgbaImage, origin: (i32, i32)) -> Self {
+		let drawn = image.enumerate_pixels().filter(|(_, _, p)| p[3] > 0)
+			.fold(None, |bounds: Option<(u32, u32, u32, u32)>, (x, y, _)| Some(match bounds {
+				Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+				None => (x, y, x, y),
+			}));
+		// Sprites can't be empty, so nothing is a transparent pixel
+		let (left, top, right, bottom) = drawn.unwrap_or((0, 0, 0, 0));
+		let image = imageops::crop_imm(image, left, top, right - left + 1, bottom - top + 1).to_image();
+		Sprite { name, image, offset: (origin.0 - left as i32, origin.1 - top as i32) }
+	}
+}
+
+// Where a sprite is in the sheet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SpritePlacement {
+	pub name: String,
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32,
+	pub x_offset: i32,
+	pub y_offset: i32,
+}
+
+// Space around each sprite, so that they don't bleed into each other
+const SPRITE_PADDING: u32 = 1;
+
+// Pack the sprites into rows, tallest first, in a roughly square sheet
+pub fn sprite_sheet(sprites: &[Sprite]) -> (RgbaImage, Vec<SpritePlacement>) {
+	let padded = |sprite: &Sprite| {
+		let (width, height) = sprite.image.dimensions();
+		(width + SPRITE_PADDING, height + SPRITE_PADDING)
+	};
+	let area: u32 = sprites.iter().map(|s| padded(s).0 * padded(s).1).sum();
+	let widest = sprites.iter().map(|s| padded(s).0).max().unwrap_or(0);
+	let sheet_width = widest.max((area as f32).sqrt().ceil() as u32);
+	let mut order: Vec<usize> = (0..sprites.len()).collect();
+	order.sort_by_key(|&index| std::cmp::Reverse(padded(&sprites[index]).1));
+	let mut positions = vec![(0, 0); sprites.len()];
+	let (mut x, mut y, mut row_height) = (0, 0, 0);
+	order.into_iter().for_each(|index| {
+		let (width, height) = padded(&sprites[index]);
+		if x + width > sheet_width {
+			x = 0;
+			y += row_height;
+			row_height = 0;
+		}
+		positions[index] = (x, y);
+		x += width;
+		row_height = row_height.max(height);
+	});
+	let mut sheet = RgbaImage::new(sheet_width, y + row_height);
+	let placements = sprites.iter().zip(positions).map(|(sprite, (x, y))| {
+		imageops::overlay(&mut sheet, &sprite.image, x as i64, y as i64);
+		SpritePlacement {
+			name: sprite.name.clone(),
+			x, y,
+			width: sprite.image.width(),
+			height: sprite.image.height(),
+			x_offset: sprite.offset.0,
+			y_offset: sprite.offset.1,
+		}
+	}).collect();
+	(sheet, placements)
+}
+
+pub fn sprite_offsets(placements: &[SpritePlacement], writer: &mut impl Write) -> Result<(), Error> {
+	serde_json::to_writer_pretty(writer, placements)?;
+	Ok(())
+}
+
+// A web page showing thumbnails of models, with their names, and the models
+// which couldn't be rendered with the reasons why. The thumbnails are pairs of
+// names and image URLs.
+pub fn thumbnail_index(thumbnails: &[(String, String)], failed: &[(String, String)], writer: &mut impl Write) -> Result<(), Error> {
+	let escape = |text: &str| text.replace('&', "&amp;").replace('<', "&lt;")
+		.replace('>', "&gt;").replace('"', "&quot;");
+	writeln!(writer, "<!DOCTYPE html>")?;
+	writeln!(writer, "<html><head><meta charset=\"utf-8\"><title>Models</title>")?;
+	writeln!(writer, "<style>body {{ background: #222; color: #ddd; font-family: sans-serif; }} \
+		figure {{ display: inline-block; margin: 4px; }} \
+		figcaption {{ max-width: 256px; overflow-wrap: anywhere; }}</style>")?;
+	writeln!(writer, "</head><body>")?;
+	thumbnails.iter().try_for_each(|(name, image)| writeln!(writer,
+		"<figure><img src=\"{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>",
+		escape(image), escape(name), escape(name)))?;
+	if !failed.is_empty() {
+		writeln!(writer, "<h2>Not rendered</h2><ul>")?;
+		failed.iter().try_for_each(|(name, error)| writeln!(writer,
+			"<li>{}: {}</li>", escape(name), escape(error)))?;
+		writeln!(writer, "</ul>")?;
+	}
+	writeln!(writer, "</body></html>")?;
+	Ok(())
+}
+
+// Draw a line of text with its top left corner at the given position. Text
+// wider than max_width is cut off.
+fn draw_text(image: &mut RgbaImage, font: &FontRef, text: &str, pos: (f32, f32), max_width: f32, colour: Rgba<u8>) {
+	let scale = PxScale::from(LABEL_SIZE);
+	let scaled = font.as_scaled(scale);
+	let baseline = pos.1 + scaled.ascent();
+	let mut caret = pos.0;
+	for c in text.chars() {
+		let id = font.glyph_id(c);
+		let advance = scaled.h_advance(id);
+		if caret + advance > pos.0 + max_width { break; }
+		let glyph = id.with_scale_and_position(scale, ab_glyph::point(caret, baseline));
+		caret += advance;
+		let outline = match font.outline_glyph(glyph) {
+			Some(o) => o,
+			None => continue,
+		};
+		let bounds = outline.px_bounds();
+		outline.draw(|gx, gy, coverage| {
+			let x = bounds.min.x as i64 + gx as i64;
+			let y = bounds.min.y as i64 + gy as i64;
+			if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+				return;
+			}
+			let pixel = image.get_pixel_mut(x as u32, y as u32);
+			pixel.0.iter_mut().zip(colour.0.iter()).for_each(|(p, &c)| {
+				*p = (*p as f32 + (c as f32 - *p as f32) * coverage) as u8;
+			});
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::md3::tests::{test_model, triangle};
+	use crate::md3::MD3TexCoord;
+	use glam::Vec2;
+
+	fn triangle_surface() -> MD3Surface {
+		MD3Surface {
+			texcoords: vec![
+				MD3TexCoord(Vec2::new(0., 0.)),
+				MD3TexCoord(Vec2::new(0.5, 0.)),
+				MD3TexCoord(Vec2::new(0., 0.5)),
+			],
+			..triangle()
+		}
+	}
+
+	#[test]
+	fn vertex_csv() {
+		let mut surf = triangle_surface();
+		surf.vertices[1].x = 64;
+		surf.vertices[2].n = 32768;
+		let mut csv = Vec::new();
+		vertex_data(&surf, 0..=5, false, VertexDataFormat::Csv, &mut csv).unwrap();
+		let csv = String::from_utf8(csv).unwrap();
+		let lines: Vec<&str> = csv.lines().collect();
+		assert_eq!(lines.len(), 4);
+		assert_eq!(lines[0], "frame,vertex,x,y,z,nx,ny,nz,n");
+		assert_eq!(lines[1], "0,0,0,0,0,0,0,1,0");
+		assert_eq!(lines[2], "0,1,1,0,0,0,0,1,0");
+		assert_eq!(lines[3], "0,2,0,0,0,0,0,-1,32768");
+		// A surface without any frames only has the header
+		let surf = MD3Surface { num_frames: 0, vertices: vec![], ..triangle_surface() };
+		let mut csv = Vec::new();
+		vertex_data(&surf, 0..=5, false, VertexDataFormat::Csv, &mut csv).unwrap();
+		assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 1);
+	}
+
+	#[test]
+	fn uv_wireframe() {
+		let surf = triangle_surface();
+		let image = uv_layout(&[&surf], 16, None);
+		assert_eq!(image.get_pixel(0, 0), &UV_LINE_COLOUR);
+		assert_eq!(image.get_pixel(4, 0), &UV_LINE_COLOUR);
+		assert_eq!(image.get_pixel(0, 4), &UV_LINE_COLOUR);
+		assert_eq!(image.get_pixel(2, 2), &Rgba([0, 0, 0, 0]));
+		assert_eq!(image.get_pixel(12, 12), &Rgba([0, 0, 0, 0]));
+	}
+
+	#[test]
+	fn structure_json() {
+		let model = test_model(vec![triangle_surface()]);
+		let mut json = Vec::new();
+		model_structure(&model, false, &mut json).unwrap();
+		let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+		assert_eq!(json["surfaces"][0]["num_triangles"], 1);
+		assert!(json["surfaces"][0].get("vertices").is_none());
+		let mut json = Vec::new();
+		model_structure(&model, true, &mut json).unwrap();
+		let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+		assert_eq!(json["surfaces"][0]["triangles"][0], serde_json::json!([0, 1, 2]));
+		assert_eq!(json["surfaces"][0]["vertices"].as_array().map(Vec::len), Some(3));
+	}
+
+	#[test]
+	fn thumbnail_page() {
+		let mut html = Vec::new();
+		thumbnail_index(&[(String::from("a&b.md3"), String::from("a&b_thumb.png"))],
+			&[(String::from("bad.md3"), String::from("<EOF>"))], &mut html).unwrap();
+		let html = String::from_utf8(html).unwrap();
+		assert!(html.contains("<img src=\"a&amp;b_thumb.png\" alt=\"a&amp;b.md3\">"));
+		assert!(html.contains("<li>bad.md3: &lt;EOF&gt;</li>"));
+	}
+
+	#[test]
+	fn contact_sheet_layout() {
+		let fonts = egui::FontDefinitions::default();
+		let font = FontRef::try_from_slice(&fonts.font_data["Hack"].font).unwrap();
+		let previews: Vec<(String, RgbaImage)> = (0..5).map(|i| {
+			(format!("model{}", i), RgbaImage::from_pixel(8, 8, Rgba([255, 0, 0, 255])))
+		}).collect();
+		let sheet = contact_sheet(&previews, &font);
+		// 5 previews need 3 columns and 2 rows
+		assert_eq!(sheet.dimensions(), (24, 2 * (8 + LABEL_HEIGHT)));
+		assert_eq!(sheet.get_pixel(17, 0), &Rgba([255, 0, 0, 255]));
+		assert_eq!(sheet.get_pixel(17, 8 + LABEL_HEIGHT), &CONTACT_SHEET_BACKGROUND);
+	}
+
+	#[test]
+	fn sprites() {
+		assert_eq!(sprite_name("TROO", 0, 0, 8).as_deref(), Some("TROOA1"));
+		assert_eq!(sprite_name("TROO", 28, 1, 16).as_deref(), Some("TROO]9"));
+		assert_eq!(sprite_name("TROO", 29, 0, 8), None);
+		assert_eq!(sprite_name("TROO", 0, 0, 4), None);
+		// A 2×3 block drawn in the middle of a 10×10 render
+		let mut render = RgbaImage::new(10, 10);
+		(4..6).for_each(|x| (3..6).for_each(|y| render.put_pixel(x, y, Rgba([255, 0, 0, 255]))));
+		let sprite = Sprite::trimmed(String::from("TROOA1"), &render, (5, 6));
+		assert_eq!(sprite.image.dimensions(), (2, 3));
+		assert_eq!(sprite.offset, (1, 3));
+		let empty = Sprite::trimmed(String::from("TROOA2"), &RgbaImage::new(10, 10), (5, 5));
+		assert_eq!(empty.image.dimensions(), (1, 1));
+		let (sheet, placements) = sprite_sheet(&[sprite, empty]);
+		assert_eq!(placements[0], SpritePlacement {
+			name: String::from("TROOA1"), x: 0, y: 0, width: 2, height: 3, x_offset: 1, y_offset: 3,
+		});
+		assert_eq!((placements[1].x, placements[1].y), (0, 4));
+		assert_eq!(sheet.get_pixel(1, 2), &Rgba([255, 0, 0, 255]));
+	}
+
+	#[test]
+	fn print_meshes() {
+		let mut first = triangle_surface();
+		first.vertices[1].x = 64;
+		first.vertices[2].y = 64;
+		// Shares an edge with the first one
+		let mut second = triangle_surface();
+		second.vertices[0].x = 64;
+		second.vertices[1].x = 64;
+		second.vertices[1].y = 64;
+		second.vertices[2].y = 64;
+		let model = test_model(vec![first, second, triangle_surface()]);
+		let (positions, triangles) = welded_mesh(&model, 0);
+		assert_eq!(positions.len(), 4);
+		// The third surface has no area
+		assert_eq!(triangles, vec![[0, 2, 1], [1, 2, 3]]);
+		assert_eq!(MeshFormat::from_path(Path::new("prop.PLY")), Some(MeshFormat::Ply));
+
+		let mut stl = vec![];
+		print_mesh(&model, 0, 2., MeshFormat::Stl, &mut stl).unwrap();
+		assert_eq!(stl.len(), 84 + 50 * 2);
+		assert_eq!(&stl[80..84], &2u32.to_le_bytes());
+		// Anticlockwise seen from above is clockwise seen from below, so it
+		// faces down
+		assert_eq!(&stl[84 + 8..84 + 12], &(-1f32).to_le_bytes());
+		// The second corner of the first triangle, which is 1 unit along Y
+		assert_eq!(&stl[84 + 24..84 + 28], &0f32.to_le_bytes());
+		assert_eq!(&stl[84 + 28..84 + 32], &2f32.to_le_bytes());
+
+		let mut ply = vec![];
+		print_mesh(&model, 0, 1., MeshFormat::Ply, &mut ply).unwrap();
+		let header_end = ply.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+		assert!(String::from_utf8_lossy(&ply[..header_end]).contains("element face 2\n"));
+		assert_eq!(ply.len(), header_end + 4 * 12 + 2 * 13);
+	}
+}
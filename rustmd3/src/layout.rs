@@ -0,0 +1,235 @@
+// Where everything in an MD3 file is, straight from the headers, for finding
+// out why an exporter's files are broken. Unlike read_md3, this doesn't stop
+// at the first problem.
+use crate::md3::{
+	MD3_FRAME_SIZE, MD3_HEADER_SIZE, MD3_SHADER_SIZE, MD3_SURFACE_HEADER_SIZE,
+	MD3_TAG_SIZE, MD3_TEXCOORD_SIZE, MD3_TRIANGLE_SIZE, MD3_VERTEX_SIZE,
+};
+use crate::str_util::StringFromBytes;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+	pub ident: [u8; 4],
+	pub version: i32,
+	pub name: String,
+	pub flags: i32,
+	pub num_frames: u32,
+	pub num_tags: u32,
+	pub num_surfaces: u32,
+	pub num_skins: u32,
+	pub offset_frames: u32,
+	pub offset_tags: u32,
+	pub offset_surfaces: u32,
+	pub offset_end: u32,
+}
+
+// The offsets are relative to the start of the surface
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SurfaceHeader {
+	// Where the surface starts in the file
+	pub start: u64,
+	pub ident: [u8; 4],
+	pub name: String,
+	pub flags: i32,
+	pub num_frames: u32,
+	pub num_shaders: u32,
+	pub num_verts: u32,
+	pub num_triangles: u32,
+	pub offset_triangles: u32,
+	pub offset_shaders: u32,
+	pub offset_texcoords: u32,
+	pub offset_vertices: u32,
+	pub offset_end: u32,
+}
+
+// A part of the file, from start up to end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+	pub name: String,
+	pub start: u64,
+	pub end: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LayoutIssue {
+	#[error("The file ends after {0} bytes, in the middle of a header")]
+	Truncated(u64),
+	#[error("{name} ({start:#x} to {end:#x}) goes past the end of the file ({len:#x})")]
+	PastEnd { name: String, start: u64, end: u64, len: u64 },
+	#[error("{0} overlaps {1}")]
+	Overlap(String, String),
+	#[error("{0} comes before {1}, although it's after it in the header")]
+	Unordered(String, String),
+	#[error("{name} says it ends at {stated:#x}, but its data ends at {actual:#x}")]
+	WrongEnd { name: String, stated: u64, actual: u64 },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Layout {
+	pub len: u64,
+	pub header: Header,
+	pub surfaces: Vec<SurfaceHeader>,
+	// In the order they are in the file
+	pub sections: Vec<Section>,
+	pub issues: Vec<LayoutIssue>,
+}
+
+struct Fields<'a> {
+	data: &'a [u8],
+	offset: usize,
+}
+
+impl<'a> Fields<'a> {
+	fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+		let bytes = self.data.get(self.offset..self.offset + N)?.try_into().ok()?;
+		self.offset += N;
+		Some(bytes)
+	}
+	fn u32(&mut self) -> Option<u32> {
+		self.bytes().map(u32::from_le_bytes)
+	}
+	fn i32(&mut self) -> Option<i32> {
+		self.bytes().map(i32::from_le_bytes)
+	}
+	fn name<const N: usize>(&mut self) -> Option<String> {
+		self.bytes::<N>().map(|name| String::from_utf8_stop(&name).into_owned())
+	}
+}
+
+fn read_header(data: &[u8]) -> Option<Header> {
+	let mut f = Fields { data, offset: 0 };
+	Some(Header {
+		ident: f.bytes()?,
+		version: f.i32()?,
+		name: f.name::<64>()?,
+		flags: f.i32()?,
+		num_frames: f.u32()?,
+		num_tags: f.u32()?,
+		num_surfaces: f.u32()?,
+		num_skins: f.u32()?,
+		offset_frames: f.u32()?,
+		offset_tags: f.u32()?,
+		offset_surfaces: f.u32()?,
+		offset_end: f.u32()?,
+	})
+}
+
+fn read_surface_header(data: &[u8], start: u64) -> Option<SurfaceHeader> {
+	let mut f = Fields { data, offset: usize::try_from(start).ok()? };
+	Some(SurfaceHeader {
+		start,
+		ident: f.bytes()?,
+		name: f.name::<64>()?,
+		flags: f.i32()?,
+		num_frames: f.u32()?,
+		num_shaders: f.u32()?,
+		num_verts: f.u32()?,
+		num_triangles: f.u32()?,
+		offset_triangles: f.u32()?,
+		offset_shaders: f.u32()?,
+		offset_texcoords: f.u32()?,
+		offset_vertices: f.u32()?,
+		offset_end: f.u32()?,
+	})
+}
+
+fn section(name: String, start: u64, count: u64, size: usize) -> Section {
+	Section { name, start, end: start.saturating_add(count.saturating_mul(size as u64)) }
+}
+
+pub fn read_layout(data: &[u8]) -> Layout {
+	let len = data.len() as u64;
+	let mut layout = Layout { len, ..Default::default() };
+	let header = match read_header(data) {
+		Some(h) => h,
+		None => {
+			layout.issues.push(LayoutIssue::Truncated(len));
+			return layout;
+		},
+	};
+	// The sections in the order the header lists them
+	let mut listed = vec![
+		section(String::from("Header"), 0, 1, MD3_HEADER_SIZE),
+		section(String::from("Frames"), header.offset_frames as u64, header.num_frames as u64, MD3_FRAME_SIZE),
+		section(String::from("Tags"), header.offset_tags as u64,
+			header.num_tags as u64 * header.num_frames as u64, MD3_TAG_SIZE),
+	];
+	// Each surface starts where the previous one ends, like in Quake 3
+	let mut start = header.offset_surfaces as u64;
+	for index in 0..header.num_surfaces {
+		let surface = match read_surface_header(data, start) {
+			Some(s) => s,
+			None => {
+				layout.issues.push(LayoutIssue::Truncated(len));
+				break;
+			},
+		};
+		let name = |part: &str| format!("Surface {} {}", index, part);
+		let sections = [
+			section(name("header"), start, 1, MD3_SURFACE_HEADER_SIZE),
+			section(name("shaders"), start + surface.offset_shaders as u64, surface.num_shaders as u64, MD3_SHADER_SIZE),
+			section(name("triangles"), start + surface.offset_triangles as u64, surface.num_triangles as u64, MD3_TRIANGLE_SIZE),
+			section(name("texture coordinates"), start + surface.offset_texcoords as u64, surface.num_verts as u64, MD3_TEXCOORD_SIZE),
+			section(name("vertices"), start + surface.offset_vertices as u64,
+				surface.num_verts as u64 * surface.num_frames as u64, MD3_VERTEX_SIZE),
+		];
+		let end = start + surface.offset_end as u64;
+		let actual = sections.iter().map(|s| s.end).max().unwrap_or(end);
+		if end != actual {
+			layout.issues.push(LayoutIssue::WrongEnd { name: format!("Surface {}", index), stated: end, actual });
+		}
+		listed.extend(sections);
+		layout.surfaces.push(surface);
+		start = end;
+	}
+	let actual = listed.iter().map(|s| s.end).max().unwrap_or(0);
+	if header.offset_end as u64 != actual {
+		layout.issues.push(LayoutIssue::WrongEnd { name: String::from("The model"), stated: header.offset_end as u64, actual });
+	}
+	// Empty sections can be anywhere
+	listed.retain(|s| s.end > s.start);
+	listed.iter().filter(|s| s.end > len).for_each(|s| layout.issues.push(LayoutIssue::PastEnd {
+		name: s.name.clone(), start: s.start, end: s.end, len,
+	}));
+	listed.windows(2).filter(|pair| pair[1].start < pair[0].start).for_each(|pair| {
+		layout.issues.push(LayoutIssue::Unordered(pair[1].name.clone(), pair[0].name.clone()));
+	});
+	listed.sort_by_key(|s| (s.start, s.end));
+	listed.windows(2).filter(|pair| pair[1].start < pair[0].end).for_each(|pair| {
+		layout.issues.push(LayoutIssue::Overlap(pair[0].name.clone(), pair[1].name.clone()));
+	});
+	layout.header = header;
+	layout.sections = listed;
+	layout
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::md3::tests::{test_model, triangle};
+	use crate::md3::{make_name, write_md3, MD3Model, MD3Surface};
+
+	#[test]
+	fn layout_issues() {
+		let model = MD3Model {
+			name: make_name("box"),
+			..test_model(vec![MD3Surface { name: make_name("side"), ..triangle() }])
+		};
+		let mut data = vec![];
+		write_md3(&model, &mut data).unwrap();
+		let layout = read_layout(&data);
+		assert_eq!(layout.issues, vec![]);
+		assert_eq!(layout.header.num_surfaces, 1);
+		assert_eq!(layout.surfaces[0].name, "side");
+		assert_eq!(layout.sections.last().map(|s| s.end), Some(data.len() as u64));
+
+		// Point the frames at the surface's triangles
+		let triangles = layout.surfaces[0].start + layout.surfaces[0].offset_triangles as u64;
+		data[92..96].copy_from_slice(&(triangles as u32).to_le_bytes());
+		let layout = read_layout(&data);
+		assert!(layout.issues.contains(&LayoutIssue::Overlap(
+			String::from("Surface 0 triangles"), String::from("Frames"))));
+		assert!(read_layout(&data[..50]).issues.contains(&LayoutIssue::Truncated(50)));
+	}
+}
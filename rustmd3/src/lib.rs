@@ -0,0 +1,11 @@
+// Reading, writing and exporting Quake 3 MD3 models, without any rendering
+pub mod md3;
+pub mod export;
+pub mod compare;
+pub mod optimize;
+pub mod lod;
+pub mod layout;
+pub mod limits;
+pub mod voxel;
+pub mod q3shader;
+pub mod str_util;
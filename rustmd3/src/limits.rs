@@ -0,0 +1,153 @@
+// How big a model each engine can load. Models over the limits are refused, or
+// their surfaces are left out, so they should be caught before they're
+// shipped.
+use crate::md3::MD3Model;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EngineProfile {
+	#[default]
+	Quake3,
+	Ioquake3,
+	OpenArena,
+	GZDoom,
+}
+
+impl EngineProfile {
+	pub const ALL: [EngineProfile; 4] = [
+		EngineProfile::Quake3, EngineProfile::Ioquake3, EngineProfile::OpenArena, EngineProfile::GZDoom,
+	];
+
+	pub fn limits(self) -> EngineLimits {
+		match self {
+			// MD3_MAX_* in qfiles.h, and SHADER_MAX_VERTEXES and
+			// SHADER_MAX_INDEXES in tr_local.h, which R_LoadMD3 checks each
+			// surface against. ioquake3 and OpenArena kept them.
+			EngineProfile::Quake3 | EngineProfile::Ioquake3 | EngineProfile::OpenArena => EngineLimits {
+				surfaces: Some(32),
+				verts: Some(1000),
+				triangles: Some(2000),
+				shaders: Some(256),
+				frames: Some(1024),
+				tags: Some(16),
+				// ResampleTexture in tr_image.c
+				texture_size: Some(2048),
+				power_of_two: true,
+			},
+			// GZDoom allocates what the file asks for
+			EngineProfile::GZDoom => EngineLimits::default(),
+		}
+	}
+}
+
+impl fmt::Display for EngineProfile {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			EngineProfile::Quake3 => "Quake III Arena",
+			EngineProfile::Ioquake3 => "ioquake3",
+			EngineProfile::OpenArena => "OpenArena",
+			EngineProfile::GZDoom => "GZDoom",
+		})
+	}
+}
+
+// None for no limit. Vertices, triangles and shaders are per surface.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EngineLimits {
+	pub surfaces: Option<usize>,
+	pub verts: Option<usize>,
+	pub triangles: Option<usize>,
+	pub shaders: Option<usize>,
+	pub frames: Option<usize>,
+	pub tags: Option<usize>,
+	// Width and height of textures
+	pub texture_size: Option<u32>,
+	// Other sizes are resampled, which blurs them
+	pub power_of_two: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LimitWarning {
+	#[error("The model has {count} surfaces, but {engine} can only load {max}.")]
+	Surfaces { engine: EngineProfile, count: usize, max: usize },
+	#[error("Surface {surface} has {count} vertices, but {engine} can only load {max}.")]
+	Verts { engine: EngineProfile, surface: usize, count: usize, max: usize },
+	#[error("Surface {surface} has {count} triangles, but {engine} can only load {max}.")]
+	Triangles { engine: EngineProfile, surface: usize, count: usize, max: usize },
+	#[error("Surface {surface} has {count} shaders, but {engine} can only load {max}.")]
+	Shaders { engine: EngineProfile, surface: usize, count: usize, max: usize },
+	#[error("The model has {count} frames, but {engine} can only load {max}.")]
+	Frames { engine: EngineProfile, count: usize, max: usize },
+	#[error("The model has {count} tags, but {engine} can only load {max}.")]
+	Tags { engine: EngineProfile, count: usize, max: usize },
+}
+
+impl LimitWarning {
+	// The surface which is too big, if it's about one surface
+	pub fn surface(&self) -> Option<usize> {
+		match self {
+			LimitWarning::Verts { surface, .. } |
+			LimitWarning::Triangles { surface, .. } |
+			LimitWarning::Shaders { surface, .. } => Some(*surface),
+			_ => None,
+		}
+	}
+}
+
+fn over(count: usize, max: Option<usize>) -> Option<usize> {
+	max.filter(|&max| count > max)
+}
+
+pub fn check_limits(model: &MD3Model, engine: EngineProfile) -> Vec<LimitWarning> {
+	let limits = engine.limits();
+	let mut warnings = vec![];
+	if let Some(max) = over(model.surfaces.len(), limits.surfaces) {
+		warnings.push(LimitWarning::Surfaces { engine, count: model.surfaces.len(), max });
+	}
+	model.surfaces.iter().enumerate().for_each(|(surface, surf)| {
+		if let Some(max) = over(surf.num_verts, limits.verts) {
+			warnings.push(LimitWarning::Verts { engine, surface, count: surf.num_verts, max });
+		}
+		if let Some(max) = over(surf.triangles.len(), limits.triangles) {
+			warnings.push(LimitWarning::Triangles { engine, surface, count: surf.triangles.len(), max });
+		}
+		if let Some(max) = over(surf.shaders.len(), limits.shaders) {
+			warnings.push(LimitWarning::Shaders { engine, surface, count: surf.shaders.len(), max });
+		}
+	});
+	if let Some(max) = over(model.frames.len(), limits.frames) {
+		warnings.push(LimitWarning::Frames { engine, count: model.frames.len(), max });
+	}
+	if let Some(max) = over(model.num_tags, limits.tags) {
+		warnings.push(LimitWarning::Tags { engine, count: model.num_tags, max });
+	}
+	warnings
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::md3::tests::{test_model, triangle};
+	use crate::md3::{MD3Surface, MD3Triangle};
+
+	#[test]
+	fn limits() {
+		// Only the counts are checked, so there aren't any vertices
+		let model = test_model(vec![MD3Surface {
+			num_verts: 1200,
+			num_frames: 1500,
+			triangles: vec![MD3Triangle([0, 1, 2]); 10],
+			..triangle()
+		}]);
+		let warnings = check_limits(&model, EngineProfile::Quake3);
+		assert_eq!(warnings, vec![
+			LimitWarning::Verts { engine: EngineProfile::Quake3, surface: 0, count: 1200, max: 1000 },
+			LimitWarning::Frames { engine: EngineProfile::Quake3, count: 1500, max: 1024 },
+		]);
+		assert_eq!(warnings[0].surface(), Some(0));
+		assert_eq!(warnings[1].to_string(), "The model has 1500 frames, but Quake III Arena can only load 1024.");
+		assert_eq!(check_limits(&model, EngineProfile::GZDoom), vec![]);
+	}
+}
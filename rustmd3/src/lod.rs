@@ -0,0 +1,257 @@
+// Making the lower detail versions of a model Quake 3 switches to when it's
+// far away (model_1.md3 and model_2.md3). Edges are collapsed by how much
+// they change the shape, over every frame at once, so the same vertices are
+// removed from the whole animation.
+use crate::md3::{MD3Model, MD3Surface, MD3Triangle};
+use crate::optimize;
+use glam::DVec3;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, BinaryHeap};
+
+// How many levels of detail Quake 3 uses, including the full model
+pub const LOD_LEVELS: usize = 3;
+
+// Keeping the edges of holes and UV seams in place matters more than
+// keeping the surface flat
+const BOUNDARY_WEIGHT: f64 = 10.;
+
+// Squared distance to a set of planes, as a symmetric 4x4 matrix
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+	fn plane(normal: DVec3, point: DVec3, weight: f64) -> Self {
+		let (a, b, c) = (normal.x, normal.y, normal.z);
+		let d = -normal.dot(point);
+		Self([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|q| q * weight))
+	}
+	fn add(&mut self, other: &Quadric) {
+		self.0.iter_mut().zip(other.0).for_each(|(q, o)| *q += o);
+	}
+	fn error(&self, p: DVec3) -> f64 {
+		let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.0;
+		let (x, y, z) = (p.x, p.y, p.z);
+		x * x * aa + 2. * x * y * ab + 2. * x * z * ac + 2. * x * ad
+			+ y * y * bb + 2. * y * z * bc + 2. * y * bd
+			+ z * z * cc + 2. * z * cd + dd
+	}
+}
+
+// Collapsing one vertex into another
+#[derive(Debug, Clone, Copy)]
+struct Collapse {
+	cost: f64,
+	from: usize,
+	to: usize,
+	// How many times each vertex had changed when this was worked out
+	stamps: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Collapse {
+	// Cheapest first
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.cost.total_cmp(&self.cost)
+	}
+}
+
+// How many triangles a surface should be reduced to
+pub fn target_triangles(triangles: usize, ratio: f32) -> usize {
+	((triangles as f32 * ratio.clamp(0., 1.)).round() as usize).clamp(1.min(triangles), triangles)
+}
+
+// A copy of the model with each surface reduced to around `ratio` of its
+// triangles
+pub fn decimate(model: &MD3Model, ratio: f32) -> MD3Model {
+	let mut lod = model.clone();
+	lod.surfaces.iter_mut().for_each(|surface| {
+		let target = target_triangles(surface.triangles.len(), ratio);
+		decimate_surface(surface, target);
+	});
+	lod
+}
+
+pub fn decimate_surface(surface: &mut MD3Surface, target: usize) {
+	let num_verts = surface.num_verts;
+	let num_frames = surface.num_frames;
+	if surface.vertices.len() < num_verts * num_frames || surface.triangles.len() <= target {
+		return;
+	}
+	let position = |frame: usize, vertex: usize| surface.vertices[frame * num_verts + vertex].position().as_dvec3();
+	let mut triangles: Vec<Option<[usize; 3]>> = surface.triangles.iter()
+		.map(|t| t.0.map(|i| i as usize))
+		.map(|t| t.iter().all(|&i| i < num_verts).then_some(t))
+		.collect();
+	let mut vertex_triangles = vec![Vec::new(); num_verts];
+	triangles.iter().enumerate().for_each(|(index, tri)| {
+		tri.iter().flatten().for_each(|&v| vertex_triangles[v].push(index));
+	});
+
+	// One quadric per vertex per frame
+	let mut quadrics = vec![Quadric::default(); num_verts * num_frames];
+	triangles.iter().flatten().for_each(|tri| {
+		// Edges only used by this triangle
+		let boundary: Vec<(usize, usize)> = (0..3)
+			.map(|corner| (tri[corner], tri[(corner + 1) % 3]))
+			.filter(|&(a, b)| vertex_triangles[a].iter()
+				.filter(|&&t| matches!(triangles[t], Some(other) if other.contains(&b)))
+				.count() == 1)
+			.collect();
+		(0..num_frames).for_each(|frame| {
+			let [p0, p1, p2] = tri.map(|v| position(frame, v));
+			let cross = (p1 - p0).cross(p2 - p0);
+			let area = cross.length() * 0.5;
+			if area <= 0. { return; }
+			let normal = cross.normalize();
+			let quadric = Quadric::plane(normal, p0, area);
+			tri.iter().for_each(|&v| quadrics[frame * num_verts + v].add(&quadric));
+			boundary.iter().for_each(|&(a, b)| {
+				let edge = position(frame, b) - position(frame, a);
+				let side = edge.cross(normal).normalize_or_zero();
+				let quadric = Quadric::plane(side, position(frame, a), edge.length_squared() * BOUNDARY_WEIGHT);
+				quadrics[frame * num_verts + a].add(&quadric);
+				quadrics[frame * num_verts + b].add(&quadric);
+			});
+		});
+	});
+
+	let cost = |from: usize, to: usize, quadrics: &[Quadric]| (0..num_frames).map(|frame| {
+		let mut quadric = quadrics[frame * num_verts + from];
+		quadric.add(&quadrics[frame * num_verts + to]);
+		quadric.error(position(frame, to))
+	}).sum::<f64>();
+	let neighbours = |vertex: usize, vertex_triangles: &[Vec<usize>], triangles: &[Option<[usize; 3]>]| {
+		vertex_triangles[vertex].iter()
+			.filter_map(|&t| triangles[t])
+			.flatten()
+			.filter(|&v| v != vertex)
+			.collect::<BTreeSet<usize>>()
+	};
+
+	let mut stamps = vec![0u32; num_verts];
+	let mut removed = vec![false; num_verts];
+	let mut heap = BinaryHeap::new();
+	(0..num_verts).for_each(|from| {
+		neighbours(from, &vertex_triangles, &triangles).into_iter().for_each(|to| {
+			heap.push(Collapse { cost: cost(from, to, &quadrics), from, to, stamps: (0, 0) });
+		});
+	});
+
+	let mut remaining = triangles.iter().flatten().count();
+	while remaining > target {
+		let Some(collapse) = heap.pop() else { break; };
+		let Collapse { from, to, .. } = collapse;
+		if removed[from] || removed[to] || collapse.stamps != (stamps[from], stamps[to]) { continue; }
+		let shared: Vec<usize> = vertex_triangles[from].iter().copied()
+			.filter(|&t| matches!(triangles[t], Some(tri) if tri.contains(&to)))
+			.collect();
+		if shared.is_empty() { continue; }
+		// Only the triangles on the edge should have both vertices as
+		// neighbours, otherwise the surface gets pinched
+		let from_neighbours = neighbours(from, &vertex_triangles, &triangles);
+		let to_neighbours = neighbours(to, &vertex_triangles, &triangles);
+		if from_neighbours.intersection(&to_neighbours).count() > shared.len() { continue; }
+		// Triangles shouldn't turn over in any frame
+		let flips = vertex_triangles[from].iter()
+			.filter(|t| !shared.contains(t))
+			.filter_map(|&t| triangles[t])
+			.any(|tri| (0..num_frames).any(|frame| {
+				let before = tri.map(|v| position(frame, v));
+				let after = tri.map(|v| position(frame, if v == from { to } else { v }));
+				let normal = |[p0, p1, p2]: [DVec3; 3]| (p1 - p0).cross(p2 - p0);
+				let (before, after) = (normal(before), normal(after));
+				before != DVec3::ZERO && before.dot(after) <= 0.
+			}));
+		if flips { continue; }
+
+		shared.iter().for_each(|&t| {
+			if let Some(tri) = triangles[t].take() {
+				tri.iter().filter(|&&v| v != from)
+					.for_each(|&v| vertex_triangles[v].retain(|&o| o != t));
+			}
+		});
+		remaining -= shared.len();
+		let moved: Vec<usize> = std::mem::take(&mut vertex_triangles[from]).into_iter()
+			.filter(|t| !shared.contains(t))
+			.collect();
+		moved.iter().for_each(|&t| {
+			if let Some(tri) = triangles[t].as_mut() {
+				tri.iter_mut().filter(|v| **v == from).for_each(|v| *v = to);
+			}
+		});
+		vertex_triangles[to].extend(moved);
+		(0..num_frames).for_each(|frame| {
+			let quadric = quadrics[frame * num_verts + from];
+			quadrics[frame * num_verts + to].add(&quadric);
+		});
+		removed[from] = true;
+		stamps[to] += 1;
+		neighbours(to, &vertex_triangles, &triangles).into_iter().for_each(|other| {
+			let stamps = (stamps[to], stamps[other]);
+			heap.push(Collapse { cost: cost(to, other, &quadrics), from: to, to: other, stamps });
+			heap.push(Collapse { cost: cost(other, to, &quadrics), from: other, to, stamps: (stamps.1, stamps.0) });
+		});
+	}
+
+	surface.triangles = triangles.into_iter().flatten()
+		.map(|tri| MD3Triangle(tri.map(|v| v as u32)))
+		.collect();
+	optimize::remove_unused(surface);
+}
+
+// File names for each level of detail, like model.md3, model_1.md3 and
+// model_2.md3
+pub fn lod_file_name(name: &str, level: usize) -> String {
+	if level == 0 {
+		return name.to_string();
+	}
+	match name.rsplit_once('.') {
+		Some((stem, extension)) => format!("{}_{}.{}", stem, level, extension),
+		None => format!("{}_{}", name, level),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::md3::tests::{test_model, test_surface};
+	use crate::md3::MD3FrameVertex;
+
+	#[test]
+	fn decimate_grid() {
+		// A flat 5x5 grid of vertices, which can be collapsed down a lot
+		// without changing its shape
+		let vertices: Vec<MD3FrameVertex> = (0..25)
+			.map(|i| MD3FrameVertex { x: (i % 5) * 64, y: (i / 5) * 64, z: 0, n: 0 })
+			.collect();
+		let triangles: Vec<MD3Triangle> = (0..16).flat_map(|cell| {
+			let v = (cell / 4) * 5 + cell % 4;
+			[[v, v + 5, v + 1], [v + 1, v + 5, v + 6]]
+		}).map(MD3Triangle).collect();
+		let model = test_model(vec![MD3Surface { triangles, ..test_surface("grid", 25, vertices) }]);
+		let lod = decimate(&model, 0.5);
+		let surface = &lod.surfaces[0];
+		// Collapsing an edge removes one or two triangles
+		assert!((15..=16).contains(&surface.triangles.len()));
+		assert!(surface.num_verts < 25);
+		assert_eq!(surface.vertices.len(), surface.num_verts);
+		// The corners are still there
+		[(0, 0), (256, 0), (0, 256), (256, 256)].iter().for_each(|&(x, y)| {
+			assert!(surface.vertices.iter().any(|v| v.x == x && v.y == y));
+		});
+		assert_eq!(lod_file_name("head.md3", 2), "head_2.md3");
+	}
+}
@@ -0,0 +1,1794 @@
+use glam::f32::{Affine3A, Vec2, Vec3, Vec4, Mat3};
+use glam::{BVec3, Quat};
+use crate::str_util::StringFromBytes;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::iter;
+use std::ops::RangeInclusive;
+use rayon::iter as riter;
+use thiserror::Error;
+use rayon::prelude::*;
+
+pub const MD3_ID: [u8; 4] = *b"IDP3";
+pub const MD3_VERSION: i32 = 15;
+
+pub type MD3Name = [u8; 64];
+
+#[derive(Debug, Clone)]
+pub struct MD3Model {
+	pub version: i32,
+	pub name: MD3Name,
+	pub num_tags: usize,
+	pub frames: Vec<MD3Frame>,
+	pub tags: Vec<MD3FrameTag>,
+	pub surfaces: Vec<MD3Surface>,
+}
+
+impl MD3Model {
+	pub fn max_radius(&self) -> f32 {
+		self.frames.iter().map(|f| f.radius).reduce(f32::max).unwrap_or(0.)
+	}
+	// Names of the tags, which are the same for every frame
+	pub fn tag_names(&self) -> impl Iterator<Item = Cow<'_, str>> {
+		self.tags.iter().take(self.num_tags).map(|t| String::from_utf8_stop(&t.name))
+	}
+	// Interpolated transform of the named tag at the given frame
+	pub fn tag_transform(&self, name: &str, frame: f32) -> Option<Affine3A> {
+		let tag_index = self.tag_names().position(|n| n == name)?;
+		let tag_a = self.tags.get(tag_index + self.num_tags * frame.floor() as usize)?;
+		let tag_b = self.tags.get(tag_index + self.num_tags * frame.ceil() as usize)?;
+		let f = frame.fract();
+		let axes = tag_a.axes * (1. - f) + tag_b.axes * f;
+		let origin = tag_a.origin.lerp(tag_b.origin, f);
+		Some(Affine3A::from_mat3_translation(axes, origin))
+	}
+	// Rename shaders using the given function, which returns None to keep a
+	// name as it is. Returns how many shaders were renamed.
+	pub fn rename_shaders(&mut self, rename: impl Fn(&str) -> Option<String>) -> usize {
+		self.surfaces.iter_mut().flat_map(|surf| surf.shaders.iter_mut())
+			.filter_map(|shader| {
+				let new_name = rename(&String::from_utf8_stop(&shader.name))?;
+				shader.name = make_name(&new_name);
+				Some(())
+			}).count()
+	}
+	// The named tag in every frame
+	pub fn tag_frames_mut(&mut self, index: usize) -> impl Iterator<Item = &mut MD3FrameTag> {
+		let num_tags = self.num_tags;
+		self.tags.iter_mut().enumerate()
+			.filter(move |(i, _)| i % num_tags == index)
+			.map(|(_, tag)| tag)
+	}
+	pub fn rename_tag(&mut self, index: usize, name: &str) -> Result<(), MD3EditError> {
+		if index >= self.num_tags {
+			return Err(MD3EditError::NoTag(index));
+		}
+		if self.tag_names().enumerate().any(|(other, n)| other != index && n == name) {
+			return Err(MD3EditError::DuplicateTag(name.to_string()));
+		}
+		let name = try_make_name(name)?;
+		self.tag_frames_mut(index).for_each(|tag| tag.name = name);
+		Ok(())
+	}
+	// Add a tag to the end of every frame, using the given function to make
+	// the tag for each frame
+	fn push_tag(&mut self, name: &str, tag: impl Fn(usize) -> MD3FrameTag) -> Result<(), MD3EditError> {
+		if self.tag_names().any(|n| n == name) {
+			return Err(MD3EditError::DuplicateTag(name.to_string()));
+		}
+		let name = try_make_name(name)?;
+		let num_tags = self.num_tags;
+		self.tags = (0..self.frames.len()).flat_map(|frame| {
+			let start = frame * num_tags;
+			let new_tag = MD3FrameTag { name, ..tag(frame) };
+			self.tags[start..start + num_tags].iter().cloned().chain(iter::once(new_tag))
+		}).collect();
+		self.num_tags += 1;
+		Ok(())
+	}
+	pub fn add_tag(&mut self, name: &str, origin: Vec3, axes: Mat3) -> Result<(), MD3EditError> {
+		self.push_tag(name, |_| MD3FrameTag { name: [0; 64], origin, axes })
+	}
+	// Copy a tag, with the same position in every frame
+	pub fn duplicate_tag(&mut self, index: usize, name: &str) -> Result<(), MD3EditError> {
+		if index >= self.num_tags {
+			return Err(MD3EditError::NoTag(index));
+		}
+		let num_tags = self.num_tags;
+		let tags = self.tags.clone();
+		self.push_tag(name, |frame| tags[frame * num_tags + index].clone())
+	}
+	pub fn remove_tag(&mut self, index: usize) -> Result<(), MD3EditError> {
+		if index >= self.num_tags {
+			return Err(MD3EditError::NoTag(index));
+		}
+		let num_tags = self.num_tags;
+		self.tags = self.tags.iter().enumerate()
+			.filter(|(i, _)| i % num_tags != index)
+			.map(|(_, tag)| tag.clone()).collect();
+		self.num_tags -= 1;
+		Ok(())
+	}
+	pub fn rename_surface(&mut self, index: usize, name: &str) -> Result<(), MD3EditError> {
+		let surface = self.surfaces.get_mut(index).ok_or(MD3EditError::NoSurface(index))?;
+		surface.name = try_make_name(name)?;
+		Ok(())
+	}
+	pub fn set_shader_name(&mut self, surface: usize, shader: usize, name: &str) -> Result<(), MD3EditError> {
+		let shader = self.surfaces.get_mut(surface).and_then(|s| s.shaders.get_mut(shader))
+			.ok_or(MD3EditError::NoShader { surface, shader })?;
+		shader.name = try_make_name(name)?;
+		Ok(())
+	}
+	// Scale, turn and move every frame of the model, including its tags and
+	// bounds. Returns how many vertex positions were too far from the origin
+	// to be stored, and had to be moved closer.
+	pub fn bake_transform(&mut self, transform: &ModelTransform, gzdoom: bool) -> usize {
+		let matrix = transform.matrix();
+		let mut clamped = 0;
+		self.surfaces.iter_mut().flat_map(|s| s.vertices.iter_mut()).for_each(|vertex| {
+			let position = matrix.transform_point3(vertex.position()) / MD3_XYZ_SCALE;
+			let stored = position.round().clamp(Vec3::splat(i16::MIN as f32), Vec3::splat(i16::MAX as f32));
+			if stored != position.round() {
+				clamped += 1;
+			}
+			let normal = transform.rotation * vertex.normal(gzdoom);
+			*vertex = MD3FrameVertex {
+				x: stored.x as i16,
+				y: stored.y as i16,
+				z: stored.z as i16,
+				n: MD3FrameVertex::encode_normal(normal, gzdoom),
+			};
+		});
+		self.tags.iter_mut().for_each(|tag| {
+			tag.origin = matrix.transform_point3(tag.origin);
+			tag.axes = Mat3::from_quat(transform.rotation) * tag.axes;
+		});
+		let surfaces = &self.surfaces;
+		self.frames.iter_mut().enumerate().for_each(|(frame, bounds)| {
+			let vertex_bounds = surfaces.iter().filter_map(|s| s.bounds(frame))
+				.reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)));
+			// Frames without vertices keep the same box, turned and moved
+			let (min, max) = vertex_bounds.unwrap_or_else(|| {
+				(0..8).map(|corner| matrix.transform_point3(Vec3::select(
+					BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0), bounds.max, bounds.min)))
+					.fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)), |(min, max), p| (min.min(p), max.max(p)))
+			});
+			bounds.min = min;
+			bounds.max = max;
+			bounds.origin = matrix.transform_point3(bounds.origin);
+			let origin = bounds.origin;
+			bounds.radius = frame_radius(surfaces, frame, origin).unwrap_or_else(|| {
+				(0..8).map(|corner| Vec3::select(BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0), max, min))
+					.map(|p| p.distance(origin)).fold(0., f32::max)
+			});
+		});
+		clamped
+	}
+	// Mirror every frame of the model across the plane through the origin
+	// which is square to the axis (0 for X). The triangles are turned around
+	// so they still face out. Tags are mirrored too, but stay the right way
+	// round, so a weapon on a mirrored tag isn't mirrored itself. Flipping
+	// the UVs as well keeps text readable on a skin flipped the same way.
+	pub fn mirror(&mut self, axis: usize, flip_uv: [bool; 2], gzdoom: bool) {
+		let flip = |mut v: Vec3| {
+			v[axis] = -v[axis];
+			v
+		};
+		self.surfaces.iter_mut().for_each(|surf| {
+			surf.vertices.iter_mut().for_each(|vertex| {
+				let mut position = [vertex.x, vertex.y, vertex.z];
+				position[axis] = position[axis].saturating_neg();
+				*vertex = MD3FrameVertex {
+					x: position[0],
+					y: position[1],
+					z: position[2],
+					n: MD3FrameVertex::encode_normal(flip(vertex.normal(gzdoom)), gzdoom),
+				};
+			});
+			surf.triangles.iter_mut().for_each(|tri| tri.0.swap(1, 2));
+			surf.texcoords.iter_mut().for_each(|uv| (0..2).filter(|&i| flip_uv[i]).for_each(|i| uv.0[i] = 1. - uv.0[i]));
+		});
+		// Reflecting both sides keeps the axes a rotation
+		let reflect = Mat3::from_diagonal(flip(Vec3::ONE));
+		self.tags.iter_mut().for_each(|tag| {
+			tag.origin = flip(tag.origin);
+			tag.axes = reflect * tag.axes * reflect;
+		});
+		self.frames.iter_mut().for_each(|frame| {
+			let (min, max) = (flip(frame.min), flip(frame.max));
+			frame.min = min.min(max);
+			frame.max = min.max(max);
+			frame.origin = flip(frame.origin);
+		});
+	}
+	// The frame with its bounds worked out from its vertices, instead of what
+	// the exporter wrote. The origin is the middle of the box.
+	pub fn fitted_frame(&self, frame: usize) -> Option<MD3Frame> {
+		let stored = self.frames.get(frame)?;
+		let (min, max) = self.surfaces.iter().filter_map(|s| s.bounds(frame))
+			.reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))?;
+		let origin = (min + max) * 0.5;
+		let radius = frame_radius(&self.surfaces, frame, origin)?;
+		Some(MD3Frame { min, max, origin, radius, name: stored.name })
+	}
+	// Replace the bounds of every frame with ones fitted to its vertices.
+	// Returns how many frames changed.
+	pub fn fit_frame_bounds(&mut self) -> usize {
+		(0..self.frames.len()).filter(|&frame| match self.fitted_frame(frame) {
+			Some(fitted) if fitted != self.frames[frame] => {
+				self.frames[frame] = fitted;
+				true
+			},
+			_ => false,
+		}).count()
+	}
+	// Move triangles of a surface into new surfaces, which go right after it.
+	// `groups` is the group of each triangle. The first group stays in the
+	// surface, and each of the others becomes a new surface, named after the
+	// surface with a number on the end. Returns the indices of the new
+	// surfaces.
+	pub fn split_surface(&mut self, index: usize, groups: &[usize]) -> Result<Vec<usize>, MD3EditError> {
+		let surface = self.surfaces.get(index).ok_or(MD3EditError::NoSurface(index))?;
+		let group = |triangle: usize| groups.get(triangle).copied().unwrap_or_default();
+		let mut ids: Vec<usize> = (0..surface.triangles.len()).map(group).collect();
+		ids.sort_unstable();
+		ids.dedup();
+		let name = String::from_utf8_stop(&surface.name);
+		let parts = ids.iter().enumerate().map(|(number, &id)| {
+			let triangles: Vec<usize> = (0..surface.triangles.len()).filter(|&t| group(t) == id).collect();
+			// The first part keeps the name as it was in the file
+			let name = match number {
+				0 => surface.name,
+				number => {
+					// The name is cut short to make room for the suffix. The
+					// limit is in bytes, and Latin-1 names take more bytes as
+					// UTF-8 than they did in the file.
+					let suffix = format!("_{}", number);
+					let budget = std::mem::size_of::<MD3Name>() - 1 - suffix.len();
+					let end = name.char_indices().map(|(i, c)| i + c.len_utf8())
+						.take_while(|&end| end <= budget).last().unwrap_or(0);
+					try_make_name(&format!("{}{}", &name[..end], suffix))?
+				},
+			};
+			Ok(surface.with_triangles(&triangles, name))
+		}).collect::<Result<Vec<MD3Surface>, MD3EditError>>()?;
+		let added = parts.len().saturating_sub(1);
+		self.surfaces.splice(index..=index, parts);
+		Ok((index + 1..index + 1 + added).collect())
+	}
+	// Rebuild the animation from the given frames, which can be in any order,
+	// and can be used more than once or not at all
+	pub fn reorder_frames(&mut self, order: &[usize]) {
+		let num_tags = self.num_tags;
+		self.frames = order.iter().map(|&frame| self.frames[frame].clone()).collect();
+		self.tags = order.iter()
+			.flat_map(|&frame| self.tags[frame * num_tags..(frame + 1) * num_tags].iter().cloned())
+			.collect();
+		self.surfaces.iter_mut().for_each(|surface| {
+			let num_verts = surface.num_verts;
+			surface.vertices = order.iter()
+				.flat_map(|&frame| surface.vertices[frame * num_verts..(frame + 1) * num_verts].iter().copied())
+				.collect();
+			surface.num_frames = order.len();
+		});
+	}
+	// Runs of frames with the same name, apart from the number at the end,
+	// like death1_01 to death1_20
+	pub fn frame_sections(&self) -> Vec<FrameSection> {
+		let mut sections: Vec<FrameSection> = vec![];
+		self.frames.iter().enumerate().for_each(|(index, frame)| {
+			let name = frame.name();
+			let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit())
+				.trim_end_matches(['_', '-', '.', ' ']);
+			match sections.last_mut() {
+				Some(section) if section.name == prefix => {
+					section.frames = *section.frames.start()..=index;
+				},
+				_ => sections.push(FrameSection { name: prefix.to_string(), frames: index..=index }),
+			}
+		});
+		sections
+	}
+	// Problems which don't prevent the model from being read, but which would
+	// make it look broken or empty.
+	pub fn warnings(&self) -> Vec<MD3Warning> {
+		let mut warnings = vec![];
+		if self.frames.is_empty() {
+			warnings.push(MD3Warning::NoFrames);
+		}
+		if self.surfaces.is_empty() {
+			warnings.push(MD3Warning::NoSurfaces);
+		}
+		self.surfaces.iter().enumerate().for_each(|(index, surf)| {
+			if surf.num_verts == 0 || surf.triangles.is_empty() {
+				warnings.push(MD3Warning::EmptySurface(index));
+			}
+		});
+		warnings
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MD3EditError {
+	#[error("\"{0}\" is longer than {1} bytes")]
+	NameTooLong(String, usize),
+	#[error("There is already a tag called \"{0}\"")]
+	DuplicateTag(String),
+	#[error("Tag {0} doesn't exist")]
+	NoTag(usize),
+	#[error("Surface {0} doesn't exist")]
+	NoSurface(usize),
+	#[error("Surface {surface} doesn't have shader {shader}")]
+	NoShader { surface: usize, shader: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MD3Warning {
+	#[error("The model has no frames, so there is no geometry or tags to show.")]
+	NoFrames,
+	#[error("The model has no surfaces, so only its bounds and tags are shown.")]
+	NoSurfaces,
+	#[error("Surface {0} has no vertices or triangles, so it is invisible.")]
+	EmptySurface(usize),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MD3Frame {
+	pub min: Vec3,
+	pub max: Vec3,
+	pub origin: Vec3,
+	pub radius: f32,
+	pub name: [u8; 16],
+}
+
+// How far the furthest vertex in the frame is from the frame's local origin,
+// which is the sphere Quake 3 culls the frame with. None if the frame has no
+// vertices.
+fn frame_radius(surfaces: &[MD3Surface], frame: usize, origin: Vec3) -> Option<f32> {
+	surfaces.iter()
+		.filter_map(|s| s.vertices.get(frame * s.num_verts..(frame + 1) * s.num_verts))
+		.flatten()
+		.map(|v| v.position().distance(origin))
+		.reduce(f32::max)
+}
+
+impl MD3Frame {
+	pub fn name(&self) -> Cow<'_, str> {
+		String::from_utf8_stop(&self.name)
+	}
+}
+
+// Applied to positions in that order: scale, then rotation, then offset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelTransform {
+	pub scale: f32,
+	pub rotation: Quat,
+	pub offset: Vec3,
+}
+
+impl Default for ModelTransform {
+	fn default() -> Self {
+		Self { scale: 1., rotation: Quat::IDENTITY, offset: Vec3::ZERO }
+	}
+}
+
+impl ModelTransform {
+	pub fn matrix(&self) -> Affine3A {
+		Affine3A::from_scale_rotation_translation(Vec3::splat(self.scale), self.rotation, self.offset)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameEdit {
+	Delete(RangeInclusive<usize>),
+	// Copy the frames, and put the copies after them
+	Duplicate(RangeInclusive<usize>),
+	// Move the frames so they're before the given frame, or at the end
+	Move(RangeInclusive<usize>, usize),
+}
+
+impl FrameEdit {
+	// Which of the original frames the animation will have, for reorder_frames
+	pub fn order(&self, num_frames: usize) -> Vec<usize> {
+		let all = 0..num_frames;
+		match self {
+			FrameEdit::Delete(frames) => all.filter(|f| !frames.contains(f)).collect(),
+			FrameEdit::Duplicate(frames) => {
+				let mut order: Vec<usize> = all.collect();
+				let at = (*frames.end() + 1).min(num_frames);
+				order.splice(at..at, frames.clone().filter(|&f| f < num_frames));
+				order
+			},
+			FrameEdit::Move(frames, before) => {
+				let mut order: Vec<usize> = all.filter(|f| !frames.contains(f)).collect();
+				let at = order.iter().position(|f| f >= before).unwrap_or(order.len());
+				order.splice(at..at, frames.clone().filter(|&f| f < num_frames));
+				order
+			},
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameSection {
+	pub name: String,
+	pub frames: RangeInclusive<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MD3FrameTag {
+	pub name: MD3Name,
+	pub origin: Vec3,
+	pub axes: Mat3,
+}
+
+#[derive(Debug, Clone)]
+pub struct MD3Surface {
+	pub name: MD3Name,
+	pub num_verts: usize,
+	pub num_frames: usize,
+	pub shaders: Vec<MD3Shader>,
+	pub triangles: Vec<MD3Triangle>,
+	pub texcoords: Vec<MD3TexCoord>,
+	pub vertices: Vec<MD3FrameVertex>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+	pub vertices: u32,
+	pub frames: u32,
+	pub rows_per_frame: u32,
+	pub data: Box<[u8]>,
+}
+
+impl MD3Surface {
+	pub fn make_animation(&self, width: Option<usize>) -> Animation {
+		let vertices = self.num_verts;
+		let frames = self.num_frames;
+		let width = width.unwrap_or(vertices);
+		let rows_per_frame = (vertices as f32 / width as f32).ceil() as usize;
+		let pixels_per_frame = width * rows_per_frame;
+		let height = frames * rows_per_frame;
+		// 4 "colour channels" * size_of(i16) bytes
+		let channels = 4usize * std::mem::size_of::<i16>();
+		let data = if frames > 1 {
+			(0..frames).into_par_iter().flat_map(|frame| {
+				let start = frame * vertices;
+				let end = start + vertices;
+				let by_slice = self.vertices[start..end].iter()
+					.map(|vert| vert.to_pixel().map(i16::to_ne_bytes))
+					.chain(iter::repeat([[0; 2]; 4])).take(pixels_per_frame)
+					.flatten().flatten().collect::<Vec<u8>>();
+				#[cfg(feature = "make_animation_is_bugged")]
+				{
+				let start = frame * pixels_per_frame;
+				let end = start + pixels_per_frame;
+				let by_index = (start..end).into_iter().flat_map(|vindex| {
+					let vindex = vindex % pixels_per_frame;
+					if vindex < vertices {
+						let vindex = frame * vertices + vindex;
+						self.vertices[vindex].to_pixel().map(i16::to_ne_bytes)
+					} else {
+						[[0; 2]; 4]
+					}
+				}).flatten().collect::<Vec<u8>>();
+				assert_eq!(by_slice.len(), by_index.len());
+				assert_eq!(by_slice, by_index);
+				}
+				by_slice
+			}).collect::<Vec<u8>>().into_boxed_slice()
+		} else {
+			let extra_count = pixels_per_frame - self.vertices.len();
+			let by_slice = self.vertices.par_iter()
+				.map(|vert| vert.to_pixel().map(i16::to_ne_bytes))
+				.chain(riter::repeatn([[0; 2]; 4], extra_count))
+				.flatten().flatten().collect::<Vec<u8>>().into_boxed_slice();
+			#[cfg(feature = "make_animation_is_bugged")]
+			{
+			let by_index = (0..pixels_per_frame).into_par_iter().flat_map(|vindex| {
+				let vindex = vindex % pixels_per_frame;
+				if vindex < vertices {
+					self.vertices[vindex].to_pixel().map(i16::to_ne_bytes)
+				} else {
+					[[0; 2]; 4]
+				}
+			}).flatten().collect::<Vec<u8>>().into_boxed_slice();
+			assert_eq!(by_slice.len(), by_index.len());
+			assert_eq!(by_slice, by_index);
+			}
+			by_slice
+		};
+		assert_eq!(data.len(), width * height * channels);
+		Animation {
+			vertices: vertices as u32, frames: frames as u32,
+			rows_per_frame: rows_per_frame as u32,
+			data
+		}
+	}
+	// Bounding box of the vertices in the given frame
+	pub fn bounds(&self, frame: usize) -> Option<(Vec3, Vec3)> {
+		let start = frame * self.num_verts;
+		self.vertices.get(start..start + self.num_verts)?.iter()
+			.map(MD3FrameVertex::position)
+			.fold(None, |bounds, pos| match bounds {
+				Some((min, max)) => Some((pos.min(min), pos.max(max))),
+				None => Some((pos, pos)),
+			})
+	}
+	// Per-vertex tangents for the given frame, calculated from the positions
+	// and UVs. The handedness of the bitangent is stored in w.
+	pub fn tangents(&self, frame: usize) -> Vec<Vec4> {
+		let num_verts = self.num_verts;
+		let start = frame * num_verts;
+		let verts = match self.vertices.get(start..start + num_verts) {
+			Some(v) => v,
+			None => return vec![Vec4::new(1., 0., 0., 1.); num_verts],
+		};
+		let mut tangents = vec![Vec3::ZERO; num_verts];
+		let mut bitangents = vec![Vec3::ZERO; num_verts];
+		self.triangles.iter().for_each(|tri| {
+			let [a, b, c] = tri.0.map(|i| i as usize);
+			if a >= num_verts || b >= num_verts || c >= num_verts { return; }
+			let (p0, p1, p2) = (verts[a].position(), verts[b].position(), verts[c].position());
+			let uv = |i: usize| self.texcoords.get(i).map(|t| t.0).unwrap_or_default();
+			let (w0, w1, w2) = (uv(a), uv(b), uv(c));
+			let (e1, e2) = (p1 - p0, p2 - p0);
+			let (d1, d2) = (w1 - w0, w2 - w0);
+			let det = d1.x * d2.y - d2.x * d1.y;
+			if det.abs() < f32::EPSILON { return; }
+			let tangent = (e1 * d2.y - e2 * d1.y) / det;
+			let bitangent = (e2 * d1.x - e1 * d2.x) / det;
+			[a, b, c].into_iter().for_each(|i| {
+				tangents[i] += tangent;
+				bitangents[i] += bitangent;
+			});
+		});
+		verts.iter().zip(tangents.into_iter().zip(bitangents))
+			.map(|(vert, (tangent, bitangent))| {
+				let normal = vert.normal(false);
+				// Gram-Schmidt orthogonalization
+				let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+				let tangent = if tangent == Vec3::ZERO {
+					normal.any_orthonormal_vector()
+				} else { tangent };
+				let handedness = if normal.cross(tangent).dot(bitangent) < 0. { -1. } else { 1. };
+				tangent.extend(handedness)
+			}).collect()
+	}
+	// How far across the model the whole width and height of the texture
+	// go, averaged over the triangles by their area in the texture. The
+	// shape of a texture should match it, or its texels are stretched.
+	pub fn uv_span(&self, frame: usize) -> Option<Vec2> {
+		let num_verts = self.num_verts;
+		let start = frame * num_verts;
+		let verts = self.vertices.get(start..start + num_verts)?;
+		let (span, total) = self.triangles.iter().fold((Vec2::ZERO, 0.), |(span, total), tri| {
+			let [a, b, c] = tri.0.map(|i| i as usize);
+			if a >= num_verts || b >= num_verts || c >= num_verts { return (span, total); }
+			let (p0, p1, p2) = (verts[a].position(), verts[b].position(), verts[c].position());
+			let uv = |i: usize| self.texcoords.get(i).map(|t| t.0).unwrap_or_default();
+			let (w0, w1, w2) = (uv(a), uv(b), uv(c));
+			let (e1, e2) = (p1 - p0, p2 - p0);
+			let (d1, d2) = (w1 - w0, w2 - w0);
+			let det = d1.x * d2.y - d2.x * d1.y;
+			if det.abs() < f32::EPSILON { return (span, total); }
+			// Same as the tangent and bitangent above, without normalizing
+			let along_u = ((e1 * d2.y - e2 * d1.y) / det).length();
+			let along_v = ((e2 * d1.x - e1 * d2.x) / det).length();
+			(span + Vec2::new(along_u, along_v) * det.abs(), total + det.abs())
+		});
+		(total > 0.).then(|| span / total)
+	}
+	// Per-vertex normals for the given frame, calculated from the triangles
+	// around each vertex. Vertices in the same place share a normal, so UV
+	// seams don't show.
+	pub fn smooth_normals(&self, frame: usize, weighting: NormalWeighting) -> Vec<Vec3> {
+		let num_verts = self.num_verts;
+		let start = frame * num_verts;
+		let verts = match self.vertices.get(start..start + num_verts) {
+			Some(v) => v,
+			None => return vec![Vec3::ZERO; num_verts],
+		};
+		let mut normals: HashMap<(i16, i16, i16), Vec3> = HashMap::new();
+		self.triangles.iter().for_each(|tri| {
+			let [a, b, c] = tri.0.map(|i| i as usize);
+			if a >= num_verts || b >= num_verts || c >= num_verts { return; }
+			let (p0, p1, p2) = (verts[a].position(), verts[b].position(), verts[c].position());
+			// Triangles are clockwise. The length of the cross product is
+			// twice the area of the triangle.
+			let normal = (p2 - p0).cross(p1 - p0);
+			[(a, p0, p1, p2), (b, p1, p2, p0), (c, p2, p0, p1)].into_iter()
+				.for_each(|(i, corner, next, previous)| {
+					let weighted = match weighting {
+						NormalWeighting::Area => normal,
+						NormalWeighting::Angle => normal.normalize_or_zero() *
+							(next - corner).angle_between(previous - corner),
+					};
+					let v = &verts[i];
+					*normals.entry((v.x, v.y, v.z)).or_default() += weighted;
+				});
+		});
+		verts.iter().map(|v| {
+			normals.get(&(v.x, v.y, v.z)).copied().unwrap_or_default().normalize_or_zero()
+		}).collect()
+	}
+	// Angle in degrees between the stored and calculated normals of each
+	// vertex in each frame, or 0 for vertices which aren't part of a triangle
+	pub fn normal_errors(&self, gzdoom: bool) -> Vec<f32> {
+		(0..self.num_frames).flat_map(|frame| {
+			let start = frame * self.num_verts;
+			self.smooth_normals(frame, NormalWeighting::Area).into_iter()
+				.zip(self.vertices.get(start..start + self.num_verts).unwrap_or(&[]))
+				.map(|(smooth, vert)| match smooth {
+					Vec3::ZERO => 0.,
+					smooth => smooth.dot(vert.normal(gzdoom)).clamp(-1., 1.).acos().to_degrees(),
+				}).collect::<Vec<_>>()
+		}).collect()
+	}
+	// Which UV island each triangle is in. Triangles are in the same island
+	// if they share a corner in UV space, and islands are numbered in the
+	// order of their first triangles.
+	pub fn uv_islands(&self) -> Vec<usize> {
+		let mut parent: Vec<usize> = (0..self.triangles.len()).collect();
+		fn root(parent: &mut [usize], mut t: usize) -> usize {
+			while parent[t] != t {
+				parent[t] = parent[parent[t]];
+				t = parent[t];
+			}
+			t
+		}
+		let mut corners: HashMap<[u32; 2], usize> = HashMap::new();
+		self.triangles.iter().enumerate().for_each(|(triangle, tri)| tri.0.iter().for_each(|&v| {
+			let uv = self.texcoords.get(v as usize).map_or(Vec2::NAN, |t| t.0);
+			let other = *corners.entry([uv.x.to_bits(), uv.y.to_bits()]).or_insert(triangle);
+			let (a, b) = (root(&mut parent, triangle), root(&mut parent, other));
+			parent[a.max(b)] = a.min(b);
+		}));
+		let mut numbers: HashMap<usize, usize> = HashMap::new();
+		(0..self.triangles.len()).map(|triangle| {
+			let next = numbers.len();
+			*numbers.entry(root(&mut parent, triangle)).or_insert(next)
+		}).collect()
+	}
+	// A copy of the surface with only the given triangles, and the vertices
+	// they use
+	fn with_triangles(&self, triangles: &[usize], name: MD3Name) -> MD3Surface {
+		let mut new_index: HashMap<u32, u32> = HashMap::new();
+		let mut kept: Vec<usize> = vec![];
+		let triangles = triangles.iter().filter_map(|&t| self.triangles.get(t)).map(|tri| MD3Triangle(tri.0.map(|v| {
+			*new_index.entry(v).or_insert_with(|| {
+				kept.push(v as usize);
+				kept.len() as u32 - 1
+			})
+		}))).collect();
+		let vertices = (0..self.num_frames).flat_map(|frame| kept.iter().map(move |&v| {
+			self.vertices.get(frame * self.num_verts + v).copied().unwrap_or_default()
+		})).collect();
+		MD3Surface {
+			name,
+			num_verts: kept.len(),
+			num_frames: self.num_frames,
+			shaders: self.shaders.clone(),
+			triangles,
+			texcoords: kept.iter().map(|&v| self.texcoords.get(v).copied().unwrap_or_default()).collect(),
+			vertices,
+		}
+	}
+	// Triangles in the given frame which face the opposite way to the stored
+	// normals of their vertices, i.e. which are wound the wrong way round
+	pub fn flipped_triangles(&self, frame: usize, gzdoom: bool) -> Vec<usize> {
+		let num_verts = self.num_verts;
+		let start = frame * num_verts;
+		let verts = match self.vertices.get(start..start + num_verts) {
+			Some(v) => v,
+			None => return vec![],
+		};
+		self.triangles.iter().enumerate().filter(|(_, tri)| {
+			let [a, b, c] = tri.0.map(|i| i as usize);
+			if a >= num_verts || b >= num_verts || c >= num_verts { return false; }
+			let (p0, p1, p2) = (verts[a].position(), verts[b].position(), verts[c].position());
+			let normal = (p2 - p0).cross(p1 - p0);
+			let stored = verts[a].normal(gzdoom) + verts[b].normal(gzdoom) + verts[c].normal(gzdoom);
+			normal.dot(stored) < 0.
+		}).map(|(index, _)| index).collect()
+	}
+	// Whether any triangle in the given frame is between the two points, from
+	// either side. Triangles which touch either end don't count.
+	pub fn blocks_segment(&self, frame: usize, from: Vec3, to: Vec3) -> bool {
+		let num_verts = self.num_verts;
+		let start = frame * num_verts;
+		let verts = match self.vertices.get(start..start + num_verts) {
+			Some(v) => v,
+			None => return false,
+		};
+		let direction = to - from;
+		self.triangles.iter().any(|tri| {
+			let [a, b, c] = tri.0.map(|i| i as usize);
+			if a >= num_verts || b >= num_verts || c >= num_verts { return false; }
+			let p0 = verts[a].position();
+			let (edge1, edge2) = (verts[b].position() - p0, verts[c].position() - p0);
+			// Möller–Trumbore
+			let p = direction.cross(edge2);
+			let det = edge1.dot(p);
+			if det.abs() < f32::EPSILON { return false; }
+			let offset = from - p0;
+			let u = offset.dot(p) / det;
+			if !(0. ..=1.).contains(&u) { return false; }
+			let q = offset.cross(edge1);
+			let v = direction.dot(q) / det;
+			if v < 0. || u + v > 1. { return false; }
+			let t = edge2.dot(q) / det;
+			t > 0.001 && t < 0.999
+		})
+	}
+	// Replace the stored normals with smooth normals, in every frame. Vertices
+	// which aren't part of a triangle keep their normals.
+	pub fn recalculate_normals(&mut self, weighting: NormalWeighting, gzdoom: bool) {
+		(0..self.num_frames).for_each(|frame| {
+			let start = frame * self.num_verts;
+			let normals = self.smooth_normals(frame, weighting);
+			let Some(vertices) = self.vertices.get_mut(start..start + self.num_verts) else { return; };
+			vertices.iter_mut().zip(normals)
+				.filter(|(_, normal)| *normal != Vec3::ZERO)
+				.for_each(|(vert, normal)| vert.n = MD3FrameVertex::encode_normal(normal, gzdoom));
+		});
+	}
+	// Drop frames, or add copies of the last frame, so the surface has the
+	// given number of frames
+	fn fit_frames(&mut self, num_frames: usize) {
+		let num_verts = self.num_verts;
+		self.vertices.truncate(num_frames * num_verts);
+		while self.vertices.len() < num_frames * num_verts {
+			let last = match self.vertices.len() {
+				0 => vec![MD3FrameVertex::default(); num_verts],
+				len => self.vertices[len - num_verts..].to_vec(),
+			};
+			self.vertices.extend(last);
+		}
+		self.num_frames = num_frames;
+	}
+}
+
+// How much each triangle around a vertex counts towards its smooth normal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NormalWeighting {
+	#[default]
+	Area,
+	// The angle of the triangle's corner at the vertex
+	Angle,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MD3Shader {
+	pub name: MD3Name,
+	pub index: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MD3Triangle(pub [u32; 3]);
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MD3TexCoord(pub Vec2);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MD3FrameVertex {
+	pub x: i16,
+	pub y: i16,
+	pub z: i16,
+	pub n: u16,
+}
+
+pub const MD3_XYZ_SCALE: f32 = 1. / 64.;
+
+impl MD3FrameVertex {
+	// The normal is stored as a signed integer, like the rest, so it has to
+	// be masked to get it back
+	pub fn to_pixel(&self) -> [i16; 4] {
+		[self.x, self.y, self.z, self.n as i16]
+	}
+	pub fn position(&self) -> Vec3 {
+		Vec3::new(self.x as f32, self.y as f32, self.z as f32) * MD3_XYZ_SCALE
+	}
+	// Latitude and longitude of the normal, which go from 0 to 255
+	pub fn lat_long(&self) -> (u8, u8) {
+		((self.n >> 8) as u8, self.n as u8)
+	}
+	// Same as toPosNorm in md3.vert
+	pub fn normal(&self, gzdoom: bool) -> Vec3 {
+		use std::f32::consts::TAU;
+		if !gzdoom {
+			match self.n {
+				0 => return Vec3::Z,
+				32768 => return Vec3::NEG_Z,
+				_ => (),
+			}
+		}
+		let (latitude, longtude) = self.lat_long();
+		let latitude = latitude as f32 * TAU / 255.;
+		let longtude = longtude as f32 * TAU / 255.;
+		Vec3::new(
+			latitude.cos() * longtude.sin(),
+			latitude.sin() * longtude.sin(),
+			longtude.cos())
+	}
+	// Same as normal, in reverse
+	pub fn encode_normal(normal: Vec3, gzdoom: bool) -> u16 {
+		use std::f32::consts::TAU;
+		if !gzdoom && normal.x == 0. && normal.y == 0. && normal.z < 0. {
+			return 32768;
+		}
+		let latitude = (normal.y.atan2(normal.x) * 255. / TAU).rem_euclid(255.).round() as u8;
+		let longtude = (normal.z.clamp(-1., 1.).acos() * 255. / TAU).round() as u8;
+		u16::from_be_bytes([latitude, longtude])
+	}
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum MD3ReadError {
+	#[error("Wrong ID ({0:?} instead of IDP3)!")]
+	WrongId([u8; 4]),
+	#[error("Unsupported version (version is {0})")]
+	UnsupportedVersion(i32),
+	#[error("Reached end of file")]
+	EOF,
+	#[error("Reader is after end position (position is {0})!")]
+	AfterEnd(u64),
+	#[error("{count} {what} at offset {offset} would go past the end of the file ({len} bytes)")]
+	OutOfBounds { what: &'static str, offset: u64, count: u64, len: u64 },
+	#[error("A triangle uses vertex {index}, but the surface only has {num_verts} vertices")]
+	InvalidVertexIndex { index: u32, num_verts: usize },
+	#[error("A surface has {surface} frames, but the model has {model}")]
+	FrameCountMismatch { surface: usize, model: usize },
+}
+
+// trait ReadStream : Read + Seek {}
+type MD3Result<T> = Result<T, MD3ReadError>;
+
+// Make sure that count items of the given size fit between offset and the
+// end of the file, before allocating space for them. Without this, a broken
+// or malicious file could make the reader allocate gigabytes.
+fn check_fits(what: &'static str, offset: u64, count: u64, size: usize, len: u64) -> MD3Result<()> {
+	let end = count.checked_mul(size as u64).and_then(|s| s.checked_add(offset));
+	match end {
+		Some(end) if end <= len => Ok(()),
+		_ => Err(MD3ReadError::OutOfBounds { what, offset, count, len }),
+	}
+}
+
+// Read a model from memory, e.g. from an entry in a PK3
+pub fn read_md3_bytes(data: &[u8]) -> MD3Result<MD3Model> {
+	read_md3(&mut io::Cursor::new(data))
+}
+
+pub fn read_md3(data: &mut (impl Read + Seek)) -> MD3Result<MD3Model> {
+	read_md3_with(data, &mut Recovery(None))
+}
+
+// Read as much of a slightly broken model as possible, instead of giving up at
+// the first problem. The problems which were worked around are returned
+// along with the model.
+pub fn read_md3_tolerant(data: &mut (impl Read + Seek)) -> MD3Result<(MD3Model, Vec<MD3ReadError>)> {
+	let mut recovery = Recovery(Some(vec![]));
+	let model = read_md3_with(data, &mut recovery)?;
+	Ok((model, recovery.0.unwrap_or_default()))
+}
+
+// Whether to stop at problems, or to work around them and keep a list of them
+struct Recovery(Option<Vec<MD3ReadError>>);
+
+impl Recovery {
+	fn recover(&mut self, error: MD3ReadError) -> MD3Result<()> {
+		match &mut self.0 {
+			Some(recovered) => {
+				recovered.push(error);
+				Ok(())
+			},
+			None => Err(error),
+		}
+	}
+	// How many items to read: all of them, or as many as there's room for if
+	// they would go past the end of the file
+	fn fit(&mut self, what: &'static str, offset: u64, count: u64, size: usize, len: u64) -> MD3Result<u64> {
+		match check_fits(what, offset, count, size, len) {
+			Ok(()) => Ok(count),
+			Err(e) => {
+				self.recover(e)?;
+				Ok(count.min(len.saturating_sub(offset) / size as u64))
+			},
+		}
+	}
+}
+
+fn read_md3_with(data: &mut (impl Read + Seek), recovery: &mut Recovery) -> MD3Result<MD3Model> {
+	use MD3ReadError::*;
+	let mut model = MD3Model {
+		version: MD3_VERSION,
+		name: [0; 64],
+		num_tags: 0,
+		frames: vec![],
+		tags: vec![],
+		surfaces: vec![],
+	};
+	let len = data.seek(SeekFrom::End(0)).or(Err(EOF))?;
+	data.rewind().or(Err(EOF))?;
+	let mut int_buf = [0; 4];
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	if int_buf != MD3_ID { return Err(WrongId(int_buf)); }
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let version = i32::from_le_bytes(int_buf);
+	if version != MD3_VERSION { recovery.recover(UnsupportedVersion(version))?; }
+	data.read_exact(&mut model.name).or(Err(EOF))?;
+	/* data.read_exact(&mut int_buf).or(Err(EOF))?; */
+	data.seek(SeekFrom::Current(4)).or(Err(EOF))?;
+	/* let flags = i32::from_le_bytes(int_buf); */
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let num_frames = u32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let num_tags = u32::from_le_bytes(int_buf);
+	model.num_tags = num_tags as usize;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let num_surfs = u32::from_le_bytes(int_buf);
+	/* data.read_exact(&mut int_buf).or(Err(EOF))?; */
+	data.seek(SeekFrom::Current(4)).or(Err(EOF))?;
+	/* let num_skins = u32::from_le_bytes(int_buf); */
+	// Offsets
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let offset_frames = u32::from_le_bytes(int_buf) as u64;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let offset_tags = u32::from_le_bytes(int_buf) as u64;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let offset_surfaces = u32::from_le_bytes(int_buf) as u64;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let offset_end = u32::from_le_bytes(int_buf) as u64;
+	// Frames
+	let num_frames = recovery.fit("frames", offset_frames, num_frames as u64, MD3_FRAME_SIZE, len)?;
+	data.seek(SeekFrom::Start(offset_frames)).or(Err(EOF))?;
+	model.frames = (0..num_frames).map(|_| read_frame(data))
+		.collect::<MD3Result<Vec<MD3Frame>>>()?;
+	// Tags
+	{
+	let num_tags = num_tags as u64 * model.frames.len() as u64;
+	let fit = recovery.fit("tags", offset_tags, num_tags, MD3_TAG_SIZE, len)?;
+	// Only whole frames of tags, the missing frames are copies of the last one
+	let fit = fit - fit % (model.num_tags as u64).max(1);
+	data.seek(SeekFrom::Start(offset_tags)).or(Err(EOF))?;
+	model.tags = (0..fit).map(|_| read_tag(data))
+		.collect::<MD3Result<Vec<MD3FrameTag>>>()?;
+	if model.tags.is_empty() {
+		model.num_tags = 0;
+	}
+	while (model.tags.len() as u64) < num_tags && model.num_tags > 0 {
+		let last = model.tags[model.tags.len() - model.num_tags..].to_vec();
+		model.tags.extend(last);
+	}
+	}
+	// Surfaces
+	let num_surfs = recovery.fit("surfaces", offset_surfaces, num_surfs as u64, MD3_SURFACE_HEADER_SIZE, len)?;
+	data.seek(SeekFrom::Start(offset_surfaces)).or(Err(EOF))?;
+	for _ in 0..num_surfs {
+		let start = data.stream_position().or(Err(EOF))?;
+		match read_surface(data, len, recovery) {
+			Ok(surface) => model.surfaces.push(surface),
+			Err(e) => {
+				recovery.recover(e)?;
+				// Skip the broken surface, if its header says where it ends
+				match surface_end(data, start).filter(|&end| end >= start + MD3_SURFACE_HEADER_SIZE as u64 && end <= len) {
+					Some(end) => data.seek(SeekFrom::Start(end)).or(Err(EOF))?,
+					None => break,
+				};
+			},
+		}
+	}
+	let num_frames = model.frames.len();
+	for surface in model.surfaces.iter_mut().filter(|s| s.num_frames != num_frames) {
+		recovery.recover(FrameCountMismatch { surface: surface.num_frames, model: num_frames })?;
+		surface.fit_frames(num_frames);
+	}
+	let pos = data.stream_position().or(Err(EOF))?;
+	if pos > offset_end {
+		recovery.recover(AfterEnd(pos))?;
+	}
+	Ok(model)
+}
+
+// Where the surface starting at the given offset ends, according to its header
+fn surface_end(data: &mut (impl Read + Seek), start: u64) -> Option<u64> {
+	let mut int_buf = [0; 4];
+	data.seek(SeekFrom::Start(start + MD3_SURFACE_HEADER_SIZE as u64 - 4)).ok()?;
+	data.read_exact(&mut int_buf).ok()?;
+	Some(start + u32::from_le_bytes(int_buf) as u64)
+}
+
+fn read_frame(data: &mut (impl Read + Seek)) -> MD3Result<MD3Frame> {
+	use MD3ReadError::*;
+	let mut frame = MD3Frame {
+		min: Vec3::ZERO,
+		max: Vec3::ZERO,
+		origin: Vec3::ZERO,
+		radius: 0.,
+		name: [0; 16],
+	};
+	let mut int_buf = [0; 4];
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.min.x = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.min.y = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.min.z = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.max.x = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.max.y = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.max.z = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.origin.x = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.origin.y = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.origin.z = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	frame.radius = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut frame.name).or(Err(EOF))?;
+	Ok(frame)
+}
+
+fn read_tag(data: &mut (impl Read + Seek)) -> MD3Result<MD3FrameTag> {
+	use MD3ReadError::*;
+	let mut tag = MD3FrameTag {
+		name: [0; 64],
+		origin: Vec3::ZERO,
+		axes: Mat3::ZERO,
+	};
+	let mut int_buf = [0; 4];
+	data.read_exact(&mut tag.name).or(Err(EOF))?;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.origin.x = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.origin.y = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.origin.z = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.axes.x_axis.x = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.axes.x_axis.y = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.axes.x_axis.z = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.axes.y_axis.x = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.axes.y_axis.y = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.axes.y_axis.z = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.axes.z_axis.x = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.axes.z_axis.y = f32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	tag.axes.z_axis.z = f32::from_le_bytes(int_buf);
+	Ok(tag)
+}
+
+fn read_surface(data: &mut (impl Read + Seek), len: u64, recovery: &mut Recovery) -> MD3Result<MD3Surface> {
+	use MD3ReadError::*;
+	let mut surface = MD3Surface {
+		name: [0; 64],
+		num_verts: 0,
+		num_frames: 0,
+		shaders: vec![],
+		triangles: vec![],
+		texcoords: vec![],
+		vertices: vec![],
+	};
+	let offset_ref = data.stream_position().or(Err(EOF))?;
+	let mut int_buf = [0; 4];
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	if int_buf != MD3_ID {
+		return Err(WrongId(int_buf));
+	}
+	data.read_exact(&mut surface.name).or(Err(EOF))?;
+	data.seek(SeekFrom::Current(4)).or(Err(EOF))?; // flags (unused)
+	// Sizes/counts
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	surface.num_frames = u32::from_le_bytes(int_buf) as usize;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let num_shaders = u32::from_le_bytes(int_buf);
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	surface.num_verts = u32::from_le_bytes(int_buf) as usize;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let num_tris = u32::from_le_bytes(int_buf);
+	// Offsets
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let offset_triangles = offset_ref + u32::from_le_bytes(int_buf) as u64;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let offset_shaders = offset_ref + u32::from_le_bytes(int_buf) as u64;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let offset_uvs = offset_ref + u32::from_le_bytes(int_buf) as u64;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let offset_verts = offset_ref + u32::from_le_bytes(int_buf) as u64;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	let offset_end = offset_ref + u32::from_le_bytes(int_buf) as u64;
+	// Shaders
+	let num_shaders = recovery.fit("shaders", offset_shaders, num_shaders as u64, MD3_SHADER_SIZE, len)?;
+	data.seek(SeekFrom::Start(offset_shaders)).or(Err(EOF))?;
+	surface.shaders = (0..num_shaders).map(|_| read_shader(data))
+		.collect::<MD3Result<Vec<MD3Shader>>>()?;
+	// Triangles
+	let num_tris = recovery.fit("triangles", offset_triangles, num_tris as u64, MD3_TRIANGLE_SIZE, len)?;
+	data.seek(SeekFrom::Start(offset_triangles)).or(Err(EOF))?;
+	surface.triangles = (0..num_tris).map(|_| read_triangle(data))
+		.collect::<MD3Result<Vec<MD3Triangle>>>()?;
+	let bad_index = surface.triangles.iter().flat_map(|t| t.0)
+		.find(|&index| index as usize >= surface.num_verts);
+	if let Some(index) = bad_index {
+		recovery.recover(InvalidVertexIndex { index, num_verts: surface.num_verts })?;
+		let num_verts = surface.num_verts;
+		surface.triangles.retain(|t| t.0.iter().all(|&index| (index as usize) < num_verts));
+	}
+	// UVs
+	check_fits("texture coordinates", offset_uvs, surface.num_verts as u64, MD3_TEXCOORD_SIZE, len)?;
+	data.seek(SeekFrom::Start(offset_uvs)).or(Err(EOF))?;
+	surface.texcoords = (0..surface.num_verts).map(|_| read_texcoord(data))
+		.collect::<MD3Result<Vec<MD3TexCoord>>>()?;
+	// Vertices
+	{
+		let num_verts = surface.num_verts as u64 * surface.num_frames as u64;
+		let fit = recovery.fit("vertices", offset_verts, num_verts, MD3_VERTEX_SIZE, len)?;
+		// Only the frames which are all there
+		let frames = fit / (surface.num_verts as u64).max(1);
+		let num_verts = match surface.num_verts {
+			0 => num_verts,
+			_ => {
+				surface.num_frames = frames as usize;
+				frames * surface.num_verts as u64
+			},
+		};
+		data.seek(SeekFrom::Start(offset_verts)).or(Err(EOF))?;
+		surface.vertices = (0..num_verts).map(|_| read_vertex(data))
+			.collect::<MD3Result<Vec<MD3FrameVertex>>>()?;
+	}
+	let pos = data.stream_position().or(Err(EOF))?;
+	if pos > offset_end {
+		recovery.recover(AfterEnd(pos))?;
+	}
+	Ok(surface)
+}
+
+fn read_shader(data: &mut (impl Read + Seek)) -> MD3Result<MD3Shader> {
+	use MD3ReadError::*;
+	let mut shader = MD3Shader {
+		name: [0; 64],
+		index: 0,
+	};
+	let mut int_buf = [0; 4];
+	data.read_exact(&mut shader.name).or(Err(EOF))?;
+	data.read_exact(&mut int_buf).or(Err(EOF))?;
+	shader.index = u32::from_le_bytes(int_buf);
+	Ok(shader)
+}
+
+fn read_triangle(data: &mut (impl Read + Seek)) -> MD3Result<MD3Triangle> {
+	use MD3ReadError::*;
+	let mut triangle = [0; 3];
+	let mut int_buf = [0; 4];
+	// When `array_try_map` is stabilized...
+	// See https://github.com/rust-lang/rust/issues/79711
+	/* triangle = triangle.try_map(|_| {
+		data.read_exact(&mut int_buf).or(Err(EOF))?;
+		Ok(u32::from_le_bytes(int_buf))
+	})?; */
+	for i in 0..triangle.len() {
+		data.read_exact(&mut int_buf).or(Err(EOF))?;
+		triangle[i] = u32::from_le_bytes(int_buf);
+	}
+	let tmp = triangle[0];
+	triangle[0] = triangle[2];
+	triangle[2] = tmp;
+	Ok(MD3Triangle(triangle))
+}
+
+fn read_texcoord(data: &mut (impl Read + Seek)) -> MD3Result<MD3TexCoord> {
+	use MD3ReadError::*;
+	let mut int_buf = [0; 4];
+	let mut coords = [0.; 2];
+	for i in 0..coords.len() {
+		data.read_exact(&mut int_buf).or(Err(EOF))?;
+		coords[i] = f32::from_le_bytes(int_buf);
+	}
+	Ok(MD3TexCoord(Vec2::from(coords)))
+}
+
+fn read_vertex(data: &mut (impl Read + Seek)) -> MD3Result<MD3FrameVertex> {
+	use MD3ReadError::*;
+	let mut short_buf = [0; 2];
+	let mut vertex = MD3FrameVertex {
+		x: 0,
+		y: 0,
+		z: 0,
+		n: 0,
+	};
+	data.read_exact(&mut short_buf).or(Err(EOF))?;
+	vertex.x = i16::from_le_bytes(short_buf);
+	data.read_exact(&mut short_buf).or(Err(EOF))?;
+	vertex.y = i16::from_le_bytes(short_buf);
+	data.read_exact(&mut short_buf).or(Err(EOF))?;
+	vertex.z = i16::from_le_bytes(short_buf);
+	data.read_exact(&mut short_buf).or(Err(EOF))?;
+	vertex.n = u16::from_le_bytes(short_buf);
+	Ok(vertex)
+}
+
+pub(crate) const MD3_HEADER_SIZE: usize = 108;
+pub(crate) const MD3_FRAME_SIZE: usize = 56;
+pub(crate) const MD3_TAG_SIZE: usize = 112;
+pub(crate) const MD3_SURFACE_HEADER_SIZE: usize = 108;
+pub(crate) const MD3_SHADER_SIZE: usize = 68;
+pub(crate) const MD3_TRIANGLE_SIZE: usize = 12;
+pub(crate) const MD3_TEXCOORD_SIZE: usize = 8;
+pub(crate) const MD3_VERTEX_SIZE: usize = 8;
+
+impl MD3Surface {
+	fn file_size(&self) -> usize {
+		MD3_SURFACE_HEADER_SIZE +
+		self.shaders.len() * MD3_SHADER_SIZE +
+		self.triangles.len() * MD3_TRIANGLE_SIZE +
+		self.texcoords.len() * MD3_TEXCOORD_SIZE +
+		self.vertices.len() * MD3_VERTEX_SIZE
+	}
+}
+
+// Write the model in the same layout id Software's tools use: header,
+// frames, tags, and then the surfaces, each followed by its data.
+impl MD3Model {
+	// Size in bytes of the model when it's written
+	pub fn file_size(&self) -> usize {
+		MD3_HEADER_SIZE +
+		self.frames.len() * MD3_FRAME_SIZE +
+		self.tags.len() * MD3_TAG_SIZE +
+		self.surfaces.iter().map(MD3Surface::file_size).sum::<usize>()
+	}
+}
+
+pub fn write_md3(model: &MD3Model, data: &mut impl Write) -> io::Result<()> {
+	let offset_frames = MD3_HEADER_SIZE;
+	let offset_tags = offset_frames + model.frames.len() * MD3_FRAME_SIZE;
+	let offset_surfaces = offset_tags + model.tags.len() * MD3_TAG_SIZE;
+	let offset_end = model.file_size();
+	data.write_all(&MD3_ID)?;
+	data.write_all(&model.version.to_le_bytes())?;
+	data.write_all(&model.name)?;
+	data.write_all(&0u32.to_le_bytes())?; // flags
+	data.write_all(&(model.frames.len() as u32).to_le_bytes())?;
+	let num_tags = if model.frames.is_empty() { 0 } else { model.num_tags };
+	data.write_all(&(num_tags as u32).to_le_bytes())?;
+	data.write_all(&(model.surfaces.len() as u32).to_le_bytes())?;
+	data.write_all(&0u32.to_le_bytes())?; // skins
+	for offset in [offset_frames, offset_tags, offset_surfaces, offset_end] {
+		data.write_all(&(offset as u32).to_le_bytes())?;
+	}
+	model.frames.iter().try_for_each(|frame| write_frame(frame, data))?;
+	model.tags.iter().try_for_each(|tag| write_tag(tag, data))?;
+	model.surfaces.iter().try_for_each(|surf| write_surface(surf, data))?;
+	Ok(())
+}
+fn write_vec3(v: Vec3, data: &mut impl Write) -> io::Result<()> {
+	v.to_array().iter().try_for_each(|c| data.write_all(&c.to_le_bytes()))
+}
+fn write_frame(frame: &MD3Frame, data: &mut impl Write) -> io::Result<()> {
+	write_vec3(frame.min, data)?;
+	write_vec3(frame.max, data)?;
+	write_vec3(frame.origin, data)?;
+	data.write_all(&frame.radius.to_le_bytes())?;
+	data.write_all(&frame.name)
+}
+fn write_tag(tag: &MD3FrameTag, data: &mut impl Write) -> io::Result<()> {
+	data.write_all(&tag.name)?;
+	write_vec3(tag.origin, data)?;
+	write_vec3(tag.axes.x_axis, data)?;
+	write_vec3(tag.axes.y_axis, data)?;
+	write_vec3(tag.axes.z_axis, data)
+}
+fn write_surface(surface: &MD3Surface, data: &mut impl Write) -> io::Result<()> {
+	let offset_shaders = MD3_SURFACE_HEADER_SIZE;
+	let offset_triangles = offset_shaders + surface.shaders.len() * MD3_SHADER_SIZE;
+	let offset_uvs = offset_triangles + surface.triangles.len() * MD3_TRIANGLE_SIZE;
+	let offset_verts = offset_uvs + surface.texcoords.len() * MD3_TEXCOORD_SIZE;
+	let offset_end = surface.file_size();
+	data.write_all(&MD3_ID)?;
+	data.write_all(&surface.name)?;
+	data.write_all(&0u32.to_le_bytes())?; // flags
+	data.write_all(&(surface.num_frames as u32).to_le_bytes())?;
+	data.write_all(&(surface.shaders.len() as u32).to_le_bytes())?;
+	data.write_all(&(surface.num_verts as u32).to_le_bytes())?;
+	data.write_all(&(surface.triangles.len() as u32).to_le_bytes())?;
+	for offset in [offset_triangles, offset_shaders, offset_uvs, offset_verts, offset_end] {
+		data.write_all(&(offset as u32).to_le_bytes())?;
+	}
+	for shader in surface.shaders.iter() {
+		data.write_all(&shader.name)?;
+		data.write_all(&shader.index.to_le_bytes())?;
+	}
+	for triangle in surface.triangles.iter() {
+		// The reader reverses the winding order
+		let [a, b, c] = triangle.0;
+		for index in [c, b, a] {
+			data.write_all(&index.to_le_bytes())?;
+		}
+	}
+	for uv in surface.texcoords.iter() {
+		data.write_all(&uv.0.x.to_le_bytes())?;
+		data.write_all(&uv.0.y.to_le_bytes())?;
+	}
+	for vertex in surface.vertices.iter() {
+		data.write_all(&vertex.x.to_le_bytes())?;
+		data.write_all(&vertex.y.to_le_bytes())?;
+		data.write_all(&vertex.z.to_le_bytes())?;
+		data.write_all(&vertex.n.to_le_bytes())?;
+	}
+	Ok(())
+}
+
+// Make a fixed-size, null-terminated name. Names that are too long are cut off.
+pub fn make_name<const N: usize>(name: &str) -> [u8; N] {
+	let mut bytes = [0; N];
+	let len = name.len().min(N - 1);
+	bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+	bytes
+}
+
+// Same as make_name, but names which don't fit are an error instead of being
+// cut short. The last byte is always 0.
+pub fn try_make_name<const N: usize>(name: &str) -> Result<[u8; N], MD3EditError> {
+	if name.len() >= N {
+		return Err(MD3EditError::NameTooLong(name.to_string(), N - 1));
+	}
+	Ok(make_name(name))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+
+	// Models for the tests here and in the other modules. The model has as
+	// many frames as its first surface.
+	pub(crate) fn test_model(surfaces: Vec<MD3Surface>) -> MD3Model {
+		MD3Model {
+			version: MD3_VERSION,
+			name: [0; 64],
+			num_tags: 0,
+			frames: vec![MD3Frame::default(); surfaces.first().map_or(0, |s| s.num_frames)],
+			tags: vec![],
+			surfaces,
+		}
+	}
+
+	// A surface without shaders or triangles, with each frame's vertices one
+	// after another
+	pub(crate) fn test_surface(name: &str, num_verts: usize, vertices: Vec<MD3FrameVertex>) -> MD3Surface {
+		MD3Surface {
+			name: make_name(name),
+			num_verts,
+			num_frames: vertices.len() / num_verts.max(1),
+			shaders: vec![],
+			triangles: vec![],
+			texcoords: vec![Default::default(); num_verts],
+			vertices,
+		}
+	}
+
+	// One triangle with all of its corners at the origin
+	pub(crate) fn triangle() -> MD3Surface {
+		MD3Surface {
+			triangles: vec![MD3Triangle([0, 1, 2])],
+			..test_surface("body", 3, vec![Default::default(); 3])
+		}
+	}
+
+	// A square 1 unit across and facing up, split along a diagonal, using all
+	// of the texture
+	pub(crate) fn quad() -> MD3Surface {
+		let vertex = |x, y| MD3FrameVertex { x, y, z: 0, n: 0 };
+		MD3Surface {
+			triangles: vec![MD3Triangle([0, 2, 1]), MD3Triangle([0, 3, 2])],
+			texcoords: [(0., 0.), (1., 0.), (1., 1.), (0., 1.)].map(|(u, v)| MD3TexCoord(Vec2::new(u, v))).to_vec(),
+			..test_surface("floor", 4, vec![vertex(0, 0), vertex(64, 0), vertex(64, 64), vertex(0, 64)])
+		}
+	}
+
+	#[test]
+	fn empty_model_warnings() {
+		let model = test_model(vec![]);
+		assert_eq!(model.warnings(), vec![MD3Warning::NoFrames, MD3Warning::NoSurfaces]);
+	}
+
+	#[test]
+	fn interpolated_tag() {
+		let mut name = [0; 64];
+		name[..10].copy_from_slice(b"tag_weapon");
+		let tag = |x| MD3FrameTag { name, origin: Vec3::new(x, 0., 0.), axes: Mat3::IDENTITY };
+		let model = MD3Model {
+			num_tags: 1,
+			frames: vec![Default::default(); 2],
+			tags: vec![tag(0.), tag(8.)],
+			..test_model(vec![])
+		};
+		let transform = model.tag_transform("tag_weapon", 0.5).unwrap();
+		assert_eq!(transform.translation, Vec3::new(4., 0., 0.).into());
+		assert_eq!(transform.matrix3, Mat3::IDENTITY.into());
+		assert!(model.tag_transform("tag_head", 0.).is_none());
+		assert!(model.tag_transform("tag_weapon", 2.).is_none());
+	}
+
+	#[test]
+	fn tag_editing() {
+		let tag = |name, x| MD3FrameTag { name: make_name(name), origin: Vec3::new(x, 0., 0.), axes: Mat3::IDENTITY };
+		let mut model = MD3Model {
+			num_tags: 2,
+			frames: vec![Default::default(); 2],
+			tags: vec![tag("tag_head", 0.), tag("tag_weapon", 1.), tag("tag_head", 2.), tag("tag_weapon", 3.)],
+			..test_model(vec![])
+		};
+		model.duplicate_tag(1, "tag_flag").unwrap();
+		assert_eq!(model.tag_names().collect::<Vec<_>>(), ["tag_head", "tag_weapon", "tag_flag"]);
+		assert_eq!(model.tags[5].origin.x, 3.);
+		assert_eq!(model.rename_tag(2, "tag_head"), Err(MD3EditError::DuplicateTag("tag_head".into())));
+		let long_name = "tag_".repeat(16);
+		assert_eq!(model.rename_tag(2, &long_name), Err(MD3EditError::NameTooLong(long_name, 63)));
+		model.remove_tag(0).unwrap();
+		model.rename_tag(1, "tag_torso").unwrap();
+		model.tag_frames_mut(0).for_each(|tag| tag.origin.y = 8.);
+		assert_eq!(model.num_tags, 2);
+		assert_eq!(model.tag_names().collect::<Vec<_>>(), ["tag_weapon", "tag_torso"]);
+		assert_eq!(model.tags.iter().map(|t| t.origin).collect::<Vec<_>>(), [
+			Vec3::new(1., 8., 0.), Vec3::new(1., 0., 0.), Vec3::new(3., 8., 0.), Vec3::new(3., 0., 0.)]);
+		assert_eq!(model.remove_tag(2), Err(MD3EditError::NoTag(2)));
+	}
+
+	#[test]
+	fn surface_names() {
+		let mut model = test_model(vec![MD3Surface {
+			shaders: vec![MD3Shader { name: make_name("models\\players\\sarge\\head.tga"), index: 0 }],
+			..test_surface("h_head", 0, vec![])
+		}]);
+		model.rename_surface(0, "h_visor").unwrap();
+		model.set_shader_name(0, 0, "models/players/sarge/visor").unwrap();
+		assert_eq!(String::from_utf8_stop(&model.surfaces[0].name), "h_visor");
+		assert_eq!(String::from_utf8_stop(&model.surfaces[0].shaders[0].name), "models/players/sarge/visor");
+		// There's always room for the terminating 0
+		let path = "x".repeat(64);
+		assert_eq!(model.set_shader_name(0, 0, &path), Err(MD3EditError::NameTooLong(path, 63)));
+		assert!(model.set_shader_name(0, 0, &"x".repeat(63)).is_ok());
+		assert_eq!(model.set_shader_name(0, 1, "x"), Err(MD3EditError::NoShader { surface: 0, shader: 1 }));
+		assert_eq!(model.rename_surface(1, "x"), Err(MD3EditError::NoSurface(1)));
+	}
+
+	#[test]
+	fn frame_edits() {
+		assert_eq!(FrameEdit::Delete(1..=2).order(5), [0, 3, 4]);
+		assert_eq!(FrameEdit::Duplicate(1..=2).order(4), [0, 1, 2, 1, 2, 3]);
+		assert_eq!(FrameEdit::Move(3..=4, 1).order(5), [0, 3, 4, 1, 2]);
+		assert_eq!(FrameEdit::Move(0..=1, 5).order(5), [2, 3, 4, 0, 1]);
+		let tag = |x| MD3FrameTag { name: make_name("tag_weapon"), origin: Vec3::new(x, 0., 0.), axes: Mat3::IDENTITY };
+		let vertex = |x| MD3FrameVertex { x, y: 0, z: 0, n: 0 };
+		let mut model = MD3Model {
+			num_tags: 1,
+			frames: (0..3).map(|f| MD3Frame { radius: f as f32, ..Default::default() }).collect(),
+			tags: vec![tag(0.), tag(1.), tag(2.)],
+			..test_model(vec![test_surface("body", 2, [0, 1, 10, 11, 20, 21].map(vertex).to_vec())])
+		};
+		model.reorder_frames(&[2, 0, 0]);
+		assert_eq!(model.frames.iter().map(|f| f.radius).collect::<Vec<_>>(), [2., 0., 0.]);
+		assert_eq!(model.tags.iter().map(|t| t.origin.x).collect::<Vec<_>>(), [2., 0., 0.]);
+		assert_eq!(model.surfaces[0].vertices.iter().map(|v| v.x).collect::<Vec<_>>(), [20, 21, 0, 1, 0, 1]);
+		assert_eq!(model.surfaces[0].num_frames, 3);
+	}
+
+	#[test]
+	fn transform() {
+		let vertex = |x, y, z| MD3FrameVertex { x, y, z, n: MD3FrameVertex::encode_normal(Vec3::X, false) };
+		let mut model = MD3Model {
+			num_tags: 1,
+			tags: vec![MD3FrameTag { name: make_name("tag_weapon"), origin: Vec3::new(1., 0., 0.), axes: Mat3::IDENTITY }],
+			..test_model(vec![test_surface("body", 2, vec![vertex(64, 0, 0), vertex(32767, 0, 64)])])
+		};
+		// Twice as big, facing left, and a bit higher
+		let transform = ModelTransform {
+			scale: 2.,
+			rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+			offset: Vec3::new(0., 0., 1.),
+		};
+		assert_eq!(model.bake_transform(&transform, false), 1);
+		let surface = &model.surfaces[0];
+		assert_eq!((surface.vertices[0].x, surface.vertices[0].y, surface.vertices[0].z), (0, 128, 64));
+		assert_eq!((surface.vertices[1].x, surface.vertices[1].y, surface.vertices[1].z), (0, 32767, 192));
+		assert!(surface.vertices[0].normal(false).distance(Vec3::Y) < 0.02);
+		assert!(model.tags[0].origin.distance(Vec3::new(0., 2., 1.)) < 1e-5);
+		assert!(model.tags[0].axes.x_axis.distance(Vec3::Y) < 1e-5);
+		let frame = &model.frames[0];
+		assert_eq!((frame.min, frame.max), (Vec3::new(0., 2., 1.), Vec3::new(0., 32767. / 64., 3.)));
+		// The furthest vertex from the frame's origin, which moved up
+		assert_eq!(frame.origin, Vec3::new(0., 0., 1.));
+		assert_eq!(frame.radius, frame.max.distance(frame.origin));
+	}
+
+	#[test]
+	fn mirror() {
+		let vertex = |x, y| MD3FrameVertex { x, y, z: 0, n: MD3FrameVertex::encode_normal(Vec3::new(1., 1., 0.).normalize(), false) };
+		// A weapon tag on the left, turned to face forward and left
+		let axes = Mat3::from_rotation_z(std::f32::consts::FRAC_PI_4);
+		let mut model = MD3Model {
+			num_tags: 1,
+			frames: vec![MD3Frame { min: Vec3::new(0., 1., 0.), max: Vec3::new(1., 2., 0.), origin: Vec3::Y, ..Default::default() }],
+			tags: vec![MD3FrameTag { name: make_name("tag_weapon"), origin: Vec3::new(1., 2., 3.), axes }],
+			..test_model(vec![MD3Surface {
+				triangles: vec![MD3Triangle([0, 1, 2])],
+				texcoords: vec![MD3TexCoord(Vec2::new(0.25, 0.5)); 3],
+				..test_surface("hand", 3, vec![vertex(0, 64), vertex(64, 64), vertex(0, 128)])
+			}])
+		};
+		model.mirror(1, [true, false], false);
+		let surface = &model.surfaces[0];
+		assert_eq!((surface.vertices[2].x, surface.vertices[2].y), (0, -128));
+		assert!(surface.vertices[0].normal(false).distance(Vec3::new(1., -1., 0.).normalize()) < 0.02);
+		assert_eq!(surface.triangles[0].0, [0, 2, 1]);
+		assert_eq!(surface.texcoords[0].0, Vec2::new(0.75, 0.5));
+		let tag = &model.tags[0];
+		assert_eq!(tag.origin, Vec3::new(1., -2., 3.));
+		// Facing forward and right now, and still a rotation
+		assert!(tag.axes.x_axis.distance(Vec3::new(1., -1., 0.).normalize()) < 1e-5);
+		assert!((tag.axes.determinant() - 1.).abs() < 1e-5);
+		let frame = &model.frames[0];
+		assert_eq!((frame.min, frame.max, frame.origin), (Vec3::new(0., -2., 0.), Vec3::new(1., -1., 0.), -Vec3::Y));
+	}
+
+	#[test]
+	fn fitted_bounds() {
+		let vertex = |x, y, z| MD3FrameVertex { x, y, z, n: 0 };
+		let mut model = test_model(vec![
+			test_surface("body", 2, vec![vertex(0, 0, 0), vertex(128, 0, 64), vertex(0, 0, 0), vertex(0, 0, 0)]),
+		]);
+		// The exporter wrote garbage for the first frame
+		model.frames[0] = MD3Frame { min: Vec3::splat(-1000.), radius: 5000., ..Default::default() };
+		let fitted = model.fitted_frame(0).unwrap();
+		assert_eq!((fitted.min, fitted.max), (Vec3::ZERO, Vec3::new(2., 0., 1.)));
+		assert_eq!(fitted.origin, Vec3::new(1., 0., 0.5));
+		assert_eq!(fitted.radius, Vec3::new(1., 0., 0.5).length());
+		// The second frame is already right
+		assert_eq!(model.fit_frame_bounds(), 1);
+		assert_eq!(model.frames[0], fitted);
+		assert_eq!(model.fit_frame_bounds(), 0);
+		assert_eq!(model.fitted_frame(2), None);
+	}
+
+	#[test]
+	fn split() {
+		let vertex = |x| MD3FrameVertex { x, y: 0, z: 0, n: 0 };
+		let uv = |u, v| MD3TexCoord(Vec2::new(u, v));
+		// A quad in one corner of the texture, and a triangle with a corner
+		// in the same place as one of the quad's
+		let mut model = test_model(vec![MD3Surface {
+			triangles: [[0, 1, 2], [4, 5, 6], [0, 2, 3]].map(MD3Triangle).to_vec(),
+			texcoords: vec![uv(0., 0.), uv(0.5, 0.), uv(0.5, 0.5), uv(0., 0.5), uv(0.5, 0.5), uv(1., 1.), uv(0.5, 1.)],
+			..test_surface("body", 7, (0..14).map(vertex).collect())
+		}]);
+		assert_eq!(model.surfaces[0].uv_islands(), [0, 0, 0]);
+		// Moved away, so it's an island of its own
+		model.surfaces[0].texcoords[4] = uv(1., 0.5);
+		let islands = model.surfaces[0].uv_islands();
+		assert_eq!(islands, [0, 1, 0]);
+		assert_eq!(model.split_surface(0, &islands), Ok(vec![1]));
+		assert_eq!(model.surfaces.len(), 2);
+		let (body, part) = (&model.surfaces[0], &model.surfaces[1]);
+		assert_eq!(String::from_utf8_stop(&part.name), "body_1");
+		assert_eq!(body.triangles.iter().map(|t| t.0).collect::<Vec<_>>(), [[0, 1, 2], [0, 2, 3]]);
+		assert_eq!(part.triangles[0].0, [0, 1, 2]);
+		assert_eq!(part.num_verts, 3);
+		// Each frame's vertices are kept
+		assert_eq!(part.vertices.iter().map(|v| v.x).collect::<Vec<_>>(), [4, 5, 6, 11, 12, 13]);
+		assert_eq!(body.vertices.len(), 8);
+		assert_eq!(model.split_surface(3, &[]), Err(MD3EditError::NoSurface(3)));
+		// Latin-1 names take more bytes once they're read
+		let mut name = [0xe9; 64];
+		name[63] = 0;
+		model.surfaces[0].name = name;
+		assert_eq!(model.split_surface(0, &[0, 1]), Ok(vec![1]));
+		assert_eq!(model.surfaces[0].name, name);
+		assert_eq!(String::from_utf8_stop(&model.surfaces[1].name), "é".repeat(30) + "_1");
+	}
+
+	#[test]
+	fn uv_span() {
+		// Stretched to 4 units wide and 1 unit high
+		let mut surface = quad();
+		[1, 2].into_iter().for_each(|v| surface.vertices[v].x = 256);
+		assert!(surface.uv_span(0).unwrap().distance(Vec2::new(4., 1.)) < 1e-5);
+		assert_eq!(surface.uv_span(1), None);
+	}
+
+	#[test]
+	fn frame_sections() {
+		let frame = |name: &str| MD3Frame { name: make_name(name), ..Default::default() };
+		let model = MD3Model {
+			frames: vec![frame("death1_01"), frame("death1_02"), frame("death2_01"),
+				frame("idle"), frame("death1_03")],
+			..test_model(vec![])
+		};
+		let section = |name: &str, frames| FrameSection { name: name.to_string(), frames };
+		assert_eq!(model.frame_sections(), vec![section("death1", 0..=1), section("death2", 2..=2),
+			section("idle", 3..=3), section("death1", 4..=4)]);
+	}
+
+	#[test]
+	fn normal_errors() {
+		// A square facing up, with a normal which is stored facing down
+		let mut surface = quad();
+		surface.vertices[3].n = 32768;
+		assert_eq!(surface.vertices[3].lat_long(), (128, 0));
+		assert_eq!(surface.smooth_normals(0, NormalWeighting::Area), vec![Vec3::Z; 4]);
+		assert_eq!(surface.smooth_normals(0, NormalWeighting::Angle), vec![Vec3::Z; 4]);
+		assert_eq!(surface.normal_errors(false), vec![0., 0., 0., 180.]);
+		let mut fixed = surface.clone();
+		fixed.recalculate_normals(NormalWeighting::Area, false);
+		assert_eq!(fixed.normal_errors(false), vec![0.; 4]);
+		// The other frames' vertices are missing from the file
+		let mut short = surface.clone();
+		short.num_frames = 3;
+		assert_eq!(short.normal_errors(false), vec![0., 0., 0., 180.]);
+		short.recalculate_normals(NormalWeighting::Area, false);
+		assert_eq!(short.normal_errors(false), vec![0.; 4]);
+	}
+
+	#[test]
+	fn flipped_triangles() {
+		// The same square with normals facing up, and the second triangle
+		// wound the wrong way round
+		let mut surface = quad();
+		surface.triangles[1] = MD3Triangle([0, 2, 3]);
+		assert_eq!(surface.flipped_triangles(0, false), vec![1]);
+		assert!(surface.flipped_triangles(1, false).is_empty());
+	}
+
+	#[test]
+	fn blocked_segments() {
+		let surface = quad();
+		// The square is 1 unit across, at z = 0
+		let (above, below) = (Vec3::new(0.5, 0.5, 1.), Vec3::new(0.5, 0.5, -1.));
+		assert!(surface.blocks_segment(0, above, below));
+		assert!(surface.blocks_segment(0, below, above));
+		assert!(!surface.blocks_segment(0, above, Vec3::new(0.5, 0.5, 0.)));
+		assert!(!surface.blocks_segment(0, above, Vec3::new(2., 0.5, -1.)));
+		assert!(!surface.blocks_segment(1, above, below));
+	}
+
+	#[test]
+	fn animation_pixels() {
+		let surface = test_surface("body", 3, (0..6).map(|i| MD3FrameVertex { x: i, y: -i, z: 0, n: 32768 }).collect());
+		// Two rows per frame, with a blank pixel at the end of each frame
+		let animation = surface.make_animation(Some(2));
+		assert_eq!(animation.rows_per_frame, 2);
+		assert_eq!(animation.data.len(), 2 * 2 * 2 * 4 * 2);
+		let pixel = |index: usize| -> [i16; 4] {
+			let bytes = &animation.data[index * 8..index * 8 + 8];
+			[0, 1, 2, 3].map(|c| i16::from_ne_bytes([bytes[c * 2], bytes[c * 2 + 1]]))
+		};
+		assert_eq!(pixel(1), [1, -1, 0, i16::MIN]);
+		assert_eq!(pixel(3), [0; 4]);
+		assert_eq!(pixel(4), [3, -3, 0, i16::MIN]);
+		assert_eq!(pixel(4)[3] as u16, 32768);
+	}
+
+	#[test]
+	fn encode_normal() {
+		let vertex = |n| MD3FrameVertex { x: 0, y: 0, z: 0, n };
+		assert_eq!(MD3FrameVertex::encode_normal(Vec3::Z, false), 0);
+		assert_eq!(MD3FrameVertex::encode_normal(Vec3::NEG_Z, false), 32768);
+		assert!(vertex(MD3FrameVertex::encode_normal(Vec3::NEG_Z, true)).normal(true).z < -0.999);
+		[Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::new(1., -2., 3.).normalize()]
+			.into_iter().for_each(|normal| {
+				let decoded = vertex(MD3FrameVertex::encode_normal(normal, false)).normal(false);
+				assert!(decoded.angle_between(normal) < 0.02, "{} became {}", normal, decoded);
+			});
+	}
+
+	#[test]
+	fn write_round_trip() {
+		let mut name = [0; 64];
+		name[..10].copy_from_slice(b"tag_weapon");
+		let mut model = MD3Model {
+			name: make_name("models/test.md3"),
+			num_tags: 1,
+			frames: vec![MD3Frame { radius: 4., max: Vec3::ONE, ..Default::default() }],
+			tags: vec![MD3FrameTag { name, origin: Vec3::new(1., 2., 3.), axes: Mat3::IDENTITY }],
+			..test_model(vec![MD3Surface {
+				shaders: vec![MD3Shader { name: make_name("old/skin.png"), index: 0 }],
+				texcoords: vec![MD3TexCoord(Vec2::new(0.5, 1.)); 3],
+				vertices: vec![MD3FrameVertex { x: 64, y: -64, z: 1, n: 300 }; 3],
+				..triangle()
+			}])
+		};
+		let renamed = model.rename_shaders(|name| {
+			name.starts_with("old/").then(|| name.replacen("old/", "new/", 1))
+		});
+		assert_eq!(renamed, 1);
+		let mut data = Vec::new();
+		write_md3(&model, &mut data).unwrap();
+		let read = read_md3(&mut io::Cursor::new(&data)).unwrap();
+		assert_eq!(String::from_utf8_stop(&read.name), "models/test.md3");
+		assert_eq!(read.frames[0].radius, 4.);
+		assert_eq!(read.frames[0].max, Vec3::ONE);
+		assert_eq!(read.tags[0].origin, Vec3::new(1., 2., 3.));
+		assert_eq!(read.tags[0].axes, Mat3::IDENTITY);
+		let surf = &read.surfaces[0];
+		assert_eq!(String::from_utf8_stop(&surf.shaders[0].name), "new/skin.png");
+		assert_eq!(surf.triangles[0].0, [0, 1, 2]);
+		assert_eq!(surf.texcoords[2].0, Vec2::new(0.5, 1.));
+		assert_eq!(surf.vertices[1].y, -64);
+		assert_eq!(surf.vertices[1].n, 300);
+	}
+
+	#[test]
+	fn untrusted_input() {
+		let model = test_model(vec![triangle()]);
+		let mut data = Vec::new();
+		write_md3(&model, &mut data).unwrap();
+		assert!(read_md3_bytes(&data).is_ok());
+		// Way more frames than could fit in the file
+		let mut huge = data.clone();
+		huge[76..80].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(matches!(read_md3_bytes(&huge),
+			Err(MD3ReadError::OutOfBounds { what: "frames", .. })));
+		// Offset of the surfaces is past the end of the file
+		let mut past_end = data.clone();
+		past_end[100..104].copy_from_slice(&(data.len() as u32).to_le_bytes());
+		assert!(matches!(read_md3_bytes(&past_end),
+			Err(MD3ReadError::OutOfBounds { what: "surfaces", .. })));
+		// Triangle using a vertex which doesn't exist
+		let triangle = MD3_HEADER_SIZE + MD3_FRAME_SIZE + MD3_SURFACE_HEADER_SIZE;
+		let mut bad_triangle = data.clone();
+		bad_triangle[triangle..triangle + 4].copy_from_slice(&3u32.to_le_bytes());
+		assert!(matches!(read_md3_bytes(&bad_triangle),
+			Err(MD3ReadError::InvalidVertexIndex { index: 3, num_verts: 3 })));
+		assert!(read_md3_bytes(&data[..50]).is_err());
+	}
+
+	#[test]
+	fn tolerant_reading() {
+		let surface = MD3Surface { triangles: vec![MD3Triangle([0, 1, 2]), MD3Triangle([2, 1, 0])], ..triangle() };
+		let model = test_model(vec![surface.clone(), MD3Surface { name: make_name("head"), ..surface.clone() }, surface]);
+		let mut data = Vec::new();
+		write_md3(&model, &mut data).unwrap();
+		// A newer version, a triangle using a vertex which doesn't exist, and
+		// a surface without its ID
+		data[4..8].copy_from_slice(&16i32.to_le_bytes());
+		let first = MD3_HEADER_SIZE + MD3_FRAME_SIZE;
+		let triangle = first + MD3_SURFACE_HEADER_SIZE;
+		data[triangle..triangle + 4].copy_from_slice(&3u32.to_le_bytes());
+		let second = first + model.surfaces[0].file_size();
+		data[second..second + 4].copy_from_slice(b"XXXX");
+		assert!(matches!(read_md3_bytes(&data), Err(MD3ReadError::UnsupportedVersion(16))));
+		let (read, errors) = read_md3_tolerant(&mut io::Cursor::new(&data)).unwrap();
+		assert_eq!(errors.len(), 3);
+		assert!(matches!(errors[1], MD3ReadError::InvalidVertexIndex { index: 3, num_verts: 3 }));
+		assert!(matches!(errors[2], MD3ReadError::WrongId(_)));
+		assert_eq!(read.surfaces.len(), 2);
+		assert_eq!(read.surfaces[0].triangles.len(), 1);
+		assert_eq!(String::from_utf8_stop(&read.surfaces[1].name), "body");
+
+		// Missing the last frame of vertices
+		let mut model = model;
+		model.surfaces.truncate(1);
+		model.frames.push(Default::default());
+		model.surfaces[0].num_frames = 2;
+		model.surfaces[0].vertices.extend([MD3FrameVertex { x: 64, ..Default::default() }; 3]);
+		let mut data = Vec::new();
+		write_md3(&model, &mut data).unwrap();
+		data.truncate(data.len() - MD3_VERTEX_SIZE);
+		let (read, errors) = read_md3_tolerant(&mut io::Cursor::new(&data)).unwrap();
+		assert!(matches!(errors[0], MD3ReadError::OutOfBounds { what: "vertices", .. }));
+		assert!(matches!(errors[1], MD3ReadError::FrameCountMismatch { surface: 1, model: 2 }));
+		assert_eq!(read.surfaces[0].num_frames, 2);
+		assert_eq!(read.surfaces[0].vertices.len(), 6);
+		assert_eq!(read.surfaces[0].vertices[5].x, 0);
+	}
+}
@@ -0,0 +1,311 @@
+// Making models smaller without changing how they look. Every vertex is
+// stored once per frame, and is part of the animation texture, so extra
+// vertices are expensive.
+use crate::limits::EngineLimits;
+use crate::md3::{MD3FrameVertex, MD3Model, MD3Surface, MD3Triangle};
+use crate::q3shader::shader_key;
+use crate::str_util::StringFromBytes;
+use glam::Vec3;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizeSettings {
+	// Merge vertices with the same position in every frame, and the same UV
+	pub weld: bool,
+	// Only merge vertices which have the same normals too, to keep hard edges
+	pub match_normals: bool,
+	// Remove triangles with no area in any frame
+	pub remove_degenerate: bool,
+	pub remove_duplicates: bool,
+	// Remove vertices which aren't part of any triangle
+	pub remove_unused: bool,
+}
+
+impl Default for OptimizeSettings {
+	fn default() -> Self {
+		Self { weld: true, match_normals: true, remove_degenerate: true, remove_duplicates: true, remove_unused: true }
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptimizeReport {
+	pub vertices: (usize, usize),
+	pub triangles: (usize, usize),
+	// Bytes
+	pub file_size: (usize, usize),
+}
+
+impl fmt::Display for OptimizeReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "Vertices: {} → {}", self.vertices.0, self.vertices.1)?;
+		writeln!(f, "Triangles: {} → {}", self.triangles.0, self.triangles.1)?;
+		write!(f, "File size: {} → {} bytes", self.file_size.0, self.file_size.1)
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SnapReport {
+	// Vertex positions which moved, counting each frame separately
+	pub moved: usize,
+	// The furthest a vertex moved, in units, and in which frame
+	pub max_distance: f32,
+	pub max_frame: usize,
+}
+
+impl fmt::Display for SnapReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.moved {
+			0 => write!(f, "Every vertex was already on the grid"),
+			moved => write!(f, "{} vertex positions moved, by up to {:.4} units (in frame {})",
+				moved, self.max_distance, self.max_frame),
+		}
+	}
+}
+
+// Round every vertex position in every frame to a multiple of `grid`, in the
+// 1/64 unit steps they're stored in, to get rid of the noise some exporters
+// leave which stops vertices on seams from lining up
+pub fn snap_to_grid(model: &mut MD3Model, grid: u16) -> SnapReport {
+	let grid = grid.max(1) as f32;
+	let snap = |n: i16| {
+		let snapped = (n as f32 / grid).round() * grid;
+		// The nearest grid line might be too far out to store
+		let snapped = match snapped {
+			s if s > i16::MAX as f32 => s - grid,
+			s if s < i16::MIN as f32 => s + grid,
+			s => s,
+		};
+		snapped as i16
+	};
+	let mut report = SnapReport::default();
+	model.surfaces.iter_mut().for_each(|surf| {
+		let num_verts = surf.num_verts.max(1);
+		surf.vertices.iter_mut().enumerate().for_each(|(index, vertex)| {
+			let before = vertex.position();
+			(vertex.x, vertex.y, vertex.z) = (snap(vertex.x), snap(vertex.y), snap(vertex.z));
+			let distance = before.distance(vertex.position());
+			if distance == 0. { return; }
+			report.moved += 1;
+			if distance > report.max_distance {
+				report.max_distance = distance;
+				report.max_frame = index / num_verts;
+			}
+		});
+	});
+	report
+}
+
+pub fn optimize(model: &mut MD3Model, settings: &OptimizeSettings) -> OptimizeReport {
+	let count = |model: &MD3Model| (
+		model.surfaces.iter().map(|s| s.num_verts).sum::<usize>(),
+		model.surfaces.iter().map(|s| s.triangles.len()).sum::<usize>(),
+		model.file_size(),
+	);
+	let before = count(model);
+	model.surfaces.iter_mut().for_each(|surface| optimize_surface(surface, settings));
+	let after = count(model);
+	OptimizeReport {
+		vertices: (before.0, after.0),
+		triangles: (before.1, after.1),
+		file_size: (before.2, after.2),
+	}
+}
+
+fn optimize_surface(surface: &mut MD3Surface, settings: &OptimizeSettings) {
+	if settings.weld {
+		// Every frame of the vertex, and its UV
+		let key = |vertex: usize| {
+			let frames: Vec<(i16, i16, i16, u16)> = surface.vertices.iter().skip(vertex)
+				.step_by(surface.num_verts)
+				.map(|v| (v.x, v.y, v.z, if settings.match_normals { v.n } else { 0 }))
+				.collect();
+			let uv = surface.texcoords.get(vertex).map(|t| t.0.to_array().map(f32::to_bits));
+			(frames, uv)
+		};
+		let mut first = HashMap::new();
+		let new_index: Vec<usize> = (0..surface.num_verts)
+			.map(|vertex| *first.entry(key(vertex)).or_insert(vertex))
+			.collect();
+		remap_triangles(surface, &new_index);
+	}
+	if settings.remove_degenerate {
+		let verts = surface.num_verts;
+		let vertices = &surface.vertices;
+		surface.triangles.retain(|tri| {
+			let [a, b, c] = tri.0.map(|i| i as usize);
+			if a == b || b == c || c == a { return false; }
+			(0..surface.num_frames).any(|frame| {
+				let position = |i: usize| vertices.get(frame * verts + i).map(MD3FrameVertex::position);
+				match (position(a), position(b), position(c)) {
+					(Some(pa), Some(pb), Some(pc)) => (pb - pa).cross(pc - pa) != Vec3::ZERO,
+					_ => true,
+				}
+			})
+		});
+	}
+	if settings.remove_duplicates {
+		// The same triangle can start at any of its corners
+		let mut seen = HashSet::new();
+		surface.triangles.retain(|tri| {
+			let first = (0..3).min_by_key(|&i| tri.0[i]).unwrap_or(0);
+			let corners = [0, 1, 2].map(|i| tri.0[(first + i) % 3]);
+			seen.insert(corners)
+		});
+	}
+	// Including vertices which were welded to other vertices
+	if settings.remove_unused {
+		remove_unused(surface);
+	}
+}
+
+pub(crate) fn remove_unused(surface: &mut MD3Surface) {
+	let mut used = vec![false; surface.num_verts];
+	surface.triangles.iter().flat_map(|t| t.0)
+		.for_each(|i| if let Some(u) = used.get_mut(i as usize) { *u = true; });
+	let kept: Vec<usize> = (0..surface.num_verts).filter(|&v| used[v]).collect();
+	let mut new_index = vec![0; surface.num_verts];
+	kept.iter().enumerate().for_each(|(new, &old)| new_index[old] = new);
+	remap_triangles(surface, &new_index);
+	let num_verts = surface.num_verts;
+	surface.vertices = (0..surface.num_frames)
+		.flat_map(|frame| kept.iter().map(move |&v| frame * num_verts + v))
+		.filter_map(|i| surface.vertices.get(i).copied())
+		.collect();
+	surface.texcoords = kept.iter().filter_map(|&v| surface.texcoords.get(v).copied()).collect();
+	surface.num_verts = kept.len();
+}
+
+fn remap_triangles(surface: &mut MD3Surface, new_index: &[usize]) {
+	surface.triangles.iter_mut().for_each(|tri| {
+		*tri = MD3Triangle(tri.0.map(|i| new_index.get(i as usize).map_or(i, |&n| n as u32)));
+	});
+}
+
+// Which surface each surface would be merged into, so that surfaces with the
+// same shaders are drawn together. Merged surfaces are kept within the
+// engine's limits, and each one takes the place of its first surface.
+pub fn merge_plan(model: &MD3Model, limits: &EngineLimits) -> Vec<usize> {
+	let key = |surf: &MD3Surface| (surf.num_frames, surf.shaders.iter()
+		.map(|s| shader_key(&String::from_utf8_stop(&s.name))).collect::<Vec<_>>());
+	// The key, vertices and triangles of each merged surface
+	let mut merged: Vec<((usize, Vec<String>), usize, usize)> = vec![];
+	model.surfaces.iter().map(|surf| {
+		let surf_key = key(surf);
+		let fits = |verts: usize, triangles: usize| {
+			limits.verts.is_none_or(|max| verts + surf.num_verts <= max) &&
+			limits.triangles.is_none_or(|max| triangles + surf.triangles.len() <= max)
+		};
+		match merged.iter().position(|(key, verts, triangles)| *key == surf_key && fits(*verts, *triangles)) {
+			Some(index) => {
+				merged[index].1 += surf.num_verts;
+				merged[index].2 += surf.triangles.len();
+				index
+			},
+			None => {
+				merged.push((surf_key, surf.num_verts, surf.triangles.len()));
+				merged.len() - 1
+			},
+		}
+	}).collect()
+}
+
+// Merge the surfaces as merge_plan says, and return which surface each one
+// went into. Merged surfaces are named after their first surface.
+pub fn merge_surfaces(model: &mut MD3Model, limits: &EngineLimits) -> Vec<usize> {
+	let plan = merge_plan(model, limits);
+	let mut merged: Vec<MD3Surface> = vec![];
+	model.surfaces.drain(..).zip(plan.iter()).for_each(|(surf, &index)| match merged.get_mut(index) {
+		Some(target) => append_surface(target, &surf),
+		None => merged.push(surf),
+	});
+	model.surfaces = merged;
+	plan
+}
+
+fn append_surface(target: &mut MD3Surface, surf: &MD3Surface) {
+	let offset = target.num_verts as u32;
+	target.triangles.extend(surf.triangles.iter().map(|tri| MD3Triangle(tri.0.map(|i| i + offset))));
+	target.texcoords.resize(target.num_verts, Default::default());
+	target.texcoords.extend(surf.texcoords.iter().copied());
+	// The vertices are stored frame by frame
+	let frame_verts = |s: &MD3Surface, frame: usize| {
+		let verts = s.vertices.get(frame * s.num_verts..(frame + 1) * s.num_verts).unwrap_or_default().to_vec();
+		let missing = s.num_verts - verts.len();
+		verts.into_iter().chain(std::iter::repeat_n(MD3FrameVertex::default(), missing))
+	};
+	target.vertices = (0..target.num_frames)
+		.flat_map(|frame| frame_verts(target, frame).chain(frame_verts(surf, frame)))
+		.collect();
+	target.num_verts += surf.num_verts;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::md3::tests::{test_model, test_surface};
+	use crate::md3::{make_name, MD3Shader};
+	use glam::Vec2;
+
+	#[test]
+	fn optimize_quad() {
+		let vertex = |x, y| MD3FrameVertex { x, y, z: 0, n: 0 };
+		// Two triangles which don't share vertices, one of them twice, a
+		// triangle with no area, and an unused vertex
+		let mut model = test_model(vec![MD3Surface {
+			triangles: [[0, 2, 1], [3, 5, 4], [4, 3, 5], [0, 1, 1]].map(MD3Triangle).to_vec(),
+			..test_surface("floor", 7, vec![vertex(0, 0), vertex(64, 0), vertex(64, 64),
+				vertex(0, 0), vertex(64, 64), vertex(0, 64), vertex(128, 128)])
+		}]);
+		let report = optimize(&mut model, &OptimizeSettings::default());
+		assert_eq!(report.vertices, (7, 4));
+		assert_eq!(report.triangles, (4, 2));
+		assert_eq!(report.file_size.0 - report.file_size.1, 3 * 8 + 2 * 12 + 3 * 8);
+		let surface = &model.surfaces[0];
+		assert_eq!(surface.triangles.iter().map(|t| t.0).collect::<Vec<_>>(), [[0, 2, 1], [0, 3, 2]]);
+		assert_eq!(surface.vertices.len(), 4);
+	}
+
+	#[test]
+	fn snap() {
+		let vertex = |x, y, z| MD3FrameVertex { x, y, z, n: 0 };
+		let mut model = test_model(vec![
+			test_surface("pillar", 2, vec![vertex(64, -1, 0), vertex(128, 0, 0), vertex(63, 0, 0), vertex(-70, 0, 32767)]),
+		]);
+		let report = snap_to_grid(&mut model, 8);
+		let positions: Vec<[i16; 3]> = model.surfaces[0].vertices.iter().map(|v| [v.x, v.y, v.z]).collect();
+		assert_eq!(positions, [[64, 0, 0], [128, 0, 0], [64, 0, 0], [-72, 0, 32760]]);
+		assert_eq!(report.moved, 3);
+		assert_eq!(report.max_frame, 1);
+		assert_eq!(report.max_distance, Vec2::new(2., 7.).length() / 64.);
+		assert_eq!(snap_to_grid(&mut model, 8).moved, 0);
+	}
+
+	#[test]
+	fn merge() {
+		let vertex = |x| MD3FrameVertex { x, y: 0, z: 0, n: 0 };
+		let surface = |name: &str, shader: &str, x: i16| MD3Surface {
+			shaders: vec![MD3Shader { name: make_name(shader), index: 0 }],
+			triangles: vec![MD3Triangle([0, 1, 2])],
+			..test_surface(name, 3, [x, x, x, x + 1, x + 1, x + 1].map(vertex).to_vec())
+		};
+		let mut model = test_model(vec![
+			surface("crate", "textures/crate.tga", 0),
+			surface("lid", "textures/lid", 10),
+			surface("side", "Textures/Crate", 20),
+			surface("more", "textures/crate", 30),
+		]);
+		let limits = EngineLimits { verts: Some(6), ..Default::default() };
+		assert_eq!(merge_plan(&model, &limits), [0, 1, 0, 2]);
+		assert_eq!(merge_surfaces(&mut model, &limits), [0, 1, 0, 2]);
+		assert_eq!(model.surfaces.len(), 3);
+		let merged = &model.surfaces[0];
+		assert_eq!(String::from_utf8_stop(&merged.name), "crate");
+		assert_eq!(merged.num_verts, 6);
+		assert_eq!(merged.triangles.iter().map(|t| t.0).collect::<Vec<_>>(), [[0, 1, 2], [3, 4, 5]]);
+		assert_eq!(merged.texcoords.len(), 6);
+		// The first frame of both surfaces, then the second
+		assert_eq!(merged.vertices.iter().map(|v| v.x).collect::<Vec<_>>(), [0, 0, 0, 20, 20, 20, 1, 1, 1, 21, 21, 21]);
+		assert_eq!(model.surfaces[2].vertices[0].x, 30);
+	}
+}
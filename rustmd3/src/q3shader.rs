@@ -0,0 +1,144 @@
+// Just enough of the Quake 3 shader script format to know how a model
+// surface should be drawn. See the Q3 shader manual for the full format.
+use std::{
+	collections::HashMap,
+	fs,
+	io,
+	path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Cull {
+	// Quake 3 calls culling the back of a triangle "cull front"
+	#[default]
+	Front,
+	Back,
+	None,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Q3Shader {
+	pub name: String,
+	pub cull: Cull,
+}
+
+impl Q3Shader {
+	pub fn two_sided(&self) -> bool {
+		self.cull == Cull::None
+	}
+}
+
+// Split a shader script into words and braces, without comments
+fn tokens(text: &str) -> impl Iterator<Item = &str> {
+	text.lines()
+		.map(|line| line.split_once("//").map(|(code, _)| code).unwrap_or(line))
+		.flat_map(|line| line.split_whitespace())
+		.flat_map(|word| {
+			// Braces aren't always separated from the words around them
+			let mut parts = Vec::new();
+			let mut rest = word;
+			while let Some(brace) = rest.find(['{', '}']) {
+				if brace > 0 {
+					parts.push(&rest[..brace]);
+				}
+				parts.push(&rest[brace..brace + 1]);
+				rest = &rest[brace + 1..];
+			}
+			if !rest.is_empty() {
+				parts.push(rest);
+			}
+			parts
+		})
+}
+
+// Parse all of the shaders in a script. Unknown keywords are ignored.
+pub fn parse_shaders(text: &str) -> Vec<Q3Shader> {
+	let mut shaders = Vec::new();
+	let mut depth = 0;
+	let mut tokens = tokens(text).peekable();
+	while let Some(token) = tokens.next() {
+		match (token, depth) {
+			("{", _) => depth += 1,
+			("}", _) => depth = (depth - 1).max(0),
+			(name, 0) => shaders.push(Q3Shader { name: name.replace('\\', "/"), ..Default::default() }),
+			// Stages can't change the culling
+			(keyword, 1) if keyword.eq_ignore_ascii_case("cull") => {
+				let cull = tokens.next_if(|t| *t != "{" && *t != "}");
+				if let Some(shader) = shaders.last_mut() {
+					shader.cull = match cull.map(str::to_ascii_lowercase).as_deref() {
+						Some("none" | "disable" | "twosided") => Cull::None,
+						Some("back" | "backside" | "backsided") => Cull::Back,
+						_ => Cull::Front,
+					};
+				}
+			},
+			_ => (),
+		}
+	}
+	shaders
+}
+
+// Shader names don't need extensions, and are case insensitive
+pub fn shader_key(name: &str) -> String {
+	let name = name.trim().replace('\\', "/").to_ascii_lowercase();
+	match name.rsplit_once('.') {
+		Some((stem, ext)) if !ext.contains('/') => stem.to_string(),
+		_ => name,
+	}
+}
+
+// The "scripts" folder of the game or mod a file is in, if there is one
+pub fn find_scripts(path: &Path) -> Option<PathBuf> {
+	path.ancestors().skip(1)
+		.map(|dir| dir.join("scripts"))
+		.find(|scripts| scripts.is_dir())
+}
+
+// Read all of the shaders in a scripts folder, by shader_key
+pub fn read_scripts(scripts: &Path) -> io::Result<HashMap<String, Q3Shader>> {
+	let mut paths: Vec<PathBuf> = fs::read_dir(scripts)?
+		.filter_map(|entry| entry.ok().map(|e| e.path()))
+		.filter(|path| path.extension().is_some_and(|e| e.eq_ignore_ascii_case("shader")))
+		.collect();
+	// Later definitions of a shader replace earlier ones
+	paths.sort();
+	let mut shaders = HashMap::new();
+	paths.iter().try_for_each(|path| -> io::Result<()> {
+		let text = fs::read(path)?;
+		parse_shaders(&String::from_utf8_lossy(&text)).into_iter().for_each(|shader| {
+			shaders.insert(shader_key(&shader.name), shader);
+		});
+		Ok(())
+	})?;
+	Ok(shaders)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_cull() {
+		let script = "// Flags\n\
+			models/mapobjects/flag/banner{\n\
+				cull none // two-sided\n\
+				{\n\
+					map models/mapobjects/flag/banner.tga\n\
+					cull back\n\
+				}\n\
+			}\n\
+			models/players/sarge/band\n\
+			{\n\
+				CULL Disable\n\
+			}\n\
+			models/players/sarge/cigar { { map $whiteimage } }\n";
+		let shaders = parse_shaders(script);
+		assert_eq!(shaders.len(), 3);
+		assert_eq!(shaders[0].name, "models/mapobjects/flag/banner");
+		assert!(shaders[0].two_sided());
+		assert!(shaders[1].two_sided());
+		assert_eq!(shaders[2].cull, Cull::Front);
+		assert_eq!(shader_key("Models\\Players\\Sarge\\Band.TGA"), "models/players/sarge/band");
+		assert_eq!(shader_key("models/players/sarge.v2/band"), "models/players/sarge.v2/band");
+	}
+}
@@ -0,0 +1,75 @@
+use std::borrow::Cow;
+
+pub trait StringFromBytes {
+	/// Convert a byte slice to a string, starting at the first printable
+	/// character, and stopping at the terminating null or the first control
+	/// character after it. Bytes which aren't valid UTF-8 are read as Latin-1,
+	/// which older tools used for names.
+	fn from_utf8_stop(bytes: &[u8]) -> Cow<'_, str>;
+}
+
+impl StringFromBytes for String {
+	fn from_utf8_stop(bytes: &[u8]) -> Cow<'_, str> {
+		let valid = |b: &u8| !b.is_ascii_control();
+		let first_valid = match bytes.iter().position(valid) {
+			Some(first_valid) => first_valid,
+			None => return Cow::Borrowed(""),
+		};
+		let bytes = &bytes[first_valid..];
+		let bytes = &bytes[..bytes.iter().position(|b| !valid(b)).unwrap_or(bytes.len())];
+		if let Ok(name) = std::str::from_utf8(bytes) {
+			return Cow::Borrowed(name);
+		}
+		let mut name = String::with_capacity(bytes.len());
+		bytes.utf8_chunks().for_each(|chunk| {
+			name.push_str(chunk.valid());
+			name.extend(chunk.invalid().iter().map(|&b| char::from(b)));
+		});
+		Cow::Owned(name)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn start() {
+		let bytes = b"YEE HAW\0\0\0\0\0\0\0\0\0";
+		let expected = "YEE HAW";
+		let actual = String::from_utf8_stop(bytes);
+		assert_eq!(expected, actual)
+	}
+
+	#[test]
+	fn middle() {
+		let bytes = b"\0\0\0\0YEE HAW\0\0\0\0\0";
+		let expected = "YEE HAW";
+		let actual = String::from_utf8_stop(bytes);
+		assert_eq!(expected, actual)
+	}
+
+	#[test]
+	fn ending() {
+		let bytes = b"\0\0\0\0\0\0\0\0\0YEE HAW";
+		let expected = "YEE HAW";
+		let actual = String::from_utf8_stop(bytes);
+		assert_eq!(expected, actual)
+	}
+
+	#[test]
+	fn nothing() {
+		let bytes = b"\0\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F";
+		let expected = "";
+		let actual = String::from_utf8_stop(bytes);
+		assert_eq!(expected, actual)
+	}
+
+	#[test]
+	fn non_ascii() {
+		assert_eq!(String::from_utf8_stop("tête\0\0".as_bytes()), "tête");
+		// "tête" and "é" in Latin-1
+		assert_eq!(String::from_utf8_stop(b"t\xeate\0\xe9"), "tête");
+		assert_eq!(String::from_utf8_stop(b"caf\xc3\xa9/\xe9t\xe9\0"), "café/été");
+	}
+}
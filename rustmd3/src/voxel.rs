@@ -0,0 +1,189 @@
+// Turning a frame of a model into voxels, and saving them as MagicaVoxel .vox
+// files
+use anyhow::Error;
+use crate::md3::MD3Model;
+use glam::{UVec3, Vec2, Vec3};
+use std::{collections::HashMap, io::Write};
+
+// The biggest models MagicaVoxel can open
+pub const MAX_VOX_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VoxelGrid {
+	// Number of voxels along each axis
+	pub size: UVec3,
+	// The corner of the first voxel, in model units
+	pub origin: Vec3,
+	// Width of a voxel, in model units
+	pub voxel_size: f32,
+	// The colours of the voxels which are filled
+	pub voxels: HashMap<[u32; 3], [u8; 3]>,
+}
+
+impl VoxelGrid {
+	pub fn is_filled(&self, x: i64, y: i64, z: i64) -> bool {
+		match (u32::try_from(x), u32::try_from(y), u32::try_from(z)) {
+			(Ok(x), Ok(y), Ok(z)) => self.voxels.contains_key(&[x, y, z]),
+			_ => false,
+		}
+	}
+}
+
+// Fill the voxels which the surfaces of the frame go through. resolution is
+// the number of voxels along the longest side of the frame, and colour is
+// the colour of a surface at a UV coordinate.
+pub fn voxelize(model: &MD3Model, frame: usize, resolution: u32, colour: impl Fn(usize, Vec2) -> [u8; 3]) -> VoxelGrid {
+	// The frame's own bounds can't be trusted
+	let bounds = model.surfaces.iter().filter_map(|s| s.bounds(frame))
+		.reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)));
+	let (min, max) = match bounds {
+		Some(b) => b,
+		None => return VoxelGrid::default(),
+	};
+	let resolution = resolution.max(1);
+	let voxel_size = match (max - min).max_element() {
+		longest if longest > 0. => longest / resolution as f32,
+		_ => 1.,
+	};
+	let size = ((max - min) / voxel_size).ceil().as_uvec3().max(UVec3::ONE).min(UVec3::splat(resolution));
+	let mut grid = VoxelGrid { size, origin: min, voxel_size, voxels: HashMap::new() };
+	let cell = |p: Vec3| {
+		let cell = ((p - min) / voxel_size).floor().max(Vec3::ZERO).as_uvec3().min(size - UVec3::ONE);
+		[cell.x, cell.y, cell.z]
+	};
+	model.surfaces.iter().enumerate().for_each(|(index, surf)| {
+		let num_verts = surf.num_verts;
+		let verts = match surf.vertices.get(frame * num_verts..(frame + 1) * num_verts) {
+			Some(v) => v,
+			None => return,
+		};
+		surf.triangles.iter().for_each(|tri| {
+			let [a, b, c] = tri.0.map(|i| i as usize);
+			if a >= num_verts || b >= num_verts || c >= num_verts { return; }
+			let positions = [a, b, c].map(|i| verts[i].position());
+			let uvs = [a, b, c].map(|i| surf.texcoords.get(i).map(|t| t.0).unwrap_or_default());
+			// Sample the triangle at half a voxel apart, so that it doesn't
+			// miss any of the voxels it goes through
+			let longest = [(0, 1), (1, 2), (2, 0)].iter()
+				.map(|&(i, j)| positions[i].distance(positions[j])).fold(0f32, f32::max);
+			let steps = ((longest / (voxel_size * 0.5)).ceil() as usize).max(1);
+			(0..=steps).for_each(|i| (0..=steps - i).for_each(|j| {
+				let (u, v) = (i as f32 / steps as f32, j as f32 / steps as f32);
+				let w = 1. - u - v;
+				let position = positions[0] * w + positions[1] * u + positions[2] * v;
+				grid.voxels.entry(cell(position))
+					.or_insert_with(|| colour(index, uvs[0] * w + uvs[1] * u + uvs[2] * v));
+			}));
+		});
+	});
+	grid
+}
+
+// Up to 255 colours, rounded more coarsely until they fit, and the index of
+// each colour in them
+fn palette(grid: &VoxelGrid) -> (Vec<[u8; 3]>, HashMap<[u8; 3], u8>) {
+	let round = |colour: [u8; 3], bits: u32| colour.map(|c| {
+		let step = 1u16 << (8 - bits);
+		((c as u16 / step * step) + step / 2).min(255) as u8
+	});
+	let mut colours: Vec<[u8; 3]> = grid.voxels.values().copied().collect();
+	colours.sort_unstable();
+	colours.dedup();
+	let bits = (2..=8).rev().find(|&bits| {
+		let mut rounded: Vec<[u8; 3]> = colours.iter().map(|&c| round(c, bits)).collect();
+		rounded.sort_unstable();
+		rounded.dedup();
+		rounded.len() <= 255
+	}).unwrap_or(2);
+	let bits_needed = |colour: [u8; 3]| if bits == 8 { colour } else { round(colour, bits) };
+	let mut palette: Vec<[u8; 3]> = colours.iter().map(|&c| bits_needed(c)).collect();
+	palette.sort_unstable();
+	palette.dedup();
+	let indices = colours.iter().map(|&c| {
+		let index = palette.binary_search(&bits_needed(c)).unwrap_or(0);
+		// Colour 0 is empty space
+		(c, index as u8 + 1)
+	}).collect();
+	(palette, indices)
+}
+
+fn write_chunk(writer: &mut impl Write, id: &[u8; 4], content: &[u8], children: &[u8]) -> Result<(), Error> {
+	writer.write_all(id)?;
+	writer.write_all(&(content.len() as u32).to_le_bytes())?;
+	writer.write_all(&(children.len() as u32).to_le_bytes())?;
+	writer.write_all(content)?;
+	writer.write_all(children)?;
+	Ok(())
+}
+
+// Save the voxels as a MagicaVoxel model, with a palette made from their
+// colours
+pub fn write_vox(grid: &VoxelGrid, writer: &mut impl Write) -> Result<(), Error> {
+	if grid.size.max_element() > MAX_VOX_SIZE {
+		return Err(Error::msg(format!("MagicaVoxel models can be at most {} voxels across", MAX_VOX_SIZE)));
+	}
+	let (palette, indices) = palette(grid);
+	let size: Vec<u8> = grid.size.to_array().iter().flat_map(|n| n.to_le_bytes()).collect();
+	let mut voxels: Vec<(&[u32; 3], &[u8; 3])> = grid.voxels.iter().collect();
+	voxels.sort_unstable_by_key(|(position, _)| **position);
+	let mut xyzi = (voxels.len() as u32).to_le_bytes().to_vec();
+	voxels.iter().for_each(|(position, colour)| {
+		xyzi.extend(position.map(|n| n as u8));
+		xyzi.push(indices[*colour]);
+	});
+	// Entry i is the colour of index i + 1
+	let mut rgba = vec![0u8; 256 * 4];
+	palette.iter().enumerate().for_each(|(index, colour)| {
+		rgba[index * 4..index * 4 + 4].copy_from_slice(&[colour[0], colour[1], colour[2], 255]);
+	});
+	let mut children = vec![];
+	write_chunk(&mut children, b"SIZE", &size, &[])?;
+	write_chunk(&mut children, b"XYZI", &xyzi, &[])?;
+	write_chunk(&mut children, b"RGBA", &rgba, &[])?;
+	writer.write_all(b"VOX ")?;
+	writer.write_all(&150i32.to_le_bytes())?;
+	write_chunk(writer, b"MAIN", &[], &children)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::md3::tests::{quad, test_model};
+
+	#[test]
+	fn voxels() {
+		// A flat square, 4 units across, coloured by U
+		let mut surface = quad();
+		surface.vertices.iter_mut().for_each(|v| (v.x, v.y) = (v.x * 4, v.y * 4));
+		let model = test_model(vec![surface]);
+		let grid = voxelize(&model, 0, 4, |_, uv| if uv.x < 0.5 { [255, 0, 0] } else { [0, 0, 255] });
+		assert_eq!(grid.size, UVec3::new(4, 4, 1));
+		assert_eq!(grid.voxel_size, 1.);
+		assert_eq!(grid.voxels.len(), 16);
+		assert_eq!(grid.voxels[&[0, 0, 0]], [255, 0, 0]);
+		assert_eq!(grid.voxels[&[3, 3, 0]], [0, 0, 255]);
+		assert!(grid.is_filled(1, 2, 0));
+		assert!(!grid.is_filled(-1, 0, 0));
+
+		let mut data = vec![];
+		write_vox(&grid, &mut data).unwrap();
+		assert_eq!(&data[..8], b"VOX \x96\0\0\0");
+		assert_eq!(&data[8..12], b"MAIN");
+		// SIZE, then XYZI with 16 voxels, then RGBA
+		let children = 12 + 12 + 12 + 4 + 16 * 4 + 12 + 256 * 4;
+		assert_eq!(u32::from_le_bytes(data[16..20].try_into().unwrap()), children as u32);
+		assert_eq!(data.len(), 20 + children);
+		// Blue sorts before red, so it's colour 1
+		let rgba = &data[data.len() - 256 * 4..];
+		assert_eq!(&rgba[..8], &[0, 0, 255, 255, 255, 0, 0, 255]);
+	}
+
+	#[test]
+	fn palette_rounding() {
+		let voxels = (0..1000u32).map(|i| ([i, 0, 0], [(i % 256) as u8, (i / 4 % 256) as u8, (i / 8) as u8])).collect();
+		let grid = VoxelGrid { size: UVec3::new(1000, 1, 1), voxels, ..Default::default() };
+		let (palette, indices) = palette(&grid);
+		assert!(palette.len() <= 255);
+		assert!(indices.values().all(|&i| i >= 1 && i as usize <= palette.len()));
+	}
+}